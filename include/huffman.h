@@ -0,0 +1,51 @@
+/*
+ * C interface to the rust_compression huffman codec.
+ *
+ * Link against the shared library built by `cargo build --release`.
+ * Every buffer handed out by the library must be released with
+ * huffman_free, passing back the length that was reported.
+ */
+#ifndef HUFFMAN_H
+#define HUFFMAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* One-shot calls, returning NULL on failure */
+uint8_t *huffman_compress(const uint8_t *data, size_t len, size_t *out_len);
+uint8_t *huffman_decompress(const uint8_t *data, size_t len, size_t *out_len);
+void huffman_free(uint8_t *data, size_t len);
+
+/*
+ * Streaming calls. Feed input in chunks of any size, then call finish once.
+ * flush compresses everything fed so far, so the receiver can decode it
+ * before more input comes.
+ * Output that is ready is returned through out/out_len, out is NULL when
+ * nothing is ready yet. Functions return 0 on success and -1 on failure,
+ * after which the handle may only be destroyed.
+ */
+typedef struct HuffmanEncoder HuffmanEncoder;
+typedef struct HuffmanDecoder HuffmanDecoder;
+
+HuffmanEncoder *huffman_encoder_new(void);
+int huffman_encoder_feed(HuffmanEncoder *encoder, const uint8_t *data, size_t len,
+                         uint8_t **out, size_t *out_len);
+int huffman_encoder_flush(HuffmanEncoder *encoder, uint8_t **out, size_t *out_len);
+int huffman_encoder_finish(HuffmanEncoder *encoder, uint8_t **out, size_t *out_len);
+void huffman_encoder_destroy(HuffmanEncoder *encoder);
+
+HuffmanDecoder *huffman_decoder_new(void);
+int huffman_decoder_feed(HuffmanDecoder *decoder, const uint8_t *data, size_t len,
+                         uint8_t **out, size_t *out_len);
+int huffman_decoder_finish(HuffmanDecoder *decoder, uint8_t **out, size_t *out_len);
+void huffman_decoder_destroy(HuffmanDecoder *decoder);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,70 @@
+"""Huffman compression backed by the rust_compression crate.
+
+Build the shared library first with ``cargo build --release``.
+The library is looked up in ``$HUFFMAN_LIB``, next to this file,
+and finally in the crate's ``target/release`` directory.
+
+Calls go through ``ctypes.CDLL`` which releases the GIL while
+rust is compressing or decompressing, so other threads keep running.
+"""
+import ctypes
+import os
+import sys
+
+__all__ = ["compress", "decompress", "HuffmanError"]
+
+
+class HuffmanError(ValueError):
+    """Raised when the input cannot be compressed or decompressed."""
+
+
+def _library_name():
+    if sys.platform == "darwin":
+        return "librust_compression.dylib"
+    if sys.platform == "win32":
+        return "rust_compression.dll"
+    return "librust_compression.so"
+
+
+def _load():
+    here = os.path.dirname(os.path.abspath(__file__))
+    candidates = [
+        os.environ.get("HUFFMAN_LIB"),
+        os.path.join(here, _library_name()),
+        os.path.join(here, "..", "target", "release", _library_name()),
+    ]
+    for path in candidates:
+        if path and os.path.exists(path):
+            return ctypes.CDLL(path)
+    raise ImportError("could not find " + _library_name() + ", set HUFFMAN_LIB")
+
+
+_lib = _load()
+for _name in ("huffman_compress", "huffman_decompress"):
+    _fn = getattr(_lib, _name)
+    _fn.argtypes = [ctypes.c_char_p, ctypes.c_size_t, ctypes.POINTER(ctypes.c_size_t)]
+    _fn.restype = ctypes.POINTER(ctypes.c_ubyte)
+_lib.huffman_free.argtypes = [ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t]
+_lib.huffman_free.restype = None
+
+
+def _call(function, data, error):
+    data = bytes(data)
+    out_len = ctypes.c_size_t(0)
+    result = function(data, len(data), ctypes.byref(out_len))
+    if not result:
+        raise HuffmanError(error)
+    try:
+        return ctypes.string_at(result, out_len.value)
+    finally:
+        _lib.huffman_free(result, out_len.value)
+
+
+def compress(data):
+    """Compress ``data`` (bytes, possibly empty) and return the compressed bytes."""
+    return _call(_lib.huffman_compress, data, "input could not be compressed")
+
+
+def decompress(data):
+    """Decompress bytes produced by :func:`compress`."""
+    return _call(_lib.huffman_decompress, data, "corrupt compressed data")
@@ -0,0 +1,295 @@
+//! Byte statistics of data and the compression they predict
+//!
+//! Everything here works on symbol counts alone, so data can be analyzed
+//! as it streams by without ever being compressed.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Read};
+
+/// Member header without optional fields, tree marker and padding byte
+const FIXED_OVERHEAD: u64 = 17 + 1 + 1;
+
+/// Byte distribution of some data
+#[derive(Clone)]
+pub struct Analysis {
+	pub bytes: u64,
+	/// Number of occurences of every byte value
+	pub symbols: [u64; 256],
+}
+
+impl Default for Analysis {
+	fn default() -> Analysis {
+		Analysis {
+			bytes: 0,
+			symbols: [0; 256],
+		}
+	}
+}
+
+impl Analysis {
+	pub fn of(data: &[u8]) -> Analysis {
+		let mut analysis = Analysis::default();
+		analysis.add(data);
+		analysis
+	}
+	/// Analyze everything `reader` produces, a chunk at a time
+	pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Analysis> {
+		let mut analysis = Analysis::default();
+		let mut buffer = vec![0; 1 << 16];
+		loop {
+			match reader.read(&mut buffer) {
+				Ok(0) => return Ok(analysis),
+				Ok(len) => analysis.add(&buffer[..len]),
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+	/// Count more data
+	pub fn add(&mut self, data: &[u8]) {
+		for &byte in data {
+			self.symbols[byte as usize] += 1;
+		}
+		self.bytes += data.len() as u64;
+	}
+	/// Number of distinct byte values
+	pub fn distinct(&self) -> usize {
+		self.symbols.iter().filter(|&&count| count != 0).count()
+	}
+	/// Shannon entropy in bits per byte
+	pub fn entropy(&self) -> f64 {
+		entropy(&self.symbols)
+	}
+	/// Size in bits of the data when huffman coded, see `huffman_bits`
+	pub fn huffman_bits(&self) -> u64 {
+		huffman_bits(&self.symbols)
+	}
+	/// Estimated size of a single member holding the data, including header and tree
+	///
+	/// Assumes the data is ASCII text, as the char model codes chars rather than bytes
+	pub fn estimated_size(&self) -> u64 {
+		let data_size = self.huffman_bits().div_ceil(8);
+		FIXED_OVERHEAD + (self.tree_size() + data_size).min(self.bytes)
+	}
+	/// Size of the tree embedded for the data
+	fn tree_size(&self) -> u64 {
+		let distinct = self.distinct() as u64;
+		// Leaf count, a bit per node and a byte per leaf
+		if distinct == 0 {
+			0
+		} else {
+			4 + (2 * distinct - 1).div_ceil(8) + distinct
+		}
+	}
+	/// Bits the bytes counted in `other` take more when coded for the distribution of
+	/// these bytes than for their own, the Kullback-Leibler divergence times their number
+	///
+	/// Bytes missing here count as half an occurence, so none costs infinitely many bits.
+	pub fn extra_bits(&self, other: &Analysis) -> f64 {
+		let total = self.bytes as f64 + 128.0;
+		(0..256)
+			.filter(|&byte| other.symbols[byte] != 0)
+			.map(|byte| {
+				let count = other.symbols[byte] as f64;
+				let own = count / other.bytes as f64;
+				let here = (self.symbols[byte] as f64 + 0.5) / total;
+				count * (own / here).log2()
+			})
+			.sum()
+	}
+	/// Whether the data is mostly text, with control bytes other than line breaks and tabs
+	/// making up less than 1 in 100
+	pub fn looks_like_text(&self) -> bool {
+		let controls: u64 = (0..0x20)
+			.filter(|&byte| !b"\t\n\r\x0c".contains(&byte))
+			.map(|byte| self.symbols[byte as usize])
+			.sum();
+		controls * 100 < self.bytes
+	}
+	/// Estimated compressed size divided by original size
+	pub fn estimated_ratio(&self) -> f64 {
+		if self.bytes == 0 {
+			return 1.0;
+		}
+		self.estimated_size() as f64 / self.bytes as f64
+	}
+}
+
+/// Magic bytes of formats that are compressed already, with their offset
+const COMPRESSED_MAGICS: &[(usize, &[u8])] = &[
+	(0, b"PK\x03\x04"),         // zip, and jar, docx, ... built on it
+	(0, b"\x1f\x8b"),           // gzip
+	(0, b"BZh"),                // bzip2
+	(0, b"\xfd7zXZ\x00"),       // xz
+	(0, b"\x28\xb5\x2f\xfd"),   // zstd
+	(0, b"7z\xbc\xaf\x27\x1c"), // 7-zip
+	(0, b"\xff\xd8\xff"),       // jpeg
+	(0, b"\x89PNG\r\n\x1a\n"),  // png
+	(0, b"GIF8"),               // gif
+	(4, b"ftyp"),               // mp4, mov and other ISO media
+	(0, b"OggS"),               // ogg
+	(0, b"ID3"),                // mp3
+];
+
+/// Entropy in bits per byte above which data is taken to be compressed already
+const COMPRESSED_ENTROPY: f64 = 7.5;
+
+/// Whether data starting with `sample` is compressed already, so compressing it again
+/// gains nothing. Detects common formats by their magic bytes, anything else by the
+/// entropy of a sample of at least 4 KiB
+pub fn looks_compressed(sample: &[u8]) -> bool {
+	let magic = COMPRESSED_MAGICS.iter().any(|&(offset, magic)| {
+		sample
+			.get(offset..)
+			.is_some_and(|sample| sample.starts_with(magic))
+	});
+	magic || (sample.len() >= 4096 && Analysis::of(sample).entropy() > COMPRESSED_ENTROPY)
+}
+
+/// How a block is coded when the settings are picked for it, see `strategy`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+	/// Stored as is, the data looks random
+	Stored,
+	/// Huffman coded a char at a time
+	Huffman,
+	/// Run-length encoded, then huffman coded
+	Rle,
+	/// Huffman coded a word at a time, for text that repeats itself
+	Words,
+	/// Huffman coded a byte at a time by the previous byte, for other data that repeats itself
+	Context,
+}
+
+/// Bytes of data `strategy` looks at at most
+const SAMPLE_SIZE: usize = 64 << 10;
+/// Number of parts spread over the data a sample is made of
+const SAMPLE_PARTS: usize = 4;
+/// Fraction of bytes repeating the previous one above which runs are worth encoding
+const RUN_FRACTION: f64 = 0.3;
+/// Fraction of positions starting 4 bytes seen before above which data counts as repetitive
+const REPEAT_FRACTION: f64 = 0.5;
+
+/// Parts of `data` to judge it by, all of it if it is short
+fn sample(data: &[u8]) -> Vec<&[u8]> {
+	if data.len() <= SAMPLE_SIZE {
+		return vec![data];
+	}
+	let part = SAMPLE_SIZE / SAMPLE_PARTS;
+	let step = (data.len() - part) / (SAMPLE_PARTS - 1);
+	(0..SAMPLE_PARTS)
+		.map(|i| &data[i * step..i * step + part])
+		.collect()
+}
+
+/// Pick how to code `data` from a sample of it
+///
+/// The entropy tells random data, the share of bytes repeating their predecessor tells
+/// runs, and the share of positions where 4 bytes seen before start tells data with
+/// repeated strings. Coding only keeps what comes out smaller, so a poor pick costs ratio
+/// but never makes a block larger than stored.
+pub fn strategy(data: &[u8]) -> Strategy {
+	let parts = sample(data);
+	let mut analysis = Analysis::default();
+	let (mut repeated_bytes, mut repeated_strings) = (0, 0);
+	let mut seen = std::collections::HashSet::new();
+	for part in &parts {
+		analysis.add(part);
+		repeated_bytes += part.windows(2).filter(|pair| pair[0] == pair[1]).count();
+		for window in part.windows(4) {
+			if !seen.insert(window) {
+				repeated_strings += 1;
+			}
+		}
+	}
+	let len = analysis.bytes.max(1) as f64;
+	if analysis.bytes >= 4096 && analysis.entropy() > COMPRESSED_ENTROPY {
+		Strategy::Stored
+	} else if repeated_bytes as f64 / len > RUN_FRACTION {
+		Strategy::Rle
+	} else if repeated_strings as f64 / len <= REPEAT_FRACTION {
+		Strategy::Huffman
+	} else if analysis.looks_like_text() {
+		Strategy::Words
+	} else {
+		Strategy::Context
+	}
+}
+
+/// Bytes `shifts` judges at a time
+const SHIFT_WINDOW: usize = 4096;
+
+/// `offset`, moved past UTF-8 continuation bytes to the start of the next char
+fn char_start(data: &[u8], mut offset: usize) -> usize {
+	for _ in 0..3 {
+		if data.get(offset).is_some_and(|&byte| byte & 0xC0 == 0x80) {
+			offset += 1;
+		}
+	}
+	offset
+}
+
+/// Offsets where the byte distribution of `data` changes so much that starting a new
+/// block there, with a tree of its own, pays off. Ascending, never 0, and never inside
+/// a char of UTF-8 text
+///
+/// Every window of `SHIFT_WINDOW` bytes is compared with the block it would join, and
+/// starts a new one if its bytes take more than three times the cost of a member header
+/// and tree in extra bits with the distribution of the block, see `Analysis::extra_bits`.
+pub fn shifts(data: &[u8]) -> Vec<usize> {
+	let mut shifts = Vec::new();
+	let mut block = Analysis::default();
+	let mut start = 0;
+	while start < data.len() {
+		// A short last window joins the one before
+		let end = if data.len() - start < 2 * SHIFT_WINDOW {
+			data.len()
+		} else {
+			char_start(data, start + SHIFT_WINDOW)
+		};
+		let window = Analysis::of(&data[start..end]);
+		let overhead = 8 * (FIXED_OVERHEAD + window.tree_size());
+		if block.bytes != 0 && block.extra_bits(&window) > 3.0 * overhead as f64 {
+			shifts.push(start);
+			block = Analysis::default();
+		}
+		block.add(&data[start..end]);
+		start = end;
+	}
+	shifts
+}
+
+/// Shannon entropy in bits per symbol of data with the given symbol counts
+pub fn entropy(symbols: &[u64]) -> f64 {
+	let total: u64 = symbols.iter().sum();
+	symbols
+		.iter()
+		.filter(|&&count| count != 0)
+		.map(|&count| {
+			let p = count as f64 / total as f64;
+			-p * p.log2()
+		})
+		.sum()
+}
+
+/// Size in bits of data with the given symbol counts, when huffman coded
+///
+/// Equals the sum of the weights of all internal nodes of the huffman tree.
+/// A single symbol still takes a bit per occurence.
+pub fn huffman_bits(symbols: &[u64]) -> u64 {
+	let mut heap: BinaryHeap<Reverse<u64>> = symbols
+		.iter()
+		.filter(|&&count| count != 0)
+		.map(|&count| Reverse(count))
+		.collect();
+	if heap.len() == 1 {
+		return heap.pop().unwrap().0;
+	}
+	let mut bits = 0;
+	while heap.len() > 1 {
+		let (Reverse(a), Reverse(b)) = (heap.pop().unwrap(), heap.pop().unwrap());
+		bits += a + b;
+		heap.push(Reverse(a + b));
+	}
+	bits
+}
@@ -0,0 +1,703 @@
+//! Multi-file archive built on top of the huffman codec
+//!
+//! # Data Format
+//! Magic bytes: `HFA\x02`, archives starting with `HFA\x01` lack modification times
+//!
+//! Member data: Output of `huffman::compress_with_options` for every file, back to back
+//!
+//! Index: Number of entries (u32), followed by every entry
+//! - Path length (u16) and UTF-8 path, always using `/` as separator
+//! - Kind (u8): 0 for a file, 1 for a deleted file, 2 for a symlink whose
+//!   member data is its UTF-8 target, stored as is
+//! - Method (u8): 0 for huffman coded data, 1 for data stored as is,
+//!   2 for a deduplicated file whose member data is its chunk list
+//! - Offset of member data from start of archive (u64)
+//! - Length of member data (u64)
+//! - Original size (u64)
+//! - Modification time in seconds since the epoch (u64), 0 when unknown
+//!
+//! Chunk list: Number of chunks (u32), followed by every chunk of the file in order
+//! - Offset of chunk data from start of archive (u64)
+//! - Length of chunk data (u64)
+//! - Original size (u64)
+//! - Method (u8) of the chunk data, 0 or 1 as above
+//! - CRC-32 (u32) and FNV-1a hash (u64) of the original chunk
+//!
+//! Chunk data is written once, by the first file containing the chunk,
+//! and referenced by every later chunk list with the same chunk.
+//!
+//! Trailer: Offset of index (u64), magic bytes
+//!
+//! All integers are little endian.
+//! Empty files have no member data.
+use crate::checksum;
+use crate::compress::huffman::{self, CompressOptions};
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+pub const MAGIC: &[u8; 4] = b"HFA\x02";
+const MAGIC_V1: &[u8; 4] = b"HFA\x01";
+const TRAILER_LEN: u64 = 8 + MAGIC.len() as u64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntryKind {
+	File,
+	/// File present in an older snapshot, but removed since
+	Deleted,
+	Symlink,
+}
+
+/// How the contents of a member are stored
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Method {
+	Huffman,
+	/// Raw bytes, for data that would not benefit from compression
+	Stored,
+	/// Split into chunks stored once per archive, see `ArchiveWriter::set_dedup`
+	Chunked,
+}
+
+impl Method {
+	fn code(self) -> u8 {
+		match self {
+			Method::Huffman => 0,
+			Method::Stored => 1,
+			Method::Chunked => 2,
+		}
+	}
+
+	fn from_code(code: u8) -> io::Result<Method> {
+		match code {
+			0 => Ok(Method::Huffman),
+			1 => Ok(Method::Stored),
+			2 => Ok(Method::Chunked),
+			_ => Err(invalid("Unknown compression method")),
+		}
+	}
+}
+
+/// A piece of a deduplicated file, as recorded in its chunk list
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Chunk {
+	offset: u64,
+	length: u64,
+	size: u64,
+	method: Method,
+	checksum: u32,
+	hash: u64,
+}
+
+const CHUNK_LEN: usize = 8 + 8 + 8 + 1 + 4 + 8;
+const CHUNK_MIN: usize = 2 << 10;
+const CHUNK_MAX: usize = 64 << 10;
+/// Cut where the top 13 bits of the rolling hash are 0, every 8 KiB on average
+const CHUNK_MASK: u64 = !(u64::MAX >> 13);
+
+/// Random value for every byte, mixed into the rolling hash, built at compile time
+const GEAR: [u64; 256] = {
+	let mut table = [0; 256];
+	let mut state: u64 = 0;
+	let mut i = 0;
+	while i < 256 {
+		// splitmix64
+		state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+		let mut z = state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+		table[i] = z ^ (z >> 31);
+		i += 1;
+	}
+	table
+};
+
+/// Split `data` where its contents say so, so that an insertion only changes
+/// the chunks around it. Cuts never fall inside a UTF-8 char.
+fn chunks(data: &[u8]) -> Vec<&[u8]> {
+	let mut chunks = Vec::new();
+	let mut start = 0;
+	let mut hash: u64 = 0;
+	let mut i = 0;
+	while i < data.len() {
+		hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+		i += 1;
+		let len = i - start;
+		if (len >= CHUNK_MIN && hash & CHUNK_MASK == 0) || len >= CHUNK_MAX {
+			while i < data.len() && data[i] & 0xC0 == 0x80 {
+				i += 1;
+			}
+			chunks.push(&data[start..i]);
+			start = i;
+			hash = 0;
+		}
+	}
+	if start < data.len() {
+		chunks.push(&data[start..]);
+	}
+	chunks
+}
+
+/// 64 bit FNV-1a hash, telling chunks apart together with their CRC-32 and size
+fn fnv1a(data: &[u8]) -> u64 {
+	data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+		(hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+	})
+}
+
+fn parse_chunks(mut list: &[u8]) -> io::Result<Vec<Chunk>> {
+	let count = read_u32(&mut list)? as usize;
+	if list.len() != count * CHUNK_LEN {
+		return Err(invalid("Chunk list has the wrong length"));
+	}
+	let mut chunks = Vec::with_capacity(count);
+	for _ in 0..count {
+		let (offset, length, size) = (
+			read_u64(&mut list)?,
+			read_u64(&mut list)?,
+			read_u64(&mut list)?,
+		);
+		let mut method = [0];
+		list.read_exact(&mut method)?;
+		chunks.push(Chunk {
+			offset,
+			length,
+			size,
+			method: Method::from_code(method[0])?,
+			checksum: read_u32(&mut list)?,
+			hash: read_u64(&mut list)?,
+		});
+	}
+	Ok(chunks)
+}
+
+/// A single archive member, as recorded in the index
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+	pub path: String,
+	pub kind: EntryKind,
+	pub method: Method,
+	pub offset: u64,
+	pub length: u64,
+	pub size: u64,
+	/// Seconds since the epoch
+	pub mtime: Option<u64>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u16(input: &mut impl Read) -> io::Result<u16> {
+	let mut buf = [0; 2];
+	input.read_exact(&mut buf)?;
+	Ok(u16::from_le_bytes(buf))
+}
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+	let mut buf = [0; 4];
+	input.read_exact(&mut buf)?;
+	Ok(u32::from_le_bytes(buf))
+}
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+	let mut buf = [0; 8];
+	input.read_exact(&mut buf)?;
+	Ok(u64::from_le_bytes(buf))
+}
+
+/// Writes members one after another, followed by the index on `finish`
+pub struct ArchiveWriter<W: Write> {
+	output: W,
+	offset: u64,
+	index: Vec<Entry>,
+	dedup: bool,
+	/// Chunks written so far, keyed by hash, CRC-32 and size
+	chunks: HashMap<(u64, u32, u64), Chunk>,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+	pub fn new(mut output: W) -> io::Result<ArchiveWriter<W>> {
+		output.write_all(MAGIC)?;
+		Ok(ArchiveWriter {
+			output,
+			offset: MAGIC.len() as u64,
+			index: Vec::new(),
+			dedup: false,
+			chunks: HashMap::new(),
+		})
+	}
+
+	/// Split the files added from now on into content-defined chunks, and store
+	/// each distinct chunk only once. Pays off for many similar files, e.g. backups
+	pub fn set_dedup(&mut self, dedup: bool) {
+		self.dedup = dedup;
+	}
+
+	/// Bytes written so far
+	pub fn position(&self) -> u64 {
+		self.offset
+	}
+
+	/// Write the chunks of `contents` not stored yet, returning the chunk list
+	fn add_chunks(
+		&mut self,
+		contents: &[u8],
+		method: Method,
+		options: &CompressOptions,
+	) -> io::Result<Vec<u8>> {
+		let pieces = chunks(contents);
+		let mut list = Vec::with_capacity(4 + pieces.len() * CHUNK_LEN);
+		list.extend(&(pieces.len() as u32).to_le_bytes());
+		for piece in pieces {
+			let (hash, checksum, size) = (fnv1a(piece), checksum::crc32(piece), piece.len() as u64);
+			let chunk = match self.chunks.get(&(hash, checksum, size)) {
+				Some(&chunk) => chunk,
+				None => {
+					let coded = match std::str::from_utf8(piece) {
+						Ok(text) if method == Method::Huffman => Some(
+							huffman::compress_with_options(text, options)
+								.map_err(|e| invalid(&e.to_string()))?,
+						),
+						_ => None,
+					};
+					let (data, method) = match &coded {
+						Some(coded) if coded.len() < piece.len() => (&coded[..], Method::Huffman),
+						_ => (piece, Method::Stored),
+					};
+					self.output.write_all(data)?;
+					let chunk = Chunk {
+						offset: self.offset,
+						length: data.len() as u64,
+						size,
+						method,
+						checksum,
+						hash,
+					};
+					self.offset += data.len() as u64;
+					self.chunks.insert((hash, checksum, size), chunk);
+					chunk
+				}
+			};
+			list.extend(&chunk.offset.to_le_bytes());
+			list.extend(&chunk.length.to_le_bytes());
+			list.extend(&chunk.size.to_le_bytes());
+			list.push(chunk.method.code());
+			list.extend(&chunk.checksum.to_le_bytes());
+			list.extend(&chunk.hash.to_le_bytes());
+		}
+		Ok(list)
+	}
+
+	/// Store `contents` under `path` using `method`, last modified at `mtime`
+	///
+	/// Huffman coding requires `contents` to be UTF-8. With deduplication on,
+	/// the file is stored as `Method::Chunked` and its chunks use `method`
+	pub fn add_file(
+		&mut self,
+		path: &str,
+		contents: &[u8],
+		method: Method,
+		mtime: Option<u64>,
+	) -> io::Result<()> {
+		self.add_file_with_options(path, contents, method, &CompressOptions::default(), mtime)
+	}
+
+	/// `add_file`, huffman coding with `options`. Archives are read without a dictionary,
+	/// so `options` can't have one
+	pub fn add_file_with_options(
+		&mut self,
+		path: &str,
+		contents: &[u8],
+		method: Method,
+		options: &CompressOptions,
+		mtime: Option<u64>,
+	) -> io::Result<()> {
+		if method == Method::Huffman && std::str::from_utf8(contents).is_err() {
+			return Err(invalid(&(path.to_string() + " is not UTF-8")));
+		}
+		if options.dictionary.is_some() {
+			return Err(invalid("Archives can't be coded with a dictionary"));
+		}
+		let (data, method) = match method {
+			_ if contents.is_empty() => (Vec::new(), method),
+			Method::Chunked => return Err(invalid("Chunked is chosen with set_dedup")),
+			_ if self.dedup => (self.add_chunks(contents, method, options)?, Method::Chunked),
+			// Checked above
+			Method::Huffman => (
+				huffman::compress_with_options(std::str::from_utf8(contents).unwrap(), options)
+					.map_err(|e| invalid(&e.to_string()))?,
+				method,
+			),
+			Method::Stored => (contents.to_vec(), method),
+		};
+		self.output.write_all(&data)?;
+		self.index.push(Entry {
+			path: path.to_string(),
+			kind: EntryKind::File,
+			method,
+			offset: self.offset,
+			length: data.len() as u64,
+			size: contents.len() as u64,
+			mtime,
+		});
+		self.offset += data.len() as u64;
+		Ok(())
+	}
+
+	/// Store a symlink at `path` pointing to `target`
+	pub fn add_symlink(&mut self, path: &str, target: &str, mtime: Option<u64>) -> io::Result<()> {
+		self.output.write_all(target.as_bytes())?;
+		self.index.push(Entry {
+			path: path.to_string(),
+			kind: EntryKind::Symlink,
+			method: Method::Stored,
+			offset: self.offset,
+			length: target.len() as u64,
+			size: target.len() as u64,
+			mtime,
+		});
+		self.offset += target.len() as u64;
+		Ok(())
+	}
+
+	/// Record that `path` no longer exists
+	pub fn add_deletion(&mut self, path: &str) {
+		self.index.push(Entry {
+			path: path.to_string(),
+			kind: EntryKind::Deleted,
+			method: Method::Stored,
+			offset: self.offset,
+			length: 0,
+			size: 0,
+			mtime: None,
+		});
+	}
+
+	/// Entries added so far, including those of an archive opened with `append`
+	pub fn entries(&self) -> &[Entry] {
+		&self.index
+	}
+
+	/// Write the index and trailer, returning the underlying writer
+	pub fn finish(mut self) -> io::Result<W> {
+		let mut index = Vec::new();
+		index.extend(&(self.index.len() as u32).to_le_bytes());
+		for entry in &self.index {
+			if entry.path.len() > u16::MAX as usize {
+				return Err(invalid("Path too long for archive"));
+			}
+			index.extend(&(entry.path.len() as u16).to_le_bytes());
+			index.extend(entry.path.as_bytes());
+			index.push(match entry.kind {
+				EntryKind::File => 0,
+				EntryKind::Deleted => 1,
+				EntryKind::Symlink => 2,
+			});
+			index.push(entry.method.code());
+			index.extend(&entry.offset.to_le_bytes());
+			index.extend(&entry.length.to_le_bytes());
+			index.extend(&entry.size.to_le_bytes());
+			index.extend(&entry.mtime.unwrap_or(0).to_le_bytes());
+		}
+		index.extend(&self.offset.to_le_bytes());
+		index.extend(MAGIC);
+		self.output.write_all(&index)?;
+		self.output.flush()?;
+		Ok(self.output)
+	}
+}
+
+impl<W: Read + Write + Seek> ArchiveWriter<W> {
+	/// Open an existing archive to add more members
+	///
+	/// New members overwrite the old index, which `finish` writes again with
+	/// all entries. Members of a path already present take precedence over it.
+	/// Chunks of deduplicated files already in the archive are reused.
+	pub fn append(output: W) -> io::Result<ArchiveWriter<W>> {
+		let mut archive = Archive::new(output)?;
+		let mut chunks = HashMap::new();
+		for entry in archive.entries.clone() {
+			if entry.method == Method::Chunked && entry.length > 0 {
+				for chunk in archive.read_chunks(&entry)? {
+					chunks.insert((chunk.hash, chunk.checksum, chunk.size), chunk);
+				}
+			}
+		}
+		let Archive {
+			input: mut output,
+			entries,
+			index_offset,
+		} = archive;
+		// The index is written in the current version
+		output.seek(SeekFrom::Start(0))?;
+		output.write_all(MAGIC)?;
+		output.seek(SeekFrom::Start(index_offset))?;
+		Ok(ArchiveWriter {
+			output,
+			offset: index_offset,
+			index: entries,
+			dedup: false,
+			chunks,
+		})
+	}
+}
+
+/// An archive opened for reading, with its index loaded
+pub struct Archive<R: Read + Seek> {
+	input: R,
+	entries: Vec<Entry>,
+	index_offset: u64,
+}
+
+impl<R: Read + Seek> Archive<R> {
+	pub fn new(mut input: R) -> io::Result<Archive<R>> {
+		let mut magic = [0; 4];
+		input.seek(SeekFrom::Start(0))?;
+		input.read_exact(&mut magic)?;
+		if &magic != MAGIC && &magic != MAGIC_V1 {
+			return Err(invalid("Not an archive"));
+		}
+		let version = magic;
+		let end = input.seek(SeekFrom::End(0))?;
+		if end < MAGIC.len() as u64 + TRAILER_LEN {
+			return Err(invalid("Archive truncated"));
+		}
+		input.seek(SeekFrom::Start(end - TRAILER_LEN))?;
+		let index_offset = read_u64(&mut input)?;
+		input.read_exact(&mut magic)?;
+		if magic != version || index_offset > end - TRAILER_LEN {
+			return Err(invalid("Archive index missing"));
+		}
+		input.seek(SeekFrom::Start(index_offset))?;
+		let count = read_u32(&mut input)?;
+		let mut entries = Vec::new();
+		for _ in 0..count {
+			let mut path = vec![0; read_u16(&mut input)? as usize];
+			input.read_exact(&mut path)?;
+			let path = String::from_utf8(path).map_err(|_| invalid("Path is not UTF-8"))?;
+			let mut kind = [0; 2];
+			input.read_exact(&mut kind)?;
+			let (kind, method) = (kind[0], kind[1]);
+			let kind = match kind {
+				0 => EntryKind::File,
+				1 => EntryKind::Deleted,
+				2 => EntryKind::Symlink,
+				_ => return Err(invalid("Unknown entry kind")),
+			};
+			let method = Method::from_code(method)?;
+			let (offset, length, size) = (
+				read_u64(&mut input)?,
+				read_u64(&mut input)?,
+				read_u64(&mut input)?,
+			);
+			let mtime = match &version {
+				MAGIC => Some(read_u64(&mut input)?).filter(|&mtime| mtime != 0),
+				_ => None,
+			};
+			if offset
+				.checked_add(length)
+				.filter(|&end| end <= index_offset)
+				.is_none()
+			{
+				return Err(invalid("Entry lies outside archive"));
+			}
+			entries.push(Entry {
+				path,
+				kind,
+				method,
+				offset,
+				length,
+				size,
+				mtime,
+			});
+		}
+		Ok(Archive {
+			input,
+			entries,
+			index_offset,
+		})
+	}
+
+	/// Offset of the index, where members appended to the archive start
+	pub fn index_offset(&self) -> u64 {
+		self.index_offset
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	/// Latest entry stored under `path`, `None` if there is none or it was deleted
+	pub fn find(&self, path: &str) -> Option<&Entry> {
+		self.entries
+			.iter()
+			.rev()
+			.find(|entry| entry.path == path)
+			.filter(|entry| entry.kind != EntryKind::Deleted)
+	}
+
+	/// Seek to `entry` and read its member data without decompressing it
+	pub fn read_raw(&mut self, entry: &Entry) -> io::Result<Vec<u8>> {
+		let mut data = vec![0; entry.length as usize];
+		self.input.seek(SeekFrom::Start(entry.offset))?;
+		self.input.read_exact(&mut data)?;
+		Ok(data)
+	}
+
+	/// Target of the symlink `entry`
+	pub fn read_link(&mut self, entry: &Entry) -> io::Result<String> {
+		String::from_utf8(self.read_raw(entry)?).map_err(|_| invalid("Link target is not UTF-8"))
+	}
+
+	/// Chunk list of the deduplicated file `entry`, with every chunk inside the archive
+	fn read_chunks(&mut self, entry: &Entry) -> io::Result<Vec<Chunk>> {
+		let chunks = parse_chunks(&self.read_raw(entry)?)?;
+		let outside = chunks.iter().any(|chunk| {
+			chunk.method == Method::Chunked
+				|| chunk
+					.offset
+					.checked_add(chunk.length)
+					.filter(|&end| end <= self.index_offset)
+					.is_none()
+		});
+		if outside {
+			return Err(invalid("Chunk lies outside archive"));
+		}
+		Ok(chunks)
+	}
+
+	/// Seek to `entry` and decompress its contents
+	pub fn read(&mut self, entry: &Entry) -> io::Result<Vec<u8>> {
+		if entry.length == 0 {
+			return Ok(Vec::new());
+		}
+		let data = self.read_raw(entry)?;
+		match entry.method {
+			Method::Huffman => huffman::decompress(&data)
+				.map(String::into_bytes)
+				.map_err(|e| invalid(&e.to_string())),
+			Method::Stored => Ok(data),
+			Method::Chunked => {
+				let mut contents = Vec::new();
+				for chunk in self.read_chunks(entry)? {
+					let piece = Entry {
+						method: chunk.method,
+						offset: chunk.offset,
+						length: chunk.length,
+						size: chunk.size,
+						..entry.clone()
+					};
+					let piece = self.read(&piece)?;
+					if checksum::crc32(&piece) != chunk.checksum {
+						return Err(invalid("Chunk checksum mismatch"));
+					}
+					contents.extend(piece);
+				}
+				if contents.len() as u64 != entry.size {
+					return Err(invalid("File size mismatch"));
+				}
+				Ok(contents)
+			}
+		}
+	}
+
+	/// Seek to `entry` and decompress `range` of its contents, cut short at their end.
+	/// Only the blocks and chunks holding it are read and decoded, skipping the others
+	/// by the lengths their headers store
+	pub fn read_range(&mut self, entry: &Entry, range: Range<u64>) -> io::Result<Vec<u8>> {
+		let range = range.start.min(entry.size)..range.end.min(entry.size);
+		if entry.length == 0 || range.is_empty() {
+			return Ok(Vec::new());
+		}
+		match entry.method {
+			Method::Huffman => self.read_blocks(entry, range),
+			Method::Stored => {
+				let mut data = vec![0; (range.end - range.start) as usize];
+				self.input
+					.seek(SeekFrom::Start(entry.offset + range.start))?;
+				self.input.read_exact(&mut data)?;
+				Ok(data)
+			}
+			Method::Chunked => {
+				let mut contents = Vec::new();
+				let mut position = 0;
+				for chunk in self.read_chunks(entry)? {
+					if position >= range.end {
+						break;
+					}
+					let start = position;
+					position += chunk.size;
+					if position <= range.start {
+						continue;
+					}
+					let piece = Entry {
+						method: chunk.method,
+						offset: chunk.offset,
+						length: chunk.length,
+						size: chunk.size,
+						..entry.clone()
+					};
+					let piece = self.read(&piece)?;
+					if checksum::crc32(&piece) != chunk.checksum {
+						return Err(invalid("Chunk checksum mismatch"));
+					}
+					let from = range.start.saturating_sub(start) as usize;
+					let to = ((range.end - start) as usize).min(piece.len());
+					contents.extend_from_slice(&piece[from.min(to)..to]);
+				}
+				Ok(contents)
+			}
+		}
+	}
+
+	/// `range` of the huffman coded `entry`, for `read_range`
+	fn read_blocks(&mut self, entry: &Entry, range: Range<u64>) -> io::Result<Vec<u8>> {
+		let corrupt = |e: huffman::DecompressError| invalid(&e.to_string());
+		let end = entry.offset + entry.length;
+		let mut contents = Vec::new();
+		let (mut offset, mut position) = (entry.offset, 0);
+		while offset < end && position < range.end {
+			// Headers are short unless they hold a long name
+			let mut header = Vec::new();
+			let (length, size) = loop {
+				let probe = (header.len() * 2).max(64).min((end - offset) as usize);
+				header.resize(probe, 0);
+				self.input.seek(SeekFrom::Start(offset))?;
+				self.input.read_exact(&mut header)?;
+				match huffman::member_sizes(&header).map_err(corrupt)? {
+					Some(sizes) => break sizes,
+					None if probe as u64 == end - offset => {
+						return Err(invalid("Block header truncated"))
+					}
+					None => {}
+				}
+			};
+			if length > end - offset {
+				return Err(invalid("Block lies outside entry"));
+			}
+			if size.is_some_and(|size| position + size <= range.start) {
+				position += size.unwrap();
+				offset += length;
+				continue;
+			}
+			let mut block = vec![0; length as usize];
+			self.input.seek(SeekFrom::Start(offset))?;
+			self.input.read_exact(&mut block)?;
+			let text = huffman::decompress_bytes(&block, None).map_err(corrupt)?;
+			let from = range.start.saturating_sub(position) as usize;
+			let to = ((range.end - position) as usize).min(text.len());
+			contents.extend_from_slice(&text[from.min(to)..to]);
+			position += text.len() as u64;
+			offset += length;
+		}
+		Ok(contents)
+	}
+
+	/// Decompress `entry` and check it against its embedded model,
+	/// see `huffman::check_model`. Entries that aren't huffman coded can't be checked.
+	pub fn check(&mut self, entry: &Entry) -> io::Result<Option<huffman::ModelCheck>> {
+		if entry.length == 0 || entry.method != Method::Huffman {
+			return Ok(None);
+		}
+		let data = self.read_raw(entry)?;
+		huffman::check_model(&data).map_err(|e| invalid(&e.to_string()))
+	}
+}
@@ -0,0 +1,194 @@
+//! Output files that only appear under their name once completely written
+//!
+//! Data goes to a hidden temporary file next to the destination, which is renamed
+//! to it on `commit`. Renaming within a directory is atomic, so an interrupted run
+//! leaves either the old file or the complete new one, never a truncated file.
+//!
+//! Files being written can also be registered as `Pending`, so that `undo_pending`
+//! cleans up after them when the process is interrupted, see `interrupt`: temporary
+//! files are removed, and files changed in place get their old bytes back.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// How to clean up after a file that isn't complete
+enum Undo {
+	Remove(PathBuf),
+	/// Put back the length of the file and the bytes at every offset
+	Restore {
+		path: PathBuf,
+		len: u64,
+		saved: Vec<(u64, Vec<u8>)>,
+	},
+}
+
+impl Undo {
+	fn run(&self) -> io::Result<()> {
+		match self {
+			Undo::Remove(path) => match fs::remove_file(path) {
+				Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+				result => result,
+			},
+			Undo::Restore { path, len, saved } => {
+				let mut file = OpenOptions::new().write(true).open(path)?;
+				for (offset, bytes) in saved {
+					file.seek(SeekFrom::Start(*offset))?;
+					file.write_all(bytes)?;
+				}
+				file.set_len(*len)?;
+				file.sync_all()
+			}
+		}
+	}
+}
+
+/// Files registered by `Pending`, by id
+static PENDING: Mutex<Vec<(u64, Undo)>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A file that `undo_pending` cleans up after until this is dropped, once the file is
+/// complete
+pub struct Pending {
+	id: u64,
+}
+
+impl Pending {
+	fn register(undo: Undo) -> Pending {
+		let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+		PENDING
+			.lock()
+			.unwrap_or_else(|e| e.into_inner())
+			.push((id, undo));
+		Pending { id }
+	}
+
+	/// Remove `path` if interrupted, for output written from scratch
+	pub fn remove<P: AsRef<Path>>(path: P) -> Pending {
+		Pending::register(Undo::Remove(path.as_ref().to_path_buf()))
+	}
+
+	/// Put back the bytes of `ranges` and the length `path` has now if interrupted,
+	/// for a file about to be changed in place there. Ranges may extend past its end
+	pub fn restore<P: AsRef<Path>>(path: P, ranges: &[Range<u64>]) -> io::Result<Pending> {
+		let path = path.as_ref();
+		let mut file = File::open(path)?;
+		let len = file.metadata()?.len();
+		let mut saved = Vec::new();
+		for range in ranges {
+			let start = range.start.min(len);
+			let mut bytes = Vec::new();
+			file.seek(SeekFrom::Start(start))?;
+			(&mut file)
+				.take(range.end.min(len) - start)
+				.read_to_end(&mut bytes)?;
+			saved.push((start, bytes));
+		}
+		Ok(Pending::register(Undo::Restore {
+			path: path.to_path_buf(),
+			len,
+			saved,
+		}))
+	}
+}
+
+impl Drop for Pending {
+	fn drop(&mut self) {
+		let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+		pending.retain(|(id, _)| *id != self.id);
+	}
+}
+
+/// Clean up after every file still pending, for a process about to exit on an
+/// interrupt. Files are left as they are from then on: the registry stays locked,
+/// which holds up any thread finishing or starting one until the process has exited
+pub fn undo_pending() {
+	let pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+	for (_, undo) in pending.iter() {
+		if let Err(e) = undo.run() {
+			let path = match undo {
+				Undo::Remove(path) | Undo::Restore { path, .. } => path,
+			};
+			eprintln!("Error cleaning up: {}: {}", path.display(), e);
+		}
+	}
+	std::mem::forget(pending);
+}
+
+/// A file being written to a temporary name, removed again unless committed
+pub struct AtomicFile {
+	file: File,
+	temp: PathBuf,
+	path: PathBuf,
+	committed: bool,
+	/// Removes the temporary file if interrupted, dropped after it is renamed
+	_pending: Pending,
+}
+
+impl AtomicFile {
+	/// Start writing the file `path`, in a temporary file of the same directory
+	pub fn create<P: AsRef<Path>>(path: P) -> io::Result<AtomicFile> {
+		let path = path.as_ref();
+		let name = path
+			.file_name()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a file name"))?;
+		for attempt in 0.. {
+			let mut temp_name = std::ffi::OsString::from(".");
+			temp_name.push(name);
+			temp_name.push(format!(".{}.{}.tmp", process::id(), attempt));
+			let temp = path.with_file_name(temp_name);
+			// Registered first, so there is no moment the file exists unregistered
+			let pending = Pending::remove(&temp);
+			match OpenOptions::new().write(true).create_new(true).open(&temp) {
+				Ok(file) => {
+					return Ok(AtomicFile {
+						file,
+						temp,
+						path: path.to_path_buf(),
+						committed: false,
+						_pending: pending,
+					})
+				}
+				// Left behind by a run that was killed
+				Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+				Err(e) => return Err(e),
+			}
+		}
+		unreachable!()
+	}
+
+	/// Flush everything to disk and move the file to its name, replacing any file there
+	pub fn commit(mut self) -> io::Result<File> {
+		self.file.sync_all()?;
+		fs::rename(&self.temp, &self.path)?;
+		self.committed = true;
+		self.file.try_clone()
+	}
+}
+
+impl Write for AtomicFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.file.write(buf)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
+
+impl Drop for AtomicFile {
+	fn drop(&mut self) {
+		if !self.committed {
+			let _ = fs::remove_file(&self.temp);
+		}
+	}
+}
+
+/// Write `data` to the file `path` atomically, like `fs::write` otherwise
+pub fn write<P: AsRef<Path>>(path: P, data: &[u8]) -> io::Result<()> {
+	let mut file = AtomicFile::create(path)?;
+	file.write_all(data)?;
+	file.commit().map(|_| ())
+}
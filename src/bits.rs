@@ -0,0 +1,100 @@
+//! Bit level I/O shared by every code in this crate
+//!
+//! Bits are packed most significant bit first, the final byte is padded with zero bits.
+use std::io::{self, Read, Write};
+
+/// Packs bits into bytes written to `W`
+pub struct BitWriter<W: Write> {
+	inner: W,
+	byte: u8,
+	/// Number of bits used in `byte`
+	used: u8,
+}
+
+impl<W: Write> BitWriter<W> {
+	pub fn new(inner: W) -> BitWriter<W> {
+		BitWriter {
+			inner,
+			byte: 0,
+			used: 0,
+		}
+	}
+	pub fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+		self.byte |= (bit as u8) << (7 - self.used);
+		self.used += 1;
+		if self.used == 8 {
+			self.inner.write_all(&[self.byte])?;
+			self.byte = 0;
+			self.used = 0;
+		}
+		Ok(())
+	}
+	/// Write the lowest `count` bits of `value`, most significant first
+	pub fn write_bits(&mut self, value: u64, count: u8) -> io::Result<()> {
+		for i in (0..count).rev() {
+			self.write_bit(value >> i & 1 != 0)?;
+		}
+		Ok(())
+	}
+	/// Number of zero bits `finish` will append to complete the last byte
+	pub fn padding(&self) -> u8 {
+		(8 - self.used) % 8
+	}
+	/// Pad the last byte with zero bits and write it, returning the writer and the padding
+	pub fn finish(mut self) -> io::Result<(W, u8)> {
+		let padding = self.padding();
+		if self.used != 0 {
+			self.inner.write_all(&[self.byte])?;
+		}
+		Ok((self.inner, padding))
+	}
+}
+
+/// Reads bits packed by `BitWriter` from `R`
+pub struct BitReader<R: Read> {
+	inner: R,
+	byte: u8,
+	/// Number of bits of `byte` not read yet
+	left: u8,
+}
+
+impl<R: Read> BitReader<R> {
+	pub fn new(inner: R) -> BitReader<R> {
+		BitReader {
+			inner,
+			byte: 0,
+			left: 0,
+		}
+	}
+	/// The next bit, `None` at the end of the input
+	pub fn read_bit(&mut self) -> io::Result<Option<bool>> {
+		if self.left == 0 {
+			let mut byte = [0];
+			if self.inner.read(&mut byte)? == 0 {
+				return Ok(None);
+			}
+			self.byte = byte[0];
+			self.left = 8;
+		}
+		self.left -= 1;
+		Ok(Some(self.byte >> self.left & 1 != 0))
+	}
+	/// The next `count` bits, most significant first, `None` if the input ends before
+	pub fn read_bits(&mut self, count: u8) -> io::Result<Option<u64>> {
+		let mut value = 0;
+		for _ in 0..count {
+			match self.read_bit()? {
+				Some(bit) => value = value << 1 | bit as u64,
+				None => return Ok(None),
+			}
+		}
+		Ok(Some(value))
+	}
+	/// Whether the rest of the input is nothing but zero bits padding the current byte
+	pub fn at_padding(&mut self) -> io::Result<bool> {
+		if self.byte & ((1u16 << self.left) - 1) as u8 != 0 {
+			return Ok(false);
+		}
+		Ok(self.inner.read(&mut [0])? == 0)
+	}
+}
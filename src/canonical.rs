@@ -0,0 +1,200 @@
+//! Canonical huffman codes over symbol indices
+//!
+//! A canonical code is fully described by the code length of every symbol,
+//! so that is all a format needs to store. Symbols of length 0 have no code.
+use crate::bits::BitReader;
+use crate::compress::huffman::CompressError;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Read};
+
+/// Code lengths of an optimal prefix code for the given symbol counts
+///
+/// A symbol occurring on its own still gets a 1 bit code.
+pub fn code_lengths(frequencies: &[u64]) -> Vec<u8> {
+	let mut heap: BinaryHeap<Reverse<(u64, usize)>> = frequencies
+		.iter()
+		.enumerate()
+		.filter(|(_, &count)| count != 0)
+		.map(|(symbol, &count)| Reverse((count, symbol)))
+		.collect();
+	let mut lengths = vec![0; frequencies.len()];
+	if heap.len() == 1 {
+		let Reverse((_, symbol)) = heap.pop().unwrap();
+		lengths[symbol] = 1;
+		return lengths;
+	}
+	// Nodes are numbered leaves first, every parent is numbered after its children
+	let mut parent = vec![0; frequencies.len()];
+	while heap.len() > 1 {
+		let (Reverse((a, left)), Reverse((b, right))) = (heap.pop().unwrap(), heap.pop().unwrap());
+		let node = parent.len();
+		parent.push(0);
+		parent[left] = node;
+		parent[right] = node;
+		heap.push(Reverse((a + b, node)));
+	}
+	let mut depth = vec![0u8; parent.len()];
+	for node in (0..parent.len().saturating_sub(1)).rev() {
+		depth[node] = depth[parent[node]] + 1;
+	}
+	for (symbol, length) in lengths.iter_mut().enumerate() {
+		if frequencies[symbol] != 0 {
+			*length = depth[symbol];
+		}
+	}
+	lengths
+}
+
+/// Code lengths of an optimal prefix code for the given symbol counts with
+/// no code longer than `max_length`, `None` if the symbols don't fit in that many bits
+///
+/// Lengths come from `code_lengths` when they already fit, otherwise from the
+/// package-merge algorithm.
+pub fn limited_code_lengths(frequencies: &[u64], max_length: u8) -> Option<Vec<u8>> {
+	let lengths = code_lengths(frequencies);
+	if lengths.iter().all(|&length| length <= max_length) {
+		return Some(lengths);
+	}
+	let mut leaves: Vec<(u64, usize)> = frequencies
+		.iter()
+		.enumerate()
+		.filter(|(_, &count)| count != 0)
+		.map(|(symbol, &count)| (count, symbol))
+		.collect();
+	if leaves.len() as u128 > 1u128 << max_length.min(64) {
+		return None;
+	}
+	leaves.sort_unstable();
+
+	// Every list holds the leaves merged with packages of pairs of the list before,
+	// as (weight, leaf); the first list is just the leaves
+	let mut lists: Vec<Vec<(u64, Option<usize>)>> = Vec::new();
+	let mut packages: Vec<u64> = Vec::new();
+	for _ in 0..max_length {
+		let mut list = Vec::with_capacity(leaves.len() + packages.len());
+		let mut pending = packages.iter().copied().peekable();
+		for &(count, symbol) in &leaves {
+			while let Some(package) = pending.next_if(|&package| package < count) {
+				list.push((package, None));
+			}
+			list.push((count, Some(symbol)));
+		}
+		list.extend(pending.map(|package| (package, None)));
+		lists.push(list);
+		packages = lists
+			.last()
+			.unwrap()
+			.chunks_exact(2)
+			.map(|pair| pair[0].0 + pair[1].0)
+			.collect();
+	}
+
+	// The cheapest 2n - 2 items of the last list make the code. Every leaf among them
+	// adds a bit to its symbol, every package selects two items of the list before
+	let mut lengths = vec![0; frequencies.len()];
+	let mut selected = 2 * leaves.len() - 2;
+	for list in lists.iter().rev() {
+		let mut packages = 0;
+		for &(_, leaf) in &list[..selected] {
+			match leaf {
+				Some(symbol) => lengths[symbol] += 1,
+				None => packages += 1,
+			}
+		}
+		selected = 2 * packages;
+	}
+	Some(lengths)
+}
+
+/// Code lengths from `code_lengths`, or from `limited_code_lengths` if `max_length` is given
+pub(crate) fn code_lengths_within(
+	frequencies: &[u64],
+	max_length: Option<u8>,
+) -> Result<Vec<u8>, CompressError> {
+	match max_length {
+		Some(max_length) => limited_code_lengths(frequencies, max_length)
+			.ok_or(CompressError::CodeLengthLimit(max_length)),
+		None => Ok(code_lengths(frequencies)),
+	}
+}
+
+/// Code of every symbol as `(bits, length)`, assigned in canonical order:
+/// shorter codes first, equal lengths by symbol index
+///
+/// Lengths must come from `code_lengths` or be checked by `Decoder::new`
+pub fn codes(lengths: &[u8]) -> Vec<(u64, u8)> {
+	let max = lengths.iter().copied().max().unwrap_or(0) as usize;
+	let mut counts = vec![0u64; max + 1];
+	for &length in lengths {
+		counts[length as usize] += 1;
+	}
+	counts[0] = 0;
+	let mut next = vec![0u64; max + 1];
+	let mut code = 0;
+	for length in 1..=max {
+		code = (code + counts[length - 1]) << 1;
+		next[length] = code;
+	}
+	lengths
+		.iter()
+		.map(|&length| {
+			if length == 0 {
+				return (0, 0);
+			}
+			let code = next[length as usize];
+			next[length as usize] += 1;
+			(code, length)
+		})
+		.collect()
+}
+
+/// Decodes symbols of a canonical code
+pub struct Decoder {
+	/// Number of codes of every length
+	counts: Vec<u64>,
+	/// Symbols in canonical order
+	symbols: Vec<usize>,
+}
+
+impl Decoder {
+	/// Returns `None` if the lengths don't describe a prefix code
+	pub fn new(lengths: &[u8]) -> Option<Decoder> {
+		let max = lengths.iter().copied().max().unwrap_or(0) as usize;
+		if max > 63 {
+			return None;
+		}
+		let mut counts = vec![0u64; max + 1];
+		for &length in lengths {
+			counts[length as usize] += 1;
+		}
+		counts[0] = 0;
+		// Kraft inequality, scaled by 2^max
+		let kraft: u128 = (1..=max)
+			.map(|length| (counts[length] as u128) << (max - length))
+			.sum();
+		if kraft > 1 << max {
+			return None;
+		}
+		let mut symbols: Vec<usize> = (0..lengths.len()).filter(|&s| lengths[s] != 0).collect();
+		symbols.sort_by_key(|&symbol| lengths[symbol]);
+		Some(Decoder { counts, symbols })
+	}
+	/// Read the next symbol, `None` if the bits run out or match no code
+	pub fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> io::Result<Option<usize>> {
+		let (mut code, mut first, mut index) = (0u64, 0u64, 0u64);
+		for &count in &self.counts[1..] {
+			match bits.read_bit()? {
+				Some(bit) => code |= bit as u64,
+				None => return Ok(None),
+			}
+			if code - first < count {
+				return Ok(Some(self.symbols[(index + code - first) as usize]));
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Ok(None)
+	}
+}
@@ -0,0 +1,53 @@
+//! CRC-32 (IEEE 802.3, as used by gzip and zip)
+
+/// Lookup table for the reflected polynomial 0xEDB88320, built at compile time
+const TABLE: [u32; 256] = {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u32;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 != 0 {
+				0xEDB8_8320 ^ (crc >> 1)
+			} else {
+				crc >> 1
+			};
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+};
+
+/// Running CRC-32, for data that arrives in pieces
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+	fn default() -> Crc32 {
+		Crc32::new()
+	}
+}
+
+impl Crc32 {
+	pub fn new() -> Crc32 {
+		Crc32(0xFFFF_FFFF)
+	}
+	pub fn update(&mut self, data: &[u8]) {
+		for &byte in data {
+			self.0 = TABLE[((self.0 ^ byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+		}
+	}
+	pub fn value(&self) -> u32 {
+		!self.0
+	}
+}
+
+/// CRC-32 of `data` in one go
+pub fn crc32(data: &[u8]) -> u32 {
+	let mut crc = Crc32::new();
+	crc.update(data);
+	crc.value()
+}
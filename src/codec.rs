@@ -0,0 +1,218 @@
+//! Compression algorithms behind one interface, picked by name with `--algo`
+//!
+//! Everything a codec encodes starts with its own magic bytes, so `detect` finds
+//! the codec to decode it with.
+use crate::checksum;
+use crate::compress::huffman::{self, Compressor, DecompressError};
+use crate::lzw;
+use crate::rle;
+use crate::wide;
+use std::io::{self, Read, Write};
+
+/// A compression algorithm with its own data format
+pub trait Codec {
+	/// Name selecting the codec, see `by_name`
+	fn name(&self) -> &'static str;
+	/// Bytes starting all data `encode` writes
+	fn magic(&self) -> &'static [u8];
+	/// Compress all of `data` to `output`
+	fn encode(&self, data: &[u8], output: &mut dyn Write) -> io::Result<()>;
+	/// Decompress all `input` produces to `output`, corrupt data fails with `InvalidData`
+	fn decode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Names of all codecs, the first is the default
+pub const NAMES: &[&str] = &["huffman", "rle", "stored", "wide", "lzw"];
+
+/// Codec called `name`, with default settings
+pub fn by_name(name: &str) -> Option<Box<dyn Codec>> {
+	match name {
+		"huffman" => Some(Box::new(Huffman::default())),
+		"rle" => Some(Box::new(Rle)),
+		"stored" => Some(Box::new(Stored)),
+		"wide" => Some(Box::new(Wide)),
+		"lzw" => Some(Box::new(Lzw)),
+		_ => None,
+	}
+}
+
+/// Codec whose magic bytes start `data`
+pub fn detect(data: &[u8]) -> Option<Box<dyn Codec>> {
+	NAMES
+		.iter()
+		.filter_map(|name| by_name(name))
+		.find(|codec| data.starts_with(codec.magic()))
+}
+
+/// Huffman coding as done by `huffman::compress_stream`, with the settings of a `Compressor`
+#[derive(Clone, Default, Debug)]
+pub struct Huffman<'a> {
+	compressor: Compressor<'a>,
+}
+
+impl<'a> Huffman<'a> {
+	/// Decoding uses the dictionary of `compressor`, if any
+	pub fn new(compressor: Compressor<'a>) -> Huffman<'a> {
+		Huffman { compressor }
+	}
+}
+
+impl Codec for Huffman<'_> {
+	fn name(&self) -> &'static str {
+		"huffman"
+	}
+	fn magic(&self) -> &'static [u8] {
+		huffman::MAGIC
+	}
+	fn encode(&self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+		self.compressor.compress_stream(data, output).map(|_| ())
+	}
+	fn decode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+		let dictionary = self.compressor.options().dictionary;
+		huffman::decompress_stream(input, output, dictionary).map(|_| ())
+	}
+}
+
+/// Run-length encoding alone, see `rle`, for any bytes
+///
+/// # Data Format
+/// Magic bytes `HUR\x01`, the run-length encoded data, then the CRC-32 of the data (u32, little endian)
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Rle;
+
+impl Codec for Rle {
+	fn name(&self) -> &'static str {
+		"rle"
+	}
+	fn magic(&self) -> &'static [u8] {
+		b"HUR\x01"
+	}
+	fn encode(&self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+		output.write_all(self.magic())?;
+		output.write_all(&rle::encode(data))?;
+		output.write_all(&checksum::crc32(data).to_le_bytes())
+	}
+	fn decode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+		let decoded = unframe(self.magic(), input, "Not run-length encoded data", |data| {
+			rle::decode(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+		})?;
+		output.write_all(&decoded)
+	}
+}
+
+/// Data as is, for data that doesn't compress, like files compressed already
+///
+/// # Data Format
+/// Magic bytes `HUS\x01`, the data, then the CRC-32 of the data (u32, little endian)
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Stored;
+
+impl Codec for Stored {
+	fn name(&self) -> &'static str {
+		"stored"
+	}
+	fn magic(&self) -> &'static [u8] {
+		b"HUS\x01"
+	}
+	fn encode(&self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+		output.write_all(self.magic())?;
+		output.write_all(data)?;
+		output.write_all(&checksum::crc32(data).to_le_bytes())
+	}
+	fn decode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+		let decoded = unframe(self.magic(), input, "Not stored data", |data| {
+			Ok(data.to_vec())
+		})?;
+		output.write_all(&decoded)
+	}
+}
+
+/// Huffman coding of 16-bit units, see `wide`, for UTF-16 text and other data
+/// of 2 byte units
+///
+/// # Data Format
+/// Magic bytes `HUW\x01`, the encoded data, then the CRC-32 of the data (u32, little endian)
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Wide;
+
+impl Codec for Wide {
+	fn name(&self) -> &'static str {
+		"wide"
+	}
+	fn magic(&self) -> &'static [u8] {
+		b"HUW\x01"
+	}
+	fn encode(&self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+		output.write_all(self.magic())?;
+		output.write_all(&wide::encode(data))?;
+		output.write_all(&checksum::crc32(data).to_le_bytes())
+	}
+	fn decode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+		let decoded = unframe(self.magic(), input, "Not 16-bit coded data", |data| {
+			wide::decode(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+		})?;
+		output.write_all(&decoded)
+	}
+}
+
+/// LZW, see `lzw`, coding repeated strings without storing a table or tree
+///
+/// # Data Format
+/// Magic bytes `HUZ\x01`, the encoded data, then the CRC-32 of the data (u32, little endian)
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Lzw;
+
+impl Codec for Lzw {
+	fn name(&self) -> &'static str {
+		"lzw"
+	}
+	fn magic(&self) -> &'static [u8] {
+		b"HUZ\x01"
+	}
+	fn encode(&self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+		output.write_all(self.magic())?;
+		output.write_all(&lzw::encode(data))?;
+		output.write_all(&checksum::crc32(data).to_le_bytes())
+	}
+	fn decode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+		let decoded = unframe(self.magic(), input, "Not LZW coded data", |data| {
+			lzw::decode(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+		})?;
+		output.write_all(&decoded)
+	}
+}
+
+/// Read data framed by `magic` and a CRC-32, decode what is between and check the CRC-32
+fn unframe<F: Fn(&[u8]) -> io::Result<Vec<u8>>>(
+	magic: &[u8],
+	input: &mut dyn Read,
+	not_framed: &str,
+	decode: F,
+) -> io::Result<Vec<u8>> {
+	let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+	let mut data = Vec::new();
+	input.read_to_end(&mut data)?;
+	if !data.starts_with(magic) {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, not_framed));
+	}
+	let data = &data[magic.len()..];
+	if data.len() < 4 {
+		return Err(invalid(DecompressError::Truncated {
+			field: "checksum",
+			offset: magic.len(),
+			expected: 4,
+			found: data.len(),
+		}));
+	}
+	let (encoded, crc) = data.split_at(data.len() - 4);
+	let decoded = decode(encoded)?;
+	let expected = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]);
+	let found = checksum::crc32(&decoded);
+	if expected != found {
+		return Err(invalid(DecompressError::ChecksumMismatch {
+			expected,
+			found,
+		}));
+	}
+	Ok(decoded)
+}
@@ -0,0 +1,97 @@
+//! Universal codes for integers of any size, for formats built on `bits`
+//!
+//! - Unary: n one bits, then a zero bit
+//! - Elias gamma, for n of at least 1: as many zero bits as n has bits after its
+//!   highest one, then n from its highest one bit on
+//! - Elias delta, for n of at least 1: the number of bits of n in Elias gamma, then
+//!   n without its highest one bit
+//! - Varints: 7 bits of n a byte, lowest first, the high bit set on every byte but the
+//!   last. Byte aligned, unlike the others, and read with `Cursor::varint`
+//!
+//! Small numbers take few bits with every one of them: gamma suits numbers
+//! that are mostly small, delta large ones.
+use crate::bits::{BitReader, BitWriter};
+use std::io::{self, Read, Write};
+
+/// Number of bits of `n` from its highest one bit on
+fn bit_len(n: u64) -> u8 {
+	(u64::BITS - n.leading_zeros()) as u8
+}
+
+fn too_long(code: &str) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("{} code of more than 64 bits", code),
+	)
+}
+
+/// Write `n` in unary
+pub fn write_unary<W: Write>(bits: &mut BitWriter<W>, mut n: u64) -> io::Result<()> {
+	while n > 0 {
+		let ones = n.min(64) as u8;
+		bits.write_bits(u64::MAX >> (64 - ones), ones)?;
+		n -= ones as u64;
+	}
+	bits.write_bit(false)
+}
+
+/// Read a number written by `write_unary`, `None` if the input ends before
+pub fn read_unary<R: Read>(bits: &mut BitReader<R>) -> io::Result<Option<u64>> {
+	let mut n = 0;
+	loop {
+		match bits.read_bit()? {
+			Some(true) => n += 1,
+			Some(false) => return Ok(Some(n)),
+			None => return Ok(None),
+		}
+	}
+}
+
+/// Write `n` in Elias gamma, `n` must not be 0
+pub fn write_gamma<W: Write>(bits: &mut BitWriter<W>, n: u64) -> io::Result<()> {
+	assert!(n > 0, "Elias gamma codes start at 1");
+	let len = bit_len(n);
+	bits.write_bits(0, len - 1)?;
+	bits.write_bits(n, len)
+}
+
+/// Read a number written by `write_gamma`, `None` if the input ends before
+pub fn read_gamma<R: Read>(bits: &mut BitReader<R>) -> io::Result<Option<u64>> {
+	let mut zeros = 0;
+	loop {
+		match bits.read_bit()? {
+			Some(true) => break,
+			Some(false) if zeros == 63 => return Err(too_long("Elias gamma")),
+			Some(false) => zeros += 1,
+			None => return Ok(None),
+		}
+	}
+	Ok(bits.read_bits(zeros)?.map(|low| 1 << zeros | low))
+}
+
+/// Write `n` in Elias delta, `n` must not be 0
+pub fn write_delta<W: Write>(bits: &mut BitWriter<W>, n: u64) -> io::Result<()> {
+	assert!(n > 0, "Elias delta codes start at 1");
+	let len = bit_len(n);
+	write_gamma(bits, len as u64)?;
+	bits.write_bits(n, len - 1)
+}
+
+/// Read a number written by `write_delta`, `None` if the input ends before
+pub fn read_delta<R: Read>(bits: &mut BitReader<R>) -> io::Result<Option<u64>> {
+	let len = match read_gamma(bits)? {
+		Some(len) if len > 64 => return Err(too_long("Elias delta")),
+		Some(len) => len as u8,
+		None => return Ok(None),
+	};
+	Ok(bits.read_bits(len - 1)?.map(|low| 1 << (len - 1) | low))
+}
+
+/// Append `n` as a varint to `output`
+pub fn write_varint(output: &mut Vec<u8>, mut n: u64) {
+	while n >= 0x80 {
+		output.push(n as u8 | 0x80);
+		n >>= 7;
+	}
+	output.push(n as u8);
+}
@@ -1,21 +1,44 @@
 pub mod huffman {
+	use crate::analysis::{self, Analysis, Strategy};
+	use crate::bits::{BitReader, BitWriter};
+	use crate::canonical;
+	use crate::checksum;
+	use crate::context;
+	use crate::cursor::Cursor;
+	use crate::delta;
+	use crate::digram;
+	use crate::json;
+	use crate::logging::{debug, info};
+	use crate::rice;
+	use crate::rle;
+	use crate::words;
+	use std::borrow::Cow;
 	use std::boxed::Box;
 	use std::cmp::Ordering;
 	use std::collections::*;
+	use std::convert::TryFrom;
+	use std::fs::File;
+	use std::io::{self, BufRead, Read, Write};
+	use std::path::Path;
+	use std::time::{Duration, Instant};
 
-	///	Node is a binary tree data structure.
-	///	It will be used by huffman compression algorithm
-	#[derive(Clone, PartialEq, Eq, Ord, std::fmt::Debug)]
+	/// Node is a binary tree data structure.
+	/// It will be used by huffman compression algorithm
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
 	struct Node {
 		letter: char,
 		freq: i32,
 		left: Option<Box<Node>>,
 		right: Option<Box<Node>>,
 	}
+	impl Ord for Node {
+		fn cmp(self: &Node, other: &Node) -> Ordering {
+			self.freq.cmp(&other.freq).reverse() // For min heap
+		}
+	}
 	impl PartialOrd for Node {
 		fn partial_cmp(self: &Node, other: &Node) -> Option<Ordering> {
-			let cmp = self.freq.cmp(&other.freq);
-			Some(cmp.reverse()) // For min heap
+			Some(self.cmp(other))
 		}
 	}
 	impl Node {
@@ -33,38 +56,42 @@ pub mod huffman {
 	///
 	/// Count the frequency of chars, return a vector of node.
 	///
-	/// Each node contains the character and corresponding frequency
-	/// > Note: Algotithm is based on sorting
+	/// Each node contains the character and corresponding frequency, sorted by character
+	/// > Note: ASCII chars are counted in an array, all others in a hashmap
 	///
 	fn freq_count(text: std::str::Chars) -> Vec<Node> {
-		let mut freq_vec = Vec::new();
-		let mut chars: Vec<char> = text.collect();
-		chars.sort();
-		let mut freq = 0;
-		let mut prev: char = *chars.first().expect("Input cannot be empty");
-		for c in chars {
-			if c == prev {
-				freq += 1;
-			} else {
-				freq_vec.push(Node::new(prev, freq));
-				freq = 1;
-				prev = c;
+		let mut ascii = [0i32; 128];
+		let mut others: HashMap<char, i32> = HashMap::new();
+		for c in text {
+			match ascii.get_mut(c as usize) {
+				Some(count) => *count += 1,
+				None => *others.entry(c).or_insert(0) += 1,
 			}
 		}
-		freq_vec.push(Node::new(prev, freq));
-		return freq_vec;
+		let mut freq_vec: Vec<Node> = (0..128u8)
+			.filter(|&c| ascii[c as usize] != 0)
+			.map(|c| Node::new(c as char, ascii[c as usize]))
+			.collect();
+		let mut others: Vec<Node> = others
+			.into_iter()
+			.map(|(c, freq)| Node::new(c, freq))
+			.collect();
+		others.sort_by_key(|node| node.letter);
+		freq_vec.extend(others);
+		assert!(!freq_vec.is_empty(), "Input cannot be empty");
+		freq_vec
 	}
 
 	/// Create huffman encoding using huffman algorithm
 	/// ## Input:
-	///     Frequency vector: A vector of Nodes containing character frequency
-	///     (Use the freq_count function)
+	/// Frequency vector: A vector of Nodes containing character frequency
+	/// (Use the freq_count function)
 	/// ## Output:
-	///     Root node of Huffman Tree of type Option<Box<Node>>
+	/// Root node of Huffman Tree of type `Node`
 	/// # Algorithm
 	/// - While priority_queue contains atleast 2 nodes:
-	/// 	- Choose two minimum elements and combine them
-	/// 	- Insert combined value back to tree
+	///     - Choose two minimum elements and combine them
+	///     - Insert combined value back to tree
 	/// - Return tree
 	///
 	fn construct_huffman_tree(freq: Vec<Node>) -> Node {
@@ -84,180 +111,3260 @@ pub mod huffman {
 		}
 		pq.pop().unwrap()
 	}
+	/// Create a huffman tree like `construct_huffman_tree`, but with no leaf deeper than `max_length`
+	///
+	/// Lengths are limited with package-merge, see `canonical::limited_code_lengths`,
+	/// the tree is shaped after the canonical codes of those lengths
+	fn limited_huffman_tree(freq: Vec<Node>, max_length: u8) -> Result<Node, CompressError> {
+		let counts: Vec<u64> = freq.iter().map(|node| node.freq as u64).collect();
+		let lengths = canonical::limited_code_lengths(&counts, max_length)
+			.ok_or(CompressError::CodeLengthLimit(max_length))?;
+		let entries = freq
+			.iter()
+			.zip(canonical::codes(&lengths))
+			.map(|(node, (code, length))| TableEntry {
+				symbol: node.letter,
+				frequency: node.freq as u64,
+				code: format!("{:01$b}", code, length as usize),
+			})
+			.collect();
+		Ok(CodeTable { entries }
+			.to_tree()
+			.expect("Canonical codes are complete"))
+	}
+	/// Create a tree like `construct_huffman_tree`, but with Shannon–Fano coding
+	///
+	/// # Algorithm
+	/// - Sort chars by descending frequency
+	/// - Split them where the two parts come closest to equal frequency
+	/// - The first part is the left subtree and the second the right one, each split likewise
+	///
+	/// The codes are never shorter in total than huffman codes, and often longer
+	fn shannon_fano_tree(mut freq: Vec<Node>) -> Node {
+		fn split(nodes: &mut Vec<Node>) -> Node {
+			if nodes.len() == 1 {
+				return nodes.pop().unwrap();
+			}
+			let total: i64 = nodes.iter().map(|node| node.freq as i64).sum();
+			let mut left = 0;
+			let mut at = 1;
+			for (i, node) in nodes.iter().enumerate().take(nodes.len() - 1) {
+				left += node.freq as i64;
+				at = i + 1;
+				if 2 * left >= total {
+					// One char fewer on the left may come closer to half
+					if 2 * left - total > total - 2 * (left - node.freq as i64) && i > 0 {
+						at = i;
+					}
+					break;
+				}
+			}
+			let mut right = nodes.split_off(at);
+			let (left, right) = (split(nodes), split(&mut right));
+			Node {
+				letter: '\0',
+				freq: left.freq + right.freq,
+				left: Some(Box::new(left)),
+				right: Some(Box::new(right)),
+			}
+		}
+		freq.sort_by_key(|node| std::cmp::Reverse(node.freq));
+		split(&mut freq)
+	}
 	/// Convert huffman tree to a hashmap with key as char and value as encoding
 	/// E.g key = 'a', value = '1000'
 	fn to_hashmap(node: &Node) -> HashMap<char, String> {
-		let mut hm = HashMap::new();
+		leaf_codes(node).into_iter().collect()
+	}
+	/// Every leaf of the tree with its code, from left to right
+	fn leaf_codes(node: &Node) -> Vec<(char, String)> {
+		let mut codes = Vec::new();
 		// Huffman tree is complete binary tree, a node will have either 0 or 2 children, 1 is not possible
 		if node.left.is_none() {
-			hm.insert(node.letter, "0".to_string());
-			return hm;
+			codes.push((node.letter, "0".to_string()));
+			return codes;
 		}
-		fn encode(hm: &mut HashMap<char, String>, node: &Node, encoding: String) {
+		fn encode(codes: &mut Vec<(char, String)>, node: &Node, encoding: String) {
 			if node.left.is_none() {
-				hm.insert(node.letter, encoding);
+				codes.push((node.letter, encoding));
 			} else {
 				let left_path = String::from(&encoding) + "0";
 				let right_path = String::from(&encoding) + "1";
 				if let Some(left) = &node.left {
-					encode(hm, &left, left_path);
+					encode(codes, left, left_path);
 				}
 				if let Some(right) = &node.right {
-					encode(hm, &right, right_path);
+					encode(codes, right, right_path);
 				}
 			}
-		};
-		encode(&mut hm, &node, "".to_string());
-		return hm;
-	}
-	/// Convert huffman node to string of chars using post-order traversal
-	fn to_string(huffman_node: &Node) -> String {
-		let mut output = String::new();
-		fn post_order(node: &Node, output_str: &mut String) {
-			if let Some(left) = &node.left {
-				post_order(left.as_ref(), output_str);
-			}
-			if let Some(right) = &node.right {
-				post_order(right.as_ref(), output_str);
-			}
-			output_str.push(node.letter);
 		}
-
-		post_order(huffman_node, &mut output);
-		return output;
+		encode(&mut codes, node, "".to_string());
+		codes
 	}
+	/// Leaf standing for "a literal byte follows" in dictionaries and built-in tables,
+	/// so text they have no code for still compresses. Embedded as the byte `0xFF`,
+	/// which never occurs in UTF-8. A `\u{FFFF}` in the text is escaped itself
+	pub const ESCAPE: char = '\u{FFFF}';
+	const ESCAPE_BYTE: u8 = 0xFF;
+
 	/// Convert huffman tree to vector of bytes
 	///
-	/// First element is length of tree
-	///
-	/// There are only 100 or so printable characters 
-	/// based on python's string.printable
-	/// So worst case tree size is 2N-1 = 199
-	/// So a unsigned char will suffice for length of tree
+	/// - `TREE_MARKER`
+	/// - Number of leaves (u32)
+	/// - Shape of the tree in post-order, a bit per node: 0 for a leaf, 1 for an internal
+	///   node, zero padded to a whole byte
+	/// - Leaves in the same order, as UTF-8, `ESCAPE` as the byte `0xFF`
 	///
-	/// Following elements are charectars in post-order traversal of tree
+	/// No byte value stands for internal nodes, so every char can be a leaf
 	fn embed_tree(huffman_node: &Node) -> Vec<u8> {
-		let mut compressed_data = to_string(huffman_node).into_bytes();
-		compressed_data.insert(0, compressed_data.len() as u8); // Append length
-		return compressed_data;
+		/// Write the shape and leaves below `node`, returning the number of leaves
+		fn post_order(node: &Node, shape: &mut BitWriter<Vec<u8>>, leaves: &mut Vec<u8>) -> u32 {
+			// Writing to a Vec can't fail
+			if let (Some(left), Some(right)) = (&node.left, &node.right) {
+				let count = post_order(left, shape, leaves) + post_order(right, shape, leaves);
+				shape.write_bit(true).unwrap();
+				return count;
+			}
+			shape.write_bit(false).unwrap();
+			match node.letter {
+				ESCAPE => leaves.push(ESCAPE_BYTE),
+				c => leaves.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+			}
+			1
+		}
+		let mut shape = BitWriter::new(Vec::new());
+		let mut leaves = Vec::new();
+		let leaf_count = post_order(huffman_node, &mut shape, &mut leaves);
+		let mut compressed_data = vec![TREE_MARKER];
+		compressed_data.extend(&leaf_count.to_le_bytes());
+		compressed_data.extend(shape.finish().unwrap().0);
+		compressed_data.extend(leaves);
+		compressed_data
 	}
 
 	/// Simply maps input characters to their corresponding encoding and return as byte array
 	///
 	/// The first element is padding, (Number of zeroes appended for last encoding), as encoding might not fit into 8 bits
-	fn compress_data(text: &String, huffman_node: &Node) -> Vec<u8> {
-		let mut byte_stream: Vec<u8> = Vec::new();
-		let (mut byte, mut count) = (0, 0);
+	///
+	/// Chars without a code are written as the code of `ESCAPE` followed by a literal byte,
+	/// once for every byte of their UTF-8 encoding
+	fn compress_data(text: &str, huffman_node: &Node) -> Result<Vec<u8>, CompressError> {
+		// Room for the padding byte, writing to a Vec can't fail
+		let mut bits = BitWriter::new(vec![0]);
 
 		let huffman_map = to_hashmap(huffman_node);
 		for c in text.chars() {
-			let encoding = huffman_map.get(&c).unwrap();
-			for e in encoding.bytes() {
-				let bit: bool = (e - '0' as u8) != 0;
-				byte = byte << 1 | (bit as u8);
-				count = (count + 1) % 8;
-				if count == 0 {
-					byte_stream.push(byte);
-					byte = 0;
-				}
-			}
-		}
-		if count != 0 {
-			let padding: u8 = 8 - count;
-			byte <<= padding;
-			byte_stream.push(byte);
-			byte_stream.insert(0, padding);
-		} else {
-			byte_stream.insert(0, 0);
+			if let Some(encoding) = huffman_map.get(&c).filter(|_| c != ESCAPE) {
+				for e in encoding.bytes() {
+					bits.write_bit(e == b'1').unwrap();
+				}
+				continue;
+			}
+			let escape = huffman_map
+				.get(&ESCAPE)
+				.ok_or(CompressError::MissingSymbol(c))?;
+			for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+				for e in escape.bytes() {
+					bits.write_bit(e == b'1').unwrap();
+				}
+				bits.write_bits(byte as u64, 8).unwrap();
+			}
 		}
-		return byte_stream;
+		let (mut byte_stream, padding) = bits.finish().unwrap();
+		byte_stream[0] = padding;
+		Ok(byte_stream)
 	}
 	/// Compression using huffman's algorithm
 	/// # Data Format
-	/// First byte (n): Length of post-order traversal of huffman tree
+	/// Output is a single member, members can be concatenated and decompress as a whole.
+	/// `compress_with_options` can split text into several members
+	///
+	/// Magic bytes: `HUF`, followed by the version byte 3
+	///
+	/// Flags (u8), every bit announces an optional field, which follow in this order:
+	/// - 0x01: Modification time of the original file in seconds since the unix epoch (u64)
+	/// - 0x02: Unix permission bits of the original file (u32)
+	/// - 0x04: Name of the original file, without directories (u16 length, then UTF-8)
+	/// - 0x08: Length of the text of this member (u64), always written
+	/// - 0x10: No field, the member holds binary data instead of UTF-8 text. Its bytes
+	///   are coded as the chars U+0000 to U+00FF of the same value, except for stored
+	///   and Rice coded payloads, which hold the bytes themselves. Length and CRC-32 are
+	///   those of the bytes
+	/// - 0x20: ID of the dictionary the payload is coded with (u32), see `Dictionary::id`.
+	///   Members coded with a dictionary written before this flag have no ID
+	///
+	/// Payload length (u64)
 	///
-	/// Following n bytes contain post-order traversal
+	/// Payload:
+	/// - First byte (n): 16, followed by the huffman tree, see `embed_tree`
+	/// - Padding byte (p): Padding for final byte
+	/// - All remaining bytes are data
 	///
-	/// Padding byte (p): Padding for final byte
+	/// Older members start with the length of a post-order traversal of the tree instead,
+	/// with a byte 0 for every internal node, which is always odd. Even values of n
+	/// are used as markers:
+	/// - n = 0: A dictionary is used instead of an embedded tree, and no tree follows
+	/// - n = 2: Compression would expand the text, so it follows as is, without padding byte
+	/// - n = 4: The text is coded with the word model, see `words`
+	/// - n = 6: The text is coded with the order-1 context model, see `context`
+	/// - n = 8: The text is run-length encoded, see `rle`, then coded as with n = 6
+	/// - n = 10: The ID of a built-in table follows, which is used instead of an
+	///   embedded tree, see `Dictionary::builtin`
+	/// - n = 12: The width of the delta filter follows, the text is delta encoded with it,
+	///   see `delta`, then coded as with n = 6
+	/// - n = 14: The text is coded with the digram model, see `digram`
+	/// - n = 18: The width of the delta filter follows, 0 for none, the text is delta
+	///   encoded with it, see `delta`, then Rice coded, see `rice`
+	///
+	/// CRC-32 of the text (u32)
+	///
+	/// All integers are little endian.
+	/// Version 2 members have no flags, version 1 members have neither flags nor CRC-32.
+	/// Data without magic bytes is a single legacy payload, from before members existed.
+	/// Its first byte, the odd length of the post-order traversal, is never the `H` of
+	/// the magic bytes, so the two can't be confused.
+	///
+	/// Use `Compressor::builder` for anything but the default settings
+	pub fn compress(text: &str) -> Vec<u8> {
+		Compressor::default()
+			.compress(text)
+			.expect("Tree is built from every char of text")
+	}
+
+	/// Size of `compress(data)` worked out from char frequencies alone, without coding
+	/// anything, to tell whether compressing is worth it
 	///
-	/// All remaining bytes are data
-	pub fn compress(text: &String) -> Vec<u8> {
+	/// Data that isn't UTF-8 can't be compressed, for it this estimates the size
+	/// of coding its bytes, see `Analysis::estimated_size`
+	pub fn estimate_compressed_size(data: &[u8]) -> u64 {
+		let text = match std::str::from_utf8(data) {
+			Ok(text) => text,
+			Err(_) => return Analysis::of(data).estimated_size(),
+		};
+		// Header with just the text length and CRC-32
+		let overhead = (MAGIC.len() + 1 + 1 + 8 + 8 + 4) as u64;
+		if text.is_empty() {
+			return overhead + 1;
+		}
 		let frequency = freq_count(text.chars());
-		let huffman_tree = construct_huffman_tree(frequency);
-		let mut compressed_data = Vec::from(embed_tree(&huffman_tree));
-		compressed_data.extend(compress_data(text, &huffman_tree));
-		return compressed_data;
+		let counts: Vec<u64> = frequency.iter().map(|node| node.freq as u64).collect();
+		let bits: u64 = canonical::code_lengths(&counts)
+			.iter()
+			.zip(&counts)
+			.map(|(&length, &count)| length as u64 * count)
+			.sum();
+		// Marker, leaf count, a bit per node and the UTF-8 of every leaf
+		let tree: u64 = 1
+			+ 4 + (2 * frequency.len() as u64 - 1).div_ceil(8)
+			+ frequency
+				.iter()
+				.map(|node| node.letter.len_utf8() as u64)
+				.sum::<u64>();
+		let coded = tree + 1 + bits.div_ceil(8);
+		overhead + coded.min(text.len() as u64 + 1)
 	}
-	fn construct_tree_from_postorder(postorder: &[u8]) -> Node {
-		// parent left right
-		// Assuming input does not contain null
-		let mut stack = Vec::new();
-		for c in postorder {
-			if *c == 0 as u8 {
-				let (left, right) = (
-					stack.pop().expect("Input contains Null byte"),
-					stack.pop().expect("Input contains Null byte"),
+
+	/// File attributes stored alongside the text
+	#[derive(Clone, Default, PartialEq, Eq, std::fmt::Debug)]
+	pub struct Metadata {
+		/// Modification time in seconds since the unix epoch
+		pub mtime: Option<u64>,
+		/// Unix permission bits
+		pub mode: Option<u32>,
+		/// Name of the original file, without directories
+		pub name: Option<String>,
+	}
+
+	/// Symbols the text is split into before huffman coding
+	#[derive(Clone, Copy, Default, PartialEq, Eq, std::fmt::Debug)]
+	pub enum Model {
+		/// Every char is a symbol
+		#[default]
+		Char,
+		/// Words and whitespace runs are symbols, see `words`
+		Word,
+		/// Every byte is a symbol, coded with a table picked by the previous byte, see `context`
+		Context,
+		/// Frequent byte pairs are symbols, other bytes are symbols on their own, see `digram`
+		Digram,
+		/// Picked for every block from a sample of it, see `analysis::strategy`
+		Auto,
+		/// Every byte is Rice coded instead, after `Filter::Delta` if given, see `rice`
+		Rice,
+	}
+
+	/// Transformation applied to the text before huffman coding
+	#[derive(Clone, Copy, Default, PartialEq, Eq, std::fmt::Debug)]
+	pub enum Filter {
+		#[default]
+		None,
+		/// Run-length encoding, see `rle`
+		Rle,
+		/// Differences of bytes this many positions apart, see `delta`.
+		/// Must not be 0
+		Delta(u8),
+	}
+
+	/// Settings for `compress_with_options`
+	#[derive(Clone, Default, std::fmt::Debug)]
+	pub struct CompressOptions<'a> {
+		/// Encode with the tree of a dictionary instead of embedding one,
+		/// only used by the char model
+		pub dictionary: Option<&'a Dictionary>,
+		pub model: Model,
+		/// Filtered text is coded with the order-1 context model instead of `model`.
+		/// Every member keeps the filter only if it makes the member smaller
+		pub filter: Filter,
+		/// Bytes of text sharing one tree, each block becomes a member.
+		/// `None` puts all text in a single member
+		pub block_size: Option<usize>,
+		/// Longest code allowed in codes built for the text, `None` for no limit.
+		/// Codes of a dictionary are used as they are
+		pub max_code_length: Option<u8>,
+		/// Split blocks further where the byte distribution changes, so every part gets
+		/// a tree of its own, see `analysis::shifts`
+		pub adaptive_blocks: bool,
+		/// Build codes with Shannon–Fano coding instead of huffman coding, to compare the
+		/// two. Only used by the char model, `max_code_length` takes precedence
+		pub shannon_fano: bool,
+		pub metadata: Metadata,
+	}
+
+	/// Compress `text` into members, as configured by `options`
+	///
+	/// Metadata is only stored in the first member
+	pub fn compress_with_options(
+		text: &str,
+		options: &CompressOptions,
+	) -> Result<Vec<u8>, CompressError> {
+		let mut output = Vec::new();
+		let mut metadata = &options.metadata;
+		let no_metadata = Metadata::default();
+		for block in blocks(text, options) {
+			let payload = compress_block(block, options, &mut Timings::default())?;
+			output.extend(frame(
+				block.as_bytes(),
+				false,
+				payload,
+				metadata,
+				options.dictionary,
+			));
+			metadata = &no_metadata;
+		}
+		Ok(output)
+	}
+
+	/// Compresses text with settings chosen once through `CompressorBuilder`, e.g.
+	/// `Compressor::builder().block_size(1 << 20).filter(Filter::Rle).build()`
+	#[derive(Clone, Default, std::fmt::Debug)]
+	pub struct Compressor<'a> {
+		options: CompressOptions<'a>,
+	}
+
+	impl<'a> Compressor<'a> {
+		/// Builder starting from the settings of `compress`
+		pub fn builder() -> CompressorBuilder<'a> {
+			CompressorBuilder::default()
+		}
+		pub fn options(&self) -> &CompressOptions<'a> {
+			&self.options
+		}
+		/// See `compress_with_options`
+		pub fn compress(&self, text: &str) -> Result<Vec<u8>, CompressError> {
+			compress_with_options(text, &self.options)
+		}
+		/// See `compress_to_writer`
+		pub fn compress_to_writer<W: Write>(&self, text: &str, writer: W) -> io::Result<W> {
+			compress_to_writer(text, writer, &self.options)
+		}
+		/// See `compress_stream`
+		pub fn compress_stream<R: Read, W: Write>(&self, reader: R, writer: W) -> io::Result<W> {
+			compress_stream(reader, writer, &self.options)
+		}
+		/// Encoder taking input a chunk at a time, see `Encoder`
+		pub fn encoder(&self) -> Encoder<'a> {
+			Encoder::new(self.options.clone())
+		}
+		/// Compress the UTF-8 text `input` yields as the output is iterated, see `CompressIter`
+		pub fn compress_iter<I: IntoIterator<Item = u8>>(
+			&self,
+			input: I,
+		) -> CompressIter<'a, I::IntoIter> {
+			CompressIter {
+				input: input.into_iter(),
+				encoder: Some(self.encoder()),
+				output: Vec::new(),
+				position: 0,
+				error: None,
+			}
+		}
+	}
+
+	/// Settings of a `Compressor`, every one left out keeps its default
+	#[derive(Clone, Default, std::fmt::Debug)]
+	pub struct CompressorBuilder<'a> {
+		options: CompressOptions<'a>,
+	}
+
+	impl<'a> CompressorBuilder<'a> {
+		pub fn model(mut self, model: Model) -> Self {
+			self.options.model = model;
+			self
+		}
+		pub fn filter(mut self, filter: Filter) -> Self {
+			self.options.filter = filter;
+			self
+		}
+		/// Bytes of text sharing one tree, see `CompressOptions::block_size`
+		pub fn block_size(mut self, block_size: usize) -> Self {
+			self.options.block_size = Some(block_size);
+			self
+		}
+		pub fn max_code_length(mut self, max_code_length: u8) -> Self {
+			self.options.max_code_length = Some(max_code_length);
+			self
+		}
+		/// See `CompressOptions::adaptive_blocks`
+		pub fn adaptive_blocks(mut self, adaptive_blocks: bool) -> Self {
+			self.options.adaptive_blocks = adaptive_blocks;
+			self
+		}
+		/// See `CompressOptions::shannon_fano`
+		pub fn shannon_fano(mut self, shannon_fano: bool) -> Self {
+			self.options.shannon_fano = shannon_fano;
+			self
+		}
+		pub fn dictionary(mut self, dictionary: &'a Dictionary) -> Self {
+			self.options.dictionary = Some(dictionary);
+			self
+		}
+		/// Attributes stored in the first member
+		pub fn metadata(mut self, metadata: Metadata) -> Self {
+			self.options.metadata = metadata;
+			self
+		}
+		pub fn build(self) -> Compressor<'a> {
+			Compressor {
+				options: self.options,
+			}
+		}
+	}
+
+	/// Compress `text` like `compress_with_options`, writing every member to `writer`
+	/// as soon as it is done instead of collecting them. Text that can't be compressed
+	/// fails with `InvalidData`
+	pub fn compress_to_writer<W: Write>(
+		text: &str,
+		mut writer: W,
+		options: &CompressOptions,
+	) -> io::Result<W> {
+		let mut metadata = &options.metadata;
+		let no_metadata = Metadata::default();
+		for block in blocks(text, options) {
+			let payload = compress_block(block, options, &mut Timings::default())
+				.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+			writer.write_all(&frame(
+				block.as_bytes(),
+				false,
+				payload,
+				metadata,
+				options.dictionary,
+			))?;
+			metadata = &no_metadata;
+		}
+		Ok(writer)
+	}
+
+	/// Block size of `compress_stream` when the options give none
+	pub const STREAM_BLOCK_SIZE: usize = 1 << 20;
+
+	/// Compress everything `reader` produces into members written to `writer`, a block at a time
+	///
+	/// Only one block and its member are held in memory. Blocks are split like those of
+	/// `compress_with_options`, `STREAM_BLOCK_SIZE` long unless the options set a size,
+	/// so both give the same output for the same block size.
+	/// Text that can't be compressed fails with `InvalidData`
+	pub fn compress_stream<R: Read, W: Write>(
+		reader: R,
+		writer: W,
+		options: &CompressOptions,
+	) -> io::Result<W> {
+		compress_stream_timed(reader, writer, options, &mut Timings::default())
+	}
+
+	/// Time spent in each stage of compression, see `compress_stream_timed`
+	#[derive(Clone, Copy, Default, PartialEq, Eq, std::fmt::Debug)]
+	pub struct Timings {
+		/// Reading input and splitting it into blocks
+		pub reading: Duration,
+		/// Counting symbol frequencies, char model only
+		pub counting: Duration,
+		/// Building the huffman tree, char model only
+		pub tree: Duration,
+		/// Coding the text and framing members, for the word and context
+		/// models also counting and building tables
+		pub encoding: Duration,
+		/// Writing members
+		pub writing: Duration,
+		/// Bytes of text compressed
+		pub bytes: u64,
+	}
+
+	/// `compress_stream`, adding the time spent in each stage to `timings`
+	pub fn compress_stream_timed<R: Read, W: Write>(
+		mut reader: R,
+		mut writer: W,
+		options: &CompressOptions,
+		timings: &mut Timings,
+	) -> io::Result<W> {
+		let block_size = options.block_size.unwrap_or(STREAM_BLOCK_SIZE).max(1);
+		let mut metadata = &options.metadata;
+		let no_metadata = Metadata::default();
+		let mut first = true;
+		loop {
+			let start = Instant::now();
+			let block = match read_block(&mut reader, block_size, first)? {
+				Some(block) => block,
+				None => return Ok(writer),
+			};
+			timings.reading += start.elapsed();
+			let member = compress_member(&block, options, metadata, timings)?;
+			let start = Instant::now();
+			writer.write_all(&member)?;
+			timings.writing += start.elapsed();
+			metadata = &no_metadata;
+			first = false;
+			if block.len() < block_size {
+				return Ok(writer);
+			}
+		}
+	}
+
+	/// `compress_stream_timed` with reading and writing on threads of their own, and blocks
+	/// encoded on `encoders` threads in parallel, at least 1. Reading and writing overlap
+	/// with encoding. Gives the same output, holding up to twice as many blocks as there
+	/// are encoders in memory
+	///
+	/// The reading and encoding times are measured on their threads and summed, so the
+	/// stages add up to more than the time taken
+	pub fn compress_stream_pipelined<R: Read + Send, W: Write>(
+		mut reader: R,
+		mut writer: W,
+		options: &CompressOptions,
+		encoders: usize,
+		timings: &mut Timings,
+	) -> io::Result<W> {
+		use std::sync::mpsc::sync_channel;
+		use std::sync::Mutex;
+		let block_size = options.block_size.unwrap_or(STREAM_BLOCK_SIZE).max(1);
+		let encoders = encoders.max(1);
+		// Blocks read but not yet written, a token is taken to read a block and
+		// returned when its member is written
+		let in_flight = 2 * encoders;
+		let (token_sender, tokens) = sync_channel::<()>(in_flight);
+		for _ in 0..in_flight {
+			token_sender.send(()).unwrap();
+		}
+		// Room for every block in flight, so sending never blocks
+		let (block_sender, blocks) = sync_channel::<(usize, io::Result<Vec<u8>>)>(in_flight);
+		let blocks = Mutex::new(blocks);
+		let (member_sender, members) = sync_channel::<(usize, io::Result<Vec<u8>>)>(in_flight);
+		std::thread::scope(|scope| {
+			let reading = scope.spawn(move || {
+				let mut reading = Duration::ZERO;
+				for index in 0.. {
+					// No token means the writer stopped, it reports why
+					if tokens.recv().is_err() {
+						break;
+					}
+					let start = Instant::now();
+					let block = read_block(&mut reader, block_size, index == 0).transpose();
+					reading += start.elapsed();
+					let last = match &block {
+						Some(Ok(block)) => block.len() < block_size,
+						_ => true,
+					};
+					if block.is_some_and(|block| block_sender.send((index, block)).is_err()) || last
+					{
+						break;
+					}
+				}
+				reading
+			});
+			let encoding: Vec<_> = (0..encoders)
+				.map(|_| {
+					let (blocks, member_sender) = (&blocks, member_sender.clone());
+					scope.spawn(move || {
+						let mut timings = Timings::default();
+						let no_metadata = Metadata::default();
+						loop {
+							let (index, block) = match blocks.lock().unwrap().recv() {
+								Ok(block) => block,
+								Err(_) => return timings,
+							};
+							let metadata = if index == 0 {
+								&options.metadata
+							} else {
+								&no_metadata
+							};
+							let member = block.and_then(|block| {
+								compress_member(&block, options, metadata, &mut timings)
+							});
+							if member_sender.send((index, member)).is_err() {
+								return timings;
+							}
+						}
+					})
+				})
+				.collect();
+			drop(member_sender);
+			// Members arrive in any order, they are written in the order of their blocks
+			let mut pending = BTreeMap::new();
+			let mut next = 0;
+			let mut result = Ok(());
+			'write: for (index, member) in members {
+				pending.insert(index, member);
+				while let Some(member) = pending.remove(&next) {
+					let start = Instant::now();
+					result = member.and_then(|member| writer.write_all(&member));
+					timings.writing += start.elapsed();
+					if result.is_err() {
+						break 'write;
+					}
+					next += 1;
+					let _ = token_sender.send(());
+				}
+			}
+			// Without tokens, reading stops
+			drop(token_sender);
+			timings.reading += reading.join().unwrap();
+			for encoding in encoding {
+				let encoding = encoding.join().unwrap();
+				timings.counting += encoding.counting;
+				timings.tree += encoding.tree;
+				timings.encoding += encoding.encoding;
+				timings.bytes += encoding.bytes;
+			}
+			result
+		})?;
+		Ok(writer)
+	}
+
+	/// Next block of input from `reader`, `None` at the end, unless it is the first
+	/// block of empty input. A char cut off at the end of the block is read in full,
+	/// like `blocks` does, unless the block isn't UTF-8 anyway
+	fn read_block<R: Read>(
+		mut reader: R,
+		block_size: usize,
+		first: bool,
+	) -> io::Result<Option<Vec<u8>>> {
+		let mut block = Vec::new();
+		let len = (&mut reader)
+			.take(block_size as u64)
+			.read_to_end(&mut block)?;
+		if len == 0 && !first {
+			return Ok(None);
+		}
+		while let Err(e) = std::str::from_utf8(&block) {
+			if e.error_len().is_some() || (&mut reader).take(1).read_to_end(&mut block)? == 0 {
+				break;
+			}
+		}
+		Ok(Some(block))
+	}
+
+	/// Member holding `data`, counted in `timings`, or several with `adaptive_blocks`.
+	/// Data that isn't UTF-8 makes a binary member, see `FLAG_BINARY`
+	fn compress_member(
+		data: &[u8],
+		options: &CompressOptions,
+		metadata: &Metadata,
+		timings: &mut Timings,
+	) -> io::Result<Vec<u8>> {
+		let shifts = if options.adaptive_blocks {
+			analysis::shifts(data)
+		} else {
+			Vec::new()
+		};
+		if !shifts.is_empty() {
+			let options = CompressOptions {
+				adaptive_blocks: false,
+				..options.clone()
+			};
+			let mut members = Vec::new();
+			let mut metadata = metadata;
+			let no_metadata = Metadata::default();
+			let mut start = 0;
+			for shift in shifts.into_iter().chain([data.len()]) {
+				members.extend(compress_member(
+					&data[start..shift],
+					&options,
+					metadata,
+					timings,
+				)?);
+				metadata = &no_metadata;
+				start = shift;
+			}
+			return Ok(members);
+		}
+		timings.bytes += data.len() as u64;
+		let (text, binary) = match std::str::from_utf8(data) {
+			Ok(text) => (Cow::Borrowed(text), false),
+			Err(_) => (
+				Cow::Owned(data.iter().map(|&byte| byte as char).collect()),
+				true,
+			),
+		};
+		// Judged by the bytes, the chars standing for bytes of binary blocks differ
+		let picked;
+		let options = match options.model {
+			Model::Auto => match auto_options(data, options) {
+				Some(options) => {
+					picked = options;
+					&picked
+				}
+				None => {
+					return Ok(frame(
+						data,
+						binary,
+						[&[STORED_MARKER], data].concat(),
+						metadata,
+						None,
+					))
+				}
+			},
+			_ => options,
+		};
+		// Rice codes the bytes themselves, the chars standing for them would hide their values
+		let mut payload = if binary && options.model == Model::Rice {
+			rice_payload(data, options.filter)
+		} else {
+			compress_block(&text, options, timings)
+				.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+		};
+		// Stored as text, bytes above 0x7F would take two bytes each
+		if binary && (payload[0] == STORED_MARKER || payload.len() > data.len() + 1) {
+			payload = [&[STORED_MARKER], data].concat();
+		}
+		let start = Instant::now();
+		let member = frame(data, binary, payload, metadata, options.dictionary);
+		timings.encoding += start.elapsed();
+		Ok(member)
+	}
+
+	/// Settings `Model::Auto` picks for a block of `data`, `None` to store it as is
+	fn auto_options<'a>(data: &[u8], options: &CompressOptions<'a>) -> Option<CompressOptions<'a>> {
+		let strategy = analysis::strategy(data);
+		info!("Block of {} bytes coded as {:?}", data.len(), strategy);
+		let (model, filter) = match strategy {
+			Strategy::Stored => return None,
+			Strategy::Huffman => (Model::Char, Filter::None),
+			Strategy::Rle => (Model::Char, Filter::Rle),
+			Strategy::Words => (Model::Word, Filter::None),
+			Strategy::Context => (Model::Context, Filter::None),
+		};
+		Some(CompressOptions {
+			model,
+			filter,
+			..options.clone()
+		})
+	}
+
+	/// Payload Rice coding `bytes`, delta encoded first with `Filter::Delta`
+	fn rice_payload(bytes: &[u8], filter: Filter) -> Vec<u8> {
+		match filter {
+			Filter::Delta(width) => {
+				let mut payload = vec![RICE_MARKER, width];
+				payload.extend(rice::encode(&delta::encode(bytes, width as usize)));
+				payload
+			}
+			_ => {
+				let mut payload = vec![RICE_MARKER, 0];
+				payload.extend(rice::encode(bytes));
+				payload
+			}
+		}
+	}
+
+	/// Split `text` into blocks of about `options.block_size` bytes, ending at char
+	/// boundaries, and further at `analysis::shifts` with `options.adaptive_blocks`
+	fn blocks<'t>(text: &'t str, options: &CompressOptions) -> Vec<&'t str> {
+		let mut blocks = Vec::new();
+		let mut rest = text;
+		let block_size = match options.block_size {
+			Some(block_size) if block_size < text.len() => block_size.max(1),
+			_ => text.len(),
+		};
+		loop {
+			let mut end = block_size.min(rest.len());
+			while !rest.is_char_boundary(end) {
+				end += 1;
+			}
+			let (block, next) = rest.split_at(end);
+			let shifts = if options.adaptive_blocks {
+				analysis::shifts(block.as_bytes())
+			} else {
+				Vec::new()
+			};
+			let mut start = 0;
+			for shift in shifts.into_iter().chain([block.len()]) {
+				blocks.push(&block[start..shift]);
+				start = shift;
+			}
+			rest = next;
+			if rest.is_empty() {
+				return blocks;
+			}
+		}
+	}
+
+	/// Payload encoding a single block
+	fn compress_block(
+		text: &str,
+		options: &CompressOptions,
+		timings: &mut Timings,
+	) -> Result<Vec<u8>, CompressError> {
+		if text.is_empty() {
+			// No tree can be built without chars
+			return Ok(vec![STORED_MARKER]);
+		}
+		let mut start = Instant::now();
+		let compressed_data = match (options.model, options.dictionary) {
+			(Model::Auto, _) => {
+				return match auto_options(text.as_bytes(), options) {
+					Some(options) => compress_block(text, &options, timings),
+					None => Ok([&[STORED_MARKER], text.as_bytes()].concat()),
+				}
+			}
+			(Model::Word, _) => {
+				let mut compressed_data = vec![WORD_MARKER];
+				compressed_data.extend(words::encode(text, options.max_code_length)?);
+				compressed_data
+			}
+			(Model::Context, _) => {
+				let mut compressed_data = vec![CONTEXT_MARKER];
+				compressed_data.extend(context::encode(text.as_bytes(), options.max_code_length)?);
+				compressed_data
+			}
+			(Model::Digram, _) => {
+				let mut compressed_data = vec![DIGRAM_MARKER];
+				compressed_data.extend(digram::encode(text, options.max_code_length)?);
+				compressed_data
+			}
+			(Model::Rice, _) => rice_payload(text.as_bytes(), options.filter),
+			(Model::Char, Some(dictionary)) => {
+				let mut compressed_data = match dictionary.builtin {
+					Some(id) => vec![BUILTIN_MARKER, id],
+					None => vec![DICTIONARY_MARKER],
+				};
+				compressed_data.extend(compress_data(text, &dictionary.tree)?);
+				compressed_data
+			}
+			(Model::Char, None) => {
+				let frequency = freq_count(text.chars());
+				timings.counting += start.elapsed();
+				start = Instant::now();
+				info!(
+					"{} tree of {} distinct chars, {} nodes",
+					if options.shannon_fano {
+						"Shannon–Fano"
+					} else {
+						"Huffman"
+					},
+					frequency.len(),
+					2 * frequency.len() - 1
 				);
-				stack.push(Node {
-					letter: '\0',
-					freq: 0,
-					left: Option::from(Box::from(right)),
-					right: Option::from(Box::from(left)),
-				});
+				let huffman_tree = match options.max_code_length {
+					Some(max_length) => limited_huffman_tree(frequency, max_length)?,
+					None if options.shannon_fano => shannon_fano_tree(frequency),
+					None => construct_huffman_tree(frequency),
+				};
+				debug_assert_eq!(CodeTable::of_tree(&huffman_tree, "").validate(), Ok(()));
+				timings.tree += start.elapsed();
+				start = Instant::now();
+				let mut compressed_data = embed_tree(&huffman_tree);
+				compressed_data.extend(compress_data(text, &huffman_tree)?);
+				compressed_data
+			}
+		};
+		let (mut filtered, bytes) = match (options.filter, options.model) {
+			// The Rice model applies the delta filter itself
+			(Filter::None, _) | (Filter::Delta(_), Model::Rice) => (Vec::new(), Vec::new()),
+			(Filter::Rle, _) => (vec![RLE_MARKER], rle::encode(text.as_bytes())),
+			(Filter::Delta(width), _) => (
+				vec![DELTA_MARKER, width],
+				delta::encode(text.as_bytes(), width as usize),
+			),
+		};
+		let compressed_data = if filtered.is_empty() {
+			compressed_data
+		} else {
+			filtered.extend(context::encode(&bytes, options.max_code_length)?);
+			if filtered.len() < compressed_data.len() {
+				filtered
 			} else {
-				stack.push(Node {
-					letter: *c as char,
-					freq: 0,
-					left: None,
-					right: None,
-				});
+				compressed_data
+			}
+		};
+		let payload = stored_if_smaller(text, compressed_data);
+		timings.encoding += start.elapsed();
+		info!(
+			"Block of {} bytes {}, payload {} bytes",
+			text.len(),
+			payload_kind(&payload),
+			payload.len()
+		);
+		Ok(payload)
+	}
+
+	/// Magic bytes starting every member
+	pub const MAGIC: &[u8; 3] = b"HUF";
+	/// Member format version written by `compress`
+	pub const VERSION: u8 = 3;
+
+	const FLAG_MTIME: u8 = 0x01;
+	const FLAG_MODE: u8 = 0x02;
+	const FLAG_NAME: u8 = 0x04;
+	const FLAG_SIZE: u8 = 0x08;
+	const FLAG_BINARY: u8 = 0x10;
+	const FLAG_DICTIONARY: u8 = 0x20;
+
+	/// Wrap the payload encoding `data` into a member, marked binary if `binary`.
+	/// Payloads coded with `dictionary` store its ID
+	fn frame(
+		data: &[u8],
+		binary: bool,
+		payload: Vec<u8>,
+		metadata: &Metadata,
+		dictionary: Option<&Dictionary>,
+	) -> Vec<u8> {
+		let header = Header {
+			version: VERSION,
+			metadata: metadata.clone(),
+			original_size: Some(data.len() as u64),
+			binary,
+			dictionary_id: dictionary
+				.filter(|_| payload[0] == DICTIONARY_MARKER)
+				.map(Dictionary::id),
+			payload_len: payload.len() as u64,
+		};
+		let mut member = header.to_bytes();
+		member.extend(payload);
+		member.extend(&checksum::crc32(data).to_le_bytes());
+		member
+	}
+
+	/// Fields of a member header
+	///
+	/// Version 1 and 2 headers hold nothing but the payload length, version 3 adds a
+	/// byte of flags telling which of the optional fields follow.
+	struct Header {
+		version: u8,
+		metadata: Metadata,
+		/// Length of the text, if stored
+		original_size: Option<u64>,
+		/// Member holds binary data, see `FLAG_BINARY`
+		binary: bool,
+		/// ID of the dictionary the payload is coded with, if stored
+		dictionary_id: Option<u32>,
+		payload_len: u64,
+	}
+
+	impl Header {
+		/// The header as it starts a member, in the layout of its version
+		///
+		/// Fields older versions have no room for are left out.
+		fn to_bytes(&self) -> Vec<u8> {
+			let mut header = MAGIC.to_vec();
+			header.push(self.version);
+			if self.version >= 3 {
+				let mut flags = 0;
+				let mut fields: Vec<u8> = Vec::new();
+				if let Some(mtime) = self.metadata.mtime {
+					flags |= FLAG_MTIME;
+					fields.extend(&mtime.to_le_bytes());
+				}
+				if let Some(mode) = self.metadata.mode {
+					flags |= FLAG_MODE;
+					fields.extend(&mode.to_le_bytes());
+				}
+				// No file system allows names anywhere near this long
+				let name = self
+					.metadata
+					.name
+					.as_ref()
+					.filter(|name| name.len() <= u16::MAX as usize);
+				if let Some(name) = name {
+					flags |= FLAG_NAME;
+					fields.extend(&(name.len() as u16).to_le_bytes());
+					fields.extend(name.as_bytes());
+				}
+				if self.binary {
+					flags |= FLAG_BINARY;
+				}
+				if let Some(original_size) = self.original_size {
+					flags |= FLAG_SIZE;
+					fields.extend(&original_size.to_le_bytes());
+				}
+				if let Some(id) = self.dictionary_id {
+					flags |= FLAG_DICTIONARY;
+					fields.extend(&id.to_le_bytes());
+				}
+				header.push(flags);
+				header.extend(fields);
 			}
+			header.extend(&self.payload_len.to_le_bytes());
+			header
+		}
+
+		/// Parse the header of the member at the start of `data`, returning it and its length
+		fn from_bytes(data: &[u8]) -> Result<(Header, usize), DecompressError> {
+			let mut cursor = Cursor::new(data);
+			cursor.take(MAGIC.len(), "magic")?;
+			let version = cursor.u8("version")?;
+			let flags = match version {
+				1 | 2 => 0,
+				3 => cursor.u8("flags")?,
+				_ => return Err(DecompressError::UnknownVersion(version)),
+			};
+			let known =
+				FLAG_MTIME | FLAG_MODE | FLAG_NAME | FLAG_SIZE | FLAG_BINARY | FLAG_DICTIONARY;
+			if flags & !known != 0 {
+				return Err(DecompressError::UnknownFlags(flags));
+			}
+			let mut metadata = Metadata::default();
+			if flags & FLAG_MTIME != 0 {
+				metadata.mtime = Some(cursor.u64("modification time")?);
+			}
+			if flags & FLAG_MODE != 0 {
+				metadata.mode = Some(cursor.u32("mode")?);
+			}
+			if flags & FLAG_NAME != 0 {
+				let len = cursor.u16("name length")? as usize;
+				let name = cursor.take(len, "name")?;
+				let name = std::str::from_utf8(name).map_err(|_| DecompressError::InvalidUtf8)?;
+				metadata.name = Some(name.to_string());
+			}
+			let original_size = match flags & FLAG_SIZE {
+				0 => None,
+				_ => Some(cursor.u64("original size")?),
+			};
+			let dictionary_id = match flags & FLAG_DICTIONARY {
+				0 => None,
+				_ => Some(cursor.u32("dictionary ID")?),
+			};
+			let payload_len = cursor.u64("payload length")?;
+			let header = Header {
+				version,
+				metadata,
+				original_size,
+				binary: flags & FLAG_BINARY != 0,
+				dictionary_id,
+				payload_len,
+			};
+			Ok((header, cursor.position()))
+		}
+
+		/// Length of the member this header of `header_len` bytes starts
+		fn member_len(&self, header_len: usize) -> u64 {
+			(header_len as u64)
+				.saturating_add(self.payload_len)
+				.saturating_add(trailer_len(self.version))
+		}
+	}
+
+	/// Length of the CRC-32 following the payload
+	fn trailer_len(version: u8) -> u64 {
+		if version == 1 {
+			0
+		} else {
+			4
 		}
+	}
 
-		return stack.pop().unwrap();
+	/// Total length of the member starting at `data`
+	///
+	/// Returns `Ok(None)` if `data` ends before the length is known
+	pub fn member_len(data: &[u8]) -> Result<Option<u64>, DecompressError> {
+		match Header::from_bytes(data) {
+			Ok((header, header_len)) => Ok(Some(header.member_len(header_len))),
+			Err(DecompressError::Truncated { .. }) => Ok(None),
+			Err(e) => Err(e),
+		}
 	}
 
-	fn decompress_data(data: &[u8], tree: &Node) -> String {
-		let padding = *data.first().expect("Data empty");
-		let data = &data[1..]; // Remove first element which stores number of padded bits
-		let mut bit_stream = Vec::new();
-		let mut tmp = tree;
-		let mut output = String::new();
-		for character in data.iter() {
-			let mut character = *character;
-			for _ in 0..8 {
-				let bit: bool = (character >> 7 & 1) != 0;
-				character <<= 1;
-				bit_stream.push(bit);
+	/// Total length of the member starting at `data` and the length of its text, if its
+	/// header stores it, to skip members without reading them
+	///
+	/// Returns `Ok(None)` if `data` ends before the header does
+	pub fn member_sizes(data: &[u8]) -> Result<Option<(u64, Option<u64>)>, DecompressError> {
+		if data.len() >= MAGIC.len() && !data.starts_with(MAGIC) {
+			return Err(DecompressError::TrailingGarbage(0));
+		}
+		match Header::from_bytes(data) {
+			Ok((header, header_len)) => {
+				Ok(Some((header.member_len(header_len), header.original_size)))
 			}
+			Err(DecompressError::Truncated { .. }) => Ok(None),
+			Err(e) => Err(e),
 		}
-		bit_stream.resize(bit_stream.len() - padding as usize, false); // Remove padding bits
-		if tree.left.is_none() {
-			// Huffman tree is complete binary tree, a node will have either 0 or 2 children, 1 is not possible
-			for _ in 0..bit_stream.len() {
-				output.push(tree.letter);
+	}
+
+	/// A member split off compressed data
+	struct Member<'a> {
+		/// 0 for a legacy payload
+		version: u8,
+		/// Position of the member in the data
+		offset: usize,
+		metadata: Metadata,
+		/// Length of the text, if stored
+		original_size: Option<u64>,
+		/// Member holds binary data, see `FLAG_BINARY`
+		binary: bool,
+		/// ID of the dictionary the payload is coded with, if stored
+		dictionary_id: Option<u32>,
+		payload: &'a [u8],
+		checksum: Option<u32>,
+	}
+
+	/// Split `data` into its members
+	fn members(data: &[u8]) -> Result<Vec<Member<'_>>, DecompressError> {
+		if data.is_empty() {
+			return Err(DecompressError::Empty);
+		}
+		// Legacy data, see `compress`
+		if !data.starts_with(MAGIC) {
+			return Ok(vec![Member {
+				version: 0,
+				offset: 0,
+				metadata: Metadata::default(),
+				original_size: None,
+				binary: false,
+				dictionary_id: None,
+				payload: data,
+				checksum: None,
+			}]);
+		}
+		let mut members = Vec::new();
+		let mut offset = 0;
+		while offset < data.len() {
+			let (member, length) = member_at(data, offset)?;
+			members.push(member);
+			offset += length;
+		}
+		Ok(members)
+	}
+
+	/// The member starting at `offset` in `data`, with its length
+	fn member_at(data: &[u8], offset: usize) -> Result<(Member<'_>, usize), DecompressError> {
+		let rest = &data[offset..];
+		if !rest.starts_with(MAGIC) {
+			return Err(DecompressError::TrailingGarbage(offset));
+		}
+		let (header, header_len) = Header::from_bytes(rest)?;
+		let length = header.member_len(header_len);
+		if length > rest.len() as u64 {
+			return Err(DecompressError::Truncated {
+				field: "member",
+				offset,
+				expected: length.min(usize::MAX as u64) as usize,
+				found: rest.len(),
+			});
+		}
+		let member = &rest[..length as usize];
+		let payload_end = header_len + header.payload_len as usize;
+		let payload = &member[header_len..payload_end];
+		let checksum = match header.version {
+			1 => None,
+			_ => Some(Cursor::new(&member[payload_end..]).u32("checksum")?),
+		};
+		let member = Member {
+			version: header.version,
+			offset,
+			metadata: header.metadata,
+			original_size: header.original_size,
+			binary: header.binary,
+			dictionary_id: header.dictionary_id,
+			payload,
+			checksum,
+		};
+		Ok((member, length as usize))
+	}
+
+	/// Metadata stored in the first member of `data`, without decompressing anything
+	pub fn read_metadata(data: &[u8]) -> Result<Metadata, DecompressError> {
+		Ok(members(data)?
+			.into_iter()
+			.next()
+			.map(|member| member.metadata)
+			.unwrap_or_default())
+	}
+
+	const DICTIONARY_MARKER: u8 = 0;
+	const STORED_MARKER: u8 = 2;
+	const WORD_MARKER: u8 = 4;
+	const CONTEXT_MARKER: u8 = 6;
+	const RLE_MARKER: u8 = 8;
+	const BUILTIN_MARKER: u8 = 10;
+	const DELTA_MARKER: u8 = 12;
+	const DIGRAM_MARKER: u8 = 14;
+	const TREE_MARKER: u8 = 16;
+	const RICE_MARKER: u8 = 18;
+
+	/// Fall back to storing `text` as is when `compressed_data` is larger,
+	/// bounding expansion to a single byte
+	fn stored_if_smaller(text: &str, compressed_data: Vec<u8>) -> Vec<u8> {
+		if compressed_data.len() <= text.len() + 1 {
+			return compressed_data;
+		}
+		info!(
+			"Block of {} bytes would grow to {} bytes, stored as is",
+			text.len(),
+			compressed_data.len()
+		);
+		let mut stored = vec![STORED_MARKER];
+		stored.extend(text.as_bytes());
+		stored
+	}
+
+	/// Reasons text can be rejected by `compress_with_options`
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub enum CompressError {
+		/// Text contains a char the dictionary has no code for
+		MissingSymbol(char),
+		/// Text has more symbols than codes of the maximum code length can tell apart
+		CodeLengthLimit(u8),
+	}
+	impl std::fmt::Display for CompressError {
+		fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			match self {
+				CompressError::MissingSymbol(c) => {
+					write!(f, "Dictionary has no code for {:?}", c)
+				}
+				CompressError::CodeLengthLimit(max_length) => write!(
+					f,
+					"Too many distinct symbols for codes of at most {} bits",
+					max_length
+				),
 			}
-			return output;
 		}
-		for &bit in &bit_stream {
-			if tmp.left.is_none() {
-				output.push(tmp.letter);
-				tmp = tree;
+	}
+	impl std::error::Error for CompressError {}
+
+	/// ID of the built-in table for English and other ASCII text
+	pub const ENGLISH: u8 = 1;
+
+	/// Code length of every ASCII char in the table `ENGLISH`, from the char frequencies
+	/// of typical English prose. Members only refer to it by ID, so it must never change.
+	/// NUL's code is used for `ESCAPE` instead
+	#[rustfmt::skip]
+	const ENGLISH_LENGTHS: [u8; 128] = [
+		17, 17, 17, 17, 17, 17, 17, 17, 17, 12, 6, 17, 17, 17, 17, 17,
+		17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17,
+		3, 11, 9, 16, 16, 15, 15, 9, 12, 12, 15, 15, 7, 9, 7, 15,
+		11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 12, 15, 15, 15, 11,
+		15, 9, 11, 11, 10, 8, 11, 11, 9, 9, 15, 12, 10, 11, 9, 9,
+		11, 15, 10, 9, 9, 11, 12, 11, 15, 11, 15, 15, 15, 15, 15, 15,
+		15, 4, 7, 6, 5, 3, 6, 6, 4, 4, 10, 8, 5, 6, 4, 4,
+		6, 10, 5, 4, 4, 6, 7, 6, 10, 6, 11, 15, 15, 15, 15, 16,
+	];
+
+	/// A huffman tree trained ahead of time and shared by compressor and decompressor,
+	/// so small inputs don't each pay for an embedded tree
+	///
+	/// # Data Format
+	/// Magic bytes `HFD\x02`, the ID (u32, little endian), then the tree as embedded by
+	/// `compress`. Version 1 files, with magic bytes `HFD\x01`, have no ID
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub struct Dictionary {
+		tree: Node,
+		/// Built-in table the tree comes from, members store this instead of marker 0
+		builtin: Option<u8>,
+		id: u32,
+	}
+	impl Dictionary {
+		fn new(tree: Node, builtin: Option<u8>) -> Dictionary {
+			let id = checksum::crc32(&embed_tree(&tree));
+			Dictionary { tree, builtin, id }
+		}
+		/// CRC-32 of the embedded tree, telling dictionaries apart
+		pub fn id(&self) -> u32 {
+			self.id
+		}
+		/// Built-in table `id`, known to every decoder so members only store the ID.
+		/// `None` for unknown IDs
+		///
+		/// Built-in tables can't be written with `to_bytes`
+		pub fn builtin(id: u8) -> Option<Dictionary> {
+			let lengths = match id {
+				ENGLISH => &ENGLISH_LENGTHS,
+				_ => return None,
+			};
+			let entries = (0..128u8)
+				.zip(canonical::codes(lengths))
+				.map(|(c, (code, length))| TableEntry {
+					symbol: if c == 0 { ESCAPE } else { c as char },
+					frequency: 0,
+					code: format!("{:01$b}", code, length as usize),
+				})
+				.collect();
+			Some(Dictionary::new(
+				CodeTable { entries }
+					.to_tree()
+					.expect("Built-in codes are complete"),
+				Some(id),
+			))
+		}
+
+		const MAGIC: &'static [u8; 4] = b"HFD\x02";
+		const MAGIC_V1: &'static [u8; 4] = b"HFD\x01";
+
+		/// Build a dictionary from sample text, which must not be empty
+		///
+		/// Chars missing from the sample are escaped, see `ESCAPE`
+		pub fn train(sample: &str) -> Dictionary {
+			Dictionary::train_samples([sample.as_bytes()]).expect("Sample cannot be empty")
+		}
+		/// Build a dictionary from the char frequencies of all `samples` together,
+		/// `None` if they are all empty
+		///
+		/// Samples that aren't UTF-8 count every byte as a char, as binary members do
+		pub fn train_samples<S: AsRef<[u8]>, I: IntoIterator<Item = S>>(
+			samples: I,
+		) -> Option<Dictionary> {
+			let mut merged: BTreeMap<char, i32> = BTreeMap::new();
+			for sample in samples {
+				let sample = sample.as_ref();
+				let binary;
+				let text = match std::str::from_utf8(sample) {
+					Ok(text) => text,
+					Err(_) => {
+						binary = sample.iter().map(|&byte| byte as char).collect::<String>();
+						&binary
+					}
+				};
+				if text.is_empty() {
+					continue;
+				}
+				for node in freq_count(text.chars()) {
+					*merged.entry(node.letter).or_insert(0) += node.freq;
+				}
+			}
+			if merged.is_empty() {
+				return None;
 			}
-			let right: &Node = tmp.right.as_ref().unwrap().as_ref();
-			let left: &Node = tmp.left.as_ref().unwrap().as_ref();
-			tmp = if bit { right } else { left };
+			merged.remove(&ESCAPE);
+			let mut frequency: Vec<Node> = merged
+				.into_iter()
+				.map(|(letter, freq)| Node::new(letter, freq))
+				.collect();
+			frequency.push(Node::new(ESCAPE, 1));
+			Some(Dictionary::new(construct_huffman_tree(frequency), None))
 		}
-		if tmp != tree {
-			output.push(tmp.letter);
+		pub fn to_bytes(&self) -> Vec<u8> {
+			let mut bytes = Dictionary::MAGIC.to_vec();
+			bytes.extend(&self.id.to_le_bytes());
+			bytes.extend(embed_tree(&self.tree));
+			bytes
 		}
-		return output;
+		pub fn from_bytes(data: &[u8]) -> Result<Dictionary, DecompressError> {
+			let mut cursor = Cursor::new(data);
+			let id = match cursor.take(4, "magic bytes") {
+				Ok(magic) if magic == Dictionary::MAGIC => Some(cursor.u32("dictionary ID")?),
+				Ok(magic) if magic == Dictionary::MAGIC_V1 => None,
+				_ => return Err(DecompressError::InvalidDictionary),
+			};
+			let (tree, rest) = read_tree(cursor.rest())?;
+			if !rest.is_empty() || CodeTable::of_tree(&tree, "").validate().is_err() {
+				return Err(DecompressError::InvalidDictionary);
+			}
+			let dictionary = Dictionary::new(tree, None);
+			if id.is_some_and(|id| id != dictionary.id) {
+				return Err(DecompressError::InvalidDictionary);
+			}
+			Ok(dictionary)
+		}
+	}
+
+	/// Symbol statistics and codes of a huffman tree, exchanged as JSON
+	///
+	/// # Data Format
+	/// ```text
+	/// {"symbols": [{"symbol": "e", "frequency": 12, "code": "010"}, ...]}
+	/// ```
+	/// Frequencies are informative, the codes alone define the tree.
+	/// A symbol `"\uffff"` is the escape code, see `ESCAPE`.
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub struct CodeTable {
+		/// Sorted by code length, then code
+		pub entries: Vec<TableEntry>,
 	}
-	pub fn decompress(data: &Vec<u8>) -> String {
-		let post_order_length = *data.first().expect("Data cannot be empty") as usize;
-		let post_order = &data[1..=post_order_length];
-		let huffman_tree = construct_tree_from_postorder(post_order);
-		let data = &data[post_order_length + 1..];
-		decompress_data(data, &huffman_tree)
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub struct TableEntry {
+		pub symbol: char,
+		pub frequency: u64,
+		/// Code as a string of `0` and `1`
+		pub code: String,
+	}
+	/// Symbols with codes of one length, see `CodeTable::length_histogram`
+	#[derive(Clone, Copy, PartialEq, Eq, std::fmt::Debug)]
+	pub struct LengthCount {
+		pub length: usize,
+		pub symbols: usize,
+		/// Sum of the frequencies of those symbols
+		pub frequency: u64,
+	}
+	impl CodeTable {
+		/// Every code length in use, shortest first
+		pub fn length_histogram(&self) -> Vec<LengthCount> {
+			let mut histogram: BTreeMap<usize, LengthCount> = BTreeMap::new();
+			for entry in &self.entries {
+				let length = entry.code.len();
+				let count = histogram.entry(length).or_insert(LengthCount {
+					length,
+					symbols: 0,
+					frequency: 0,
+				});
+				count.symbols += 1;
+				count.frequency += entry.frequency;
+			}
+			histogram.into_values().collect()
+		}
+		/// Length of the longest code, the depth of the tree
+		pub fn max_depth(&self) -> usize {
+			self.entries
+				.iter()
+				.map(|entry| entry.code.len())
+				.max()
+				.unwrap_or(0)
+		}
+		/// Code length averaged over the symbols of the text, `None` without any
+		pub fn mean_length(&self) -> Option<f64> {
+			let (bits, count) = self.entries.iter().fold((0, 0), |(bits, count), entry| {
+				(
+					bits + entry.frequency * entry.code.len() as u64,
+					count + entry.frequency,
+				)
+			});
+			if count == 0 {
+				None
+			} else {
+				Some(bits as f64 / count as f64)
+			}
+		}
+		/// Table of the tree `compress` builds for `text`, `None` if it is empty
+		pub fn for_text(text: &str) -> Option<CodeTable> {
+			if text.is_empty() {
+				return None;
+			}
+			Some(CodeTable::of_tree(
+				&construct_huffman_tree(freq_count(text.chars())),
+				text,
+			))
+		}
+		/// Huffman codes for bytes with the given frequencies, for applications that count
+		/// their symbols already. Bytes are the chars U+0000 to U+00FF, as in binary
+		/// members, so `Dictionary::from_table` makes a dictionary for binary data of it.
+		/// Bytes counted 0 times get no code, `None` if no byte is counted
+		pub fn from_frequencies(frequencies: &HashMap<u8, u64>) -> Option<CodeTable> {
+			let mut counted: Vec<(u8, u64)> = frequencies
+				.iter()
+				.filter(|&(_, &frequency)| frequency != 0)
+				.map(|(&byte, &frequency)| (byte, frequency))
+				.collect();
+			if counted.is_empty() {
+				return None;
+			}
+			counted.sort_unstable();
+			let counts: Vec<u64> = counted.iter().map(|&(_, frequency)| frequency).collect();
+			let lengths = canonical::code_lengths(&counts);
+			let mut entries: Vec<TableEntry> = counted
+				.iter()
+				.zip(canonical::codes(&lengths))
+				.map(|(&(byte, frequency), (code, length))| TableEntry {
+					symbol: byte as char,
+					frequency,
+					code: format!("{:01$b}", code, length as usize),
+				})
+				.collect();
+			entries.sort_by(|a, b| (a.code.len(), &a.code).cmp(&(b.code.len(), &b.code)));
+			Some(CodeTable { entries })
+		}
+		/// Table of `tree`, with the frequencies of its symbols in `text`. Chars without
+		/// a code count as the escape once per byte, as that's how they are coded
+		fn of_tree(tree: &Node, text: &str) -> CodeTable {
+			let codes = leaf_codes(tree);
+			let lookup: HashSet<char> = codes.iter().map(|(symbol, _)| *symbol).collect();
+			let mut frequency: HashMap<char, u64> = HashMap::new();
+			for c in text.chars() {
+				if lookup.contains(&c) && c != ESCAPE {
+					*frequency.entry(c).or_insert(0) += 1;
+				} else {
+					*frequency.entry(ESCAPE).or_insert(0) += c.len_utf8() as u64;
+				}
+			}
+			let mut entries: Vec<TableEntry> = codes
+				.into_iter()
+				.map(|(symbol, code)| TableEntry {
+					symbol,
+					frequency: frequency.get(&symbol).copied().unwrap_or(0),
+					code,
+				})
+				.collect();
+			entries.sort_by(|a, b| (a.code.len(), &a.code).cmp(&(b.code.len(), &b.code)));
+			CodeTable { entries }
+		}
+		pub fn to_json(&self) -> String {
+			let entries: Vec<String> = self
+				.entries
+				.iter()
+				.map(|entry| {
+					format!(
+						"  {{\"symbol\": \"{}\", \"frequency\": {}, \"code\": \"{}\"}}",
+						json::escape(&entry.symbol.to_string()),
+						entry.frequency,
+						entry.code
+					)
+				})
+				.collect();
+			format!("{{\"symbols\": [\n{}\n]}}\n", entries.join(",\n"))
+		}
+		pub fn from_json(text: &str) -> Result<CodeTable, String> {
+			let value = json::parse(text)?;
+			let symbols = value
+				.get("symbols")
+				.and_then(|symbols| symbols.as_array())
+				.ok_or("Expected an object with a `symbols` array")?;
+			let mut entries = Vec::new();
+			for (i, entry) in symbols.iter().enumerate() {
+				let error = |msg: &str| format!("Symbol {}: {}", i + 1, msg);
+				let mut chars = entry
+					.get("symbol")
+					.and_then(|symbol| symbol.as_str())
+					.ok_or_else(|| error("missing `symbol`"))?
+					.chars();
+				let symbol = match (chars.next(), chars.next()) {
+					(Some(symbol), None) => symbol,
+					_ => return Err(error("`symbol` must be a single char")),
+				};
+				let frequency = match entry.get("frequency") {
+					Some(frequency) => frequency
+						.as_u64()
+						.ok_or_else(|| error("`frequency` must be an integer"))?,
+					None => 0,
+				};
+				let code = entry
+					.get("code")
+					.and_then(|code| code.as_str())
+					.ok_or_else(|| error("missing `code`"))?;
+				if code.is_empty() || code.bytes().any(|bit| bit != b'0' && bit != b'1') {
+					return Err(error("`code` must be a string of 0 and 1"));
+				}
+				entries.push(TableEntry {
+					symbol,
+					frequency,
+					code: code.to_string(),
+				});
+			}
+			Ok(CodeTable { entries })
+		}
+		/// Check that the codes can be decoded: there are symbols, every symbol appears
+		/// once, no code is a prefix of another and the codes are complete, so the
+		/// Kraft-McMillan sum of 2^-length over all codes is exactly 1. A lone symbol
+		/// is always valid, it needs no code
+		pub fn validate(&self) -> Result<(), String> {
+			match self.entries.as_slice() {
+				[] => return Err("Table has no symbols".to_string()),
+				[_] => return Ok(()),
+				_ => {}
+			}
+			let mut seen = HashSet::new();
+			for entry in &self.entries {
+				if !seen.insert(entry.symbol) {
+					return Err(format!("Symbol {:?} appears twice", entry.symbol));
+				}
+				if entry.code.is_empty() || entry.code.bytes().any(|bit| bit != b'0' && bit != b'1')
+				{
+					return Err(format!(
+						"Code of {:?} is not a string of 0 and 1",
+						entry.symbol
+					));
+				}
+			}
+			// In lexicographic order a code is followed by the codes it is a prefix of
+			let mut sorted: Vec<&TableEntry> = self.entries.iter().collect();
+			sorted.sort_by(|a, b| a.code.cmp(&b.code));
+			for pair in sorted.windows(2) {
+				if pair[1].code.starts_with(&pair[0].code) {
+					return Err(format!(
+						"Code of {:?} is a prefix of the code of {:?}",
+						pair[0].symbol, pair[1].symbol
+					));
+				}
+			}
+			// Sum 2^-length exactly, carrying pairs of codes of one length to the next shorter
+			let longest = sorted.iter().map(|entry| entry.code.len()).max().unwrap();
+			let mut counts = vec![0u64; longest + 1];
+			for entry in &sorted {
+				counts[entry.code.len()] += 1;
+			}
+			let mut fraction = false;
+			for length in (1..=longest).rev() {
+				fraction |= counts[length] % 2 == 1;
+				counts[length - 1] += counts[length] / 2;
+			}
+			match (counts[0], fraction) {
+				(1, false) => Ok(()),
+				(0, _) => {
+					Err("Codes are incomplete, some bit strings decode to nothing".to_string())
+				}
+				_ => Err("Codes violate the Kraft inequality".to_string()),
+			}
+		}
+		/// Rebuild the tree the codes describe
+		fn to_tree(&self) -> Result<Node, String> {
+			/// Tree under construction
+			enum Partial {
+				Empty,
+				Leaf(char),
+				Internal(Box<Partial>, Box<Partial>),
+			}
+			fn insert(node: &mut Partial, symbol: char, code: &[u8]) -> Result<(), String> {
+				match (node, code.split_first()) {
+					(node @ Partial::Empty, None) => {
+						*node = Partial::Leaf(symbol);
+						Ok(())
+					}
+					(node @ Partial::Empty, Some(_)) => {
+						*node =
+							Partial::Internal(Box::new(Partial::Empty), Box::new(Partial::Empty));
+						insert(node, symbol, code)
+					}
+					(Partial::Internal(left, right), Some((&bit, rest))) => {
+						insert(if bit == b'0' { left } else { right }, symbol, rest)
+					}
+					_ => Err(format!("Code of {:?} overlaps another code", symbol)),
+				}
+			}
+			fn complete(node: Partial) -> Result<Node, String> {
+				match node {
+					Partial::Empty => {
+						Err("Codes are incomplete, some bit strings decode to nothing".to_string())
+					}
+					Partial::Leaf(symbol) => Ok(Node::new(symbol, 0)),
+					Partial::Internal(left, right) => Ok(Node {
+						letter: '\0',
+						freq: 0,
+						left: Some(Box::new(complete(*left)?)),
+						right: Some(Box::new(complete(*right)?)),
+					}),
+				}
+			}
+			match self.entries.as_slice() {
+				[] => return Err("Table has no symbols".to_string()),
+				// A lone symbol has no real code
+				[entry] => return Ok(Node::new(entry.symbol, 0)),
+				_ => {}
+			}
+			let mut root = Partial::Empty;
+			let mut seen = HashSet::new();
+			for entry in &self.entries {
+				if !seen.insert(entry.symbol) {
+					return Err(format!("Symbol {:?} appears twice", entry.symbol));
+				}
+				insert(&mut root, entry.symbol, entry.code.as_bytes())?;
+			}
+			complete(root)
+		}
+	}
+
+	impl Dictionary {
+		/// Dictionary encoding with exactly the codes of `table`
+		pub fn from_table(table: &CodeTable) -> Result<Dictionary, String> {
+			table.validate()?;
+			Ok(Dictionary::new(table.to_tree()?, None))
+		}
+	}
+
+	/// Compress `text` using the tree of `dictionary` instead of an embedded one
+	pub fn compress_with_dictionary(
+		text: &str,
+		dictionary: &Dictionary,
+	) -> Result<Vec<u8>, CompressError> {
+		compress_with_options(
+			text,
+			&CompressOptions {
+				dictionary: Some(dictionary),
+				..CompressOptions::default()
+			},
+		)
+	}
+	/// Reasons a byte stream can be rejected by `decompress`
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub enum DecompressError {
+		/// Input contains no bytes at all
+		Empty,
+		/// Input ended before a field was complete
+		///
+		/// `expected` bytes of `field` were needed at `offset`, only `found` were left.
+		/// Offsets count from the start of the structure being read, a member header
+		/// or a payload.
+		Truncated {
+			field: &'static str,
+			offset: usize,
+			expected: usize,
+			found: usize,
+		},
+		/// Post-order traversal does not describe a single complete tree
+		InvalidTree,
+		/// Padding byte is larger than 7 or than the number of data bits
+		InvalidPadding(u8),
+		/// Data bits end in the middle of a code
+		IncompleteCode,
+		/// Data was compressed with a dictionary, but none was given
+		DictionaryRequired,
+		/// Data was compressed with the dictionary of ID `required`, `given` is another one
+		/// or none, see `Dictionary::id`
+		WrongDictionary { required: u32, given: Option<u32> },
+		/// Dictionary file is malformed
+		InvalidDictionary,
+		/// Text stored without compression is not UTF-8, or data asked for as text is binary
+		InvalidUtf8,
+		/// Binary member decodes to a char above U+00FF, which stands for no byte
+		InvalidByte,
+		/// Bytes following the last member at the given offset are not a member
+		TrailingGarbage(usize),
+		/// Member has a version this decoder doesn't know
+		UnknownVersion(u8),
+		/// Member header announces fields this decoder doesn't know
+		UnknownFlags(u8),
+		/// Member is coded with a built-in table this decoder doesn't know
+		UnknownTable(u8),
+		/// Delta filter has a width of 0
+		InvalidDeltaWidth,
+		/// LZW code stands for a string not in the table yet, see `lzw`
+		InvalidCode(u32),
+		/// Rice code stands for a value beyond a byte, see `rice`
+		ValueTooLarge(u64),
+		/// Varint of this field doesn't fit 64 bits, see `codes`
+		InvalidVarint(&'static str),
+		/// Buffer given to `decompress_into` is shorter than the text
+		OutputTooSmall { needed: usize, available: usize },
+		/// Text is longer than the limit given to `decompress_stream_limited`
+		OutputTooLarge(u64),
+		/// Text has a different length than the header declares
+		SizeMismatch { expected: u64, found: u64 },
+		/// Data continues after the last code
+		UnusedBits,
+		/// Decompressed text doesn't match the CRC-32 stored with it
+		ChecksumMismatch { expected: u32, found: u32 },
+		/// Error in one of several members, numbered from 1, starting at `offset`
+		Block {
+			block: usize,
+			offset: usize,
+			error: Box<DecompressError>,
+		},
+	}
+	impl std::fmt::Display for DecompressError {
+		fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			match self {
+				DecompressError::Empty => write!(f, "Data is empty"),
+				DecompressError::Truncated {
+					field,
+					offset,
+					expected,
+					found,
+				} => write!(
+					f,
+					"Data truncated in {} at offset {}: expected {} bytes, found {}",
+					field, offset, expected, found
+				),
+				DecompressError::InvalidTree => write!(f, "Huffman tree is malformed"),
+				DecompressError::InvalidPadding(padding) => {
+					write!(f, "Padding byte of {} bits is not possible", padding)
+				}
+				DecompressError::IncompleteCode => write!(f, "Data ends in the middle of a code"),
+				DecompressError::DictionaryRequired => {
+					write!(f, "Data was compressed with a dictionary")
+				}
+				DecompressError::WrongDictionary { required, given } => {
+					write!(f, "Dictionary {:#010x} required", required)?;
+					match given {
+						Some(given) => write!(f, ", not {:#010x}", given),
+						None => Ok(()),
+					}
+				}
+				DecompressError::InvalidDictionary => write!(f, "Dictionary is malformed"),
+				DecompressError::InvalidUtf8 => write!(f, "Text is not UTF-8"),
+				DecompressError::InvalidByte => {
+					write!(f, "Binary data decodes to a char that is no byte")
+				}
+				DecompressError::TrailingGarbage(offset) => {
+					write!(f, "Unexpected data after last member at offset {}", offset)
+				}
+				DecompressError::UnknownVersion(version) => {
+					write!(f, "Unknown format version {}", version)
+				}
+				DecompressError::UnknownFlags(flags) => {
+					write!(f, "Unknown header flags {:#04x}", flags)
+				}
+				DecompressError::UnknownTable(id) => write!(f, "Unknown built-in table {}", id),
+				DecompressError::SizeMismatch { expected, found } => write!(
+					f,
+					"Text is {} bytes long, the header declares {}",
+					found, expected
+				),
+				DecompressError::OutputTooSmall { needed, available } => write!(
+					f,
+					"Text of {} bytes doesn't fit in {} bytes",
+					needed, available
+				),
+				DecompressError::OutputTooLarge(limit) => {
+					write!(f, "Text is longer than the limit of {} bytes", limit)
+				}
+				DecompressError::InvalidDeltaWidth => {
+					write!(f, "Delta filter width of 0 is not possible")
+				}
+				DecompressError::InvalidCode(code) => {
+					write!(f, "Code {} is not in the table", code)
+				}
+				DecompressError::ValueTooLarge(value) => {
+					write!(f, "Code of value {} is too large for a byte", value)
+				}
+				DecompressError::InvalidVarint(field) => {
+					write!(f, "Integer in {} doesn't fit 64 bits", field)
+				}
+				DecompressError::UnusedBits => write!(f, "Data continues after the last code"),
+				DecompressError::ChecksumMismatch { expected, found } => write!(
+					f,
+					"Checksum mismatch: expected {:08x}, found {:08x}",
+					expected, found
+				),
+				DecompressError::Block {
+					block,
+					offset,
+					error,
+				} => write!(f, "Block {} at offset {}: {}", block, offset, error),
+			}
+		}
+	}
+	impl std::error::Error for DecompressError {}
+
+	fn construct_tree_from_postorder(postorder: &[u8]) -> Result<Node, DecompressError> {
+		// parent left right
+		// Assuming input does not contain null
+		let mut stack = Vec::new();
+		for c in postorder {
+			if *c == 0 {
+				let (left, right) = match (stack.pop(), stack.pop()) {
+					(Some(left), Some(right)) => (left, right),
+					_ => return Err(DecompressError::InvalidTree),
+				};
+				stack.push(Node {
+					letter: '\0',
+					freq: 0,
+					left: Option::from(Box::from(right)),
+					right: Option::from(Box::from(left)),
+				});
+			} else {
+				stack.push(Node {
+					letter: if *c == ESCAPE_BYTE {
+						ESCAPE
+					} else {
+						*c as char
+					},
+					freq: 0,
+					left: None,
+					right: None,
+				});
+			}
+		}
+		if stack.len() != 1 {
+			return Err(DecompressError::InvalidTree);
+		}
+		Ok(stack.pop().unwrap())
+	}
+
+	/// Split the padding byte off the front of `data`, returning it and the data bytes
+	///
+	/// The padding counts the unused bits at the end of the last byte, so it has to be
+	/// below 8, and 0 when there are no data bytes.
+	fn split_padding(data: &[u8]) -> Result<(u8, &[u8]), DecompressError> {
+		let mut cursor = Cursor::new(data);
+		let padding = cursor.u8("padding")?;
+		let data = cursor.rest();
+		if padding > 7 || (data.is_empty() && padding != 0) {
+			return Err(DecompressError::InvalidPadding(padding));
+		}
+		Ok((padding, data))
+	}
+
+	fn decompress_data(data: &[u8], tree: &Node) -> Result<String, DecompressError> {
+		let (padding, data) = split_padding(data)?;
+		let bit_count = data.len() * 8 - padding as usize; // Without padding bits
+		if tree.left.is_none() {
+			// Huffman tree is complete binary tree, a node will have either 0 or 2 children, 1 is not possible
+			let mut output = String::new();
+			for _ in 0..bit_count {
+				output.push(tree.letter);
+			}
+			return Ok(output);
+		}
+		let mut bits = BitReader::new(data);
+		let mut remaining = bit_count;
+		let mut output = Vec::new();
+		while remaining > 0 {
+			let mut tmp = tree;
+			while let (Some(left), Some(right)) = (&tmp.left, &tmp.right) {
+				if remaining == 0 {
+					return Err(DecompressError::IncompleteCode);
+				}
+				let bit = bits.read_bit().unwrap().unwrap(); // Within data
+				remaining -= 1;
+				tmp = if bit { right } else { left };
+			}
+			if tmp.letter == ESCAPE {
+				if remaining < 8 {
+					return Err(DecompressError::IncompleteCode);
+				}
+				output.push(bits.read_bits(8).unwrap().unwrap() as u8);
+				remaining -= 8;
+			} else {
+				output.extend(tmp.letter.encode_utf8(&mut [0; 4]).as_bytes());
+			}
+		}
+		String::from_utf8(output).map_err(|_| DecompressError::InvalidUtf8)
+	}
+	/// Split an embedded tree off the front of `data`, returning the tree and remaining bytes
+	///
+	/// Reads trees of `embed_tree`, and post-order traversals of older members
+	fn read_tree(data: &[u8]) -> Result<(Node, &[u8]), DecompressError> {
+		let mut cursor = Cursor::new(data);
+		let post_order_length = cursor.u8("huffman tree")?;
+		if post_order_length == TREE_MARKER {
+			return read_tree_shape(cursor);
+		}
+		let post_order = cursor.take(post_order_length as usize, "huffman tree")?;
+		let huffman_tree = construct_tree_from_postorder(post_order)?;
+		Ok((huffman_tree, cursor.rest()))
+	}
+	/// Read a tree written by `embed_tree` following its marker at `cursor`, returning
+	/// the tree and remaining bytes
+	fn read_tree_shape(mut cursor: Cursor<'_>) -> Result<(Node, &[u8]), DecompressError> {
+		let leaf_count = cursor.u32("huffman tree")?;
+		let node_count = (2 * leaf_count as u64)
+			.checked_sub(1)
+			.ok_or(DecompressError::InvalidTree)?;
+		let shape_len = usize::try_from(node_count.div_ceil(8)).unwrap_or(usize::MAX);
+		let mut shape = BitReader::new(cursor.take(shape_len, "huffman tree")?);
+		let mut stack = Vec::new();
+		for _ in 0..node_count {
+			// Within the shape, which holds a bit for every node
+			match shape.read_bit().unwrap().unwrap() {
+				true => {
+					let (right, left) = match (stack.pop(), stack.pop()) {
+						(Some(right), Some(left)) => (right, left),
+						_ => return Err(DecompressError::InvalidTree),
+					};
+					stack.push(Node {
+						letter: '\0',
+						freq: 0,
+						left: Some(Box::new(left)),
+						right: Some(Box::new(right)),
+					});
+				}
+				false => stack.push(Node::new(read_leaf(&mut cursor)?, 0)),
+			}
+		}
+		if stack.len() != 1 {
+			return Err(DecompressError::InvalidTree);
+		}
+		Ok((stack.pop().unwrap(), cursor.rest()))
+	}
+	/// Read a leaf of a tree written by `embed_tree` at `cursor`
+	fn read_leaf(cursor: &mut Cursor) -> Result<char, DecompressError> {
+		let len = match cursor.rest().first() {
+			Some(&ESCAPE_BYTE) => {
+				cursor.u8("huffman tree")?;
+				return Ok(ESCAPE);
+			}
+			Some(byte) if byte.leading_ones() == 1 || byte.leading_ones() > 4 => {
+				return Err(DecompressError::InvalidTree)
+			}
+			Some(byte) => (byte.leading_ones() as usize).max(1),
+			None => 1,
+		};
+		let bytes = cursor.take(len, "huffman tree")?;
+		std::str::from_utf8(bytes)
+			.map_err(|_| DecompressError::InvalidTree)
+			.map(|leaf| leaf.chars().next().unwrap())
+	}
+	fn decompress_payload(
+		payload: &[u8],
+		dictionary: Option<&Dictionary>,
+	) -> Result<String, DecompressError> {
+		match (payload.first(), dictionary) {
+			(Some(&DICTIONARY_MARKER), Some(dictionary)) => {
+				decompress_data(&payload[1..], &dictionary.tree)
+			}
+			(Some(&DICTIONARY_MARKER), None) => Err(DecompressError::DictionaryRequired),
+			(Some(&BUILTIN_MARKER), _) => {
+				let id = builtin_id(payload)?;
+				let dictionary =
+					Dictionary::builtin(id).ok_or(DecompressError::UnknownTable(id))?;
+				decompress_data(&payload[2..], &dictionary.tree)
+			}
+			(Some(&STORED_MARKER), _) => {
+				String::from_utf8(payload[1..].to_vec()).map_err(|_| DecompressError::InvalidUtf8)
+			}
+			(Some(&WORD_MARKER), _) => words::decode(&payload[1..]),
+			(Some(&CONTEXT_MARKER), _) => String::from_utf8(context::decode(&payload[1..])?)
+				.map_err(|_| DecompressError::InvalidUtf8),
+			(Some(&DIGRAM_MARKER), _) => digram::decode(&payload[1..]),
+			(Some(&RICE_MARKER), _) => {
+				String::from_utf8(decode_rice(payload)?).map_err(|_| DecompressError::InvalidUtf8)
+			}
+			(Some(&RLE_MARKER), _) => {
+				String::from_utf8(rle::decode(&context::decode(&payload[1..])?)?)
+					.map_err(|_| DecompressError::InvalidUtf8)
+			}
+			(Some(&DELTA_MARKER), _) => {
+				let width = delta_width(payload)?;
+				String::from_utf8(delta::decode(&context::decode(&payload[2..])?, width))
+					.map_err(|_| DecompressError::InvalidUtf8)
+			}
+			_ => {
+				let (huffman_tree, data) = read_tree(payload)?;
+				decompress_data(data, &huffman_tree)
+			}
+		}
+	}
+	/// ID of the built-in table a payload with `BUILTIN_MARKER` is coded with
+	fn builtin_id(payload: &[u8]) -> Result<u8, DecompressError> {
+		let mut cursor = Cursor::new(payload);
+		cursor.u8("marker")?;
+		cursor.u8("table ID")
+	}
+	/// Width of the delta filter of a payload with `DELTA_MARKER`
+	fn delta_width(payload: &[u8]) -> Result<usize, DecompressError> {
+		let mut cursor = Cursor::new(payload);
+		cursor.u8("marker")?;
+		match cursor.u8("delta width")? {
+			0 => Err(DecompressError::InvalidDeltaWidth),
+			width => Ok(width as usize),
+		}
+	}
+	/// Width of the delta filter of a payload with `RICE_MARKER`, 0 for none
+	fn rice_width(payload: &[u8]) -> Result<u8, DecompressError> {
+		let mut cursor = Cursor::new(payload);
+		cursor.u8("marker")?;
+		cursor.u8("delta width")
+	}
+	/// Bytes coded by a payload with `RICE_MARKER`
+	fn decode_rice(payload: &[u8]) -> Result<Vec<u8>, DecompressError> {
+		let width = rice_width(payload)?;
+		let bytes = rice::decode(&payload[2..])?;
+		Ok(match width {
+			0 => bytes,
+			width => delta::decode(&bytes, width as usize),
+		})
+	}
+	/// Decompress a single member and check its CRC-32
+	fn decompress_member(
+		member: &Member,
+		dictionary: Option<&Dictionary>,
+	) -> Result<Vec<u8>, DecompressError> {
+		if let Some(required) = member.dictionary_id {
+			let given = dictionary.map(Dictionary::id);
+			if given != Some(required) {
+				return Err(DecompressError::WrongDictionary { required, given });
+			}
+		}
+		let data = match (member.binary, member.payload.first()) {
+			(true, Some(&STORED_MARKER)) => member.payload[1..].to_vec(),
+			(true, Some(&RICE_MARKER)) => decode_rice(member.payload)?,
+			(true, _) => decompress_payload(member.payload, dictionary)?
+				.chars()
+				.map(|c| u8::try_from(c).map_err(|_| DecompressError::InvalidByte))
+				.collect::<Result<_, _>>()?,
+			(false, _) => decompress_payload(member.payload, dictionary)?.into_bytes(),
+		};
+		debug!(
+			"Member of {} bytes, {}, decoded to {} bytes",
+			member.payload.len(),
+			payload_kind(member.payload),
+			data.len()
+		);
+		if let Some(expected) = member.original_size {
+			let found = data.len() as u64;
+			if found != expected {
+				return Err(DecompressError::SizeMismatch { expected, found });
+			}
+		}
+		if let Some(expected) = member.checksum {
+			let found = checksum::crc32(&data);
+			if found != expected {
+				return Err(DecompressError::ChecksumMismatch { expected, found });
+			}
+		}
+		Ok(data)
+	}
+	/// Decompress every member and concatenate the output
+	///
+	/// When there are several members, errors tell which one failed
+	fn decompress_members(
+		data: &[u8],
+		dictionary: Option<&Dictionary>,
+	) -> Result<Vec<u8>, DecompressError> {
+		let mut output = Vec::with_capacity(declared_size(data));
+		for_each_member(data, dictionary, |data| output.extend_from_slice(data))?;
+		Ok(output)
+	}
+	/// Sum of the text lengths the members of `data` declare, to allocate output up front.
+	/// 0 if a member doesn't declare one. Limited, as corrupt headers may declare anything
+	fn declared_size(data: &[u8]) -> usize {
+		let sizes = members(data)
+			.unwrap_or_default()
+			.iter()
+			.map(|member| member.original_size)
+			.sum::<Option<u64>>();
+		sizes.unwrap_or(0).min(data.len() as u64 * 16) as usize
+	}
+	/// Decompress every member, passing its data to `f`, see `decompress_members`
+	fn for_each_member<F: FnMut(&[u8])>(
+		data: &[u8],
+		dictionary: Option<&Dictionary>,
+		mut f: F,
+	) -> Result<(), DecompressError> {
+		let members = members(data)?;
+		let several = members.len() > 1;
+		for (i, member) in members.iter().enumerate() {
+			f(&decompress_member(member, dictionary).map_err(|error| {
+				if several {
+					DecompressError::Block {
+						block: i + 1,
+						offset: member.offset,
+						error: Box::new(error),
+					}
+				} else {
+					error
+				}
+			})?);
+		}
+		Ok(())
+	}
+
+	/// Read from `reader` until `buffer` starts with a whole member, returning its length.
+	/// `None` at the end of input, with any partial member left in `buffer`.
+	/// `offset` is the position of `buffer` in the input, for errors
+	fn read_member<R: Read>(
+		reader: &mut R,
+		buffer: &mut Vec<u8>,
+		offset: usize,
+	) -> io::Result<Option<usize>> {
+		let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+		loop {
+			if buffer.len() >= MAGIC.len() {
+				if !buffer.starts_with(MAGIC) {
+					return Err(invalid(DecompressError::TrailingGarbage(offset)));
+				}
+				match member_len(buffer).map_err(invalid)? {
+					Some(len) if len <= buffer.len() as u64 => return Ok(Some(len as usize)),
+					Some(len) => {
+						let missing = len - buffer.len() as u64;
+						if reader.by_ref().take(missing).read_to_end(buffer)? == 0 {
+							return Ok(None);
+						}
+						continue;
+					}
+					None => {}
+				}
+			}
+			let mut chunk = [0; 256];
+			match reader.read(&mut chunk) {
+				Ok(0) => return Ok(None),
+				Ok(n) => buffer.extend(&chunk[..n]),
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Decompress from `reader` to `writer` a member at a time, so memory stays bounded
+	/// by the block size. Corrupt data fails with `InvalidData` wrapping a `DecompressError`,
+	/// after everything before it was written
+	pub fn decompress_stream<R: Read, W: Write>(
+		reader: R,
+		mut writer: W,
+		dictionary: Option<&Dictionary>,
+	) -> io::Result<W> {
+		io::copy(&mut Decompressor::new(reader, dictionary), &mut writer)?;
+		Ok(writer)
+	}
+
+	/// `decompress_stream`, failing with `InvalidData` wrapping `OutputTooLarge` once the
+	/// text is longer than `limit` bytes. Members after the limit aren't decompressed
+	pub fn decompress_stream_limited<R: Read, W: Write>(
+		reader: R,
+		mut writer: W,
+		dictionary: Option<&Dictionary>,
+		limit: u64,
+	) -> io::Result<W> {
+		let mut text = Decompressor::new(reader, dictionary).take(limit.saturating_add(1));
+		if io::copy(&mut text, &mut writer)? > limit {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				DecompressError::OutputTooLarge(limit),
+			));
+		}
+		Ok(writer)
+	}
+
+	/// Decompress `data` to `writer` a member at a time, without holding all of the text,
+	/// see `decompress_stream`
+	pub fn decompress_to_writer<W: Write>(
+		data: &[u8],
+		writer: W,
+		dictionary: Option<&Dictionary>,
+	) -> io::Result<W> {
+		decompress_stream(data, writer, dictionary)
+	}
+
+	/// Reads the text of the compressed data `reader` produces, a member at a time,
+	/// see `decompress_stream`. Members are only decompressed once their text is read,
+	/// so `take(n)` decompresses just those holding the first `n` bytes
+	pub struct Decompressor<'a, R: Read> {
+		reader: R,
+		dictionary: Option<&'a Dictionary>,
+		/// Compressed data read ahead of the current member
+		buffer: Vec<u8>,
+		/// Text of the current member and how much of it was read
+		text: Vec<u8>,
+		position: usize,
+		block: usize,
+		offset: usize,
+		started: bool,
+		finished: bool,
+	}
+
+	impl<'a, R: Read> Decompressor<'a, R> {
+		pub fn new(reader: R, dictionary: Option<&'a Dictionary>) -> Decompressor<'a, R> {
+			Decompressor {
+				reader,
+				dictionary,
+				buffer: Vec::new(),
+				text: Vec::new(),
+				position: 0,
+				block: 1,
+				offset: 0,
+				started: false,
+				finished: false,
+			}
+		}
+
+		/// Decompress the next member into `text`, false at the end of the data
+		fn next_member(&mut self) -> io::Result<bool> {
+			let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+			if self.finished {
+				return Ok(false);
+			}
+			if !self.started {
+				self.started = true;
+				(&mut self.reader)
+					.take(MAGIC.len() as u64)
+					.read_to_end(&mut self.buffer)?;
+				if !self.buffer.starts_with(MAGIC) {
+					// Legacy data isn't made of members
+					self.finished = true;
+					self.reader.read_to_end(&mut self.buffer)?;
+					self.text =
+						decompress_members(&self.buffer, self.dictionary).map_err(invalid)?;
+					self.position = 0;
+					return Ok(true);
+				}
+			}
+			let (block, offset) = (self.block, self.offset);
+			let len = match read_member(&mut self.reader, &mut self.buffer, offset)? {
+				Some(len) => len,
+				None if self.buffer.is_empty() => {
+					self.finished = true;
+					return Ok(false);
+				}
+				None => {
+					// Report what is missing of the last member
+					self.finished = true;
+					let error = members(&self.buffer)
+						.err()
+						.unwrap_or(DecompressError::Truncated {
+							field: "header",
+							offset: 0,
+							expected: MAGIC.len(),
+							found: self.buffer.len(),
+						});
+					return Err(invalid(DecompressError::Block {
+						block,
+						offset,
+						error: Box::new(error),
+					}));
+				}
+			};
+			self.text =
+				decompress_members(&self.buffer[..len], self.dictionary).map_err(|error| {
+					invalid(DecompressError::Block {
+						block,
+						offset,
+						error: Box::new(error),
+					})
+				})?;
+			self.position = 0;
+			self.buffer.drain(..len);
+			self.block += 1;
+			self.offset += len;
+			Ok(true)
+		}
+	}
+
+	impl<R: Read> Read for Decompressor<'_, R> {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			while self.position == self.text.len() {
+				if !self.next_member()? {
+					return Ok(0);
+				}
+			}
+			let len = buf.len().min(self.text.len() - self.position);
+			buf[..len].copy_from_slice(&self.text[self.position..self.position + len]);
+			self.position += len;
+			Ok(len)
+		}
+	}
+
+	/// A file opened with `open`
+	enum Opened {
+		Plain(io::BufReader<File>),
+		Compressed(Decompressor<'static, io::BufReader<File>>),
+	}
+
+	impl Read for Opened {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			match self {
+				Opened::Plain(file) => file.read(buf),
+				Opened::Compressed(decompressor) => decompressor.read(buf),
+			}
+		}
+	}
+
+	/// Open `path` for reading its text, decompressing it if it starts with `MAGIC`,
+	/// so compressed and plain files can be read alike.
+	/// Files compressed with a dictionary can't be read this way
+	pub fn open<P: AsRef<Path>>(path: P) -> io::Result<impl Read> {
+		let mut file = io::BufReader::new(File::open(path)?);
+		Ok(if file.fill_buf()?.starts_with(MAGIC) {
+			Opened::Compressed(Decompressor::new(file, None))
+		} else {
+			Opened::Plain(file)
+		})
+	}
+
+	/// Compresses input pushed to it a chunk at a time, for callers that don't have
+	/// it all at once. Gives the same members as `compress_stream` for the same options,
+	/// but none at all for empty input
+	pub struct Encoder<'a> {
+		options: CompressOptions<'a>,
+		/// Input not compressed yet
+		pending: Vec<u8>,
+	}
+
+	impl<'a> Encoder<'a> {
+		pub fn new(options: CompressOptions<'a>) -> Encoder<'a> {
+			Encoder {
+				options,
+				pending: Vec::new(),
+			}
+		}
+
+		/// Compress `len` bytes of pending input into a member
+		fn compress_pending(&mut self, len: usize, output: &mut Vec<u8>) -> io::Result<()> {
+			output.extend(compress_member(
+				&self.pending[..len],
+				&self.options,
+				&self.options.metadata,
+				&mut Timings::default(),
+			)?);
+			self.options.metadata = Metadata::default();
+			self.pending.drain(..len);
+			Ok(())
+		}
+
+		/// Add `data` to the input, returning the members completed by it
+		pub fn push(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+			self.pending.extend(data);
+			let block_size = self.options.block_size.unwrap_or(STREAM_BLOCK_SIZE).max(1);
+			let mut output = Vec::new();
+			while self.pending.len() >= block_size {
+				// Like `compress_stream`, end the block after a char split at its end
+				let mut len = block_size;
+				let complete = loop {
+					match std::str::from_utf8(&self.pending[..len]) {
+						Err(e) if e.error_len().is_none() => {
+							if len == self.pending.len() {
+								break false;
+							}
+							len += 1;
+						}
+						// Input that isn't UTF-8 makes a binary member
+						_ => break true,
+					}
+				};
+				if !complete {
+					break; // Wait for the rest of the char
+				}
+				self.compress_pending(len, &mut output)?;
+			}
+			Ok(output)
+		}
+
+		/// Compress all input so far into a member, so the receiver can decode every
+		/// byte pushed until now without waiting for more, as request/response protocols
+		/// need. Members end byte aligned with their checksum, which is the sync point.
+		/// Returns nothing if no input came since the last member
+		///
+		/// A char split by the flush makes the member binary, so flush after whole messages
+		pub fn flush(&mut self) -> io::Result<Vec<u8>> {
+			let mut output = Vec::new();
+			if !self.pending.is_empty() {
+				let len = self.pending.len();
+				self.compress_pending(len, &mut output)?;
+			}
+			Ok(output)
+		}
+
+		/// Compress the rest of the input, returning the last member
+		pub fn finish(mut self) -> io::Result<Vec<u8>> {
+			self.flush()
+		}
+	}
+
+	/// Decompresses input pushed to it a chunk at a time, every member as soon as it is
+	/// complete. Legacy data without members is only decompressed by `finish`
+	pub struct Decoder<'a> {
+		dictionary: Option<&'a Dictionary>,
+		/// Input not decompressed yet
+		pending: Vec<u8>,
+		block: usize,
+		offset: usize,
+	}
+
+	impl<'a> Decoder<'a> {
+		pub fn new(dictionary: Option<&'a Dictionary>) -> Decoder<'a> {
+			Decoder {
+				dictionary,
+				pending: Vec::new(),
+				block: 1,
+				offset: 0,
+			}
+		}
+
+		/// Whether the input is legacy data, which is only known to end at `finish`
+		fn is_legacy(&self) -> bool {
+			let prefix = self.pending.len().min(MAGIC.len());
+			!MAGIC.starts_with(&self.pending[..prefix])
+		}
+
+		/// Decompress the member of `len` bytes starting the pending input
+		fn decompress_pending(&mut self, len: usize) -> Result<Vec<u8>, DecompressError> {
+			let text =
+				decompress_members(&self.pending[..len], self.dictionary).map_err(|error| {
+					DecompressError::Block {
+						block: self.block,
+						offset: self.offset,
+						error: Box::new(error),
+					}
+				})?;
+			self.pending.drain(..len);
+			self.block += 1;
+			self.offset += len;
+			Ok(text)
+		}
+
+		/// Add `data` to the input, returning the text of the members completed by it
+		pub fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+			self.pending.extend(data);
+			let mut output = Vec::new();
+			while !self.pending.is_empty() && !self.is_legacy() {
+				let len = match member_len(&self.pending)? {
+					Some(len) if len <= self.pending.len() as u64 => len as usize,
+					_ => break,
+				};
+				output.extend(self.decompress_pending(len)?);
+			}
+			Ok(output)
+		}
+
+		/// Decompress the rest of the input, which fails if it ends within a member
+		pub fn finish(mut self) -> Result<Vec<u8>, DecompressError> {
+			if self.pending.is_empty() {
+				return Ok(Vec::new());
+			}
+			if self.is_legacy() {
+				return decompress_members(&self.pending, self.dictionary);
+			}
+			// A truncated member, which `decompress_members` reports
+			let len = self.pending.len();
+			self.decompress_pending(len)
+		}
+	}
+
+	/// Input bytes taken from an iterator at a time by `CompressIter` and `DecompressIter`
+	const ITER_CHUNK: usize = 1 << 16;
+
+	/// Compressed bytes of the text an iterator yields, see `compress_iter`
+	///
+	/// Iteration ends early if the input can't be compressed, `error` tells why
+	pub struct CompressIter<'a, I: Iterator<Item = u8>> {
+		input: I,
+		encoder: Option<Encoder<'a>>,
+		output: Vec<u8>,
+		position: usize,
+		error: Option<io::Error>,
+	}
+
+	impl<'a, I: Iterator<Item = u8>> CompressIter<'a, I> {
+		/// Why iteration ended before the end of the input, if it did
+		pub fn error(&self) -> Option<&io::Error> {
+			self.error.as_ref()
+		}
+	}
+
+	impl<I: Iterator<Item = u8>> Iterator for CompressIter<'_, I> {
+		type Item = u8;
+		fn next(&mut self) -> Option<u8> {
+			while self.position == self.output.len() {
+				let mut encoder = self.encoder.take()?;
+				let chunk: Vec<u8> = self.input.by_ref().take(ITER_CHUNK).collect();
+				let result = if chunk.is_empty() {
+					encoder.finish()
+				} else {
+					let result = encoder.push(&chunk);
+					self.encoder = Some(encoder);
+					result
+				};
+				match result {
+					Ok(output) => self.output = output,
+					Err(e) => {
+						self.encoder = None;
+						self.error = Some(e);
+						return None;
+					}
+				}
+				self.position = 0;
+			}
+			self.position += 1;
+			Some(self.output[self.position - 1])
+		}
+	}
+
+	/// Compress the UTF-8 text `input` yields, with the default settings, as it is iterated.
+	/// Use `Compressor::compress_iter` for other settings
+	pub fn compress_iter<I: IntoIterator<Item = u8>>(
+		input: I,
+	) -> CompressIter<'static, I::IntoIter> {
+		Compressor::default().compress_iter(input)
+	}
+
+	/// Text of the compressed bytes an iterator yields, see `decompress_iter`
+	///
+	/// Iteration ends early at corrupt data, `error` tells why
+	pub struct DecompressIter<'a, I: Iterator<Item = u8>> {
+		input: I,
+		decoder: Option<Decoder<'a>>,
+		output: Vec<u8>,
+		position: usize,
+		error: Option<DecompressError>,
+	}
+
+	impl<'a, I: Iterator<Item = u8>> DecompressIter<'a, I> {
+		/// Why iteration ended before the end of the input, if it did
+		pub fn error(&self) -> Option<&DecompressError> {
+			self.error.as_ref()
+		}
+	}
+
+	impl<I: Iterator<Item = u8>> Iterator for DecompressIter<'_, I> {
+		type Item = u8;
+		fn next(&mut self) -> Option<u8> {
+			while self.position == self.output.len() {
+				let mut decoder = self.decoder.take()?;
+				let chunk: Vec<u8> = self.input.by_ref().take(ITER_CHUNK).collect();
+				let result = if chunk.is_empty() {
+					decoder.finish()
+				} else {
+					let result = decoder.push(&chunk);
+					self.decoder = Some(decoder);
+					result
+				};
+				match result {
+					Ok(output) => self.output = output,
+					Err(e) => {
+						self.decoder = None;
+						self.error = Some(e);
+						return None;
+					}
+				}
+				self.position = 0;
+			}
+			self.position += 1;
+			Some(self.output[self.position - 1])
+		}
+	}
+
+	/// Decompress the bytes `input` yields as the text is iterated, a member at a time
+	pub fn decompress_iter<'a, I: IntoIterator<Item = u8>>(
+		input: I,
+		dictionary: Option<&'a Dictionary>,
+	) -> DecompressIter<'a, I::IntoIter> {
+		DecompressIter {
+			input: input.into_iter(),
+			decoder: Some(Decoder::new(dictionary)),
+			output: Vec::new(),
+			position: 0,
+			error: None,
+		}
+	}
+
+	/// Where compression can continue after `compress_stream` was interrupted
+	#[derive(Clone, Copy, Default, PartialEq, Eq, std::fmt::Debug)]
+	pub struct ResumePoint {
+		/// Bytes of complete, intact members at the start of the partial output, to keep
+		pub output_len: u64,
+		/// Bytes of text those members hold, where compression continues in the input
+		pub input_len: u64,
+		/// CRC-32 of that text, to make sure the input hasn't changed
+		pub checksum: u32,
+	}
+
+	/// Find the last complete member of partial output, everything after it is
+	/// cut off by an interruption or damaged
+	///
+	/// Members are read and checked one at a time, so memory stays bounded by the block size
+	pub fn resume_point<R: Read>(
+		mut partial: R,
+		dictionary: Option<&Dictionary>,
+	) -> io::Result<ResumePoint> {
+		let mut point = ResumePoint::default();
+		let mut crc = checksum::Crc32::new();
+		let mut buffer = Vec::new();
+		loop {
+			let len = match read_member(&mut partial, &mut buffer, point.output_len as usize) {
+				Ok(Some(len)) => len,
+				Ok(None) => return Ok(point),
+				Err(e) if e.kind() == io::ErrorKind::InvalidData => return Ok(point),
+				Err(e) => return Err(e),
+			};
+			let text = match decompress_members(&buffer[..len], dictionary) {
+				Ok(text) => text,
+				Err(_) => return Ok(point),
+			};
+			crc.update(&text);
+			point.output_len += len as u64;
+			point.input_len += text.len() as u64;
+			point.checksum = crc.value();
+			buffer.drain(..len);
+		}
+	}
+
+	/// Outcome of decompressing one member, see `verify`
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub struct BlockCheck {
+		/// Position of the member in the data
+		pub offset: usize,
+		/// Size of the member, header and checksum included
+		pub size: usize,
+		/// Size of the decompressed text, or why it couldn't be decompressed
+		pub result: Result<usize, DecompressError>,
+	}
+
+	/// Decompress every member on its own and check its CRC-32, to find which parts
+	/// of damaged data are still intact
+	///
+	/// Fails only if the members can't be told apart
+	pub fn verify(
+		data: &[u8],
+		dictionary: Option<&Dictionary>,
+	) -> Result<Vec<BlockCheck>, DecompressError> {
+		let members = members(data)?;
+		let ends = members
+			.iter()
+			.skip(1)
+			.map(|member| member.offset)
+			.chain(std::iter::once(data.len()));
+		Ok(members
+			.iter()
+			.zip(ends)
+			.map(|(member, end)| BlockCheck {
+				offset: member.offset,
+				size: end - member.offset,
+				result: decompress_member(member, dictionary).map(|text| text.len()),
+			})
+			.collect())
+	}
+	/// Part of damaged data `recover` had to skip
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub struct Lost {
+		/// Compressed bytes skipped
+		pub compressed: std::ops::Range<usize>,
+		/// Position in the recovered data where the lost text belongs
+		pub offset: usize,
+		/// Length of the lost text, if the header of its member tells it
+		pub length: Option<u64>,
+		/// Why the compressed bytes were skipped
+		pub error: DecompressError,
+	}
+
+	/// Longest text `recover` fills with zeros, a larger size in a header is taken as damage
+	const MAX_FILL: u64 = 1 << 30;
+
+	/// Decompress whatever members of damaged `data` are intact, skipping the others
+	///
+	/// The text of a member that fails to decompress is replaced by `marker`, or by zeros
+	/// of its length if `marker` is `None` and its header tells the length. Where members
+	/// can't be told apart anymore, decoding picks up again at the next member header.
+	/// Every skipped part is listed, an empty list means the data was intact
+	pub fn recover(
+		data: &[u8],
+		dictionary: Option<&Dictionary>,
+		marker: Option<&[u8]>,
+	) -> Result<(Vec<u8>, Vec<Lost>), DecompressError> {
+		if data.is_empty() {
+			return Err(DecompressError::Empty);
+		}
+		// Legacy data, see `compress`, has a single payload to try
+		if !data.starts_with(MAGIC) {
+			if let Ok(text) = decompress_members(data, dictionary) {
+				return Ok((text, Vec::new()));
+			}
+		}
+		let mut output = Vec::new();
+		let mut lost: Vec<Lost> = Vec::new();
+		let mut offset = 0;
+		// Whether a member ended right before `offset`, so a member starting there is real
+		let mut in_step = data.starts_with(MAGIC);
+		while offset < data.len() {
+			let error = match member_at(data, offset) {
+				Ok((member, length)) => match decompress_member(&member, dictionary) {
+					Ok(text) => {
+						output.extend(text);
+						offset += length;
+						in_step = true;
+						continue;
+					}
+					Err(error) if in_step => {
+						let length_known = member.original_size.filter(|&size| size <= MAX_FILL);
+						lost.push(Lost {
+							compressed: offset..offset + length,
+							offset: output.len(),
+							length: length_known,
+							error,
+						});
+						match (marker, length_known) {
+							(Some(marker), _) => output.extend(marker),
+							(None, Some(size)) => output.resize(output.len() + size as usize, 0),
+							(None, None) => {}
+						}
+						offset += length;
+						continue;
+					}
+					Err(error) => error,
+				},
+				Err(error) => error,
+			};
+			// Look for the next header where a member could start
+			let next = (offset + 1..data.len())
+				.find(|&next| data[next..].starts_with(MAGIC) && member_at(data, next).is_ok())
+				.unwrap_or(data.len());
+			match lost.last_mut() {
+				Some(last) if last.compressed.end == offset && !in_step => {
+					last.compressed.end = next;
+				}
+				_ => {
+					lost.push(Lost {
+						compressed: offset..next,
+						offset: output.len(),
+						length: None,
+						error,
+					});
+					output.extend(marker.unwrap_or_default());
+				}
+			}
+			offset = next;
+			in_step = false;
+		}
+		Ok((output, lost))
+	}
+	/// Decompress data produced by `compress`
+	///
+	/// Concatenated members decompress to the concatenation of their text.
+	/// Every field is validated, malformed input results in an error rather than a panic
+	///
+	/// Binary data fails with `InvalidUtf8`, see `decompress_bytes`
+	pub fn decompress(data: &[u8]) -> Result<String, DecompressError> {
+		String::from_utf8(decompress_members(data, None)?).map_err(|_| DecompressError::InvalidUtf8)
+	}
+	/// Decompress text or binary data, with `dictionary` if it was compressed with one
+	pub fn decompress_bytes(
+		data: &[u8],
+		dictionary: Option<&Dictionary>,
+	) -> Result<Vec<u8>, DecompressError> {
+		decompress_members(data, dictionary)
+	}
+	/// Decompress `data` into `output`, returning the length of the text,
+	/// so callers can reuse a buffer instead of allocating one for every call
+	///
+	/// Fails with `OutputTooSmall` if the text doesn't fit, telling its length
+	pub fn decompress_into(data: &[u8], output: &mut [u8]) -> Result<usize, DecompressError> {
+		let declared: Option<u64> = members(data)?
+			.iter()
+			.map(|member| member.original_size)
+			.sum();
+		if let Some(needed) = declared.filter(|&needed| needed > output.len() as u64) {
+			return Err(DecompressError::OutputTooSmall {
+				needed: needed as usize,
+				available: output.len(),
+			});
+		}
+		let mut len = 0;
+		for_each_member(data, None, |text| {
+			if let Some(target) = output.get_mut(len..len + text.len()) {
+				target.copy_from_slice(text);
+			}
+			len += text.len();
+		})?;
+		if len > output.len() {
+			return Err(DecompressError::OutputTooSmall {
+				needed: len,
+				available: output.len(),
+			});
+		}
+		Ok(len)
+	}
+	/// Decompress `data` into `output`, replacing its contents but keeping its allocation.
+	/// Returns the length of the text
+	pub fn decompress_into_vec(
+		data: &[u8],
+		output: &mut Vec<u8>,
+	) -> Result<usize, DecompressError> {
+		output.clear();
+		output.reserve(declared_size(data));
+		for_each_member(data, None, |text| output.extend_from_slice(text))?;
+		Ok(output.len())
+	}
+	/// Decompress data produced by either `compress` or `compress_with_dictionary`
+	pub fn decompress_with_dictionary(
+		data: &[u8],
+		dictionary: &Dictionary,
+	) -> Result<String, DecompressError> {
+		String::from_utf8(decompress_members(data, Some(dictionary))?)
+			.map_err(|_| DecompressError::InvalidUtf8)
+	}
+
+	/// Heuristic consistency check between an embedded tree and the text decoded with it
+	///
+	/// `compress` only puts chars that occur into the tree, builds an optimal tree for
+	/// its options and pads with zero bits. Decoded text that breaks any of these was
+	/// likely tampered with or corrupted, even when no checksum is available.
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub struct ModelCheck {
+		/// Chars in the tree that never occur in the text
+		pub unused_symbols: usize,
+		/// Size of the text when encoded with an optimal tree, or with the optimal tree
+		/// of the depth of the embedded one, for --max-code-length, or with a Shannon–Fano
+		/// tree, for --shannon-fano
+		pub optimal_bits: u64,
+		/// Size of the text when encoded with the embedded tree
+		pub actual_bits: u64,
+		/// Whether the bits padding the final byte are all zero
+		pub clean_padding: bool,
+	}
+	impl ModelCheck {
+		pub fn is_consistent(&self) -> bool {
+			self.unused_symbols == 0 && self.optimal_bits == self.actual_bits && self.clean_padding
+		}
+	}
+	impl std::fmt::Display for ModelCheck {
+		fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			if self.is_consistent() {
+				return write!(f, "high confidence, model matches content");
+			}
+			let mut reasons = Vec::new();
+			if self.unused_symbols != 0 {
+				reasons.push(format!("{} unused symbols in tree", self.unused_symbols));
+			}
+			if self.optimal_bits != self.actual_bits {
+				reasons.push(format!(
+					"tree is not optimal ({} bits instead of {})",
+					self.actual_bits, self.optimal_bits
+				));
+			}
+			if !self.clean_padding {
+				reasons.push("padding bits are set".to_string());
+			}
+			write!(f, "low confidence, {}", reasons.join(", "))
+		}
+	}
+
+	/// Code table of every member of `data`, with the frequencies of the chars of its text.
+	/// `None` for members without char codes: stored, word, context and filtered ones
+	pub fn code_tables(
+		data: &[u8],
+		dictionary: Option<&Dictionary>,
+	) -> Result<Vec<Option<CodeTable>>, DecompressError> {
+		let mut tables = Vec::new();
+		for member in members(data)? {
+			let payload = member.payload;
+			let builtin;
+			let tree = match payload.first() {
+				Some(&DICTIONARY_MARKER) => {
+					&dictionary.ok_or(DecompressError::DictionaryRequired)?.tree
+				}
+				Some(&BUILTIN_MARKER) => {
+					let id = builtin_id(payload)?;
+					builtin = Dictionary::builtin(id).ok_or(DecompressError::UnknownTable(id))?;
+					&builtin.tree
+				}
+				Some(&STORED_MARKER)
+				| Some(&WORD_MARKER)
+				| Some(&CONTEXT_MARKER)
+				| Some(&RLE_MARKER)
+				| Some(&DELTA_MARKER)
+				| Some(&DIGRAM_MARKER)
+				| Some(&RICE_MARKER) => {
+					tables.push(None);
+					continue;
+				}
+				_ => &read_tree(payload)?.0,
+			};
+			let data = decompress_member(&member, dictionary)?;
+			let text = if member.binary {
+				data.iter().map(|&byte| byte as char).collect()
+			} else {
+				String::from_utf8(data).expect("Text members are UTF-8")
+			};
+			tables.push(Some(CodeTable::of_tree(tree, &text)));
+		}
+		Ok(tables)
+	}
+
+	/// Decompress `data`, re-encode the text of every member and compare it with its model
+	///
+	/// Results of all members are summed up.
+	/// Returns `None` when no member has an embedded tree to check
+	pub fn check_model(data: &[u8]) -> Result<Option<ModelCheck>, DecompressError> {
+		let mut total: Option<ModelCheck> = None;
+		for member in members(data)? {
+			let payload = member.payload;
+			if let Some(&DICTIONARY_MARKER)
+			| Some(&BUILTIN_MARKER)
+			| Some(&STORED_MARKER)
+			| Some(&WORD_MARKER)
+			| Some(&CONTEXT_MARKER)
+			| Some(&RLE_MARKER)
+			| Some(&DELTA_MARKER)
+			| Some(&DIGRAM_MARKER)
+			| Some(&RICE_MARKER) = payload.first()
+			{
+				continue;
+			}
+			let check = check_payload(payload, &decompress_payload(payload, None)?)?;
+			total = Some(match total {
+				Some(total) => ModelCheck {
+					unused_symbols: total.unused_symbols + check.unused_symbols,
+					optimal_bits: total.optimal_bits + check.optimal_bits,
+					actual_bits: total.actual_bits + check.actual_bits,
+					clean_padding: total.clean_padding && check.clean_padding,
+				},
+				None => check,
+			});
+		}
+		Ok(total)
+	}
+	/// Compare the tree embedded in `payload` with `text`, the result of decoding it
+	fn check_payload(payload: &[u8], text: &str) -> Result<ModelCheck, DecompressError> {
+		let (huffman_tree, data) = read_tree(payload)?;
+		let (padding, data) = split_padding(data)?;
+		let clean_padding = data
+			.last()
+			.is_none_or(|last| last.trailing_zeros() >= padding as u32);
+		let code_bits = |tree: &Node, frequency: &HashMap<char, u64>| -> u64 {
+			let codes = to_hashmap(tree);
+			frequency
+				.iter()
+				.map(|(c, freq)| freq * codes.get(c).map_or(0, |code| code.len() as u64))
+				.sum()
+		};
+		let mut frequency = HashMap::new();
+		for c in text.chars() {
+			*frequency.entry(c).or_insert(0) += 1;
+		}
+		let unused_symbols = to_hashmap(&huffman_tree)
+			.keys()
+			.filter(|c| !frequency.contains_key(c))
+			.count();
+		let actual_bits = code_bits(&huffman_tree, &frequency);
+		let optimal_bits = if text.is_empty() {
+			0
+		} else {
+			let optimal = code_bits(
+				&construct_huffman_tree(freq_count(text.chars())),
+				&frequency,
+			);
+			// Trees limited in length and Shannon–Fano trees are longer on purpose,
+			// matching the tree --max-code-length or --shannon-fano would build is as good
+			// as matching the optimal tree
+			let depth = to_hashmap(&huffman_tree)
+				.values()
+				.map(|code| code.len())
+				.max()
+				.and_then(|depth| u8::try_from(depth).ok());
+			let limited = depth
+				.and_then(|depth| limited_huffman_tree(freq_count(text.chars()), depth).ok())
+				.map(|tree| code_bits(&tree, &frequency));
+			let shannon_fano = code_bits(&shannon_fano_tree(freq_count(text.chars())), &frequency);
+			limited
+				.into_iter()
+				.chain(Some(shannon_fano))
+				.find(|&bits| bits == actual_bits)
+				.unwrap_or(optimal)
+		};
+		Ok(ModelCheck {
+			unused_symbols,
+			optimal_bits,
+			actual_bits,
+			clean_padding,
+		})
+	}
+
+	/// How the text of a member is encoded
+	#[derive(Clone, Copy, PartialEq, Eq, std::fmt::Debug)]
+	pub enum PayloadKind {
+		/// Huffman coded with an embedded tree
+		Tree,
+		/// Huffman coded with the tree of a dictionary
+		Dictionary,
+		/// Huffman coded with the built-in table of this ID, see `Dictionary::builtin`
+		Builtin(u8),
+		/// Text as is
+		Stored,
+		/// Huffman coded words, see `words`
+		Words,
+		/// Huffman coded with a table per previous byte, see `context`
+		Context,
+		/// Run-length encoded, then coded like `Context`
+		RunLength,
+		/// Delta encoded with this width, then coded like `Context`
+		Delta(u8),
+		/// Huffman coded bytes and byte pairs, see `digram`
+		Digram,
+		/// Rice coded bytes, delta encoded first with this width unless 0, see `rice`
+		Rice(u8),
+	}
+	impl std::fmt::Display for PayloadKind {
+		fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			match self {
+				PayloadKind::Tree => write!(f, "huffman coded"),
+				PayloadKind::Dictionary => write!(f, "huffman coded with dictionary"),
+				PayloadKind::Builtin(id) => write!(f, "huffman coded with built-in table {}", id),
+				PayloadKind::Stored => write!(f, "stored"),
+				PayloadKind::Words => write!(f, "huffman coded words"),
+				PayloadKind::Context => write!(f, "huffman coded with order-1 contexts"),
+				PayloadKind::RunLength => {
+					write!(f, "run-length encoded, huffman coded with order-1 contexts")
+				}
+				PayloadKind::Delta(width) => write!(
+					f,
+					"delta encoded with width {}, huffman coded with order-1 contexts",
+					width
+				),
+				PayloadKind::Digram => write!(f, "huffman coded bytes and byte pairs"),
+				PayloadKind::Rice(0) => write!(f, "Rice coded"),
+				PayloadKind::Rice(width) => {
+					write!(f, "delta encoded with width {}, Rice coded", width)
+				}
+			}
+		}
+	}
+
+	/// Header fields of a member, see `inspect`
+	#[derive(Clone, PartialEq, Eq, std::fmt::Debug)]
+	pub struct MemberInfo {
+		/// Format version, 0 for legacy data without magic bytes
+		pub version: u8,
+		pub kind: PayloadKind,
+		/// Number of nodes in the embedded tree
+		pub tree_size: usize,
+		/// Number of distinct chars in the embedded tree, of tokens in a word table
+		/// or of tables in a context model
+		pub symbols: usize,
+		/// Zero bits appended to the final byte
+		pub padding: u8,
+		/// Size of the payload
+		pub compressed_size: usize,
+		/// Size of the text, when it can be known without decompressing
+		pub original_size: Option<usize>,
+		/// CRC-32 of the text, if stored
+		pub checksum: Option<u32>,
+		/// ID of the dictionary the member is coded with, if stored, see `Dictionary::id`
+		pub dictionary_id: Option<u32>,
+		pub metadata: Metadata,
+	}
+
+	/// How `payload` is coded, judging by its first byte alone
+	fn payload_kind(payload: &[u8]) -> PayloadKind {
+		match payload.first() {
+			Some(&STORED_MARKER) => PayloadKind::Stored,
+			Some(&WORD_MARKER) => PayloadKind::Words,
+			Some(&CONTEXT_MARKER) => PayloadKind::Context,
+			Some(&RLE_MARKER) => PayloadKind::RunLength,
+			Some(&DELTA_MARKER) => PayloadKind::Delta(payload.get(1).copied().unwrap_or(0)),
+			Some(&DIGRAM_MARKER) => PayloadKind::Digram,
+			Some(&RICE_MARKER) => PayloadKind::Rice(payload.get(1).copied().unwrap_or(0)),
+			Some(&DICTIONARY_MARKER) => PayloadKind::Dictionary,
+			Some(&BUILTIN_MARKER) => PayloadKind::Builtin(payload.get(1).copied().unwrap_or(0)),
+			_ => PayloadKind::Tree,
+		}
+	}
+
+	/// Read the header fields of every member without decompressing any data
+	pub fn inspect(data: &[u8]) -> Result<Vec<MemberInfo>, DecompressError> {
+		let mut infos = Vec::new();
+		for member in members(data)? {
+			let (version, metadata) = (member.version, member.metadata);
+			let (payload, checksum) = (member.payload, member.checksum);
+			let original_size = member.original_size.map(|size| size as usize);
+			let dictionary_id = member.dictionary_id;
+			let info = match payload.first() {
+				Some(&STORED_MARKER) => MemberInfo {
+					version,
+					kind: PayloadKind::Stored,
+					tree_size: 0,
+					symbols: 0,
+					padding: 0,
+					compressed_size: payload.len(),
+					original_size: original_size.or(Some(payload.len() - 1)),
+					checksum,
+					dictionary_id,
+					metadata,
+				},
+				Some(&WORD_MARKER) => MemberInfo {
+					version,
+					kind: PayloadKind::Words,
+					tree_size: 0,
+					symbols: words::table_len(&payload[1..])?,
+					padding: 0,
+					compressed_size: payload.len(),
+					original_size,
+					checksum,
+					dictionary_id,
+					metadata,
+				},
+				Some(&DIGRAM_MARKER) => MemberInfo {
+					version,
+					kind: PayloadKind::Digram,
+					tree_size: 0,
+					symbols: digram::table_len(&payload[1..])?,
+					padding: 0,
+					compressed_size: payload.len(),
+					original_size,
+					checksum,
+					dictionary_id,
+					metadata,
+				},
+				Some(&CONTEXT_MARKER) | Some(&RLE_MARKER) => MemberInfo {
+					version,
+					kind: if payload[0] == RLE_MARKER {
+						PayloadKind::RunLength
+					} else {
+						PayloadKind::Context
+					},
+					tree_size: 0,
+					symbols: context::table_count(&payload[1..])?,
+					padding: 0,
+					compressed_size: payload.len(),
+					original_size,
+					checksum,
+					dictionary_id,
+					metadata,
+				},
+				Some(&RICE_MARKER) => MemberInfo {
+					version,
+					kind: PayloadKind::Rice(rice_width(payload)?),
+					tree_size: 0,
+					symbols: 0,
+					padding: 0,
+					compressed_size: payload.len(),
+					original_size,
+					checksum,
+					dictionary_id,
+					metadata,
+				},
+				Some(&DELTA_MARKER) => MemberInfo {
+					version,
+					kind: PayloadKind::Delta(delta_width(payload)? as u8),
+					tree_size: 0,
+					symbols: context::table_count(&payload[2..])?,
+					padding: 0,
+					compressed_size: payload.len(),
+					original_size,
+					checksum,
+					dictionary_id,
+					metadata,
+				},
+				Some(&DICTIONARY_MARKER) => MemberInfo {
+					version,
+					kind: PayloadKind::Dictionary,
+					tree_size: 0,
+					symbols: 0,
+					padding: split_padding(&payload[1..])?.0,
+					compressed_size: payload.len(),
+					original_size,
+					checksum,
+					dictionary_id,
+					metadata,
+				},
+				Some(&BUILTIN_MARKER) => {
+					let id = builtin_id(payload)?;
+					let dictionary =
+						Dictionary::builtin(id).ok_or(DecompressError::UnknownTable(id))?;
+					MemberInfo {
+						version,
+						kind: PayloadKind::Builtin(id),
+						tree_size: 0,
+						symbols: to_hashmap(&dictionary.tree).len(),
+						padding: split_padding(&payload[2..])?.0,
+						compressed_size: payload.len(),
+						original_size,
+						checksum,
+						dictionary_id,
+						metadata,
+					}
+				}
+				_ => {
+					let (tree, data) = read_tree(payload)?;
+					let symbols = leaf_codes(&tree).len();
+					MemberInfo {
+						version,
+						kind: PayloadKind::Tree,
+						tree_size: 2 * symbols - 1,
+						symbols,
+						padding: split_padding(data)?.0,
+						compressed_size: payload.len(),
+						original_size,
+						checksum,
+						dictionary_id,
+						metadata,
+					}
+				}
+			};
+			infos.push(info);
+		}
+		Ok(infos)
 	}
 }
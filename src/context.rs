@@ -0,0 +1,128 @@
+//! Order-1 context model
+//!
+//! Every byte is coded with a table chosen by the byte before it, so data where
+//! a byte predicts the next one (markup, source code, records) gets shorter codes
+//! than a single table could give it. The first byte uses the table of byte 0.
+//!
+//! # Data Format
+//! - Length of the data in bytes (u64)
+//! - Bitmap of the contexts that have a table (32 bytes, bit `b % 8` of byte `b / 8`)
+//! - For every such context in ascending order: number of coded bytes minus 1 (u8),
+//!   followed by byte value and code length (u8 each) for every coded byte, ascending
+//! - Bit stream, zero padded to a whole byte
+//!
+//! Codes are canonical, see `canonical`. All integers are little endian.
+use crate::bits::{BitReader, BitWriter};
+use crate::canonical::{self, Decoder};
+use crate::compress::huffman::{CompressError, DecompressError};
+use crate::cursor::Cursor;
+
+/// Context of the first byte
+const INITIAL_CONTEXT: usize = 0;
+
+/// Encode `bytes` with the order-1 context model, with no code longer than `max_length` bits
+pub fn encode(bytes: &[u8], max_length: Option<u8>) -> Result<Vec<u8>, CompressError> {
+	let mut frequencies = vec![[0u64; 256]; 256];
+	let mut context = INITIAL_CONTEXT;
+	for &byte in bytes {
+		frequencies[context][byte as usize] += 1;
+		context = byte as usize;
+	}
+	let lengths = frequencies
+		.iter()
+		.map(|frequencies| canonical::code_lengths_within(frequencies, max_length))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let mut output = (bytes.len() as u64).to_le_bytes().to_vec();
+	let mut bitmap = [0u8; 32];
+	for (context, lengths) in lengths.iter().enumerate() {
+		if lengths.iter().any(|&length| length != 0) {
+			bitmap[context / 8] |= 1 << (context % 8);
+		}
+	}
+	output.extend(&bitmap);
+	for lengths in &lengths {
+		let coded: Vec<(usize, u8)> = lengths
+			.iter()
+			.enumerate()
+			.filter(|&(_, &length)| length != 0)
+			.map(|(byte, &length)| (byte, length))
+			.collect();
+		if coded.is_empty() {
+			continue;
+		}
+		output.push((coded.len() - 1) as u8);
+		for (byte, length) in coded {
+			output.push(byte as u8);
+			output.push(length);
+		}
+	}
+
+	let codes: Vec<Vec<(u64, u8)>> = lengths
+		.iter()
+		.map(|lengths| canonical::codes(lengths))
+		.collect();
+	// Writing to a Vec can't fail
+	let mut bits = BitWriter::new(Vec::new());
+	let mut context = INITIAL_CONTEXT;
+	for &byte in bytes {
+		let (code, length) = codes[context][byte as usize];
+		bits.write_bits(code, length).unwrap();
+		context = byte as usize;
+	}
+	output.extend(bits.finish().unwrap().0);
+	Ok(output)
+}
+
+/// Read the context tables of data produced by `encode`, returning them and the offset of the bit stream
+fn read_tables(data: &[u8]) -> Result<(u64, Vec<Option<Decoder>>, usize), DecompressError> {
+	let mut cursor = Cursor::new(data);
+	let length = cursor.u64("data length")?;
+	let bitmap = cursor.take(32, "context bitmap")?;
+	let mut decoders = Vec::new();
+	for context in 0..256 {
+		if bitmap[context / 8] & 1 << (context % 8) == 0 {
+			decoders.push(None);
+			continue;
+		}
+		let count = cursor.u8("context table")? as usize + 1;
+		let mut lengths = [0u8; 256];
+		for pair in cursor.take(2 * count, "context table")?.chunks(2) {
+			lengths[pair[0] as usize] = pair[1];
+		}
+		decoders.push(Some(
+			Decoder::new(&lengths).ok_or(DecompressError::InvalidTree)?,
+		));
+	}
+	Ok((length, decoders, cursor.position()))
+}
+
+/// Decode data produced by `encode`
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+	let (length, decoders, cursor) = read_tables(data)?;
+	let mut bits = BitReader::new(&data[cursor..]);
+	let mut output = Vec::new();
+	let mut context = INITIAL_CONTEXT;
+	for _ in 0..length {
+		let decoder = decoders[context]
+			.as_ref()
+			.ok_or(DecompressError::InvalidTree)?;
+		let byte = decoder
+			.decode(&mut bits)
+			.ok()
+			.flatten()
+			.ok_or(DecompressError::IncompleteCode)?;
+		output.push(byte as u8);
+		context = byte;
+	}
+	if !bits.at_padding().unwrap_or(false) {
+		return Err(DecompressError::UnusedBits);
+	}
+	Ok(output)
+}
+
+/// Number of contexts with a table in data produced by `encode`
+pub fn table_count(data: &[u8]) -> Result<usize, DecompressError> {
+	let (_, decoders, _) = read_tables(data)?;
+	Ok(decoders.iter().filter(|decoder| decoder.is_some()).count())
+}
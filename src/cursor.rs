@@ -0,0 +1,77 @@
+//! Checked reading of the binary formats of this crate
+//!
+//! Every read first checks that the data holds the whole field and fails with
+//! `DecompressError::Truncated` otherwise, telling the field, its offset, the bytes
+//! it needs and the bytes left. Cut off or corrupt input never panics on a slice index.
+use crate::compress::huffman::DecompressError;
+
+/// Reads fields off the front of some data, one after the other
+pub struct Cursor<'a> {
+	data: &'a [u8],
+	position: usize,
+}
+
+impl<'a> Cursor<'a> {
+	pub fn new(data: &'a [u8]) -> Cursor<'a> {
+		Cursor { data, position: 0 }
+	}
+	/// Offset of the next field in the data
+	pub fn position(&self) -> usize {
+		self.position
+	}
+	/// Data after the fields read so far
+	pub fn rest(&self) -> &'a [u8] {
+		&self.data[self.position..]
+	}
+	/// The next `len` bytes, making up `field`
+	pub fn take(&mut self, len: usize, field: &'static str) -> Result<&'a [u8], DecompressError> {
+		let left = self.data.len() - self.position;
+		if len > left {
+			return Err(DecompressError::Truncated {
+				field,
+				offset: self.position,
+				expected: len,
+				found: left,
+			});
+		}
+		let bytes = &self.data[self.position..self.position + len];
+		self.position += len;
+		Ok(bytes)
+	}
+	pub fn u8(&mut self, field: &'static str) -> Result<u8, DecompressError> {
+		Ok(self.take(1, field)?[0])
+	}
+	/// The next `len` bytes as a little endian integer
+	fn le(&mut self, len: usize, field: &'static str) -> Result<u64, DecompressError> {
+		Ok(self
+			.take(len, field)?
+			.iter()
+			.rev()
+			.fold(0, |value, &byte| value << 8 | byte as u64))
+	}
+	pub fn u16(&mut self, field: &'static str) -> Result<u16, DecompressError> {
+		self.le(2, field).map(|value| value as u16)
+	}
+	pub fn u32(&mut self, field: &'static str) -> Result<u32, DecompressError> {
+		self.le(4, field).map(|value| value as u32)
+	}
+	pub fn u64(&mut self, field: &'static str) -> Result<u64, DecompressError> {
+		self.le(8, field)
+	}
+	/// The next varint, see `codes::write_varint`
+	pub fn varint(&mut self, field: &'static str) -> Result<u64, DecompressError> {
+		let mut value = 0u64;
+		for shift in (0..64).step_by(7) {
+			let byte = self.u8(field)?;
+			let bits = (byte & 0x7F) as u64;
+			if bits << shift >> shift != bits {
+				return Err(DecompressError::InvalidVarint(field));
+			}
+			value |= bits << shift;
+			if byte < 0x80 {
+				return Ok(value);
+			}
+		}
+		Err(DecompressError::InvalidVarint(field))
+	}
+}
@@ -0,0 +1,26 @@
+//! Delta encoding, a filter applied before entropy coding
+//!
+//! Every byte is replaced by its difference to the byte `width` positions before it,
+//! modulo 256, the first `width` bytes are copied as is. Slowly varying samples
+//! `width` bytes wide, like 16-bit PCM audio with `width` 2 per channel, turn
+//! into runs of small differences that code much better than the samples.
+
+/// Delta encode `data` with samples `width` bytes apart, `width` must not be 0
+pub fn encode(data: &[u8], width: usize) -> Vec<u8> {
+	assert!(width > 0, "delta width must not be 0");
+	let mut output = data.to_vec();
+	for i in (width..data.len()).rev() {
+		output[i] = data[i].wrapping_sub(data[i - width]);
+	}
+	output
+}
+
+/// Reverse `encode`
+pub fn decode(data: &[u8], width: usize) -> Vec<u8> {
+	assert!(width > 0, "delta width must not be 0");
+	let mut output = data.to_vec();
+	for i in width..output.len() {
+		output[i] = output[i].wrapping_add(output[i - width]);
+	}
+	output
+}
@@ -0,0 +1,141 @@
+//! Digram model, coding frequent byte pairs as single symbols
+//!
+//! The most frequent pairs of adjacent bytes make up a table. The text is split
+//! from left to right into pairs of that table where one starts, and single bytes
+//! otherwise. Bytes and pairs share one huffman code, so common pairs like `th`,
+//! `e ` or `\r\n` cost a single code instead of two, without a full LZ stage.
+//!
+//! # Data Format
+//! - Number of symbols in the text (u64)
+//! - Number of pairs in the table (u16), each as its two bytes
+//! - Code length of every byte value, then of every pair, a byte each
+//! - Bit stream, zero padded to a whole byte
+//!
+//! Codes are canonical, see `canonical`. All integers are little endian.
+use crate::bits::{BitReader, BitWriter};
+use crate::canonical::{self, Decoder};
+use crate::compress::huffman::{CompressError, DecompressError};
+use crate::cursor::Cursor;
+use std::collections::HashMap;
+
+/// Most pairs in a table
+const MAX_PAIRS: usize = 1024;
+/// Fewest occurrences that earn a pair a place in the table
+const MIN_PAIR_COUNT: u64 = 16;
+/// Symbols of single bytes, pairs are numbered after them
+const BYTES: usize = 256;
+
+/// Split `bytes` into symbols: a byte, or `BYTES` plus the index of a pair in `pairs`
+fn symbols(bytes: &[u8], pairs: &HashMap<[u8; 2], usize>) -> Vec<usize> {
+	let mut symbols = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let pair = bytes
+			.get(i..i + 2)
+			.and_then(|pair| pairs.get(&[pair[0], pair[1]]));
+		match pair {
+			Some(&index) => {
+				symbols.push(BYTES + index);
+				i += 2;
+			}
+			None => {
+				symbols.push(bytes[i] as usize);
+				i += 1;
+			}
+		}
+	}
+	symbols
+}
+
+/// Encode `text` with the digram model, with no code longer than `max_length` bits
+pub fn encode(text: &str, max_length: Option<u8>) -> Result<Vec<u8>, CompressError> {
+	let bytes = text.as_bytes();
+	let mut counts: HashMap<[u8; 2], u64> = HashMap::new();
+	for pair in bytes.windows(2) {
+		*counts.entry([pair[0], pair[1]]).or_insert(0) += 1;
+	}
+	let mut candidates: Vec<([u8; 2], u64)> = counts
+		.into_iter()
+		.filter(|&(_, count)| count >= MIN_PAIR_COUNT)
+		.collect();
+	candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+	candidates.truncate(MAX_PAIRS);
+	let index = candidates
+		.iter()
+		.enumerate()
+		.map(|(i, &(pair, _))| (pair, i))
+		.collect();
+	let mut symbols = symbols(bytes, &index);
+
+	// Pairs that overlapping pairs left unused are dropped, which doesn't change
+	// how the text splits as none of them is ever matched
+	let mut renumbered: Vec<Option<usize>> = vec![None; candidates.len()];
+	let mut table = Vec::new();
+	for symbol in &mut symbols {
+		if *symbol >= BYTES {
+			let pair = *symbol - BYTES;
+			let index = *renumbered[pair].get_or_insert_with(|| {
+				table.push(candidates[pair].0);
+				table.len() - 1
+			});
+			*symbol = BYTES + index;
+		}
+	}
+
+	let mut frequencies = vec![0u64; BYTES + table.len()];
+	for &symbol in &symbols {
+		frequencies[symbol] += 1;
+	}
+	let lengths = canonical::code_lengths_within(&frequencies, max_length)?;
+
+	let mut output = (symbols.len() as u64).to_le_bytes().to_vec();
+	output.extend(&(table.len() as u16).to_le_bytes());
+	for pair in &table {
+		output.extend(pair);
+	}
+	output.extend(&lengths);
+
+	let codes = canonical::codes(&lengths);
+	// Writing to a Vec can't fail
+	let mut bits = BitWriter::new(output);
+	for symbol in symbols {
+		let (code, length) = codes[symbol];
+		bits.write_bits(code, length).unwrap();
+	}
+	Ok(bits.finish().unwrap().0)
+}
+
+/// Decode data produced by `encode`
+pub fn decode(data: &[u8]) -> Result<String, DecompressError> {
+	let mut cursor = Cursor::new(data);
+	let symbol_count = cursor.u64("symbol count")?;
+	let table_len = cursor.u16("pair table")? as usize;
+	let table = cursor.take(2 * table_len, "pair table")?;
+	let lengths = cursor.take(BYTES + table_len, "code lengths")?;
+	let decoder = Decoder::new(lengths).ok_or(DecompressError::InvalidTree)?;
+
+	let mut bits = BitReader::new(cursor.rest());
+	let mut output = Vec::new();
+	for _ in 0..symbol_count {
+		let symbol = decoder
+			.decode(&mut bits)
+			.ok()
+			.flatten()
+			.ok_or(DecompressError::IncompleteCode)?;
+		match symbol.checked_sub(BYTES) {
+			Some(pair) => output.extend(&table[2 * pair..2 * pair + 2]),
+			None => output.push(symbol as u8),
+		}
+	}
+	if !bits.at_padding().unwrap_or(false) {
+		return Err(DecompressError::UnusedBits);
+	}
+	String::from_utf8(output).map_err(|_| DecompressError::InvalidUtf8)
+}
+
+/// Number of pairs in the table of data produced by `encode`
+pub fn table_len(data: &[u8]) -> Result<usize, DecompressError> {
+	let mut cursor = Cursor::new(data);
+	cursor.u64("symbol count")?;
+	Ok(cursor.u16("pair table")? as usize)
+}
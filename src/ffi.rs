@@ -0,0 +1,317 @@
+//! C ABI over the huffman codec
+//!
+//! These functions back the `huffman` python module in `python/huffman.py`,
+//! but any language able to call C can use them.
+//!
+//! Buffers returned by this module are owned by rust and must be released
+//! with `huffman_free`, passing back the length that was reported. Panics are caught and
+//! reported as failures, they never unwind into the caller.
+//!
+//! Besides one-shot calls, encoder and decoder handles process unbounded streams
+//! a chunk at a time. See `include/huffman.h` for the C declarations.
+use crate::compress::huffman;
+use std::panic;
+use std::ptr;
+use std::slice;
+
+/// Hand a byte vector over to the caller, storing its length in `out_len`
+unsafe fn into_raw(bytes: Vec<u8>, out_len: *mut usize) -> *mut u8 {
+	*out_len = bytes.len();
+	Box::into_raw(bytes.into_boxed_slice()) as *mut u8
+}
+
+/// Run `f`, failing if it panics rather than unwinding across the C ABI, which aborts
+fn catch<T, E>(f: impl FnOnce() -> Result<T, E>) -> Result<T, ()> {
+	match panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+		Ok(result) => result.map_err(|_| ()),
+		Err(_) => Err(()),
+	}
+}
+
+/// Run `f` on the encoder or decoder in `codec`, failing if there is none. One that panics
+/// is dropped, later calls fail rather than use it half updated
+fn run<C, E>(
+	codec: &mut Option<C>,
+	f: impl FnOnce(&mut C) -> Result<Vec<u8>, E>,
+) -> Result<Vec<u8>, ()> {
+	let inner = codec.as_mut().ok_or(())?;
+	match panic::catch_unwind(panic::AssertUnwindSafe(|| f(inner))) {
+		Ok(result) => result.map_err(|_| ()),
+		Err(_) => {
+			*codec = None;
+			Err(())
+		}
+	}
+}
+
+/// The `len` bytes starting at `data`, which may be null if `len` is 0
+unsafe fn input<'a>(data: *const u8, len: usize) -> &'a [u8] {
+	if len == 0 {
+		&[]
+	} else {
+		slice::from_raw_parts(data, len)
+	}
+}
+
+/// Compress `len` bytes starting at `data`
+///
+/// Input that isn't UTF-8 is compressed as binary data, empty input included.
+/// Returns null on failure, otherwise a buffer of `*out_len` bytes
+///
+/// # Safety
+/// `data` must point to `len` readable bytes and `out_len` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn huffman_compress(
+	data: *const u8,
+	len: usize,
+	out_len: *mut usize,
+) -> *mut u8 {
+	if (data.is_null() && len != 0) || out_len.is_null() {
+		return ptr::null_mut();
+	}
+	let input = input(data, len);
+	// A single member, like `huffman::compress`
+	let compressor = huffman::Compressor::builder()
+		.block_size(len.max(1))
+		.build();
+	match catch(|| compressor.compress_stream(input, Vec::new())) {
+		Ok(compressed) => into_raw(compressed, out_len),
+		Err(()) => ptr::null_mut(),
+	}
+}
+
+/// Decompress `len` bytes starting at `data`
+///
+/// Returns null if the data could not be decompressed,
+/// otherwise a buffer of `*out_len` bytes
+///
+/// # Safety
+/// `data` must point to `len` readable bytes and `out_len` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn huffman_decompress(
+	data: *const u8,
+	len: usize,
+	out_len: *mut usize,
+) -> *mut u8 {
+	if (data.is_null() && len != 0) || out_len.is_null() {
+		return ptr::null_mut();
+	}
+	let input = input(data, len);
+	match catch(|| huffman::decompress_bytes(input, None)) {
+		Ok(data) => into_raw(data, out_len),
+		Err(()) => ptr::null_mut(),
+	}
+}
+
+/// Release a buffer returned by `huffman_compress` or `huffman_decompress`
+///
+/// # Safety
+/// `data` and `len` must come from one of the functions above,
+/// and the buffer must not be freed twice
+#[no_mangle]
+pub unsafe extern "C" fn huffman_free(data: *mut u8, len: usize) {
+	if !data.is_null() {
+		drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+	}
+}
+
+/// Input consumed by the stream encoder before a member is emitted
+const BLOCK_SIZE: usize = 1 << 16;
+
+/// Streaming compressor, emits one member per block of input
+pub struct HuffmanEncoder {
+	encoder: Option<huffman::Encoder<'static>>,
+}
+
+impl HuffmanEncoder {
+	fn feed(&mut self, data: &[u8]) -> Result<Vec<u8>, ()> {
+		run(&mut self.encoder, |encoder| encoder.push(data))
+	}
+	fn flush(&mut self) -> Result<Vec<u8>, ()> {
+		run(&mut self.encoder, |encoder| encoder.flush())
+	}
+	fn finish(&mut self) -> Result<Vec<u8>, ()> {
+		let encoder = self.encoder.take().ok_or(())?;
+		catch(|| encoder.finish())
+	}
+}
+
+/// Streaming decompressor, decodes every member as soon as it is complete
+pub struct HuffmanDecoder {
+	decoder: Option<huffman::Decoder<'static>>,
+}
+
+impl HuffmanDecoder {
+	fn feed(&mut self, data: &[u8]) -> Result<Vec<u8>, ()> {
+		run(&mut self.decoder, |decoder| decoder.push(data))
+	}
+	fn finish(&mut self) -> Result<Vec<u8>, ()> {
+		let decoder = self.decoder.take().ok_or(())?;
+		catch(|| decoder.finish())
+	}
+}
+
+/// Pass the outcome of a stream operation to the caller, returning 0 on success and -1 on failure
+unsafe fn stream_result(
+	result: Result<Vec<u8>, ()>,
+	out: *mut *mut u8,
+	out_len: *mut usize,
+) -> i32 {
+	match result {
+		Ok(output) => {
+			*out = if output.is_empty() {
+				*out_len = 0;
+				ptr::null_mut()
+			} else {
+				into_raw(output, out_len)
+			};
+			0
+		}
+		Err(()) => -1,
+	}
+}
+
+/// Create a streaming compressor, release it with `huffman_encoder_destroy`
+#[no_mangle]
+pub extern "C" fn huffman_encoder_new() -> *mut HuffmanEncoder {
+	let options = huffman::CompressOptions {
+		block_size: Some(BLOCK_SIZE),
+		..huffman::CompressOptions::default()
+	};
+	Box::into_raw(Box::new(HuffmanEncoder {
+		encoder: Some(huffman::Encoder::new(options)),
+	}))
+}
+
+/// Feed `len` bytes of input to `encoder`
+///
+/// Compressed output that is ready is stored in `*out`/`*out_len`,
+/// `*out` is null when nothing is ready yet.
+/// Returns 0 on success, -1 on failure
+///
+/// # Safety
+/// `encoder` must come from `huffman_encoder_new`, `data` must point to `len` readable bytes,
+/// `out` and `out_len` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn huffman_encoder_feed(
+	encoder: *mut HuffmanEncoder,
+	data: *const u8,
+	len: usize,
+	out: *mut *mut u8,
+	out_len: *mut usize,
+) -> i32 {
+	if encoder.is_null() || (data.is_null() && len != 0) || out.is_null() || out_len.is_null() {
+		return -1;
+	}
+	let input = input(data, len);
+	stream_result((*encoder).feed(input), out, out_len)
+}
+
+/// Compress all input fed so far, storing it in `*out`/`*out_len`, so the receiver can
+/// decode all of it at once. The encoder can be fed more afterwards
+///
+/// Returns 0 on success, -1 on failure
+///
+/// # Safety
+/// `encoder` must come from `huffman_encoder_new`, `out` and `out_len` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn huffman_encoder_flush(
+	encoder: *mut HuffmanEncoder,
+	out: *mut *mut u8,
+	out_len: *mut usize,
+) -> i32 {
+	if encoder.is_null() || out.is_null() || out_len.is_null() {
+		return -1;
+	}
+	stream_result((*encoder).flush(), out, out_len)
+}
+
+/// Compress whatever input is left, storing it in `*out`/`*out_len`
+///
+/// Returns 0 on success, -1 if the input ended in the middle of a char
+///
+/// # Safety
+/// `encoder` must come from `huffman_encoder_new`, `out` and `out_len` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn huffman_encoder_finish(
+	encoder: *mut HuffmanEncoder,
+	out: *mut *mut u8,
+	out_len: *mut usize,
+) -> i32 {
+	if encoder.is_null() || out.is_null() || out_len.is_null() {
+		return -1;
+	}
+	stream_result((*encoder).finish(), out, out_len)
+}
+
+/// Release a streaming compressor
+///
+/// # Safety
+/// `encoder` must come from `huffman_encoder_new` and must not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn huffman_encoder_destroy(encoder: *mut HuffmanEncoder) {
+	if !encoder.is_null() {
+		drop(Box::from_raw(encoder));
+	}
+}
+
+/// Create a streaming decompressor, release it with `huffman_decoder_destroy`
+#[no_mangle]
+pub extern "C" fn huffman_decoder_new() -> *mut HuffmanDecoder {
+	Box::into_raw(Box::new(HuffmanDecoder {
+		decoder: Some(huffman::Decoder::new(None)),
+	}))
+}
+
+/// Feed `len` bytes of compressed input to `decoder`
+///
+/// Decompressed output that is ready is stored in `*out`/`*out_len`,
+/// `*out` is null when nothing is ready yet.
+/// Returns 0 on success, -1 if the input is corrupt
+///
+/// # Safety
+/// `decoder` must come from `huffman_decoder_new`, `data` must point to `len` readable bytes,
+/// `out` and `out_len` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn huffman_decoder_feed(
+	decoder: *mut HuffmanDecoder,
+	data: *const u8,
+	len: usize,
+	out: *mut *mut u8,
+	out_len: *mut usize,
+) -> i32 {
+	if decoder.is_null() || (data.is_null() && len != 0) || out.is_null() || out_len.is_null() {
+		return -1;
+	}
+	let input = input(data, len);
+	stream_result((*decoder).feed(input), out, out_len)
+}
+
+/// Decompress whatever input is left, storing it in `*out`/`*out_len`
+///
+/// Returns 0 on success, -1 if the input is corrupt or truncated
+///
+/// # Safety
+/// `decoder` must come from `huffman_decoder_new`, `out` and `out_len` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn huffman_decoder_finish(
+	decoder: *mut HuffmanDecoder,
+	out: *mut *mut u8,
+	out_len: *mut usize,
+) -> i32 {
+	if decoder.is_null() || out.is_null() || out_len.is_null() {
+		return -1;
+	}
+	stream_result((*decoder).finish(), out, out_len)
+}
+
+/// Release a streaming decompressor
+///
+/// # Safety
+/// `decoder` must come from `huffman_decoder_new` and must not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn huffman_decoder_destroy(decoder: *mut HuffmanDecoder) {
+	if !decoder.is_null() {
+		drop(Box::from_raw(decoder));
+	}
+}
@@ -0,0 +1,218 @@
+//! Huffman codes over any ordered symbol type: bytes, chars, 16-bit units, token IDs
+//! or a caller's own enum
+//!
+//! `Huffman<T>` maps the symbols that occur to indices in ascending order and builds
+//! a canonical code over those, see `canonical`, so the coding machinery is shared by
+//! every symbol type. How a symbol is stored in a table is up to its `Symbol`
+//! implementation.
+//!
+//! # Table Format
+//! - Number of symbols (u32)
+//! - For every symbol in ascending order: the symbol (see `Symbol::write`) and its
+//!   code length (u8)
+//!
+//! All integers are little endian.
+use crate::bits::{BitReader, BitWriter};
+use crate::canonical::{self, Decoder};
+use crate::compress::huffman::DecompressError;
+use crate::cursor::Cursor;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+
+/// A symbol a code can be built over, with the way it is stored in tables
+pub trait Symbol: Ord + Hash + Clone {
+	/// Append the symbol to `output`
+	fn write(&self, output: &mut Vec<u8>);
+	/// Read a symbol written by `write`
+	fn read(cursor: &mut Cursor) -> Result<Self, DecompressError>;
+}
+
+impl Symbol for u8 {
+	fn write(&self, output: &mut Vec<u8>) {
+		output.push(*self);
+	}
+	fn read(cursor: &mut Cursor) -> Result<u8, DecompressError> {
+		cursor.u8("symbol")
+	}
+}
+
+impl Symbol for u16 {
+	fn write(&self, output: &mut Vec<u8>) {
+		output.extend(&self.to_le_bytes());
+	}
+	fn read(cursor: &mut Cursor) -> Result<u16, DecompressError> {
+		cursor.u16("symbol")
+	}
+}
+
+impl Symbol for u32 {
+	fn write(&self, output: &mut Vec<u8>) {
+		output.extend(&self.to_le_bytes());
+	}
+	fn read(cursor: &mut Cursor) -> Result<u32, DecompressError> {
+		cursor.u32("symbol")
+	}
+}
+
+impl Symbol for u64 {
+	fn write(&self, output: &mut Vec<u8>) {
+		output.extend(&self.to_le_bytes());
+	}
+	fn read(cursor: &mut Cursor) -> Result<u64, DecompressError> {
+		cursor.u64("symbol")
+	}
+}
+
+/// Chars are stored as their scalar value (u32)
+impl Symbol for char {
+	fn write(&self, output: &mut Vec<u8>) {
+		(*self as u32).write(output);
+	}
+	fn read(cursor: &mut Cursor) -> Result<char, DecompressError> {
+		std::char::from_u32(cursor.u32("symbol")?).ok_or(DecompressError::InvalidTree)
+	}
+}
+
+/// A canonical huffman code over symbols of type `T`
+#[derive(Clone, Debug)]
+pub struct Huffman<T: Symbol> {
+	/// Symbols with a code, ascending
+	symbols: Vec<T>,
+	lengths: Vec<u8>,
+	codes: HashMap<T, (u64, u8)>,
+}
+
+impl<T: Symbol> Huffman<T> {
+	/// Code for symbols with the given counts, symbols counted 0 times get no code
+	pub fn from_frequencies<I: IntoIterator<Item = (T, u64)>>(frequencies: I) -> Huffman<T> {
+		Huffman::limited(frequencies, 63).expect("No code is longer than 63 bits")
+	}
+
+	/// Code for the symbols of `symbols`, counted as they occur
+	pub fn from_symbols<I: IntoIterator<Item = T>>(symbols: I) -> Huffman<T> {
+		let mut frequencies = HashMap::new();
+		for symbol in symbols {
+			*frequencies.entry(symbol).or_insert(0) += 1;
+		}
+		Huffman::from_frequencies(frequencies)
+	}
+
+	/// Code for symbols with the given counts and no code longer than `max_length`,
+	/// `None` if the symbols don't fit in that many bits
+	pub fn limited<I: IntoIterator<Item = (T, u64)>>(
+		frequencies: I,
+		max_length: u8,
+	) -> Option<Huffman<T>> {
+		let mut counts = BTreeMap::new();
+		for (symbol, count) in frequencies {
+			*counts.entry(symbol).or_insert(0) += count;
+		}
+		counts.retain(|_, &mut count| count != 0);
+		let frequencies: Vec<u64> = counts.values().copied().collect();
+		let lengths = canonical::limited_code_lengths(&frequencies, max_length)?;
+		Some(Huffman::from_lengths(counts.into_keys().collect(), lengths))
+	}
+
+	/// Code of `symbols` with `lengths`, which must describe a prefix code
+	fn from_lengths(symbols: Vec<T>, lengths: Vec<u8>) -> Huffman<T> {
+		let codes = symbols
+			.iter()
+			.cloned()
+			.zip(canonical::codes(&lengths))
+			.collect();
+		Huffman {
+			symbols,
+			lengths,
+			codes,
+		}
+	}
+
+	/// Number of symbols with a code
+	pub fn len(&self) -> usize {
+		self.symbols.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.symbols.is_empty()
+	}
+
+	/// Every symbol with a code and its code length, ascending
+	pub fn lengths(&self) -> impl Iterator<Item = (&T, u8)> {
+		self.symbols.iter().zip(self.lengths.iter().copied())
+	}
+
+	/// Code of `symbol` as `(bits, length)`, `None` if it has none
+	pub fn code(&self, symbol: &T) -> Option<(u64, u8)> {
+		self.codes.get(symbol).copied()
+	}
+
+	/// Write the code of every symbol of `symbols`, failing with `InvalidInput` on a
+	/// symbol that has none
+	pub fn encode<'a, I, W>(&self, symbols: I, bits: &mut BitWriter<W>) -> io::Result<()>
+	where
+		T: 'a,
+		I: IntoIterator<Item = &'a T>,
+		W: Write,
+	{
+		for symbol in symbols {
+			let (code, length) = self
+				.code(symbol)
+				.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Symbol has no code"))?;
+			bits.write_bits(code, length)?;
+		}
+		Ok(())
+	}
+
+	/// Decoder for symbols written by `encode`
+	pub fn decoder(&self) -> SymbolDecoder<'_, T> {
+		SymbolDecoder {
+			symbols: &self.symbols,
+			// The lengths describe a prefix code, checked when building or reading them
+			decoder: Decoder::new(&self.lengths).unwrap(),
+		}
+	}
+
+	/// Append the table of the code to `output`, see the module documentation
+	pub fn write_table(&self, output: &mut Vec<u8>) {
+		output.extend(&(self.symbols.len() as u32).to_le_bytes());
+		for (symbol, length) in self.lengths() {
+			symbol.write(output);
+			output.push(length);
+		}
+	}
+
+	/// Read a table written by `write_table`
+	pub fn read_table(cursor: &mut Cursor) -> Result<Huffman<T>, DecompressError> {
+		let count = cursor.u32("symbol count")?;
+		let (mut symbols, mut lengths) = (Vec::new(), Vec::new());
+		for _ in 0..count {
+			let symbol = T::read(cursor)?;
+			if symbols.last().is_some_and(|last| *last >= symbol) {
+				return Err(DecompressError::InvalidTree);
+			}
+			symbols.push(symbol);
+			lengths.push(cursor.u8("code length")?);
+		}
+		if lengths.contains(&0) || Decoder::new(&lengths).is_none() {
+			return Err(DecompressError::InvalidTree);
+		}
+		Ok(Huffman::from_lengths(symbols, lengths))
+	}
+}
+
+/// Reads symbols of a `Huffman` code, see `Huffman::decoder`
+pub struct SymbolDecoder<'a, T> {
+	symbols: &'a [T],
+	decoder: Decoder,
+}
+
+impl<T: Clone> SymbolDecoder<'_, T> {
+	/// Read the next symbol, `None` if the bits run out or match no code
+	pub fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> io::Result<Option<T>> {
+		Ok(self
+			.decoder
+			.decode(bits)?
+			.map(|index| self.symbols[index].clone()))
+	}
+}
@@ -0,0 +1,129 @@
+//! Wildcard patterns for file names, for shells that pass them on unexpanded
+//!
+//! `*` matches any run of chars and `?` any single char, neither matches `/`.
+//! In whole paths given to `matches`, `**` matches across `/` too, so `target/**`
+//! matches everything below `target` and `**/test` a `test` in any directory.
+//! `[abc]` matches one of the listed chars, `[a-z]` one of a range and
+//! `[!abc]` any char not listed.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Whether `text` contains wildcards
+pub fn is_pattern(text: &str) -> bool {
+	text.contains(['*', '?', '['])
+}
+
+/// Whether all of `text` matches `pattern`
+pub fn matches(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+	matches_chars(&pattern, &text)
+}
+
+fn matches_chars(pattern: &[char], text: &[char]) -> bool {
+	match (pattern.first(), text.first()) {
+		(None, _) => text.is_empty(),
+		(Some('*'), _) if pattern.get(1) == Some(&'*') => {
+			let rest = &pattern[2..];
+			// `**/` also matches no directory at all
+			(rest.first() == Some(&'/') && matches_chars(&rest[1..], text))
+				|| (0..=text.len()).any(|len| matches_chars(rest, &text[len..]))
+		}
+		(Some('*'), _) => {
+			// Try every run the `*` can swallow, up to the next `/`
+			let longest = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+			(0..=longest).any(|len| matches_chars(&pattern[1..], &text[len..]))
+		}
+		(_, None) | (_, Some('/')) if pattern[0] != '/' => false,
+		(Some('?'), Some(_)) => matches_chars(&pattern[1..], &text[1..]),
+		(Some('['), Some(&c)) => match class(pattern, c) {
+			Some(len) => matches_chars(&pattern[len..], &text[1..]),
+			None => false,
+		},
+		(Some(&expected), Some(&c)) => expected == c && matches_chars(&pattern[1..], &text[1..]),
+		(Some(_), None) => false,
+	}
+}
+
+/// Length of the `[...]` class starting `pattern` if it matches `c`, `None` if it doesn't.
+/// An unterminated `[` only matches itself
+fn class(pattern: &[char], c: char) -> Option<usize> {
+	let end = match pattern.iter().skip(2).position(|&c| c == ']') {
+		Some(i) => i + 2,
+		None => return if c == '[' { Some(1) } else { None },
+	};
+	let (negated, members) = match pattern[1] {
+		'!' => (true, &pattern[2..end]),
+		_ => (false, &pattern[1..end]),
+	};
+	let mut found = false;
+	let mut i = 0;
+	while i < members.len() {
+		if i + 2 < members.len() && members[i + 1] == '-' {
+			found |= (members[i]..=members[i + 2]).contains(&c);
+			i += 3;
+		} else {
+			found |= members[i] == c;
+			i += 1;
+		}
+	}
+	if found != negated {
+		Some(end + 1)
+	} else {
+		None
+	}
+}
+
+/// Paths of the existing files and directories matching `pattern`, sorted
+///
+/// Each `/` separated component of the pattern is matched against the entries
+/// of the directories matched so far. Hidden entries are only matched by
+/// components starting with `.`
+pub fn expand(pattern: &str) -> io::Result<Vec<PathBuf>> {
+	let (mut paths, rest) = match pattern.strip_prefix('/') {
+		Some(rest) => (vec![PathBuf::from("/")], rest),
+		None => (vec![PathBuf::new()], pattern),
+	};
+	for component in rest.split('/').filter(|component| !component.is_empty()) {
+		let mut next = Vec::new();
+		for path in &paths {
+			if !is_pattern(component) {
+				let path = path.join(component);
+				if fs::symlink_metadata(&path).is_ok() {
+					next.push(path);
+				}
+				continue;
+			}
+			let dir = if path.as_os_str().is_empty() {
+				Path::new(".")
+			} else {
+				path.as_path()
+			};
+			let entries = match fs::read_dir(dir) {
+				Ok(entries) => entries,
+				Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+				Err(e) if e.kind() == io::ErrorKind::NotADirectory => continue,
+				Err(e) => return Err(e),
+			};
+			let mut matched = Vec::new();
+			for entry in entries {
+				let name = entry?.file_name();
+				let name = match name.to_str() {
+					Some(name) => name,
+					None => continue,
+				};
+				if name.starts_with('.') && !component.starts_with('.') {
+					continue;
+				}
+				if matches(component, name) {
+					matched.push(path.join(name));
+				}
+			}
+			matched.sort();
+			next.extend(matched);
+		}
+		paths = next;
+	}
+	Ok(paths)
+}
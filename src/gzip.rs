@@ -0,0 +1,171 @@
+//! gzip output (RFC 1952), so compressed files can be read by `gunzip` and browsers
+//!
+//! The deflate stream (RFC 1951) inside consists of huffman only blocks: every byte
+//! is coded as a literal, there are no back references. Each block gets codes built
+//! from its own bytes, limited to the code lengths deflate allows.
+use crate::canonical;
+use crate::checksum;
+use crate::compress::huffman::Metadata;
+
+/// Input bytes per deflate block
+const BLOCK_SIZE: usize = 1 << 16;
+
+/// Symbol ending a block in the literal/length alphabet
+const END_OF_BLOCK: usize = 256;
+
+/// Longest literal/length and distance code deflate allows
+const MAX_CODE_LENGTH: u8 = 15;
+
+/// Longest code of the code length alphabet
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+
+/// Order in which code length code lengths are stored
+pub(crate) const CODE_LENGTH_ORDER: [usize; 19] = [
+	16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Packs bits least significant first, as deflate does
+#[derive(Default)]
+struct DeflateWriter {
+	bytes: Vec<u8>,
+	/// Number of bits used in the last byte, 0 when it is full
+	used: u8,
+}
+
+impl DeflateWriter {
+	fn write_bit(&mut self, bit: bool) {
+		if self.used == 0 {
+			self.bytes.push(0);
+		}
+		*self.bytes.last_mut().unwrap() |= (bit as u8) << self.used;
+		self.used = (self.used + 1) % 8;
+	}
+	/// Write an integer field, least significant bit first
+	fn write_bits(&mut self, value: u64, count: u8) {
+		for i in 0..count {
+			self.write_bit(value >> i & 1 != 0);
+		}
+	}
+	/// Write a huffman code, most significant bit first
+	fn write_code(&mut self, (code, length): (u64, u8)) {
+		for i in (0..length).rev() {
+			self.write_bit(code >> i & 1 != 0);
+		}
+	}
+}
+
+/// Run-length encode code lengths with the code length alphabet,
+/// returning `(symbol, extra bits value, extra bit count)` triples
+fn encode_lengths(lengths: &[u8]) -> Vec<(usize, u64, u8)> {
+	let mut symbols = Vec::new();
+	let mut i = 0;
+	while i < lengths.len() {
+		let length = lengths[i];
+		let run = lengths[i..].iter().take_while(|&&l| l == length).count();
+		if length == 0 && run >= 11 {
+			let run = run.min(138);
+			symbols.push((18, run as u64 - 11, 7));
+			i += run;
+		} else if length == 0 && run >= 3 {
+			symbols.push((17, run as u64 - 3, 3));
+			i += run;
+		} else if length != 0 && run >= 4 {
+			// The first length is written as is, the rest repeats it
+			symbols.push((length as usize, 0, 0));
+			let run = (run - 1).min(6);
+			symbols.push((16, run as u64 - 3, 2));
+			i += 1 + run;
+		} else {
+			symbols.push((length as usize, 0, 0));
+			i += 1;
+		}
+	}
+	symbols
+}
+
+/// Write a block coding every byte of `data` as a literal
+fn write_block(bits: &mut DeflateWriter, data: &[u8], last: bool) {
+	let mut frequencies = vec![0u64; END_OF_BLOCK + 1];
+	for &byte in data {
+		frequencies[byte as usize] += 1;
+	}
+	frequencies[END_OF_BLOCK] = 1;
+	let literal_lengths = canonical::limited_code_lengths(&frequencies, MAX_CODE_LENGTH)
+		.expect("257 symbols fit in 15 bits");
+	// No back references are used, but a distance code has to be described
+	let distance_lengths = [1u8];
+
+	let mut all_lengths = literal_lengths.clone();
+	all_lengths.extend(&distance_lengths);
+	let length_symbols = encode_lengths(&all_lengths);
+	let mut length_frequencies = [0u64; 19];
+	for &(symbol, _, _) in &length_symbols {
+		length_frequencies[symbol] += 1;
+	}
+	let length_lengths =
+		canonical::limited_code_lengths(&length_frequencies, MAX_CODE_LENGTH_CODE_LENGTH)
+			.expect("19 symbols fit in 7 bits");
+
+	bits.write_bit(last);
+	bits.write_bits(2, 2); // Dynamic codes
+	bits.write_bits((literal_lengths.len() - 257) as u64, 5);
+	bits.write_bits((distance_lengths.len() - 1) as u64, 5);
+	let stored = 4 + CODE_LENGTH_ORDER
+		.iter()
+		.rposition(|&symbol| length_lengths[symbol] != 0)
+		.unwrap_or(0)
+		.saturating_sub(3);
+	bits.write_bits((stored - 4) as u64, 4);
+	for &symbol in &CODE_LENGTH_ORDER[..stored] {
+		bits.write_bits(length_lengths[symbol] as u64, 3);
+	}
+	let length_codes = canonical::codes(&length_lengths);
+	for (symbol, extra, extra_bits) in length_symbols {
+		bits.write_code(length_codes[symbol]);
+		bits.write_bits(extra, extra_bits);
+	}
+	let literal_codes = canonical::codes(&literal_lengths);
+	for &byte in data {
+		bits.write_code(literal_codes[byte as usize]);
+	}
+	bits.write_code(literal_codes[END_OF_BLOCK]);
+}
+
+/// Raw deflate stream of `data`
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+	let mut bits = DeflateWriter::default();
+	let mut blocks = data.chunks(BLOCK_SIZE).peekable();
+	if blocks.peek().is_none() {
+		write_block(&mut bits, &[], true);
+	}
+	while let Some(block) = blocks.next() {
+		write_block(&mut bits, block, blocks.peek().is_none());
+	}
+	bits.bytes
+}
+
+/// gzip member holding `data`, with file name and modification time taken from `metadata`
+pub fn compress(data: &[u8], metadata: &Metadata) -> Vec<u8> {
+	const FNAME: u8 = 0x08;
+	let name = metadata
+		.name
+		.as_ref()
+		.filter(|name| !name.as_bytes().contains(&0));
+	let mut output = vec![0x1f, 0x8b, 8, if name.is_some() { FNAME } else { 0 }];
+	// Times beyond 2106 don't fit, 0 means unknown
+	let mtime = metadata
+		.mtime
+		.filter(|&mtime| mtime <= u32::MAX as u64)
+		.unwrap_or(0) as u32;
+	output.extend(&mtime.to_le_bytes());
+	output.push(0); // Extra flags
+	output.push(255); // Unknown operating system
+	if let Some(name) = name {
+		output.extend(name.as_bytes());
+		output.push(0);
+	}
+	output.extend(deflate(data));
+	output.extend(&checksum::crc32(data).to_le_bytes());
+	output.extend(&(data.len() as u32).to_le_bytes());
+	output
+}
@@ -0,0 +1,299 @@
+//! HTTP front end to the server, for services that can't link against this crate
+//!
+//! `POST /compress` and `POST /decompress` take the input as request body, with a
+//! `Content-Length` or chunked, and read it as it arrives. Responses carry the output with
+//! `Content-Length`, and `X-Checksum-CRC32` holding the CRC-32 of the uncompressed data
+//! in hex. Failures are answered with a status of 400 or more and a plain text message,
+//! 413 for input over `MAX_PAYLOAD` bytes or decompressing to over `MAX_OUTPUT`.
+//! Every connection carries a single request.
+use crate::checksum::Crc32;
+use crate::compress::huffman::{self, Compressor, DecompressError};
+use crate::logging::{info, warning};
+use crate::server::{MAX_OUTPUT, MAX_PAYLOAD};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// Longest request line or header line accepted
+const MAX_LINE: u64 = 8 << 10;
+/// Most headers accepted in a request
+const MAX_HEADERS: usize = 100;
+
+/// A response to send, before it is framed
+struct Response {
+	status: u16,
+	reason: &'static str,
+	body: Vec<u8>,
+	checksum: Option<u32>,
+}
+
+impl Response {
+	fn error(status: u16, reason: &'static str, message: String) -> Response {
+		Response {
+			status,
+			reason,
+			body: (message + "\n").into_bytes(),
+			checksum: None,
+		}
+	}
+}
+
+/// Passes data through, keeping the CRC-32 of everything read or written
+struct Checked<T> {
+	inner: T,
+	crc: Crc32,
+}
+
+impl<R: Read> Read for Checked<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = self.inner.read(buf)?;
+		self.crc.update(&buf[..len]);
+		Ok(len)
+	}
+}
+
+impl<W: Write> Write for Checked<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let len = self.inner.write(buf)?;
+		self.crc.update(&buf[..len]);
+		Ok(len)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+/// Error of a chunked body passing `MAX_PAYLOAD`, answered with 413
+#[derive(Debug)]
+struct BodyTooLarge;
+
+impl std::fmt::Display for BodyTooLarge {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "Body is longer than the limit of {} bytes", MAX_PAYLOAD)
+	}
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// Body sent with `Transfer-Encoding: chunked`, see RFC 9112 section 7.1
+struct Chunked<R> {
+	inner: R,
+	/// Bytes left in the current chunk
+	left: u64,
+	/// Bytes of all chunks so far, at most `MAX_PAYLOAD`
+	total: u64,
+	done: bool,
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.done || buf.is_empty() {
+			return Ok(0);
+		}
+		if self.left == 0 {
+			let line = read_line(&mut self.inner)?;
+			let size = line.split(';').next().unwrap_or("").trim();
+			self.left = u64::from_str_radix(size, 16).map_err(|_| invalid("Invalid chunk size"))?;
+			// A size overflowing the total is over the limit as well
+			self.total = self
+				.total
+				.checked_add(self.left)
+				.filter(|&total| total <= MAX_PAYLOAD as u64)
+				.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, BodyTooLarge))?;
+			if self.left == 0 {
+				// Trailers, up to the empty line
+				while !read_line(&mut self.inner)?.is_empty() {}
+				self.done = true;
+				return Ok(0);
+			}
+		}
+		let len = buf.len().min(self.left as usize);
+		let len = self.inner.read(&mut buf[..len])?;
+		if len == 0 {
+			return Err(io::ErrorKind::UnexpectedEof.into());
+		}
+		self.left -= len as u64;
+		if self.left == 0 && !read_line(&mut self.inner)?.is_empty() {
+			return Err(invalid("Missing line break after chunk"));
+		}
+		Ok(len)
+	}
+}
+
+fn invalid(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Next line of a request, without line break
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+	let mut line = Vec::new();
+	reader.take(MAX_LINE).read_until(b'\n', &mut line)?;
+	if line.pop() != Some(b'\n') {
+		return Err(invalid("Line too long or cut off"));
+	}
+	if line.last() == Some(&b'\r') {
+		line.pop();
+	}
+	String::from_utf8(line).map_err(|_| invalid("Line is not UTF-8"))
+}
+
+/// Answer the request on `stream`
+pub fn handle(stream: &TcpStream, compressor: &Compressor) -> io::Result<()> {
+	let mut reader = BufReader::new(stream);
+	let response = match respond(&mut reader, compressor) {
+		Ok(response) => response,
+		Err(e) => Response::error(400, "Bad Request", e.to_string()),
+	};
+	let mut head = format!(
+		"HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+		response.status,
+		response.reason,
+		response.body.len()
+	);
+	match response.checksum {
+		Some(crc) => {
+			head += "Content-Type: application/octet-stream\r\n";
+			head += &format!("X-Checksum-CRC32: {:08x}\r\n", crc);
+		}
+		None => head += "Content-Type: text/plain; charset=utf-8\r\n",
+	}
+	head += "\r\n";
+	let mut stream = stream;
+	stream.write_all(head.as_bytes())?;
+	stream.write_all(&response.body)?;
+	stream.flush()
+}
+
+/// Read a request and carry it out, failing only for requests that can't be parsed
+fn respond<R: BufRead>(reader: &mut R, compressor: &Compressor) -> io::Result<Response> {
+	let request_line = read_line(reader)?;
+	let mut parts = request_line.split(' ');
+	let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+		(Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+			(method.to_string(), target.to_string())
+		}
+		_ => return Err(invalid("Invalid request line")),
+	};
+	let mut length = None;
+	let mut chunked = false;
+	for count in 0.. {
+		let line = read_line(reader)?;
+		if line.is_empty() {
+			break;
+		}
+		if count == MAX_HEADERS {
+			return Err(invalid("Too many headers"));
+		}
+		let (name, value) = line
+			.split_once(':')
+			.ok_or_else(|| invalid("Invalid header"))?;
+		let value = value.trim();
+		match name.trim().to_ascii_lowercase().as_str() {
+			"content-length" => {
+				length = Some(
+					value
+						.parse::<u64>()
+						.map_err(|_| invalid("Invalid Content-Length"))?,
+				)
+			}
+			"transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+			_ => {}
+		}
+	}
+	let path = target.split('?').next().unwrap_or("");
+	if path != "/compress" && path != "/decompress" {
+		return Ok(Response::error(
+			404,
+			"Not Found",
+			"Only /compress and /decompress exist".to_string(),
+		));
+	}
+	if method != "POST" {
+		return Ok(Response::error(
+			405,
+			"Method Not Allowed",
+			format!("{} takes POST requests", path),
+		));
+	}
+	if length.is_some_and(|length| length > MAX_PAYLOAD as u64) {
+		return Ok(Response::error(
+			413,
+			"Content Too Large",
+			format!("At most {} bytes allowed", MAX_PAYLOAD),
+		));
+	}
+	let body: Box<dyn Read + '_> = match (chunked, length) {
+		(true, _) => Box::new(Chunked {
+			inner: reader,
+			left: 0,
+			total: 0,
+			done: false,
+		}),
+		(false, Some(length)) => Box::new(reader.take(length)),
+		(false, None) => Box::new(io::empty()),
+	};
+	let result = if path == "/compress" {
+		let mut input = Checked {
+			inner: body,
+			crc: Crc32::new(),
+		};
+		compressor
+			.compress_stream(&mut input, Vec::new())
+			.map(|output| (output, input.crc.value()))
+	} else {
+		let output = Checked {
+			inner: Vec::new(),
+			crc: Crc32::new(),
+		};
+		let dictionary = compressor.options().dictionary;
+		huffman::decompress_stream_limited(body, output, dictionary, MAX_OUTPUT as u64)
+			.map(|output| (output.inner, output.crc.value()))
+	};
+	Ok(match result {
+		Ok((body, crc)) => Response {
+			status: 200,
+			reason: "OK",
+			body,
+			checksum: Some(crc),
+		},
+		Err(e) if too_large(&e) => Response::error(413, "Content Too Large", e.to_string()),
+		Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+			Response::error(400, "Bad Request", e.to_string())
+		}
+		Err(e) => return Err(e),
+	})
+}
+
+/// Whether `e` is the body passing `MAX_PAYLOAD` or its text passing `MAX_OUTPUT`
+fn too_large(e: &io::Error) -> bool {
+	match e.get_ref() {
+		Some(e) if e.is::<BodyTooLarge>() => true,
+		Some(e) => e
+			.downcast_ref::<DecompressError>()
+			.is_some_and(|e| matches!(e, DecompressError::OutputTooLarge(_))),
+		None => false,
+	}
+}
+
+/// Answer requests to `listener` on `threads` threads, at least 1, each answering a
+/// request at a time, until accepting fails on all of them
+pub fn serve(listener: TcpListener, compressor: &Compressor, threads: usize) -> io::Result<()> {
+	let listener = &listener;
+	std::thread::scope(|scope| {
+		let workers: Vec<_> = (0..threads.max(1))
+			.map(|_| {
+				scope.spawn(move || loop {
+					let (stream, _) = listener.accept()?;
+					match handle(&stream, compressor) {
+						Ok(()) => info!("Request answered"),
+						Err(e) => warning!("Request failed: {}", e),
+					}
+				})
+			})
+			.collect();
+		let mut result = Ok(());
+		for worker in workers {
+			result = result.and(worker.join().unwrap());
+		}
+		result
+	})
+}
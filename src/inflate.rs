@@ -0,0 +1,494 @@
+//! Reading deflate data (RFC 1951) in gzip (RFC 1952) or zlib (RFC 1950) containers,
+//! to recompress `.gz` files without unpacking them to disk first
+//!
+//! `Reader` decodes as it is read from, keeping the last 32 KiB of output that back
+//! references reach into. gzip files may hold several members back to back, which
+//! read as one stream. The CRC-32 and length of every gzip member and the Adler-32 of
+//! zlib data are checked, a mismatch fails with `InvalidData`.
+use crate::checksum::Crc32;
+use crate::gzip::CODE_LENGTH_ORDER;
+use std::io::{self, Read};
+
+/// Farthest a back reference reaches
+const WINDOW: usize = 32 << 10;
+
+/// Output decoded ahead of the reader at most, plus a match
+const AHEAD: usize = 64 << 10;
+
+const LENGTH_BASE: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+	163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+	2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+	13,
+];
+
+fn invalid(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Container around the deflate data
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+	Gzip,
+	Zlib,
+}
+
+impl Format {
+	/// Format of data starting with `data`, by its header
+	pub fn detect(data: &[u8]) -> Option<Format> {
+		match data {
+			[0x1f, 0x8b, ..] => Some(Format::Gzip),
+			// Deflate with a window of at most 32 KiB and a header checksum
+			[cmf, flg, ..]
+				if cmf & 0x8f == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) =>
+			{
+				Some(Format::Zlib)
+			}
+			_ => None,
+		}
+	}
+}
+
+/// File name and modification time from the header of the first gzip member
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Header {
+	pub name: Option<String>,
+	pub mtime: Option<u64>,
+}
+
+/// Reads input a bit at a time, least significant bit of every byte first
+struct Bits<R: Read> {
+	input: R,
+	chunk: Vec<u8>,
+	position: usize,
+	value: u64,
+	count: u32,
+}
+
+impl<R: Read> Bits<R> {
+	/// The next byte of input, `None` at its end
+	fn next_byte(&mut self) -> io::Result<Option<u8>> {
+		if self.position == self.chunk.len() {
+			self.chunk.resize(AHEAD, 0);
+			let len = loop {
+				match self.input.read(&mut self.chunk) {
+					Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+					result => break result?,
+				}
+			};
+			self.chunk.truncate(len);
+			self.position = 0;
+			if len == 0 {
+				return Ok(None);
+			}
+		}
+		self.position += 1;
+		Ok(Some(self.chunk[self.position - 1]))
+	}
+	fn bits(&mut self, count: u32) -> io::Result<u32> {
+		while self.count < count {
+			let byte = self
+				.next_byte()?
+				.ok_or_else(|| invalid("Deflate data truncated"))?;
+			self.value |= (byte as u64) << self.count;
+			self.count += 8;
+		}
+		let bits = (self.value & ((1 << count) - 1)) as u32;
+		self.value >>= count;
+		self.count -= count;
+		Ok(bits)
+	}
+	/// Skip to the next byte boundary
+	fn align(&mut self) {
+		let skip = self.count % 8;
+		self.value >>= skip;
+		self.count -= skip;
+	}
+	/// The next byte on a byte boundary, `None` at the end of the input
+	fn byte(&mut self) -> io::Result<Option<u8>> {
+		if self.count >= 8 {
+			return self.bits(8).map(|byte| Some(byte as u8));
+		}
+		self.next_byte()
+	}
+	fn u8(&mut self) -> io::Result<u8> {
+		self.bits(8).map(|byte| byte as u8)
+	}
+	fn u16_le(&mut self) -> io::Result<u16> {
+		self.bits(16).map(|value| value as u16)
+	}
+	fn u32_le(&mut self) -> io::Result<u32> {
+		Ok(self.bits(16)? | self.bits(16)? << 16)
+	}
+}
+
+/// A canonical code as deflate assigns them, shorter codes first and equal lengths by
+/// symbol
+struct Code {
+	/// Number of codes of every length
+	counts: [u16; 16],
+	/// Symbols in code order
+	symbols: Vec<u16>,
+}
+
+impl Code {
+	/// Fails if the lengths give more codes than fit, incomplete codes are allowed
+	fn new(lengths: &[u8]) -> io::Result<Code> {
+		let mut counts = [0u16; 16];
+		for &length in lengths {
+			counts[length as usize] += 1;
+		}
+		counts[0] = 0;
+		let mut left: i32 = 1;
+		for &count in &counts[1..] {
+			left = left * 2 - count as i32;
+			if left < 0 {
+				return Err(invalid("Over-subscribed deflate code"));
+			}
+		}
+		let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+			.filter(|&symbol| lengths[symbol as usize] != 0)
+			.collect();
+		symbols.sort_by_key(|&symbol| lengths[symbol as usize]);
+		Ok(Code { counts, symbols })
+	}
+	fn decode<R: Read>(&self, bits: &mut Bits<R>) -> io::Result<u16> {
+		let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+		for &count in &self.counts[1..] {
+			code |= bits.bits(1)? as i32;
+			let count = count as i32;
+			if code - first < count {
+				return Ok(self.symbols[(index + code - first) as usize]);
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err(invalid("Invalid deflate code"))
+	}
+}
+
+/// Where decoding stands
+enum State {
+	/// Before the header of a block
+	Block,
+	/// Within a stored block, with this many bytes left
+	Stored(u16),
+	/// Within a block of huffman codes
+	Coded(Code, Code),
+	/// After the last block of a member
+	Trailer,
+	Done,
+}
+
+/// Decompresses gzip or zlib data as it is read
+pub struct Reader<R: Read> {
+	bits: Bits<R>,
+	format: Format,
+	header: Header,
+	state: State,
+	last: bool,
+	/// Recent output for back references, followed by output not read yet
+	output: Vec<u8>,
+	/// Start of the output not read yet
+	read: usize,
+	/// Start of the output of the current member, as far as it is kept
+	member: usize,
+	/// Checksum and length of the current member
+	crc: Crc32,
+	adler: (u32, u32),
+	len: u32,
+}
+
+impl<R: Read> Reader<R> {
+	/// Start reading `input` in `format`, reading the first header
+	pub fn new(input: R, format: Format) -> io::Result<Reader<R>> {
+		let mut reader = Reader {
+			bits: Bits {
+				input,
+				chunk: Vec::new(),
+				position: 0,
+				value: 0,
+				count: 0,
+			},
+			format,
+			header: Header::default(),
+			state: State::Block,
+			last: false,
+			output: Vec::new(),
+			read: 0,
+			member: 0,
+			crc: Crc32::new(),
+			adler: (1, 0),
+			len: 0,
+		};
+		reader.header = reader.read_header()?;
+		Ok(reader)
+	}
+
+	/// Header of the first gzip member, empty for zlib
+	pub fn header(&self) -> &Header {
+		&self.header
+	}
+
+	fn read_header(&mut self) -> io::Result<Header> {
+		let bits = &mut self.bits;
+		if self.format == Format::Zlib {
+			let (cmf, flg) = (bits.u8()?, bits.u8()?);
+			if Format::detect(&[cmf, flg]) != Some(Format::Zlib) {
+				return Err(invalid("Not zlib data"));
+			}
+			if flg & 0x20 != 0 {
+				return Err(invalid("zlib data needs a preset dictionary"));
+			}
+			return Ok(Header::default());
+		}
+		const FHCRC: u8 = 0x02;
+		const FEXTRA: u8 = 0x04;
+		const FNAME: u8 = 0x08;
+		const FCOMMENT: u8 = 0x10;
+		if (bits.u8()?, bits.u8()?, bits.u8()?) != (0x1f, 0x8b, 8) {
+			return Err(invalid("Not gzip data"));
+		}
+		let flags = bits.u8()?;
+		let mtime = bits.u32_le()?;
+		bits.u16_le()?; // Extra flags and operating system
+		if flags & FEXTRA != 0 {
+			let len = bits.u16_le()?;
+			for _ in 0..len {
+				bits.u8()?;
+			}
+		}
+		let mut zero_terminated = || -> io::Result<Vec<u8>> {
+			let mut field = Vec::new();
+			loop {
+				match bits.u8()? {
+					0 => return Ok(field),
+					byte => field.push(byte),
+				}
+			}
+		};
+		// Names are Latin-1, like the chars of the same values
+		let name = match flags & FNAME {
+			0 => None,
+			_ => Some(zero_terminated()?.into_iter().map(char::from).collect()),
+		};
+		if flags & FCOMMENT != 0 {
+			zero_terminated()?;
+		}
+		if flags & FHCRC != 0 {
+			bits.u16_le()?;
+		}
+		Ok(Header {
+			name,
+			mtime: Some(mtime as u64).filter(|&mtime| mtime != 0),
+		})
+	}
+
+	/// Check the trailer of a member, then go on to the next gzip member if any
+	fn read_trailer(&mut self) -> io::Result<()> {
+		self.bits.align();
+		let valid = match self.format {
+			Format::Gzip => {
+				let crc = self.bits.u32_le()?;
+				let len = self.bits.u32_le()?;
+				crc == self.crc.value() && len == self.len
+			}
+			Format::Zlib => {
+				let adler = self.bits.u32_le()?.swap_bytes();
+				adler == self.adler.1 << 16 | self.adler.0
+			}
+		};
+		if !valid {
+			return Err(invalid("Checksum of deflate data doesn't match"));
+		}
+		self.state = State::Done;
+		if self.format == Format::Gzip {
+			match self.bits.byte()? {
+				None => {}
+				// Padding after the last member
+				Some(0) => {}
+				Some(byte) => {
+					// Put the byte back, it starts the header of the next member
+					self.bits.value = self.bits.value << 8 | byte as u64;
+					self.bits.count += 8;
+					self.read_header()?;
+					self.member = self.output.len();
+					self.crc = Crc32::new();
+					self.len = 0;
+					self.last = false;
+					self.state = State::Block;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Count the output from `start` on towards the checksums of the member
+	fn emit(&mut self, start: usize) {
+		let new = &self.output[start..];
+		self.crc.update(new);
+		self.len = self.len.wrapping_add(new.len() as u32);
+		if self.format == Format::Zlib {
+			let (mut a, mut b) = self.adler;
+			for chunk in new.chunks(5552) {
+				for &byte in chunk {
+					a += byte as u32;
+					b += a;
+				}
+				a %= 65521;
+				b %= 65521;
+			}
+			self.adler = (a, b);
+		}
+	}
+
+	/// Decode until there is output to read or the data ends
+	fn fill(&mut self) -> io::Result<()> {
+		// Keep no more history than back references reach
+		if self.read > WINDOW + AHEAD {
+			let drop = self.read - WINDOW;
+			self.output.drain(..drop);
+			self.read -= drop;
+			self.member = self.member.saturating_sub(drop);
+		}
+		let start = self.output.len();
+		while self.output.len() - self.read < AHEAD {
+			match &mut self.state {
+				State::Done => break,
+				State::Trailer => {
+					self.emit(start);
+					self.read_trailer()?;
+					return Ok(());
+				}
+				State::Block => {
+					if self.last {
+						self.state = State::Trailer;
+						continue;
+					}
+					self.last = self.bits.bits(1)? == 1;
+					self.state = match self.bits.bits(2)? {
+						0 => {
+							self.bits.align();
+							let len = self.bits.u16_le()?;
+							if self.bits.u16_le()? != !len {
+								return Err(invalid("Stored block length doesn't match"));
+							}
+							State::Stored(len)
+						}
+						1 => {
+							let mut lengths = [8u8; 288];
+							lengths[144..256].iter_mut().for_each(|length| *length = 9);
+							lengths[256..280].iter_mut().for_each(|length| *length = 7);
+							State::Coded(Code::new(&lengths)?, Code::new(&[5; 30])?)
+						}
+						2 => self.read_codes()?,
+						_ => return Err(invalid("Invalid deflate block type")),
+					};
+				}
+				State::Stored(0) => self.state = State::Block,
+				State::Stored(left) => {
+					*left -= 1;
+					let byte = self.bits.u8()?;
+					self.output.push(byte);
+				}
+				State::Coded(literals, distances) => {
+					let symbol = literals.decode(&mut self.bits)? as usize;
+					if symbol < 256 {
+						self.output.push(symbol as u8);
+						continue;
+					}
+					if symbol == 256 {
+						self.state = State::Block;
+						continue;
+					}
+					let index = symbol - 257;
+					if index >= LENGTH_BASE.len() {
+						return Err(invalid("Invalid deflate length"));
+					}
+					let length = LENGTH_BASE[index] as usize
+						+ self.bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+					let index = distances.decode(&mut self.bits)? as usize;
+					if index >= DISTANCE_BASE.len() {
+						return Err(invalid("Invalid deflate distance"));
+					}
+					let distance = DISTANCE_BASE[index] as usize
+						+ self.bits.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+					// Output of earlier members can't be referred to
+					if distance > self.output.len() - self.member {
+						return Err(invalid("Deflate distance too far back"));
+					}
+					// Copied a byte at a time, the match may overlap what it produces
+					for _ in 0..length {
+						let byte = self.output[self.output.len() - distance];
+						self.output.push(byte);
+					}
+				}
+			}
+		}
+		self.emit(start);
+		Ok(())
+	}
+
+	/// Read the code lengths of a dynamic block
+	fn read_codes(&mut self) -> io::Result<State> {
+		let bits = &mut self.bits;
+		let literals = bits.bits(5)? as usize + 257;
+		let distances = bits.bits(5)? as usize + 1;
+		let code_lengths = bits.bits(4)? as usize + 4;
+		let mut lengths = [0u8; 19];
+		for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+			lengths[symbol] = bits.bits(3)? as u8;
+		}
+		let code = Code::new(&lengths)?;
+		let mut lengths = Vec::with_capacity(literals + distances);
+		while lengths.len() < literals + distances {
+			let (length, repeat) = match code.decode(bits)? {
+				symbol @ 0..=15 => (symbol as u8, 1),
+				16 => {
+					let previous = *lengths
+						.last()
+						.ok_or_else(|| invalid("Repeated code length without a previous one"))?;
+					(previous, 3 + bits.bits(2)?)
+				}
+				17 => (0, 3 + bits.bits(3)?),
+				_ => (0, 11 + bits.bits(7)?),
+			};
+			if lengths.len() + repeat as usize > literals + distances {
+				return Err(invalid("Too many code lengths"));
+			}
+			lengths.resize(lengths.len() + repeat as usize, length);
+		}
+		if lengths[256] == 0 {
+			return Err(invalid("Deflate block without an end"));
+		}
+		Ok(State::Coded(
+			Code::new(&lengths[..literals])?,
+			Code::new(&lengths[literals..])?,
+		))
+	}
+}
+
+impl<R: Read> Read for Reader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		while self.read == self.output.len() {
+			if let State::Done = self.state {
+				return Ok(0);
+			}
+			self.fill()?;
+		}
+		let len = buf.len().min(self.output.len() - self.read);
+		buf[..len].copy_from_slice(&self.output[self.read..self.read + len]);
+		self.read += len;
+		Ok(len)
+	}
+}
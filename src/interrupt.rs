@@ -0,0 +1,70 @@
+//! Clean exits on Ctrl-C and `kill`
+//!
+//! `install` traps SIGINT and SIGTERM. The signal handler only writes the signal number
+//! to a pipe, a thread waiting on it cleans up after the files still being written, see
+//! `atomic::undo_pending`, and exits with 128 plus the signal number, the status shells
+//! report for a process killed by it. Elsewhere than on unix `install` does nothing.
+use std::io;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Exit status after `signal`
+pub fn status(signal: i32) -> i32 {
+	128 + signal
+}
+
+/// Write end of the pipe to the waiting thread
+#[cfg(unix)]
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg(unix)]
+extern "C" fn handler(signal: libc::c_int) {
+	let byte = signal as u8;
+	// Only async-signal-safe calls here
+	unsafe {
+		libc::write(
+			PIPE.load(Ordering::Relaxed),
+			&byte as *const u8 as *const libc::c_void,
+			1,
+		);
+	}
+}
+
+/// Trap SIGINT and SIGTERM for the rest of the process, see the module documentation
+#[cfg(unix)]
+pub fn install() -> io::Result<()> {
+	use crate::atomic;
+	use crate::logging::warning;
+	use std::io::Read;
+	use std::os::unix::io::FromRawFd;
+
+	let mut fds = [0; 2];
+	if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	PIPE.store(fds[1], Ordering::Relaxed);
+	let mut reader = unsafe { std::fs::File::from_raw_fd(fds[0]) };
+	std::thread::spawn(move || {
+		let mut signal = [0];
+		if reader.read_exact(&mut signal).is_ok() {
+			warning!("Interrupted, removing unfinished output");
+			atomic::undo_pending();
+			std::process::exit(status(signal[0] as i32));
+		}
+	});
+	for &signal in &[libc::SIGINT, libc::SIGTERM] {
+		let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+		action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+		// Calls interrupted by the signal carry on, the thread decides what happens
+		action.sa_flags = libc::SA_RESTART;
+		if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+			return Err(io::Error::last_os_error());
+		}
+	}
+	Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install() -> io::Result<()> {
+	Ok(())
+}
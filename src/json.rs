@@ -0,0 +1,242 @@
+//! Minimal JSON support for the files this crate reads and writes
+//!
+//! Numbers keep their text, so integers of any size survive a round trip.
+use std::fmt::Write as _;
+
+/// A parsed JSON value
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(String),
+	String(String),
+	Array(Vec<Value>),
+	/// Members in the order they appear
+	Object(Vec<(String, Value)>),
+}
+
+impl Value {
+	/// Member `key` of an object
+	pub fn get(&self, key: &str) -> Option<&Value> {
+		match self {
+			Value::Object(members) => members
+				.iter()
+				.find(|(name, _)| name == key)
+				.map(|(_, value)| value),
+			_ => None,
+		}
+	}
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Value::String(text) => Some(text),
+			_ => None,
+		}
+	}
+	pub fn as_u64(&self) -> Option<u64> {
+		match self {
+			Value::Number(number) => number.parse().ok(),
+			_ => None,
+		}
+	}
+	pub fn as_array(&self) -> Option<&[Value]> {
+		match self {
+			Value::Array(values) => Some(values),
+			_ => None,
+		}
+	}
+}
+
+/// Escape `text` for use inside a JSON string
+pub fn escape(text: &str) -> String {
+	let mut escaped = String::new();
+	for c in text.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// Parse a complete JSON document
+pub fn parse(text: &str) -> Result<Value, String> {
+	let mut parser = Parser {
+		text: text.as_bytes(),
+		position: 0,
+	};
+	let value = parser.value()?;
+	parser.whitespace();
+	if parser.position != text.len() {
+		return Err(parser.error("unexpected data after value"));
+	}
+	Ok(value)
+}
+
+struct Parser<'a> {
+	text: &'a [u8],
+	position: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn error(&self, msg: &str) -> String {
+		format!("Invalid JSON at offset {}: {}", self.position, msg)
+	}
+	fn whitespace(&mut self) {
+		while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+			self.position += 1;
+		}
+	}
+	fn peek(&self) -> Option<u8> {
+		self.text.get(self.position).copied()
+	}
+	fn expect(&mut self, byte: u8) -> Result<(), String> {
+		self.whitespace();
+		if self.peek() != Some(byte) {
+			return Err(self.error(&format!("expected `{}`", byte as char)));
+		}
+		self.position += 1;
+		Ok(())
+	}
+	fn literal(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+		if !self.text[self.position..].starts_with(literal.as_bytes()) {
+			return Err(self.error("unknown literal"));
+		}
+		self.position += literal.len();
+		Ok(value)
+	}
+	fn value(&mut self) -> Result<Value, String> {
+		self.whitespace();
+		match self.peek() {
+			Some(b'n') => self.literal("null", Value::Null),
+			Some(b't') => self.literal("true", Value::Bool(true)),
+			Some(b'f') => self.literal("false", Value::Bool(false)),
+			Some(b'"') => Ok(Value::String(self.string()?)),
+			Some(b'[') => {
+				self.position += 1;
+				let mut values = Vec::new();
+				self.whitespace();
+				if self.peek() == Some(b']') {
+					self.position += 1;
+					return Ok(Value::Array(values));
+				}
+				loop {
+					values.push(self.value()?);
+					self.whitespace();
+					match self.peek() {
+						Some(b',') => self.position += 1,
+						Some(b']') => {
+							self.position += 1;
+							return Ok(Value::Array(values));
+						}
+						_ => return Err(self.error("expected `,` or `]`")),
+					}
+				}
+			}
+			Some(b'{') => {
+				self.position += 1;
+				let mut members = Vec::new();
+				self.whitespace();
+				if self.peek() == Some(b'}') {
+					self.position += 1;
+					return Ok(Value::Object(members));
+				}
+				loop {
+					self.whitespace();
+					let key = self.string()?;
+					self.expect(b':')?;
+					members.push((key, self.value()?));
+					self.whitespace();
+					match self.peek() {
+						Some(b',') => self.position += 1,
+						Some(b'}') => {
+							self.position += 1;
+							return Ok(Value::Object(members));
+						}
+						_ => return Err(self.error("expected `,` or `}`")),
+					}
+				}
+			}
+			Some(b'-') | Some(b'0'..=b'9') => {
+				let start = self.position;
+				while let Some(b'-') | Some(b'+') | Some(b'.') | Some(b'e') | Some(b'E')
+				| Some(b'0'..=b'9') = self.peek()
+				{
+					self.position += 1;
+				}
+				let number = std::str::from_utf8(&self.text[start..self.position]).unwrap();
+				if number.parse::<f64>().is_err() {
+					return Err(self.error("invalid number"));
+				}
+				Ok(Value::Number(number.to_string()))
+			}
+			_ => Err(self.error("expected a value")),
+		}
+	}
+	fn string(&mut self) -> Result<String, String> {
+		if self.peek() != Some(b'"') {
+			return Err(self.error("expected a string"));
+		}
+		self.position += 1;
+		let mut bytes = Vec::new();
+		loop {
+			let byte = self
+				.peek()
+				.ok_or_else(|| self.error("unterminated string"))?;
+			self.position += 1;
+			match byte {
+				b'"' => break,
+				b'\\' => {
+					let escaped = self
+						.peek()
+						.ok_or_else(|| self.error("unterminated string"))?;
+					self.position += 1;
+					let c = match escaped {
+						b'"' => '"',
+						b'\\' => '\\',
+						b'/' => '/',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'n' => '\n',
+						b'r' => '\r',
+						b't' => '\t',
+						b'u' => self.unicode_escape()?,
+						_ => return Err(self.error("invalid escape")),
+					};
+					bytes.extend(c.to_string().as_bytes());
+				}
+				byte => bytes.push(byte),
+			}
+		}
+		String::from_utf8(bytes).map_err(|_| self.error("string is not UTF-8"))
+	}
+	/// The char of a `\u` escape, whose `\u` was already read, including surrogate pairs
+	fn unicode_escape(&mut self) -> Result<char, String> {
+		let high = self.unicode_escape_digits()?;
+		let code = if (0xD800..0xDC00).contains(&high) {
+			if !self.text[self.position..].starts_with(b"\\u") {
+				return Err(self.error("unpaired surrogate"));
+			}
+			self.position += 2;
+			let low = self.unicode_escape_digits()?;
+			if !(0xDC00..0xE000).contains(&low) {
+				return Err(self.error("unpaired surrogate"));
+			}
+			0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+		} else {
+			high
+		};
+		char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+	}
+	fn unicode_escape_digits(&mut self) -> Result<u32, String> {
+		let digits = self
+			.text
+			.get(self.position..self.position + 4)
+			.and_then(|digits| std::str::from_utf8(digits).ok())
+			.and_then(|digits| u32::from_str_radix(digits, 16).ok())
+			.ok_or_else(|| self.error("invalid unicode escape"))?;
+		self.position += 4;
+		Ok(digits)
+	}
+}
@@ -1 +1,40 @@
+pub mod analysis;
+pub mod archive;
+pub mod atomic;
+pub mod bits;
+pub mod canonical;
+pub mod checksum;
+pub mod codec;
+pub mod codes;
 pub mod compress;
+pub mod context;
+pub mod cursor;
+pub mod delta;
+pub mod digram;
+pub mod ffi;
+pub mod generic;
+pub mod glob;
+pub mod gzip;
+pub mod http;
+pub mod inflate;
+pub mod interrupt;
+pub mod json;
+pub mod logging;
+pub mod lzw;
+pub mod manifest;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+pub mod mount;
+pub mod nary;
+pub mod parity;
+pub mod presets;
+pub mod rice;
+pub mod rle;
+pub mod selftest;
+pub mod server;
+pub mod snapshot;
+pub mod stats;
+pub mod throttle;
+pub mod tunnel;
+pub mod volume;
+pub mod wide;
+pub mod words;
@@ -0,0 +1,76 @@
+//! Log events emitted by the library, silent until a logger is installed
+//!
+//! A minimal facade in the spirit of the `log` crate: the application picks a
+//! logger with `set_logger` and the most detailed level to let through with
+//! `set_max_level`, the library reports what it does with `log`.
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// Importance of an event, from most to least important
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+	Error = 1,
+	Warn,
+	Info,
+	Debug,
+}
+
+impl fmt::Display for Level {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Level::Error => write!(f, "error"),
+			Level::Warn => write!(f, "warning"),
+			Level::Info => write!(f, "info"),
+			Level::Debug => write!(f, "debug"),
+		}
+	}
+}
+
+/// Receives every event at or above the maximum level
+pub type Logger = fn(Level, fmt::Arguments);
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+/// Most detailed level let through, 0 lets nothing through
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(Level::Warn as usize);
+
+/// Install the logger, only the first call has an effect
+pub fn set_logger(logger: Logger) {
+	let _ = LOGGER.set(logger);
+}
+
+/// Let through events up to `level`, `None` silences all of them
+pub fn set_max_level(level: Option<Level>) {
+	MAX_LEVEL.store(level.map_or(0, |level| level as usize), Ordering::Relaxed);
+}
+
+/// Whether an event of `level` would reach a logger, to skip preparing one that won't
+pub fn enabled(level: Level) -> bool {
+	level as usize <= MAX_LEVEL.load(Ordering::Relaxed) && LOGGER.get().is_some()
+}
+
+/// Pass an event to the logger
+pub fn log(level: Level, args: fmt::Arguments) {
+	if enabled(level) {
+		(LOGGER.get().unwrap())(level, args);
+	}
+}
+
+macro_rules! debug {
+	($($arg:tt)*) => {
+		$crate::logging::log($crate::logging::Level::Debug, format_args!($($arg)*))
+	};
+}
+macro_rules! info {
+	($($arg:tt)*) => {
+		$crate::logging::log($crate::logging::Level::Info, format_args!($($arg)*))
+	};
+}
+macro_rules! warning {
+	($($arg:tt)*) => {
+		$crate::logging::log($crate::logging::Level::Warn, format_args!($($arg)*))
+	};
+}
+pub(crate) use debug;
+pub(crate) use info;
+pub(crate) use warning;
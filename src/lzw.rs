@@ -0,0 +1,127 @@
+//! LZW, coding strings seen before by their index in a table built along the way
+//!
+//! The table starts out with every single byte. Every code written adds the string it
+//! stands for, extended by the byte following it, so the decoder builds the same table
+//! from the codes alone and no table or tree is stored. Codes start 9 bits wide and
+//! grow by a bit whenever the table outgrows them, up to 16 bits. Once the table holds
+//! `MAX_CODES` strings it is cleared and built anew, which also lets it adapt to data
+//! that changes.
+//!
+//! # Data Format
+//! - Codes, zero padded to a whole byte. `CLEAR` resets the table, `END` ends the data
+//!
+//! A code is as wide as the number of entries in the table after it, see `width`.
+use crate::bits::{BitReader, BitWriter};
+use crate::compress::huffman::DecompressError;
+use std::collections::HashMap;
+
+/// Code that clears the table
+const CLEAR: usize = 256;
+/// Code that ends the data
+const END: usize = 257;
+/// First code standing for a string of more than one byte
+const FIRST: usize = 258;
+/// Entries of a full table
+const MAX_CODES: usize = 1 << 16;
+
+/// Width of codes written while the table has `entries` entries
+fn width(entries: usize) -> u8 {
+	(usize::BITS - entries.leading_zeros()).clamp(9, 16) as u8
+}
+
+/// Encode `data` with LZW
+pub fn encode(data: &[u8]) -> Vec<u8> {
+	// Writing to a Vec can't fail
+	let mut bits = BitWriter::new(Vec::new());
+	let mut table: HashMap<(usize, u8), usize> = HashMap::new();
+	let mut current: Option<usize> = None;
+	for &byte in data {
+		let prefix = match current {
+			Some(prefix) => prefix,
+			None => {
+				current = Some(byte as usize);
+				continue;
+			}
+		};
+		if let Some(&code) = table.get(&(prefix, byte)) {
+			current = Some(code);
+			continue;
+		}
+		let next = FIRST + table.len();
+		bits.write_bits(prefix as u64, width(next)).unwrap();
+		table.insert((prefix, byte), next);
+		if next + 1 == MAX_CODES {
+			bits.write_bits(CLEAR as u64, width(MAX_CODES)).unwrap();
+			table.clear();
+		}
+		current = Some(byte as usize);
+	}
+	let next = FIRST + table.len();
+	match current {
+		Some(code) => {
+			bits.write_bits(code as u64, width(next)).unwrap();
+			bits.write_bits(END as u64, width(next + 1)).unwrap();
+		}
+		None => bits.write_bits(END as u64, width(next)).unwrap(),
+	}
+	bits.finish().unwrap().0
+}
+
+/// Decode data produced by `encode`
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+	let mut bits = BitReader::new(data);
+	// Every entry is an earlier entry and the byte extending it, single bytes have none
+	let mut table: Vec<(Option<usize>, u8)> = (0..=255).map(|byte| (None, byte)).collect();
+	// Never read, CLEAR and END stand for no string
+	table.extend([(None, 0), (None, 0)]);
+	let mut previous: Option<usize> = None;
+	let mut output = Vec::new();
+	let mut string = Vec::new();
+	loop {
+		// The encoder added the entry for the previous code before writing this one
+		let entries = table.len() + previous.is_some() as usize;
+		let code = bits
+			.read_bits(width(entries))
+			.ok()
+			.flatten()
+			.ok_or(DecompressError::IncompleteCode)? as usize;
+		match code {
+			CLEAR => {
+				table.truncate(FIRST);
+				previous = None;
+				continue;
+			}
+			END => break,
+			_ => {}
+		}
+		// A code may stand for the entry about to be added, the previous string
+		// extended by its own first byte
+		let known = match previous {
+			_ if code < table.len() => code,
+			Some(previous) if code == table.len() => previous,
+			_ => return Err(DecompressError::InvalidCode(code as u32)),
+		};
+		string.clear();
+		let mut entry = Some(known);
+		while let Some(index) = entry {
+			let (prefix, byte) = table[index];
+			string.push(byte);
+			entry = prefix;
+		}
+		string.reverse();
+		if code != known {
+			string.push(string[0]);
+		}
+		if let Some(previous) = previous {
+			if table.len() < MAX_CODES {
+				table.push((Some(previous), string[0]));
+			}
+		}
+		output.extend(&string);
+		previous = Some(code);
+	}
+	if !bits.at_padding().unwrap_or(false) {
+		return Err(DecompressError::UnusedBits);
+	}
+	Ok(output)
+}
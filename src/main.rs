@@ -1,15 +1,2093 @@
-pub mod compress;
 extern crate clap;
-use clap::{App, Arg, ArgGroup};
-use compress::huffman;
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use rust_compression::analysis::{self, Analysis};
+use rust_compression::archive::{self, Archive, ArchiveWriter, EntryKind, Method};
+use rust_compression::atomic::{self, AtomicFile};
+use rust_compression::checksum::{self, Crc32};
+use rust_compression::codec;
+use rust_compression::compress::huffman;
+use rust_compression::glob;
+use rust_compression::gzip;
+use rust_compression::http;
+use rust_compression::inflate;
+use rust_compression::interrupt;
+use rust_compression::json;
+use rust_compression::logging::{self, Level};
+use rust_compression::manifest;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+use rust_compression::mount;
+use rust_compression::parity;
+use rust_compression::presets::{Presets, Settings};
+use rust_compression::selftest;
+use rust_compression::server;
+use rust_compression::snapshot;
+use rust_compression::stats::CorpusStats;
+use rust_compression::throttle::Throttle;
+use rust_compression::tunnel::{self, Side};
+use rust_compression::volume;
 use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::net::TcpListener;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Exit status for corrupt compressed data and other invalid input files
+const EXIT_CORRUPT: i32 = 1;
+/// Exit status for invalid command line arguments
+const EXIT_USAGE: i32 = 2;
+/// Exit status for files that can't be read or written
+const EXIT_IO: i32 = 3;
+
+/// An error to report on stderr, and the exit status it leads to
+struct Failure {
+	status: i32,
+	message: String,
+}
+
+impl Failure {
+	fn corrupt(message: String) -> Failure {
+		Failure {
+			status: EXIT_CORRUPT,
+			message,
+		}
+	}
+	fn usage(message: String) -> Failure {
+		Failure {
+			status: EXIT_USAGE,
+			message,
+		}
+	}
+	/// Failure of `action` on `path`, data rejected as invalid counts as corrupt
+	fn io(action: &str, path: &str, e: io::Error) -> Failure {
+		let status = match e.kind() {
+			io::ErrorKind::InvalidData => EXIT_CORRUPT,
+			_ => EXIT_IO,
+		};
+		Failure {
+			status,
+			message: format!("{}: {}: {}", action, path, e),
+		}
+	}
+}
+
+/// Modification time and permissions of `path`, to store in the header
+fn file_metadata(path: &Path) -> io::Result<huffman::Metadata> {
+	let metadata = fs::metadata(path)?;
+	let mtime = metadata
+		.modified()?
+		.duration_since(UNIX_EPOCH)
+		.ok()
+		.map(|mtime| mtime.as_secs());
+	#[cfg(unix)]
+	let mode = {
+		use std::os::unix::fs::PermissionsExt;
+		Some(metadata.permissions().mode() & 0o7777)
+	};
+	#[cfg(not(unix))]
+	let mode = None;
+	Ok(huffman::Metadata {
+		mtime,
+		mode,
+		name: None,
+	})
+}
+
+/// Parse a size like `65536`, `64K` or `16M`
+fn parse_size(size: &str) -> Result<usize, String> {
+	let (digits, unit) = match size.char_indices().last() {
+		Some((i, 'K')) | Some((i, 'k')) => (&size[..i], 1 << 10),
+		Some((i, 'M')) | Some((i, 'm')) => (&size[..i], 1 << 20),
+		Some((i, 'G')) | Some((i, 'g')) => (&size[..i], 1 << 30),
+		_ => (size, 1),
+	};
+	digits
+		.parse::<usize>()
+		.ok()
+		.and_then(|digits| digits.checked_mul(unit))
+		.filter(|&size| size != 0)
+		.ok_or_else(|| format!("Invalid size `{}`, expected e.g. 64K or 16M", size))
+}
+
+/// Parse a percentage like `10%` or `10`, from 1 to 100
+fn parse_percent(percent: &str) -> Result<u32, String> {
+	match percent.strip_suffix('%').unwrap_or(percent).parse::<u32>() {
+		Ok(percent @ 1..=100) => Ok(percent),
+		_ => Err(format!(
+			"Invalid percentage `{}`, expected 1% to 100%",
+			percent
+		)),
+	}
+}
+
+/// Parse a filter like `rle`, `delta` or `delta:2`
+fn parse_model(model: &str) -> Result<huffman::Model, String> {
+	match model {
+		"char" => Ok(huffman::Model::Char),
+		"word" => Ok(huffman::Model::Word),
+		"context" => Ok(huffman::Model::Context),
+		"digram" => Ok(huffman::Model::Digram),
+		"rice" => Ok(huffman::Model::Rice),
+		_ => Err(format!(
+			"Invalid model `{}`, expected char, word, context, digram or rice",
+			model
+		)),
+	}
+}
+
+fn parse_filter(filter: &str) -> Result<huffman::Filter, String> {
+	match filter.split_once(':') {
+		None if filter == "rle" => Ok(huffman::Filter::Rle),
+		None if filter == "delta" => Ok(huffman::Filter::Delta(1)),
+		Some(("delta", width)) => match width.parse::<u8>() {
+			Ok(width @ 1..=255) => Ok(huffman::Filter::Delta(width)),
+			_ => Err(format!(
+				"Invalid delta width `{}`, expected 1 to 255 bytes",
+				width
+			)),
+		},
+		_ => Err(format!(
+			"Invalid filter `{}`, expected rle, delta or delta:WIDTH",
+			filter
+		)),
+	}
+}
+
+/// File name of `path`, to store in the header
+fn file_name(path: &Path) -> Option<String> {
+	path.file_name()
+		.and_then(|name| name.to_str())
+		.map(|name| name.to_string())
+}
+
+/// Path to decompress `file` to when restoring the name stored in its header
+///
+/// The stored name is placed next to `file`, names containing directories are refused.
+fn restored_path(file: &Path, name: &str) -> Option<PathBuf> {
+	let mut components = Path::new(name).components();
+	match (components.next(), components.next()) {
+		(Some(Component::Normal(_)), None) => Some(file.with_file_name(name)),
+		_ => None,
+	}
+}
+
+/// Apply modification time and permissions read from a header to `path`
+fn restore_metadata(path: &Path, metadata: &huffman::Metadata) -> io::Result<()> {
+	if let Some(mtime) = metadata.mtime {
+		let file = fs::OpenOptions::new().write(true).open(path)?;
+		file.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))?;
+	}
+	// Permissions last, they may make the file read only
+	#[cfg(unix)]
+	{
+		if let Some(mode) = metadata.mode {
+			use std::os::unix::fs::PermissionsExt;
+			fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+		}
+	}
+	Ok(())
+}
+
+/// Files named by a `-c` argument, expanding wildcards unless a file has that very name
+fn input_files(file: &str) -> Result<Vec<String>, Failure> {
+	if !glob::is_pattern(file) || Path::new(file).exists() {
+		return Ok(vec![file.to_string()]);
+	}
+	let files: Vec<String> = glob::expand(file)
+		.map_err(|e| Failure::io("Error expanding pattern", file, e))?
+		.into_iter()
+		.filter(|path| path.is_file())
+		.filter_map(|path| path.to_str().map(|path| path.to_string()))
+		.collect();
+	if files.is_empty() {
+		return Err(Failure {
+			status: EXIT_IO,
+			message: format!("No files match: {}", file),
+		});
+	}
+	Ok(files)
+}
+
+/// Files to work on out of those found, see --include and --exclude
+struct Selection<'a> {
+	includes: Vec<&'a str>,
+	excludes: Vec<&'a str>,
+}
+
+impl<'a> Selection<'a> {
+	fn new(matches: &'a ArgMatches) -> Selection<'a> {
+		Selection {
+			includes: matches.values_of("include").into_iter().flatten().collect(),
+			excludes: matches.values_of("exclude").into_iter().flatten().collect(),
+		}
+	}
+
+	/// Whether to keep `file`, given as a path relative to the directory it was found in.
+	/// Patterns match the whole path or just the file name
+	fn selects(&self, file: &str) -> bool {
+		let name = Path::new(file)
+			.file_name()
+			.and_then(|name| name.to_str())
+			.unwrap_or(file);
+		let matching =
+			|pattern: &&str| glob::matches(pattern, file) || glob::matches(pattern, name);
+		(self.includes.is_empty() || self.includes.iter().any(matching))
+			&& !self.excludes.iter().any(matching)
+	}
+}
+
+/// Selected files below `dir`, for --recursive. Symlinks and compressed files are left out
+fn tree_files(dir: &str, selection: &Selection) -> Result<Vec<String>, Failure> {
+	let read_error = |e| Failure::io("Error reading directory", dir, e);
+	let mut files = Vec::new();
+	for (relative, path) in snapshot::list_files(Path::new(dir), false).map_err(read_error)? {
+		if relative.ends_with(".cmp") || !selection.selects(&relative) {
+			continue;
+		}
+		if snapshot::link_target(&path).map_err(read_error)?.is_none() {
+			files.push(path.display().to_string());
+		}
+	}
+	Ok(files)
+}
+
+/// A file to compress, with the output and settings a line of --files-from may give
+/// it over those of the command line
+struct Job<'a> {
+	file: String,
+	output: Option<String>,
+	algo: Option<String>,
+	options: huffman::CompressOptions<'a>,
+}
+
+impl<'a> Job<'a> {
+	/// Job for `file` as set by the command line and `presets` for its extension. Options
+	/// on the command line go before presets, and stored presets are left out where --algo
+	/// couldn't be given
+	fn new(
+		file: String,
+		matches: &ArgMatches,
+		options: &huffman::CompressOptions<'a>,
+		presets: &Presets,
+	) -> Job<'a> {
+		let mut job = Job {
+			file,
+			output: None,
+			algo: matches.value_of("algo").map(|algo| algo.to_string()),
+			options: options.clone(),
+		};
+		if job.algo.is_none() {
+			let settings = presets.for_path(&job.file);
+			let settings = Settings {
+				model: settings.model.filter(|_| !matches.is_present("model")),
+				filter: settings.filter.filter(|_| !matches.is_present("filter")),
+				block_size: settings
+					.block_size
+					.filter(|_| !matches.is_present("block-size")),
+				..settings
+			};
+			job.options = settings.apply(&job.options);
+			let conflict = ALGO_CONFLICTS.iter().any(|&name| matches.is_present(name));
+			if settings.method == Method::Stored && !conflict {
+				job.algo = Some("stored".to_string());
+			}
+		}
+		job
+	}
+
+	/// Output file, the input file plus `extension` unless set
+	fn output(&self, extension: &str) -> String {
+		self.output
+			.clone()
+			.unwrap_or_else(|| self.file.clone() + extension)
+	}
+}
+
+/// Jobs for the files listed in `list`, - for stdin, see `manifest`
+fn manifest_jobs<'a>(
+	list: &str,
+	matches: &ArgMatches,
+	options: &huffman::CompressOptions<'a>,
+	presets: &Presets,
+) -> Result<Vec<Job<'a>>, Failure> {
+	let text = if list == "-" {
+		let mut text = String::new();
+		io::stdin().read_to_string(&mut text).map(|_| text)
+	} else {
+		fs::read_to_string(list)
+	}
+	.map_err(|e| Failure::io("Error reading file", list, e))?;
+	let parse_error = |e: String| Failure::usage(format!("Error parsing file: {}: {}", list, e));
+	let mut jobs = Vec::new();
+	for entry in manifest::parse(&text).map_err(parse_error)? {
+		let error = |msg: String| parse_error(format!("Line {}: {}", entry.line, msg));
+		let mut job = Job::new(entry.input.clone(), matches, options, presets);
+		job.output = entry.output.clone();
+		if let Some(algo) = entry.get("algo") {
+			if !codec::NAMES.contains(&algo) && algo != "auto" {
+				return Err(error(format!(
+					"Invalid algo `{}`, expected one of {}, auto",
+					algo,
+					codec::NAMES.join(", ")
+				)));
+			}
+			let conflict = ALGO_CONFLICTS
+				.iter()
+				.find(|&&name| matches.is_present(name));
+			if let (true, Some(name)) = (algo != "huffman", conflict) {
+				return Err(error(format!(
+					"algo={} can't be used with --{}",
+					algo, name
+				)));
+			}
+			job.options.model = match job.options.model {
+				_ if algo == "auto" => huffman::Model::Auto,
+				huffman::Model::Auto => huffman::Model::Char,
+				model => model,
+			};
+			job.algo = Some(algo.to_string());
+		}
+		for (key, value) in &entry.settings {
+			match key.as_str() {
+				"algo" => continue,
+				_ if job.algo.as_deref().is_some_and(|algo| algo != "huffman") => {
+					return Err(error(format!(
+						"{} needs algo huffman, not {}",
+						key,
+						job.algo.as_deref().unwrap()
+					)))
+				}
+				"model" | "filter" if options.dictionary.is_some() => {
+					return Err(error(format!(
+						"{} can't be used with a dictionary or code table",
+						key
+					)))
+				}
+				"model" => job.options.model = parse_model(value).map_err(error)?,
+				"filter" => job.options.filter = parse_filter(value).map_err(error)?,
+				_ => job.options.block_size = Some(parse_size(value).map_err(error)?),
+			}
+		}
+		jobs.push(job);
+	}
+	Ok(jobs)
+}
+
+/// Totals over the files compressed by a run, for the summary after them
+#[derive(Default)]
+struct Summary {
+	/// Files finished, failed or not
+	done: usize,
+	skipped: usize,
+	/// Sizes of the files compressed and kept
+	original_size: u64,
+	compressed_size: u64,
+	failures: Vec<Failure>,
+}
+
+/// Result of processing a file, reported with --verbose or --json
+struct Outcome {
+	/// File written, if any
+	output: Option<String>,
+	/// Output was removed again for compressing too little, see --min-ratio
+	skipped: bool,
+	original_size: u64,
+	compressed_size: u64,
+	/// CRC-32 of the original data
+	checksum: u32,
+	/// Time spent in every stage, when compressing to the huf format
+	timings: Option<huffman::Timings>,
+}
+
+/// Report `result` of `action` on `file`, which started at `start`
+///
+/// With --json as a line on stdout, otherwise failures on stderr and sizes in verbose mode
+fn report(
+	matches: &ArgMatches,
+	action: &str,
+	file: &str,
+	result: &Result<Outcome, Failure>,
+	start: Instant,
+) {
+	let seconds = start.elapsed().as_secs_f64();
+	let ratio =
+		|outcome: &Outcome| outcome.compressed_size as f64 / outcome.original_size.max(1) as f64;
+	if matches.is_present("json") {
+		let mut fields = vec![
+			format!("\"action\": \"{}\"", action),
+			format!("\"input\": \"{}\"", json::escape(file)),
+		];
+		match result {
+			Ok(outcome) => {
+				if let Some(output) = &outcome.output {
+					fields.push(format!("\"output\": \"{}\"", json::escape(output)));
+				}
+				fields.push(format!("\"original_size\": {}", outcome.original_size));
+				fields.push(format!("\"compressed_size\": {}", outcome.compressed_size));
+				fields.push(format!("\"ratio\": {:.4}", ratio(outcome)));
+				fields.push(format!("\"checksum\": \"{:08x}\"", outcome.checksum));
+				if outcome.skipped {
+					fields.push("\"skipped\": true".to_string());
+				}
+				if let (Some(timings), true) = (&outcome.timings, matches.is_present("timings")) {
+					let stages: Vec<String> = stages(timings)
+						.iter()
+						.map(|(stage, duration)| {
+							format!("\"{}\": {:.6}", stage, duration.as_secs_f64())
+						})
+						.collect();
+					fields.push(format!("\"timings\": {{{}}}", stages.join(", ")));
+				}
+			}
+			Err(failure) => {
+				fields.push(format!("\"error\": \"{}\"", json::escape(&failure.message)));
+				fields.push(format!("\"status\": {}", failure.status));
+			}
+		}
+		fields.push(format!("\"duration\": {:.6}", seconds));
+		println!("{{{}}}", fields.join(", "));
+		return;
+	}
+	if let (
+		Ok(Outcome {
+			timings: Some(timings),
+			..
+		}),
+		true,
+	) = (result, matches.is_present("timings"))
+	{
+		eprintln!("{}: time per stage", file);
+		for (stage, duration) in stages(timings) {
+			let seconds = duration.as_secs_f64();
+			eprintln!(
+				"  {:<9} {:>9.4} s {:>10.2} MB/s",
+				stage,
+				seconds,
+				timings.bytes as f64 / 1e6 / seconds.max(1e-9)
+			);
+		}
+	}
+	match result {
+		Ok(outcome) => logging::log(
+			Level::Info,
+			format_args!(
+				"{}: {} -> {} bytes, ratio {:.4}, {:.3} s",
+				file,
+				outcome.original_size,
+				outcome.compressed_size,
+				ratio(outcome),
+				seconds
+			),
+		),
+		Err(failure) => eprintln!("{}", failure.message),
+	}
+}
+
+/// Warn if compressing `outcome` made the file grow, and remove the output
+/// if it isn't at least `min_ratio` times smaller than the input
+fn check_ratio(
+	file: &str,
+	mut outcome: Outcome,
+	min_ratio: Option<f64>,
+) -> Result<Outcome, Failure> {
+	let ratio = outcome.original_size as f64 / outcome.compressed_size as f64;
+	match (outcome.output.take(), min_ratio) {
+		(Some(output_file), Some(min_ratio)) if ratio < min_ratio => {
+			remove_output(&output_file)
+				.map_err(|e| Failure::io("Error removing file", &output_file, e))?;
+			logging::log(
+				Level::Info,
+				format_args!(
+					"{}: skipped, compressed only {:.3} times smaller, less than {}",
+					file, ratio, min_ratio
+				),
+			);
+			outcome.skipped = true;
+		}
+		(output, _) => {
+			if outcome.compressed_size > outcome.original_size {
+				logging::log(
+					Level::Warn,
+					format_args!(
+						"{}: output is larger than the input, {} > {} bytes",
+						file, outcome.compressed_size, outcome.original_size
+					),
+				);
+			}
+			outcome.output = output;
+		}
+	}
+	Ok(outcome)
+}
+
+/// Stages of compression with their names, in order
+fn stages(timings: &huffman::Timings) -> [(&'static str, Duration); 5] {
+	[
+		("reading", timings.reading),
+		("counting", timings.counting),
+		("tree", timings.tree),
+		("encoding", timings.encoding),
+		("writing", timings.writing),
+	]
+}
+
+/// Counts the bytes read through it and their CRC-32
+struct Tally<R: Read> {
+	inner: R,
+	bytes: u64,
+	crc: Crc32,
+}
+
+impl<R: Read> Read for Tally<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = self.inner.read(buf)?;
+		self.bytes += len as u64;
+		self.crc.update(&buf[..len]);
+		Ok(len)
+	}
+}
+
+/// Options for compressing with the model, filter and limits given on the command line
+fn compress_options<'a>(
+	matches: &ArgMatches,
+	dictionary: Option<&'a huffman::Dictionary>,
+) -> huffman::CompressOptions<'a> {
+	let model = match matches.value_of("model") {
+		_ if matches.value_of("algo") == Some("auto") => huffman::Model::Auto,
+		Some(model) => parse_model(model).unwrap(),
+		None => huffman::Model::Char,
+	};
+	let filter = matches
+		.value_of("filter")
+		.map_or(huffman::Filter::None, |filter| {
+			parse_filter(filter).unwrap()
+		});
+	huffman::CompressOptions {
+		dictionary,
+		model,
+		filter,
+		block_size: matches
+			.value_of("block-size")
+			.map(|size| parse_size(size).unwrap()),
+		max_code_length: matches
+			.value_of("max-code-length")
+			.map(|bits| bits.parse().unwrap()),
+		adaptive_blocks: matches.is_present("adaptive-blocks"),
+		shannon_fano: matches.is_present("shannon-fano"),
+		metadata: huffman::Metadata::default(),
+	}
+}
+
+/// Compress the file of `job` as set by it and the command line, returning the name of
+/// the output file
+fn compress_file(job: &Job, matches: &ArgMatches) -> Result<Outcome, Failure> {
+	let (file, options) = (job.file.as_str(), &job.options);
+	let read_error = |e| Failure::io("Error reading file", file, e);
+	let mut metadata = if matches.is_present("no-preserve") {
+		huffman::Metadata::default()
+	} else {
+		file_metadata(Path::new(file)).map_err(read_error)?
+	};
+	if !matches.is_present("no-name") {
+		metadata.name = file_name(Path::new(file));
+	}
+	let force = matches.is_present("force-compress");
+	let sample = read_sample(file).map_err(read_error)?;
+	if !force && is_compressed_file(file, &sample) {
+		return Err(Failure::usage(format!(
+			"{}: already compressed, --force-compress compresses it again",
+			file
+		)));
+	}
+	let rate = bwlimit(matches);
+	if matches.value_of("format") == Some("gzip") {
+		let data = read_throttled(file, rate).map_err(read_error)?;
+		let output_file = job.output(".gz");
+		let compressed_data = gzip::compress(&data, &metadata);
+		write_throttled(&output_file, &compressed_data, rate)
+			.map_err(|e| Failure::io("Error writing file", &output_file, e))?;
+		return Ok(Outcome {
+			output: Some(output_file),
+			skipped: false,
+			original_size: data.len() as u64,
+			compressed_size: compressed_data.len() as u64,
+			checksum: checksum::crc32(&data),
+			timings: None,
+		});
+	}
+	let mut algo = job.algo.as_deref().filter(|&algo| algo != "huffman");
+	let probe = !force
+		&& job.algo.is_none()
+		&& !matches.is_present("volume-size")
+		&& !matches.is_present("resume");
+	if probe && analysis::looks_compressed(&sample) {
+		logging::log(
+			Level::Info,
+			format_args!("{}: looks compressed already, storing it as is", file),
+		);
+		algo = Some("stored");
+	}
+	if let Some(codec) = algo.and_then(codec::by_name) {
+		let data = read_throttled(file, rate).map_err(read_error)?;
+		let output_file = job.output(".cmp");
+		let mut compressed_data = Vec::new();
+		codec
+			.encode(&data, &mut compressed_data)
+			.map_err(|e| Failure::io("Error compressing file", file, e))?;
+		write_throttled(&output_file, &compressed_data, rate)
+			.map_err(|e| Failure::io("Error writing file", &output_file, e))?;
+		return Ok(Outcome {
+			output: Some(output_file),
+			skipped: false,
+			original_size: data.len() as u64,
+			compressed_size: compressed_data.len() as u64,
+			checksum: checksum::crc32(&data),
+			timings: None,
+		});
+	}
+	if let Some(table_file) = matches.value_of("dump-table") {
+		let data = fs::read_to_string(file).map_err(read_error)?;
+		let table = huffman::CodeTable::for_text(&data)
+			.ok_or_else(|| Failure::usage(format!("No symbols in empty file: {}", file)))?;
+		atomic::write(table_file, table.to_json().as_bytes())
+			.map_err(|e| Failure::io("Error writing file", table_file, e))?;
+	}
+	let input = fs::File::open(file).map_err(read_error)?;
+	let output_file = job.output(".cmp");
+	let write_error = |e| Failure::io("Error writing file", &output_file, e);
+	let mut input = Tally {
+		inner: io::BufReader::new(Throttle::new(input, rate)),
+		bytes: 0,
+		crc: Crc32::new(),
+	};
+	if let Some(volume_size) = matches.value_of("volume-size") {
+		let options = huffman::CompressOptions {
+			metadata,
+			..options.clone()
+		};
+		let volume_size = parse_size(volume_size).unwrap() as u64;
+		return compress_to_volumes(
+			file,
+			&output_file,
+			&mut input,
+			&options,
+			volume_size,
+			threads(matches),
+			rate,
+		);
+	}
+	// With --resume, output is written in place and kept on failure, for a later --resume
+	let resume = matches.is_present("resume") && Path::new(&output_file).exists();
+	let in_place = if resume {
+		let mut output = fs::OpenOptions::new()
+			.read(true)
+			.write(true)
+			.open(&output_file)
+			.map_err(write_error)?;
+		let point = huffman::resume_point(io::BufReader::new(&output), options.dictionary)
+			.map_err(|e| Failure::io("Error reading file", &output_file, e))?;
+		io::copy(&mut (&mut input).take(point.input_len), &mut io::sink()).map_err(read_error)?;
+		if input.bytes != point.input_len || input.crc.value() != point.checksum {
+			return Err(Failure::corrupt(format!(
+				"{}: Partial output doesn't match the input, remove it to start over",
+				output_file
+			)));
+		}
+		logging::log(
+			Level::Info,
+			format_args!(
+				"{}: resuming after {} bytes, {} already compressed",
+				file, point.output_len, point.input_len
+			),
+		);
+		output.set_len(point.output_len).map_err(write_error)?;
+		output.seek(io::SeekFrom::End(0)).map_err(write_error)?;
+		if point.output_len > 0 {
+			metadata = huffman::Metadata::default();
+		}
+		Some(output)
+	} else if matches.is_present("resume") {
+		Some(fs::File::create(&output_file).map_err(write_error)?)
+	} else {
+		None
+	};
+	let options = huffman::CompressOptions {
+		metadata,
+		..options.clone()
+	};
+	let mut timings = huffman::Timings::default();
+	let threads = threads(matches);
+	let mut compress = |output: &mut dyn Write| {
+		let output = io::BufWriter::new(Throttle::new(output, rate));
+		if threads == 1 {
+			huffman::compress_stream_timed(&mut input, output, &options, &mut timings)
+		} else {
+			huffman::compress_stream_pipelined(&mut input, output, &options, threads, &mut timings)
+		}
+		.and_then(|output| output.into_inner().map(|_| ()).map_err(|e| e.into_error()))
+	};
+	let result = match in_place {
+		Some(mut output) => compress(&mut output).and_then(|()| output.metadata()),
+		None => {
+			let mut output = AtomicFile::create(&output_file).map_err(write_error)?;
+			compress(&mut output)
+				.and_then(|()| output.commit())
+				.and_then(|output| output.metadata())
+		}
+	};
+	match result {
+		Ok(metadata) => Ok(Outcome {
+			output: Some(output_file),
+			skipped: false,
+			original_size: input.bytes,
+			compressed_size: metadata.len(),
+			checksum: input.crc.value(),
+			timings: Some(timings),
+		}),
+		Err(e) => Err(Failure::io("Error compressing file", file, e)),
+	}
+}
+
+/// Recompress the gzip or zlib file of `job`, decompressing it on the fly, for --from.
+/// Unless `job` names it, the output is named like `file` without `.gz`, `.z` or `.zz`,
+/// plus `.cmp`. The name and modification time in a gzip header are kept
+fn transcode_file(
+	job: &Job,
+	matches: &ArgMatches,
+	format: inflate::Format,
+) -> Result<Outcome, Failure> {
+	let (file, options) = (job.file.as_str(), &job.options);
+	let read_error = |e| Failure::io("Error reading file", file, e);
+	let rate = bwlimit(matches);
+	let input = Throttle::new(fs::File::open(file).map_err(read_error)?, rate);
+	let reader = inflate::Reader::new(io::BufReader::new(input), format).map_err(read_error)?;
+	let stem = match file.strip_suffix(".tgz") {
+		Some(stem) => stem.to_string() + ".tar",
+		None => [".gz", ".zz", ".z"]
+			.iter()
+			.find_map(|suffix| file.strip_suffix(suffix))
+			.filter(|stem| !stem.is_empty())
+			.unwrap_or(file)
+			.to_string(),
+	};
+	let output_file = job.output.clone().unwrap_or_else(|| stem.clone() + ".cmp");
+	let header = reader.header().clone();
+	let mut metadata = if matches.is_present("no-preserve") {
+		huffman::Metadata::default()
+	} else {
+		let metadata = file_metadata(Path::new(file)).map_err(read_error)?;
+		huffman::Metadata {
+			mtime: header.mtime.or(metadata.mtime),
+			..metadata
+		}
+	};
+	if !matches.is_present("no-name") {
+		metadata.name = header.name.or_else(|| file_name(Path::new(&stem)));
+	}
+	let options = huffman::CompressOptions {
+		metadata,
+		..options.clone()
+	};
+	let mut input = Tally {
+		inner: reader,
+		bytes: 0,
+		crc: Crc32::new(),
+	};
+	let mut timings = huffman::Timings::default();
+	let threads = threads(matches);
+	let result = AtomicFile::create(&output_file).and_then(|mut output| {
+		let writer = io::BufWriter::new(Throttle::new(&mut output, rate));
+		if threads == 1 {
+			huffman::compress_stream_timed(&mut input, writer, &options, &mut timings)
+		} else {
+			huffman::compress_stream_pipelined(&mut input, writer, &options, threads, &mut timings)
+		}
+		.and_then(|writer| writer.into_inner().map(|_| ()).map_err(|e| e.into_error()))?;
+		output.commit()?.metadata()
+	});
+	match result {
+		Ok(metadata) => Ok(Outcome {
+			output: Some(output_file),
+			skipped: false,
+			original_size: input.bytes,
+			compressed_size: metadata.len(),
+			checksum: input.crc.value(),
+			timings: Some(timings),
+		}),
+		Err(e) => Err(Failure::io("Error compressing file", file, e)),
+	}
+}
+
+/// Decompress `file` as set by the command line
+fn decompress_file(
+	file: &str,
+	matches: &ArgMatches,
+	dictionary: Option<&huffman::Dictionary>,
+) -> Result<Outcome, Failure> {
+	let rate = bwlimit(matches);
+	let (data, file) = read_compressed(file, rate)?;
+	let corrupt = |e: huffman::DecompressError| {
+		Failure::corrupt(format!("Error decompressing file: {}: {}", file, e))
+	};
+	let mut lost = Vec::new();
+	let (decompressed_data, metadata) = match other_codec(&data) {
+		None if matches.is_present("recover") => {
+			let marker = matches.value_of("marker").map(str::as_bytes);
+			let (recovered, skipped) =
+				huffman::recover(&data, dictionary, marker).map_err(corrupt)?;
+			lost = skipped;
+			let metadata = huffman::read_metadata(&data).unwrap_or_default();
+			(recovered, metadata)
+		}
+		Some(codec) => {
+			let mut decompressed_data = Vec::new();
+			codec
+				.decode(&mut &data[..], &mut decompressed_data)
+				.map_err(|e| Failure::io("Error decompressing file", file, e))?;
+			(decompressed_data, huffman::Metadata::default())
+		}
+		None => {
+			let decompressed_data =
+				huffman::decompress_bytes(&data, dictionary).map_err(corrupt)?;
+			if matches.is_present("self-check") {
+				match huffman::check_model(&data).map_err(corrupt)? {
+					Some(check) => println!("{}: {}", file, check),
+					None => println!("{}: not checked, no embedded tree", file),
+				}
+			}
+			let metadata = huffman::read_metadata(&data).map_err(corrupt)?;
+			(decompressed_data, metadata)
+		}
+	};
+	let output_file = match (matches.is_present("name"), &metadata.name) {
+		(true, Some(name)) => restored_path(Path::new(file), name)
+			.and_then(|path| path.to_str().map(|path| path.to_string()))
+			.ok_or_else(|| {
+				Failure::corrupt(format!("Invalid file name in header: {}: {}", file, name))
+			})?,
+		_ => file
+			.strip_suffix(".cmp")
+			.filter(|name| !name.is_empty())
+			.ok_or_else(|| {
+				Failure::usage(format!(
+					"Expected a name ending in .cmp, or -N to use the stored name: {}",
+					file
+				))
+			})?
+			.to_string(),
+	};
+	let write_error = |e| Failure::io("Error writing file", &output_file, e);
+	write_throttled(&output_file, &decompressed_data, rate).map_err(write_error)?;
+	if !matches.is_present("no-preserve") {
+		restore_metadata(Path::new(&output_file), &metadata).map_err(write_error)?;
+	}
+	if !lost.is_empty() {
+		let ranges: Vec<String> = lost
+			.iter()
+			.map(|lost| {
+				let text = match lost.length {
+					Some(length) => {
+						format!("bytes {} to {}", lost.offset, lost.offset as u64 + length)
+					}
+					None => format!("an unknown number of bytes at {}", lost.offset),
+				};
+				format!(
+					"\n  {} lost, compressed bytes {} to {}: {}",
+					text, lost.compressed.start, lost.compressed.end, lost.error
+				)
+			})
+			.collect();
+		return Err(Failure::corrupt(format!(
+			"{}: damaged, wrote what could be recovered to {}{}",
+			file,
+			output_file,
+			ranges.concat()
+		)));
+	}
+	Ok(Outcome {
+		output: Some(output_file),
+		skipped: false,
+		original_size: decompressed_data.len() as u64,
+		compressed_size: data.len() as u64,
+		checksum: checksum::crc32(&decompressed_data),
+		timings: None,
+	})
+}
+
+/// Print the first `limit` bytes of the text of `file`, for --bytes.
+/// Only the members holding them are decompressed
+fn preview_file(
+	file: &str,
+	limit: u64,
+	dictionary: Option<&huffman::Dictionary>,
+) -> Result<Outcome, Failure> {
+	let (data, file) = read_compressed(file, None)?;
+	let corrupt = |e| Failure::io("Error decompressing file", file, e);
+	let mut head = Vec::new();
+	match other_codec(&data) {
+		Some(codec) => {
+			codec.decode(&mut &data[..], &mut head).map_err(corrupt)?;
+			head.truncate(limit.min(head.len() as u64) as usize);
+		}
+		None => {
+			huffman::Decompressor::new(&data[..], dictionary)
+				.take(limit)
+				.read_to_end(&mut head)
+				.map_err(corrupt)?;
+		}
+	}
+	io::stdout()
+		.write_all(&head)
+		.map_err(|e| Failure::io("Error writing", "stdout", e))?;
+	Ok(Outcome {
+		output: None,
+		skipped: false,
+		original_size: head.len() as u64,
+		compressed_size: data.len() as u64,
+		checksum: checksum::crc32(&head),
+		timings: None,
+	})
+}
+
+/// Input read by filters at a time
+const FILTER_CHUNK: usize = 64 << 10;
+
+/// Read what stdin has, up to the size of `buffer`, 0 at its end
+fn read_stdin(input: &mut impl Read, buffer: &mut [u8]) -> Result<usize, Failure> {
+	loop {
+		match input.read(buffer) {
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			result => return result.map_err(|e| Failure::io("Error reading", "stdin", e)),
+		}
+	}
+}
+
+/// Write `data` to stdout right away. If the reader has gone, e.g. `| head`, there's
+/// nobody left to tell, so exit quietly like other filters
+fn write_stdout(output: &mut impl Write, data: &[u8]) -> Result<(), Failure> {
+	match output.write_all(data).and_then(|()| output.flush()) {
+		Err(e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+		result => result.map_err(|e| Failure::io("Error writing", "stdout", e)),
+	}
+}
+
+/// Compress stdin to stdout a member at a time, for `-c -`, decompressing it first with
+/// --from
+fn compress_filter(
+	options: &huffman::CompressOptions,
+	from: Option<inflate::Format>,
+) -> Result<(), Failure> {
+	let (stdin, mut output) = (io::stdin().lock(), io::stdout().lock());
+	let mut input: Box<dyn Read> = match from {
+		Some(format) => Box::new(
+			inflate::Reader::new(stdin, format)
+				.map_err(|e| Failure::io("Error reading", "stdin", e))?,
+		),
+		None => Box::new(stdin),
+	};
+	let compress_error = |e| Failure::io("Error compressing", "stdin", e);
+	let mut encoder = huffman::Encoder::new(options.clone());
+	let mut buffer = vec![0; FILTER_CHUNK];
+	loop {
+		let len = read_stdin(&mut input, &mut buffer)?;
+		if len == 0 {
+			break;
+		}
+		write_stdout(
+			&mut output,
+			&encoder.push(&buffer[..len]).map_err(compress_error)?,
+		)?;
+	}
+	write_stdout(&mut output, &encoder.finish().map_err(compress_error)?)
+}
+
+/// Decompress stdin to stdout, every member as soon as it has arrived, for `-d -`.
+/// Other codecs than huffman need all their input first
+fn decompress_filter(dictionary: Option<&huffman::Dictionary>) -> Result<(), Failure> {
+	let (mut input, mut output) = (io::stdin().lock(), io::stdout().lock());
+	let corrupt = |e: huffman::DecompressError| {
+		Failure::corrupt(format!("Error decompressing: stdin: {}", e))
+	};
+	let mut decoder = huffman::Decoder::new(dictionary);
+	let mut buffer = vec![0; FILTER_CHUNK];
+	let mut first = true;
+	loop {
+		let len = read_stdin(&mut input, &mut buffer)?;
+		if len == 0 {
+			break;
+		}
+		if let Some(codec) = other_codec(&buffer[..len]).filter(|_| first) {
+			let mut data = buffer[..len].to_vec();
+			input
+				.read_to_end(&mut data)
+				.map_err(|e| Failure::io("Error reading", "stdin", e))?;
+			let mut decompressed = Vec::new();
+			codec
+				.decode(&mut &data[..], &mut decompressed)
+				.map_err(|e| Failure::io("Error decompressing", "stdin", e))?;
+			return write_stdout(&mut output, &decompressed);
+		}
+		first = false;
+		write_stdout(&mut output, &decoder.push(&buffer[..len]).map_err(corrupt)?)?;
+	}
+	write_stdout(&mut output, &decoder.finish().map_err(corrupt)?)
+}
+
+/// Options only huffman coding takes, or that only work with it, see --algo
+const ALGO_CONFLICTS: &[&str] = &[
+	"dict",
+	"table",
+	"static",
+	"dump-table",
+	"model",
+	"filter",
+	"block-size",
+	"adaptive-blocks",
+	"shannon-fano",
+	"max-code-length",
+	"timings",
+	"format",
+	"resume",
+	"volume-size",
+	"archive",
+	"from",
+];
+
+/// Options that need files of their own, which filters don't have
+const FILE_OPTIONS: &[&str] = &[
+	"archive",
+	"volume-size",
+	"resume",
+	"parity",
+	"verify",
+	"min-ratio",
+	"format",
+	"bytes",
+	"recover",
+	"name",
+	"self-check",
+	"bwlimit",
+	"files-from",
+];
+
+/// Check that `-` for stdin comes alone and without options that need files
+fn check_filter(files: &[&str], matches: &ArgMatches) -> Result<(), Failure> {
+	if files.len() > 1 {
+		return Err(Failure::usage(
+			"- reads stdin and writes stdout, and takes no other files".to_string(),
+		));
+	}
+	let algo = matches.value_of("algo");
+	match FILE_OPTIONS.iter().find(|&&name| matches.is_present(name)) {
+		Some(name) => Err(Failure::usage(format!("--{} needs files, not stdin", name))),
+		None if algo.is_some_and(|algo| algo != "huffman" && algo != "auto") => Err(
+			Failure::usage("Only huffman compresses stdin, see --algo".to_string()),
+		),
+		None => Ok(()),
+	}
+}
+
+/// Codec other than huffman that `data` was compressed with, see --algo
+fn other_codec(data: &[u8]) -> Option<Box<dyn codec::Codec>> {
+	codec::detect(data).filter(|codec| codec.name() != "huffman")
+}
+
+/// Path to store `file` under in an archive, relative and using `/` as separator,
+/// empty for the current directory
+fn archive_path(file: &str) -> Option<String> {
+	let mut parts = Vec::new();
+	for component in Path::new(file).components() {
+		match component {
+			Component::Normal(part) => parts.push(part.to_str()?),
+			Component::ParentDir => return None,
+			_ => {}
+		}
+	}
+	Some(parts.join("/"))
+}
+
+/// Add the file or symlink `file` to `archive` under `path`
+fn archive_entry<W: Write>(
+	archive: &mut ArchiveWriter<W>,
+	archive_file: &str,
+	file: &str,
+	path: &str,
+	dereference: bool,
+	settings: &Settings,
+) -> Result<Outcome, Failure> {
+	let read_error = |e| Failure::io("Error reading file", file, e);
+	let write_error = |e| Failure::io("Error writing archive", archive_file, e);
+	let position = archive.position();
+	let link = if dereference {
+		None
+	} else {
+		snapshot::link_target(Path::new(file)).map_err(read_error)?
+	};
+	let contents = match link {
+		Some(target) => {
+			let mtime = fs::symlink_metadata(file)
+				.and_then(|metadata| metadata.modified())
+				.map_err(read_error)?
+				.duration_since(UNIX_EPOCH)
+				.ok()
+				.map(|mtime| mtime.as_secs());
+			archive
+				.add_symlink(path, &target, mtime)
+				.map_err(write_error)?;
+			target.into_bytes()
+		}
+		None => {
+			let contents = fs::read(file).map_err(read_error)?;
+			let mtime = file_metadata(Path::new(file)).map_err(read_error)?.mtime;
+			let method = match std::str::from_utf8(&contents) {
+				Ok(_) => settings.method,
+				Err(_) => Method::Stored,
+			};
+			let options = settings.apply(&huffman::CompressOptions::default());
+			archive
+				.add_file_with_options(path, &contents, method, &options, mtime)
+				.map_err(write_error)?;
+			contents
+		}
+	};
+	Ok(Outcome {
+		output: Some(archive_file.to_string()),
+		skipped: false,
+		original_size: contents.len() as u64,
+		compressed_size: archive.position() - position,
+		checksum: checksum::crc32(&contents),
+		timings: None,
+	})
+}
+
+/// Store `files` in the archive `archive_file`, after the entries already in it with `--append`
+///
+/// Directories are stored with everything below them. Symlinks are stored as links
+/// unless `--dereference` stores what they point to. Every file is reported on its own,
+/// failed ones are left out of the archive. Returns the first failure.
+fn archive_files(
+	archive_file: &str,
+	files: &[String],
+	matches: &ArgMatches,
+	presets: &Presets,
+) -> Result<(), Failure> {
+	let write_error = |e| Failure::io("Error writing archive", archive_file, e);
+	let failures = if matches.is_present("append") && Path::new(archive_file).exists() {
+		let mut file = fs::OpenOptions::new()
+			.read(true)
+			.write(true)
+			.open(archive_file)
+			.map_err(write_error)?;
+		// Appending rewrites the magic bytes and the index, put back if interrupted
+		let index_offset = Archive::new(&mut file)
+			.map(|archive| archive.index_offset())
+			.map_err(write_error)?;
+		let _pending = atomic::Pending::restore(
+			archive_file,
+			&[0..archive::MAGIC.len() as u64, index_offset..u64::MAX],
+		)
+		.map_err(write_error)?;
+		let mut archive = ArchiveWriter::append(file).map_err(write_error)?;
+		let failures = add_to_archive(&mut archive, archive_file, files, matches, presets);
+		// Write the index even after failures, an archive being appended to stays readable
+		archive.finish().map_err(write_error)?;
+		failures
+	} else {
+		let mut archive = AtomicFile::create(archive_file)
+			.and_then(ArchiveWriter::new)
+			.map_err(write_error)?;
+		let failures = add_to_archive(&mut archive, archive_file, files, matches, presets);
+		archive
+			.finish()
+			.and_then(AtomicFile::commit)
+			.map_err(write_error)?;
+		failures
+	};
+	match failures.into_iter().next() {
+		Some(failure) => Err(failure),
+		None => Ok(()),
+	}
+}
+
+/// Add `files` to `archive`, returning the failures, already reported
+fn add_to_archive<W: Write>(
+	archive: &mut ArchiveWriter<W>,
+	archive_file: &str,
+	files: &[String],
+	matches: &ArgMatches,
+	presets: &Presets,
+) -> Vec<Failure> {
+	archive.set_dedup(matches.is_present("dedup"));
+	let dereference = matches.is_present("dereference");
+	let selection = Selection::new(matches);
+	let mut failures = Vec::new();
+	for file in files {
+		let start = Instant::now();
+		let metadata = if dereference {
+			fs::metadata(file)
+		} else {
+			fs::symlink_metadata(file)
+		};
+		let is_dir = metadata.is_ok_and(|metadata| metadata.is_dir());
+		// Only a directory can stand for the root of the archive
+		let path = match archive_path(file).filter(|path| is_dir || !path.is_empty()) {
+			Some(path) => path,
+			None => {
+				let failure = Err(Failure::usage(format!(
+					"Can't store a path leaving the directory: {}",
+					file
+				)));
+				report(matches, "archive", file, &failure, start);
+				failures.extend(failure.err());
+				continue;
+			}
+		};
+		let members = if is_dir {
+			snapshot::list_files(Path::new(file), dereference).map(|files| {
+				files
+					.into_iter()
+					.filter(|(relative, _)| selection.selects(relative))
+					.map(|(relative, member)| {
+						let path = match path.as_str() {
+							"" => relative,
+							_ => path.clone() + "/" + &relative,
+						};
+						(member.display().to_string(), path)
+					})
+					.collect()
+			})
+		} else {
+			Ok(vec![(file.clone(), path)])
+		};
+		let members = match members {
+			Ok(members) => members,
+			Err(e) => {
+				let failure = Err(Failure::io("Error reading directory", file, e));
+				report(matches, "archive", file, &failure, start);
+				failures.extend(failure.err());
+				continue;
+			}
+		};
+		for (member, path) in members {
+			let start = Instant::now();
+			let settings = presets.for_path(&path);
+			let result = archive_entry(
+				archive,
+				archive_file,
+				&member,
+				&path,
+				dereference,
+				&settings,
+			);
+			report(matches, "archive", &member, &result, start);
+			failures.extend(result.err());
+		}
+	}
+	failures
+}
+
+/// Extract the entry `path` of `archive` to the same path below the current directory,
+/// reading only its member
+fn extract_entry<R: Read + Seek>(
+	archive: &mut Archive<R>,
+	archive_file: &str,
+	path: &str,
+) -> Result<Outcome, Failure> {
+	let entry = archive_path(path)
+		.and_then(|path| archive.find(&path))
+		.cloned()
+		.ok_or_else(|| Failure::usage(format!("No entry {} in {}", path, archive_file)))?;
+	let output_file = snapshot::resolve(Path::new("."), &entry.path)
+		.map_err(|e| Failure::io("Error reading archive", archive_file, e))?;
+	let contents = snapshot::extract(archive, &entry, &output_file).map_err(|e| {
+		Failure::io(
+			"Error extracting",
+			&format!("{}: {}", archive_file, entry.path),
+			e,
+		)
+	})?;
+	Ok(Outcome {
+		output: Some(entry.path.clone()),
+		skipped: false,
+		original_size: contents.len() as u64,
+		compressed_size: entry.length,
+		checksum: checksum::crc32(&contents),
+		timings: None,
+	})
+}
+
+/// `secs` since the epoch as a UTC date and time, `YYYY-MM-DD HH:MM`
+fn format_time(secs: u64) -> String {
+	// Civil date of a day count, after Howard Hinnant's `civil_from_days`
+	let days = secs / 86400 + 719468;
+	let (era, day_of_era) = (days / 146097, days % 146097);
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let shifted_month = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+	let month = if shifted_month < 10 {
+		shifted_month + 3
+	} else {
+		shifted_month - 9
+	};
+	let year = era * 400 + year_of_era + (month <= 2) as u64;
+	format!(
+		"{:04}-{:02}-{:02} {:02}:{:02}",
+		year,
+		month,
+		day,
+		secs % 86400 / 3600,
+		secs % 3600 / 60
+	)
+}
+
+/// Serve `archive_file` as a read-only file system on `mountpoint` until it is unmounted
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+fn mount_archive(archive_file: &str, mountpoint: &str) -> Result<(), Failure> {
+	let archive = fs::File::open(archive_file)
+		.and_then(Archive::new)
+		.map_err(|e| Failure::io("Error reading archive", archive_file, e))?;
+	logging::log(
+		Level::Info,
+		format_args!(
+			"Serving {} on {}, unmount it with fusermount -u {} to stop",
+			archive_file, mountpoint, mountpoint
+		),
+	);
+	mount::mount(archive, Path::new(mountpoint), archive_file)
+		.map_err(|e| Failure::io("Error mounting", mountpoint, e))
+}
+
+#[cfg(not(all(feature = "fuse", target_os = "linux")))]
+fn mount_archive(_: &str, _: &str) -> Result<(), Failure> {
+	Err(Failure::usage(
+		"mount needs a build with the fuse feature, on Linux".to_string(),
+	))
+}
+
+/// Print the entries of `archive_file` as a table, or a line of JSON each with `--json`
+fn list_archive(archive_file: &str, matches: &ArgMatches) -> Result<(), Failure> {
+	let read_error = |e| Failure::io("Error reading archive", archive_file, e);
+	let mut archive = fs::File::open(archive_file)
+		.and_then(Archive::new)
+		.map_err(read_error)?;
+	if !matches.is_present("json") {
+		println!(
+			"{:>10} {:>10} {:>6}  {:<16}  path",
+			"size", "compressed", "ratio", "modified"
+		);
+	}
+	for entry in archive.entries().to_vec() {
+		let ratio = entry.length as f64 / entry.size.max(1) as f64;
+		let method = match entry.method {
+			Method::Huffman => "huffman",
+			Method::Stored => "stored",
+			Method::Chunked => "chunked",
+		};
+		match (entry.kind, matches.is_present("json")) {
+			(EntryKind::File, true) => println!(
+				"{{\"path\": \"{}\", \"method\": \"{}\", \"original_size\": {}, \
+				 \"compressed_size\": {}, \"ratio\": {:.4}, \"mtime\": {}}}",
+				json::escape(&entry.path),
+				method,
+				entry.size,
+				entry.length,
+				ratio,
+				entry
+					.mtime
+					.map_or("null".to_string(), |mtime| mtime.to_string())
+			),
+			(EntryKind::Symlink, true) => println!(
+				"{{\"path\": \"{}\", \"link\": \"{}\", \"mtime\": {}}}",
+				json::escape(&entry.path),
+				json::escape(&archive.read_link(&entry).map_err(read_error)?),
+				entry
+					.mtime
+					.map_or("null".to_string(), |mtime| mtime.to_string())
+			),
+			(EntryKind::Symlink, false) => println!(
+				"{:>10} {:>10} {:>6}  {:<16}  {} -> {}",
+				"-",
+				"-",
+				"-",
+				entry.mtime.map_or("-".to_string(), format_time),
+				entry.path,
+				archive.read_link(&entry).map_err(read_error)?
+			),
+			(EntryKind::Deleted, true) => println!(
+				"{{\"path\": \"{}\", \"deleted\": true}}",
+				json::escape(&entry.path)
+			),
+			(EntryKind::File, false) => println!(
+				"{:>10} {:>10} {:>6.4}  {:<16}  {}",
+				entry.size,
+				entry.length,
+				ratio,
+				entry.mtime.map_or("-".to_string(), format_time),
+				entry.path
+			),
+			(EntryKind::Deleted, false) => println!(
+				"{:>10} {:>10} {:>6}  {:<16}  {} (deleted)",
+				"-", "-", "-", "-", entry.path
+			),
+		}
+	}
+	Ok(())
+}
+
+/// Compares what is written to it with `original`, for the diff subcommand.
+/// Writing fails at the first difference, recorded in `mismatch`
+struct Comparison<R> {
+	original: R,
+	offset: u64,
+	mismatch: Option<u64>,
+}
+
+impl<R: Read> Comparison<R> {
+	/// Offset of the first byte of `original` after everything written, if there is one
+	fn extra(&mut self) -> io::Result<Option<u64>> {
+		let mut byte = [0];
+		match self.original.read(&mut byte)? {
+			0 => Ok(None),
+			_ => Ok(Some(self.offset)),
+		}
+	}
+}
+
+impl<R: Read> io::Write for Comparison<R> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let mut expected = Vec::with_capacity(buf.len());
+		self.original
+			.by_ref()
+			.take(buf.len() as u64)
+			.read_to_end(&mut expected)?;
+		let same = buf
+			.iter()
+			.zip(&expected)
+			.take_while(|(a, b)| a == b)
+			.count();
+		if same < buf.len() {
+			self.mismatch = Some(self.offset + same as u64);
+			return Err(io::Error::other("Contents differ"));
+		}
+		self.offset += buf.len() as u64;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Compress `input` to the volumes `FILE.cmp.001`, `FILE.cmp.002`, ...
+fn compress_to_volumes<R: Read + Send>(
+	file: &str,
+	output_file: &str,
+	input: &mut Tally<R>,
+	options: &huffman::CompressOptions,
+	volume_size: u64,
+	threads: usize,
+	rate: Option<u64>,
+) -> Result<Outcome, Failure> {
+	let base = PathBuf::from(output_file);
+	let output = io::BufWriter::new(Throttle::new(
+		volume::VolumeWriter::new(&base, volume_size),
+		rate,
+	));
+	let mut timings = huffman::Timings::default();
+	let result = if threads == 1 {
+		huffman::compress_stream_timed(&mut *input, output, options, &mut timings)
+	} else {
+		huffman::compress_stream_pipelined(&mut *input, output, options, threads, &mut timings)
+	}
+	.and_then(|output| output.into_inner().map_err(|e| e.into_error()))
+	.and_then(|output| output.into_inner().finish())
+	.and_then(|paths| {
+		paths
+			.iter()
+			.map(|path| fs::metadata(path).map(|metadata| metadata.len()))
+			.sum()
+	});
+	match result {
+		Ok(compressed_size) => Ok(Outcome {
+			output: Some(volume::volume_path(&base, 1).display().to_string()),
+			skipped: false,
+			original_size: input.bytes,
+			compressed_size,
+			checksum: input.crc.value(),
+			timings: Some(timings),
+		}),
+		Err(e) => {
+			let _ = volume::remove(&base);
+			Err(Failure::io("Error compressing file", file, e))
+		}
+	}
+}
+
+/// Serve requests on the unix domain socket `socket` until accepting fails, replacing
+/// the socket of a server that is gone
+#[cfg(unix)]
+fn serve(socket: &str, compressor: &huffman::Compressor, threads: usize) -> Result<(), Failure> {
+	use std::os::unix::net::{UnixListener, UnixStream};
+	let error = |e| Failure::io("Error listening on socket", socket, e);
+	let listener = match UnixListener::bind(socket) {
+		Err(e) if e.kind() == io::ErrorKind::AddrInUse && UnixStream::connect(socket).is_err() => {
+			fs::remove_file(socket).map_err(error)?;
+			UnixListener::bind(socket)
+		}
+		listener => listener,
+	}
+	.map_err(error)?;
+	logging::log(Level::Info, format_args!("Listening on {}", socket));
+	server::serve(listener, compressor, threads).map_err(error)
+}
+
+#[cfg(not(unix))]
+fn serve(_socket: &str, _compressor: &huffman::Compressor, _threads: usize) -> Result<(), Failure> {
+	Err(Failure::usage(
+		"serve needs unix domain sockets, not available here".to_string(),
+	))
+}
+
+/// Number of threads set by --threads, one per core for 0
+/// `address` with `host` in front if it is only a port like `:9000`
+fn host_address(address: &str, host: &str) -> String {
+	match address.strip_prefix(':') {
+		Some(port) => format!("{}:{}", host, port),
+		None => address.to_string(),
+	}
+}
+
+/// Bytes per second to read and to write at most, for --bwlimit
+fn bwlimit(matches: &ArgMatches) -> Option<u64> {
+	matches
+		.value_of("bwlimit")
+		.map(|rate| parse_size(rate).unwrap() as u64)
+}
+
+/// Contents of `file`, read at most `rate` bytes per second
+fn read_throttled(file: &str, rate: Option<u64>) -> io::Result<Vec<u8>> {
+	let mut data = Vec::new();
+	Throttle::new(fs::File::open(file)?, rate).read_to_end(&mut data)?;
+	Ok(data)
+}
+
+/// `atomic::write`, writing at most `rate` bytes per second
+fn write_throttled(path: &str, data: &[u8], rate: Option<u64>) -> io::Result<()> {
+	let mut output = AtomicFile::create(path)?;
+	Throttle::new(&mut output, rate).write_all(data)?;
+	output.commit().map(|_| ())
+}
+
+/// Threads of a single file, by default the cores shared among the files compressed at
+/// once
+fn threads(matches: &ArgMatches) -> usize {
+	match matches
+		.value_of("threads")
+		.map(|threads| threads.parse().unwrap())
+	{
+		Some(0) | None => (cores() / jobs(matches)).max(1),
+		Some(threads) => threads,
+	}
+}
+
+/// Per-extension settings of --config, none without it
+fn load_presets(matches: &ArgMatches) -> Result<Presets, Failure> {
+	let config = match matches.value_of("config") {
+		Some(config) => config,
+		None => return Ok(Presets::default()),
+	};
+	let text =
+		fs::read_to_string(config).map_err(|e| Failure::io("Error reading file", config, e))?;
+	Presets::parse(&text)
+		.map_err(|e| Failure::usage(format!("Error parsing file: {}: {}", config, e)))
+}
+
+/// Files compressed at once, for --jobs
+fn jobs(matches: &ArgMatches) -> usize {
+	match matches.value_of("jobs").map(|jobs| jobs.parse().unwrap()) {
+		Some(0) => cores(),
+		Some(jobs) => jobs,
+		None => 1,
+	}
+}
+
+fn cores() -> usize {
+	std::thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Print a line per symbol of `table`, like `'e'  freq=4312  len=3  code=101`
+fn print_codes(table: &huffman::CodeTable) {
+	let symbol = |c: char| match c {
+		huffman::ESCAPE => "ESC".to_string(),
+		c => format!("{:?}", c),
+	};
+	let width =
+		|f: &dyn Fn(&huffman::TableEntry) -> usize| table.entries.iter().map(f).max().unwrap_or(0);
+	let symbol_width = width(&|entry| symbol(entry.symbol).chars().count());
+	let frequency_width = width(&|entry| entry.frequency.to_string().len());
+	let length_width = width(&|entry| entry.code.len().to_string().len());
+	for entry in &table.entries {
+		println!(
+			"{:<sw$}  freq={:<fw$}  len={:<lw$}  code={}",
+			symbol(entry.symbol),
+			entry.frequency,
+			entry.code.len(),
+			entry.code,
+			sw = symbol_width,
+			fw = frequency_width,
+			lw = length_width
+		);
+	}
+}
+
+/// Print how many symbols have codes of every length, and how often they occur
+fn print_lengths(table: &huffman::CodeTable) {
+	let total: u64 = table.entries.iter().map(|entry| entry.frequency).sum();
+	println!("length  symbols  occurrences   share");
+	for count in table.length_histogram() {
+		println!(
+			"{:>6}  {:>7}  {:>11}  {:5.1}%",
+			count.length,
+			count.symbols,
+			count.frequency,
+			100.0 * count.frequency as f64 / total.max(1) as f64
+		);
+	}
+	print!("max depth: {}", table.max_depth());
+	match table.mean_length() {
+		Some(mean) => println!(", mean code length: {:.3} bits", mean),
+		None => println!(),
+	}
+}
+
+/// Start of `file`, enough to tell whether it is compressed already
+fn read_sample(file: &str) -> io::Result<Vec<u8>> {
+	let mut sample = Vec::new();
+	fs::File::open(file)?
+		.take(1 << 16)
+		.read_to_end(&mut sample)?;
+	Ok(sample)
+}
+
+/// Whether `file`, starting with `sample`, was written by this program
+fn is_compressed_file(file: &str, sample: &[u8]) -> bool {
+	let version = sample.get(huffman::MAGIC.len()).copied();
+	file.ends_with(".cmp")
+		|| codec::detect(sample).is_some_and(|codec| {
+			codec.name() != "huffman"
+				|| version.is_some_and(|version| (1..=huffman::VERSION).contains(&version))
+		})
+}
+
+/// Contents of `file`, joined from all volumes if it is the first of several,
+/// with the name to treat them under. Parity written by --parity is removed and
+/// rebuilds what it can of damaged data. A single file is read at most `rate` bytes
+/// per second
+fn read_compressed(file: &str, rate: Option<u64>) -> Result<(Vec<u8>, &str), Failure> {
+	let (mut data, file) = match volume::base_path(file) {
+		Some(base) => volume::join(Path::new(base))
+			.map(|data| (data, base))
+			.map_err(|e| Failure::io("Error reading volumes", file, e)),
+		None => read_throttled(file, rate)
+			.map(|data| (data, file))
+			.map_err(|e| Failure::io("Error reading file", file, e)),
+	}?;
+	match parity::repair(&mut data) {
+		Some(repair) if repair.damaged == 0 => {}
+		Some(repair) if repair.repaired => logging::log(
+			Level::Warn,
+			format_args!(
+				"{}: {} damaged blocks, rebuilt from parity",
+				file, repair.damaged
+			),
+		),
+		Some(repair) => logging::log(
+			Level::Warn,
+			format_args!(
+				"{}: {} damaged blocks, more than the {} parity blocks can rebuild",
+				file, repair.damaged, repair.layout.parity_shards
+			),
+		),
+		None => {}
+	}
+	Ok((data, file))
+}
+
+/// Append parity of `percent` percent to the output written for `file`, for --parity
+fn add_parity(file: &str, mut outcome: Outcome, percent: u32) -> Result<Outcome, Failure> {
+	let output_file = match &outcome.output {
+		Some(output_file) => output_file,
+		None => return Ok(outcome),
+	};
+	let mut data =
+		fs::read(output_file).map_err(|e| Failure::io("Error reading file", output_file, e))?;
+	let parity = parity::protect(&data, percent);
+	data.extend(&parity);
+	atomic::write(output_file, &data)
+		.map_err(|e| Failure::io("Error writing file", output_file, e))?;
+	logging::log(
+		Level::Info,
+		format_args!(
+			"{}: {} bytes of parity appended to {}",
+			file,
+			parity.len(),
+			output_file
+		),
+	);
+	outcome.compressed_size = data.len() as u64;
+	Ok(outcome)
+}
+
+/// Remove an output file, or all volumes if it is the first of several
+fn remove_output(output_file: &str) -> io::Result<()> {
+	match volume::base_path(output_file) {
+		Some(base) => volume::remove(Path::new(base)),
+		None => fs::remove_file(output_file),
+	}
+}
+
+/// Decompress the output written for `file` again and compare it with the input, for
+/// --verify. Output that doesn't match is removed
+fn verify_output(
+	file: &str,
+	outcome: Outcome,
+	dictionary: Option<&huffman::Dictionary>,
+) -> Result<Outcome, Failure> {
+	let output_file = match &outcome.output {
+		Some(output_file) => output_file,
+		None => return Ok(outcome),
+	};
+	let reason = match test_file(output_file, dictionary) {
+		Ok(check)
+			if check.checksum == outcome.checksum
+				&& check.original_size == outcome.original_size =>
+		{
+			logging::log(
+				Level::Info,
+				format_args!("{}: verified, decompresses to the input", output_file),
+			);
+			return Ok(outcome);
+		}
+		Ok(_) => "it decompresses to other data".to_string(),
+		Err(failure) => failure.message,
+	};
+	remove_output(output_file).map_err(|e| Failure::io("Error removing file", output_file, e))?;
+	Err(Failure::corrupt(format!(
+		"{}: verification failed, removed {}: {}",
+		file, output_file, reason
+	)))
+}
+
+/// Decompress `file` without writing the result, to check its integrity
+fn test_file(file: &str, dictionary: Option<&huffman::Dictionary>) -> Result<Outcome, Failure> {
+	let (data, _) = read_compressed(file, None)?;
+	if let Some(codec) = other_codec(&data) {
+		let mut decompressed_data = Vec::new();
+		codec
+			.decode(&mut &data[..], &mut decompressed_data)
+			.map_err(|e| Failure::corrupt(format!("{}: corrupt, {}", file, e)))?;
+		return Ok(Outcome {
+			output: None,
+			skipped: false,
+			original_size: decompressed_data.len() as u64,
+			compressed_size: data.len() as u64,
+			checksum: checksum::crc32(&decompressed_data),
+			timings: None,
+		});
+	}
+	let result = huffman::decompress_bytes(&data, dictionary);
+	let decompressed_data = match result {
+		Ok(decompressed_data) => decompressed_data,
+		Err(e @ huffman::DecompressError::Block { .. }) => {
+			// Check every block on its own to list all damaged ones
+			let blocks = huffman::verify(&data, dictionary).unwrap_or_default();
+			let damaged: Vec<String> = blocks
+				.iter()
+				.enumerate()
+				.filter_map(|(i, block)| {
+					let error = block.result.as_ref().err()?;
+					Some(format!(
+						"\n  block {} of {}, bytes {} to {}: {}",
+						i + 1,
+						blocks.len(),
+						block.offset,
+						block.offset + block.size,
+						error
+					))
+				})
+				.collect();
+			if damaged.is_empty() {
+				return Err(Failure::corrupt(format!("{}: corrupt, {}", file, e)));
+			}
+			return Err(Failure::corrupt(format!(
+				"{}: corrupt, {} of {} blocks damaged{}",
+				file,
+				damaged.len(),
+				blocks.len(),
+				damaged.concat()
+			)));
+		}
+		Err(e) => return Err(Failure::corrupt(format!("{}: corrupt, {}", file, e))),
+	};
+	Ok(Outcome {
+		output: None,
+		skipped: false,
+		original_size: decompressed_data.len() as u64,
+		compressed_size: data.len() as u64,
+		checksum: checksum::crc32(&decompressed_data),
+		timings: None,
+	})
+}
+
+/// Rewrite `file` in the current format with `options`, keeping its metadata, unless it
+/// is in the current format already and not `all`. Returns the old and new size if it
+/// was rewritten
+///
+/// The file is only replaced once the new data is known to decompress to the same bytes
+fn migrate_file(
+	file: &str,
+	options: &huffman::CompressOptions,
+	all: bool,
+) -> Result<Option<(usize, usize)>, Failure> {
+	let data = fs::read(file).map_err(|e| Failure::io("Error reading file", file, e))?;
+	let corrupt = |e: huffman::DecompressError| {
+		Failure::corrupt(format!("Error decompressing file: {}: {}", file, e))
+	};
+	let members = huffman::inspect(&data).map_err(corrupt)?;
+	if !all
+		&& members
+			.iter()
+			.all(|member| member.version == huffman::VERSION)
+	{
+		return Ok(None);
+	}
+	let original = huffman::decompress_bytes(&data, options.dictionary).map_err(corrupt)?;
+	let options = huffman::CompressOptions {
+		metadata: huffman::read_metadata(&data).map_err(corrupt)?,
+		..options.clone()
+	};
+	let migrated = huffman::compress_stream(&original[..], Vec::new(), &options)
+		.map_err(|e| Failure::io("Error compressing file", file, e))?;
+	let check =
+		huffman::decompress_bytes(&migrated, options.dictionary).map(|data| checksum::crc32(&data));
+	if check != Ok(checksum::crc32(&original)) {
+		return Err(Failure::corrupt(format!(
+			"Error migrating file: {}: recompressed data doesn't match, left as it was",
+			file
+		)));
+	}
+	atomic::write(file, &migrated).map_err(|e| Failure::io("Error writing file", file, e))?;
+	Ok(Some((data.len(), migrated.len())))
+}
+
 fn main() {
+	// auto isn't a codec of its own, it picks huffman settings for every block
+	let algos = [codec::NAMES, &["auto"]].concat();
 	let matches = App::new("Simple compression using rust")
+		.setting(AppSettings::SubcommandsNegateReqs)
 		.arg(
 			Arg::with_name("compress")
 				.short("c")
 				.long("compress")
 				.value_name("FILE")
+				.help("Compress every FILE to FILE.cmp, - from stdin to stdout")
+				.multiple(true)
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("files-from")
+				.long("files-from")
+				.value_name("LIST")
+				.help(
+					"Compress the files listed in LIST, - for stdin, one per line, each optionally \
+					 followed by a tab and its output file, and by tab separated settings \
+					 algo=, model=, filter= or block-size=",
+				)
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("quiet")
+				.short("q")
+				.long("quiet")
+				.help("Print nothing but errors")
+				.conflicts_with("verbose")
+				.global(true),
+		)
+		.arg(
+			Arg::with_name("verbose")
+				.short("v")
+				.long("verbose")
+				.help("Print ratios, tree statistics and timings, twice for details of every block")
+				.multiple(true)
+				.global(true),
+		)
+		.arg(
+			Arg::with_name("json")
+				.long("json")
+				.help("Report the result of every file as a line of JSON on stdout"),
+		)
+		.arg(
+			Arg::with_name("min-ratio")
+				.long("min-ratio")
+				.value_name("RATIO")
+				.help(
+					"Don't keep compressed files unless the input is at least RATIO times \
+					 their size, e.g. 1.1",
+				)
+				.validator(|ratio| match ratio.parse::<f64>() {
+					Ok(ratio) if ratio > 0.0 => Ok(()),
+					_ => Err("Expected a positive number".to_string()),
+				})
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("recover")
+				.long("recover")
+				.help(
+					"When decompressing, skip damaged blocks instead of failing, filling the \
+					 text they held with zeros, and list what was lost",
+				)
+				.requires("decompress"),
+		)
+		.arg(
+			Arg::with_name("marker")
+				.long("marker")
+				.value_name("TEXT")
+				.help("Fill the text of damaged blocks with TEXT instead of zeros")
+				.requires("recover")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("parity")
+				.long("parity")
+				.value_name("PERCENT")
+				.help(
+					"Append Reed–Solomon parity of PERCENT of every compressed file, e.g. 10%, \
+					 so that damaged blocks of up to that much can be rebuilt",
+				)
+				.validator(|percent| parse_percent(&percent).map(|_| ()))
+				.requires("compressing")
+				.conflicts_with_all(&["format", "archive", "volume-size", "resume"])
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("verify")
+				.long("verify")
+				.help(
+					"Decompress every compressed file again and compare it with the input, \
+					 removing it if they differ",
+				)
+				.requires("compressing")
+				.conflicts_with_all(&["format", "archive"]),
+		)
+		.arg(
+			Arg::with_name("force-compress")
+				.long("force-compress")
+				.help("Compress files that are compressed already instead of refusing or storing them"),
+		)
+		.arg(
+			Arg::with_name("timings")
+				.long("timings")
+				.help("When compressing, report time and throughput of every stage"),
+		)
+		.arg(
+			Arg::with_name("archive")
+				.long("archive")
+				.value_name("ARCHIVE")
+				.help(
+					"Store the files to compress together in ARCHIVE instead of one FILE.cmp each",
+				)
+				.requires("compress")
+				.conflicts_with_all(&[
+					"dict",
+					"table",
+					"static",
+					"dump-table",
+					"model",
+					"filter",
+					"block-size",
+					"adaptive-blocks",
+					"shannon-fano",
+					"max-code-length",
+					"timings",
+					"format",
+					"min-ratio",
+					"resume",
+					"volume-size",
+				])
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("dereference")
+				.long("dereference")
+				.help("Store the files symlinks point to in ARCHIVE instead of the links")
+				.requires("archive"),
+		)
+		.arg(
+			Arg::with_name("dedup")
+				.long("dedup")
+				.help("Store chunks shared by the files in ARCHIVE only once, for similar files")
+				.requires("archive"),
+		)
+		.arg(
+			Arg::with_name("append")
+				.long("append")
+				.help("Add the files to an existing ARCHIVE, rewriting only its index")
+				.requires("archive"),
+		)
+		.arg(
+			Arg::with_name("volume-size")
+				.long("volume-size")
+				.value_name("SIZE")
+				.help(
+					"Split compressed output into FILE.cmp.001, .002, ... of SIZE bytes, e.g. 100M",
+				)
+				.validator(|size| match parse_size(&size) {
+					Ok(size) if size >= 1 << 10 => Ok(()),
+					Ok(_) => Err("Volumes need to be at least 1K".to_string()),
+					Err(e) => Err(e),
+				})
+				.conflicts_with("resume")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("resume")
+				.long("resume")
+				.help("Continue compressing after the last complete block of a partial FILE.cmp"),
+		)
+		.arg(
+			Arg::with_name("recursive")
+				.short("r")
+				.long("recursive")
+				.help(
+					"Compress every file below directories given to -c, except *.cmp and symlinks",
+				)
+				.requires("compress")
+				.conflicts_with("archive"),
+		)
+		.arg(
+			Arg::with_name("include")
+				.long("include")
+				.value_name("PATTERN")
+				.help(
+					"Only compress or archive files whose path relative to the directory given, \
+					 or name, matches PATTERN, e.g. '*.rs'",
+				)
+				.multiple(true)
+				.number_of_values(1)
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("exclude")
+				.long("exclude")
+				.value_name("PATTERN")
+				.help("Skip files whose path or name matches PATTERN, e.g. 'target/**' or '*.log'")
+				.multiple(true)
+				.number_of_values(1)
 				.takes_value(true),
 		)
 		.arg(
@@ -17,29 +2095,1055 @@ fn main() {
 				.short("d")
 				.long("decompress")
 				.value_name("FILE")
+				.help("Decompress FILE to FILE without .cmp, - from stdin to stdout")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("bytes")
+				.long("bytes")
+				.value_name("SIZE")
+				.help("Print only the first SIZE bytes of the text instead of writing a file, e.g. 4K")
+				.requires("decompress")
+				.validator(|size| parse_size(&size).map(|_| ()))
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("extract")
+				.short("x")
+				.long("extract")
+				.value_name("ARCHIVE PATH")
+				.help("Extract the entries PATH... of ARCHIVE below the current directory")
+				.multiple(true)
+				.min_values(2)
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("list")
+				.short("l")
+				.long("list")
+				.value_name("ARCHIVE")
+				.help("List the entries of ARCHIVE with their sizes, ratios and modification times")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("test")
+				.short("t")
+				.long("test")
+				.value_name("FILE")
+				.help("Decompress and verify checksums without writing output")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("dict")
+				.long("dict")
+				.value_name("FILE")
+				.help("Dictionary built by `train`, needed again to decompress")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("algo")
+				.long("algo")
+				.value_name("ALGO")
+				.help("Compression algorithm, rle codes runs alone and takes any bytes, stored copies them \
+					 as is, wide codes 16-bit units like UTF-16 text, lzw codes repeated strings by \
+					 a table built along the way, auto picks the huffman model and \
+					 filter for every block from a sample of it (default: huffman)")
+				.possible_values(&algos)
+				.conflicts_with_all(ALGO_CONFLICTS)
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("format")
+				.long("format")
+				.value_name("FORMAT")
+				.help("Output format when compressing, gzip writes FILE.gz readable by gunzip")
+				.possible_values(&["huf", "gzip"])
+				.conflicts_with_all(&[
+					"dict",
+					"table",
+					"static",
+					"dump-table",
+					"model",
+					"filter",
+					"block-size",
+					"adaptive-blocks",
+					"shannon-fano",
+					"max-code-length",
+					"timings",
+					"resume",
+					"volume-size",
+				])
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("from")
+				.long("from")
+				.value_name("FORMAT")
+				.help(
+					"Input format when compressing: decompress gzip or zlib input on the fly and \
+					 recompress it, FILE.gz to FILE.cmp",
+				)
+				.possible_values(&["gzip", "zlib"])
+				.requires("compressing")
+				.conflicts_with_all(&[
+					"format",
+					"algo",
+					"archive",
+					"volume-size",
+					"resume",
+					"verify",
+					"dump-table",
+				])
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("table")
+				.long("table")
+				.value_name("FILE")
+				.help("Code table in JSON, as written by --dump-table, needed again to decompress")
+				.conflicts_with("dict")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("static")
+				.long("static")
+				.help("Code with the built-in table for English text, no flag needed to decompress")
+				.conflicts_with_all(&["dict", "table", "model", "filter", "max-code-length"]),
+		)
+		.arg(
+			Arg::with_name("dump-table")
+				.long("dump-table")
+				.value_name("FILE")
+				.help("When compressing, write symbol frequencies and codes of the input as JSON")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("model")
+				.long("model")
+				.value_name("MODEL")
+				.help(
+					"Symbols to huffman code: chars, words for natural language text, \
+					 bytes in the context of the previous byte, or frequent byte pairs. \
+					 rice codes bytes with Rice codes instead, for small differences \
+					 like those of --filter delta",
+				)
+				.possible_values(&["char", "word", "context", "digram", "rice"])
+				.conflicts_with_all(&["dict", "table"])
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("filter")
+				.long("filter")
+				.value_name("FILTER")
+				.help(
+					"Transform input before huffman coding, rle for input with long runs, \
+					 delta[:WIDTH] for slowly varying samples WIDTH bytes wide (default: 1)",
+				)
+				.validator(|filter| parse_filter(&filter).map(|_| ()))
+				.conflicts_with_all(&["dict", "table"])
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("block-size")
+				.long("block-size")
+				.value_name("SIZE")
+				.help("Bytes sharing one huffman tree, e.g. 64K to 16M (default: 1M)")
+				.validator(|size| parse_size(&size).map(|_| ()))
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("adaptive-blocks")
+				.long("adaptive-blocks")
+				.help("Also start a new block with a tree of its own where the byte distribution changes"),
+		)
+		.arg(
+			Arg::with_name("bwlimit")
+				.long("bwlimit")
+				.value_name("RATE")
+				.help(
+					"Read and write files at most RATE bytes per second each, with K, M or G \
+					 suffixes, e.g. 10M, to leave the disk to others",
+				)
+				.validator(|rate| parse_size(&rate).map(|_| ()))
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("threads")
+				.long("threads")
+				.value_name("N")
+				.help(
+					"Blocks compressed at once and connections served at once, 0 for one per \
+					 core, shared among --jobs, 1 to do everything in order on a single thread \
+					 (default: 0)",
+				)
+				.validator(|threads| match threads.parse::<usize>() {
+					Ok(_) => Ok(()),
+					Err(_) => Err("Expected a number of threads".to_string()),
+				})
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("config")
+				.long("config")
+				.value_name("FILE")
+				.help(
+					"Per-extension settings for the files to compress or archive, e.g. lines \
+					 `jpg = stored` or `log = model word, block-size 1M`",
+				)
+				.requires("compressing")
+				.conflicts_with_all(&["dict", "table", "static"])
 				.takes_value(true),
 		)
+		.arg(
+			Arg::with_name("jobs")
+				.long("jobs")
+				.short("j")
+				.value_name("N")
+				.help(
+					"Files compressed at once, each with --threads of its own, 0 for one per \
+					 core (default: 1)",
+				)
+				.validator(|jobs| match jobs.parse::<usize>() {
+					Ok(_) => Ok(()),
+					Err(_) => Err("Expected a number of files".to_string()),
+				})
+				.requires("compressing")
+				.conflicts_with("archive")
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("shannon-fano")
+				.long("shannon-fano")
+				.help(
+					"Build codes with Shannon–Fano coding instead of huffman coding, to compare the \
+					 two classic algorithms. Never compresses better, decompresses as usual",
+				)
+				.conflicts_with_all(&["dict", "table", "static", "max-code-length"]),
+		)
+		.arg(
+			Arg::with_name("max-code-length")
+				.long("max-code-length")
+				.value_name("BITS")
+				.help("Longest huffman code allowed, e.g. 15 for table driven decoders")
+				.validator(|bits| match bits.parse::<u8>() {
+					Ok(1..=63) => Ok(()),
+					_ => Err("Expected a number of bits from 1 to 63".to_string()),
+				})
+				.conflicts_with_all(&["dict", "table"])
+				.takes_value(true),
+		)
+		.arg(
+			Arg::with_name("no-preserve")
+				.long("no-preserve")
+				.help("Neither store nor restore modification time and permissions"),
+		)
+		.arg(
+			Arg::with_name("name")
+				.short("N")
+				.long("name")
+				.help("When decompressing, restore the original file name stored in the header"),
+		)
+		.arg(
+			Arg::with_name("no-name")
+				.long("no-name")
+				.help("When compressing, don't store the original file name")
+				.conflicts_with("name"),
+		)
+		.arg(
+			Arg::with_name("self-check")
+				.long("self-check")
+				.help("Re-encode decompressed data and report how well it matches its model"),
+		)
 		.group(
 			ArgGroup::with_name("action")
-				.args(&["compress", "decompress"])
+				.args(&["compress", "files-from", "decompress", "test", "extract", "list"])
 				.required(true),
 		)
-		.get_matches();
+		.group(ArgGroup::with_name("compressing").args(&["compress", "files-from"]))
+		.subcommand(
+			SubCommand::with_name("train")
+				.about(
+					"Build a dictionary from sample files, and every file below sample \
+					 directories, for use with --dict",
+				)
+				.arg(
+					Arg::with_name("samples")
+						.value_name("PATH")
+						.multiple(true)
+						.required(true),
+				)
+				.arg(
+					Arg::with_name("output")
+						.short("o")
+						.long("output")
+						.value_name("FILE")
+						.takes_value(true)
+						.required(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("info")
+				.alias("inspect")
+				.about("Show header fields of a compressed file without decompressing it")
+				.arg(Arg::with_name("file").value_name("FILE").required(true)),
+		)
+		.subcommand(
+			SubCommand::with_name("diff")
+				.about(
+					"Check that a compressed file decompresses to ORIGINAL, without writing it out",
+				)
+				.arg(
+					Arg::with_name("original")
+						.value_name("ORIGINAL")
+						.required(true),
+				)
+				.arg(Arg::with_name("file").value_name("FILE").required(true)),
+		)
+		.subcommand(
+			SubCommand::with_name("codes")
+				.about(
+					"Show the code of every symbol, for a text file or every block of a compressed file",
+				)
+				.arg(Arg::with_name("file").value_name("FILE").required(true))
+				.arg(
+					Arg::with_name("histogram")
+						.long("histogram")
+						.help("Show the number of codes of every length and the tree depth instead"),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("analyze")
+				.about("Show byte frequencies, entropy and the expected ratio of a file")
+				.arg(Arg::with_name("file").value_name("FILE").required(true)),
+		)
+		.subcommand(
+			SubCommand::with_name("stats")
+				.about("Export symbol statistics of a corpus of files as JSON")
+				.arg(
+					Arg::with_name("corpus")
+						.long("corpus")
+						.value_name("DIR")
+						.takes_value(true)
+						.required(true),
+				)
+				.arg(
+					Arg::with_name("out")
+						.long("out")
+						.value_name("FILE")
+						.help("Defaults to standard output")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("diff-archive")
+				.about("Archive files that are new or changed in NEW_DIR compared to OLD_DIR")
+				.arg(Arg::with_name("old").value_name("OLD_DIR").required(true))
+				.arg(Arg::with_name("new").value_name("NEW_DIR").required(true))
+				.arg(
+					Arg::with_name("output")
+						.short("o")
+						.long("output")
+						.value_name("FILE")
+						.takes_value(true)
+						.required(true),
+				)
+				.arg(
+					Arg::with_name("dereference")
+						.long("dereference")
+						.help("Store the files symlinks point to instead of the links"),
+				)
+				.arg(
+					Arg::with_name("config")
+						.long("config")
+						.value_name("FILE")
+						.help(
+							"Per-extension settings, e.g. lines `jpg = stored` or \
+							 `log = model word`",
+						)
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("apply")
+				.about("Reconstruct a directory from OLD_DIR and a differential archive")
+				.arg(Arg::with_name("old").value_name("OLD_DIR").required(true))
+				.arg(Arg::with_name("delta").value_name("FILE").required(true))
+				.arg(
+					Arg::with_name("output")
+						.short("o")
+						.long("output")
+						.value_name("NEW_DIR")
+						.help("Write the result here instead of modifying OLD_DIR")
+						.takes_value(true),
+				)
+				.arg(Arg::with_name("self-check").long("self-check").help(
+					"Re-encode every extracted file and report how well it matches its model",
+				)),
+		)
+		.subcommand(
+			SubCommand::with_name("serve")
+				.about(
+					"Compress and decompress requests on a unix domain socket, see the server \
+					 module for the protocol, or over HTTP",
+				)
+				.arg(
+					Arg::with_name("socket")
+						.long("socket")
+						.value_name("PATH")
+						.takes_value(true)
+						.required_unless("http")
+						.conflicts_with("http"),
+				)
+				.arg(
+					Arg::with_name("http")
+						.long("http")
+						.value_name("ADDRESS")
+						.help("Serve POST /compress and /decompress over HTTP, e.g. on 127.0.0.1:8080")
+						.takes_value(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("tunnel")
+				.about(
+					"Forward TCP connections to another tunnel, compressing the traffic \
+					 between the two, e.g. tunnel --listen :9000 --connect host:9001 near the \
+					 clients and tunnel --decompress --listen :9001 --connect :80 near the server",
+				)
+				.arg(
+					Arg::with_name("listen")
+						.long("listen")
+						.value_name("ADDRESS")
+						.help("Address to accept connections on, all interfaces if only a port")
+						.takes_value(true)
+						.required(true),
+				)
+				.arg(
+					Arg::with_name("connect")
+						.long("connect")
+						.value_name("ADDRESS")
+						.help("Address to forward every connection to, localhost if only a port")
+						.takes_value(true)
+						.required(true),
+				)
+				.arg(Arg::with_name("decompress").long("decompress").help(
+					"Accept compressed connections from another tunnel, and forward them plain",
+				)),
+		)
+		.subcommand(
+			SubCommand::with_name("mount")
+				.about(
+					"Serve an archive as a read-only file system on MOUNTPOINT until it is \
+					 unmounted, with FUSE on Linux",
+				)
+				.arg(Arg::with_name("archive").value_name("ARCHIVE").required(true))
+				.arg(
+					Arg::with_name("mountpoint")
+						.value_name("MOUNTPOINT")
+						.required(true),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("migrate")
+				.about(
+					"Rewrite compressed files of older formats in the current one, with the \
+					 options given before migrate, e.g. --model word migrate FILE",
+				)
+				.arg(
+					Arg::with_name("files")
+						.value_name("FILE")
+						.multiple(true)
+						.required(true),
+				)
+				.arg(
+					Arg::with_name("all")
+						.long("all")
+						.help("Recompress files in the current format as well"),
+				),
+		)
+		.subcommand(
+			SubCommand::with_name("selftest")
+				.about("Check that this build compresses built-in test vectors as expected"),
+		)
+		.get_matches_safe()
+		.unwrap_or_else(|e| {
+			if !e.use_stderr() {
+				e.exit(); // --help and --version
+			}
+			eprintln!("{}", e.message);
+			process::exit(EXIT_USAGE);
+		});
+	logging::set_logger(|level, args| eprintln!("{}: {}", level, args));
+	logging::set_max_level(match matches.occurrences_of("verbose") {
+		_ if matches.is_present("quiet") => Some(Level::Error),
+		0 => Some(Level::Warn),
+		1 => Some(Level::Info),
+		_ => Some(Level::Debug),
+	});
+	if let Err(e) = interrupt::install() {
+		logging::log(
+			Level::Warn,
+			format_args!("Ctrl-C won't clean up unfinished output: {}", e),
+		);
+	}
+	if let Err(failure) = run(&matches) {
+		eprintln!("{}", failure.message);
+		process::exit(failure.status);
+	}
+}
 
-	if let Some(file) = matches.value_of("compress") {
-		let error_msg = "Error reading file: ".to_string() + file;
-		let data = fs::read_to_string(file).expect(&error_msg);
-		let compressed_data = huffman::compress(&data);
-		let output_file = file.to_string() + ".cmp";
-		let error_msg = "Error writing file: ".to_string() + &output_file;
-		fs::write(output_file, compressed_data).expect(&error_msg);
+/// Carry out the action selected on the command line
+fn run(matches: &ArgMatches) -> Result<(), Failure> {
+	let dictionary = match matches.value_of("dict") {
+		Some(dict) => {
+			let data = fs::read(dict).map_err(|e| Failure::io("Error reading file", dict, e))?;
+			let dictionary = huffman::Dictionary::from_bytes(&data).map_err(|e| {
+				Failure::corrupt(format!("Error parsing dictionary: {}: {}", dict, e))
+			})?;
+			Some(dictionary)
+		}
+		None => None,
+	};
+	let dictionary = match (dictionary, matches.value_of("table")) {
+		(None, Some(table)) => {
+			let text = fs::read_to_string(table)
+				.map_err(|e| Failure::io("Error reading file", table, e))?;
+			let parse_error =
+				|e: String| Failure::corrupt(format!("Error parsing code table: {}: {}", table, e));
+			let table = huffman::CodeTable::from_json(&text).map_err(parse_error)?;
+			Some(huffman::Dictionary::from_table(&table).map_err(parse_error)?)
+		}
+		(dictionary, _) => dictionary,
+	};
+	let dictionary = if matches.is_present("static") {
+		huffman::Dictionary::builtin(huffman::ENGLISH)
+	} else {
+		dictionary
+	};
+	if matches.is_present("compress") || matches.is_present("files-from") {
+		let options = compress_options(matches, dictionary.as_ref());
+		let presets = load_presets(matches)?;
+		let files: Vec<&str> = matches
+			.values_of("compress")
+			.into_iter()
+			.flatten()
+			.collect();
+		let from = matches.value_of("from").map(|format| match format {
+			"zlib" => inflate::Format::Zlib,
+			_ => inflate::Format::Gzip,
+		});
+		if files.contains(&"-") {
+			check_filter(&files, matches)?;
+			return compress_filter(&options, from);
+		}
+		let selection = Selection::new(matches);
+		let mut inputs = Vec::new();
+		let mut failures = Vec::new();
+		for file in files {
+			let files = input_files(file).and_then(|files| {
+				let mut selected = Vec::new();
+				for file in files {
+					if !Path::new(&file).is_dir() {
+						if selection.selects(&file) {
+							selected.push(file);
+						}
+					} else if matches.is_present("recursive") {
+						selected.extend(tree_files(&file, &selection)?);
+					} else if matches.is_present("archive") {
+						selected.push(file);
+					} else {
+						return Err(Failure::usage(format!(
+							"{} is a directory, -r compresses the files below it",
+							file
+						)));
+					}
+				}
+				Ok(selected)
+			});
+			match files {
+				Ok(files) => inputs.extend(
+					files
+						.into_iter()
+						.map(|file| Job::new(file, matches, &options, &presets)),
+				),
+				Err(failure) => {
+					let failure = Err(failure);
+					report(matches, "compress", file, &failure, Instant::now());
+					failures.extend(failure.err());
+				}
+			}
+		}
+		if let Some(list) = matches.value_of("files-from") {
+			if matches.is_present("archive") {
+				return Err(Failure::usage(
+					"--files-from can't be used with --archive".to_string(),
+				));
+			}
+			inputs.extend(manifest_jobs(list, matches, &options, &presets)?);
+		}
+		if let Some(archive_file) = matches.value_of("archive") {
+			let inputs: Vec<String> = inputs.into_iter().map(|job| job.file).collect();
+			failures.extend(archive_files(archive_file, &inputs, matches, &presets).err());
+			if let Some(failure) = failures.into_iter().next() {
+				process::exit(failure.status);
+			}
+			return Ok(());
+		}
+		let total = inputs.len() + failures.len();
+		let min_ratio = matches
+			.value_of("min-ratio")
+			.map(|ratio| ratio.parse().unwrap());
+		let parity = matches
+			.value_of("parity")
+			.map(|percent| parse_percent(percent).unwrap());
+		let quiet = matches.is_present("quiet") || matches.is_present("json");
+		let compress = |job: &Job| {
+			let file = &job.file;
+			match from {
+				Some(format) => transcode_file(job, matches, format),
+				None => compress_file(job, matches),
+			}
+			.and_then(|outcome| match parity {
+				Some(percent) => add_parity(file, outcome, percent),
+				None => Ok(outcome),
+			})
+			.and_then(|outcome| {
+				if matches.is_present("verify") {
+					verify_output(file, outcome, job.options.dictionary)
+				} else {
+					Ok(outcome)
+				}
+			})
+			.and_then(|outcome| check_ratio(file, outcome, min_ratio))
+		};
+		let jobs = jobs(matches);
+		let start = Instant::now();
+		let summary = Mutex::new(Summary {
+			done: failures.len(),
+			failures,
+			..Summary::default()
+		});
+		// Every worker takes the next file when done with one, so a large file holds up
+		// one worker and not the others
+		let next = AtomicUsize::new(0);
+		std::thread::scope(|scope| {
+			for _ in 0..jobs.min(inputs.len()) {
+				scope.spawn(|| {
+					while let Some(job) = inputs.get(next.fetch_add(1, Ordering::Relaxed)) {
+						let (file, start) = (&job.file, Instant::now());
+						let result = compress(job);
+						let mut summary = summary.lock().unwrap();
+						summary.done += 1;
+						report(matches, "compress", file, &result, start);
+						let progress = match jobs {
+							1 => String::new(),
+							_ => format!("[{}/{}] ", summary.done, total),
+						};
+						match result {
+							Ok(outcome) => {
+								if outcome.skipped {
+									summary.skipped += 1;
+								} else {
+									summary.original_size += outcome.original_size;
+									summary.compressed_size += outcome.compressed_size;
+								}
+								if total > 1 && !quiet {
+									let status = match &outcome.output {
+										Some(output_file) => format!("OK, wrote {}", output_file),
+										None => "skipped, see --min-ratio".to_string(),
+									};
+									println!("{}{}: {}", progress, file, status);
+								}
+							}
+							Err(failure) => summary.failures.push(failure),
+						}
+					}
+				});
+			}
+		});
+		let summary = summary.into_inner().unwrap();
+		let failed = summary.failures.len();
+		if total > 1 && !quiet {
+			println!(
+				"{} of {} files compressed, {} skipped, {} failed, {} -> {} bytes, ratio {:.4}, \
+				 {:.3} s",
+				total - summary.skipped - failed,
+				total,
+				summary.skipped,
+				failed,
+				summary.original_size,
+				summary.compressed_size,
+				summary.compressed_size as f64 / summary.original_size.max(1) as f64,
+				start.elapsed().as_secs_f64()
+			);
+		}
+		if let Some(failure) = summary.failures.into_iter().next() {
+			// Already reported
+			process::exit(failure.status);
+		}
 	}
 	if let Some(file) = matches.value_of("decompress") {
-		let error_msg = "Error reading file: ".to_string() + file;
-		let data = fs::read(file).expect(&error_msg);
-		let compressed_data = huffman::decompress(&data);
-		let output_file = &file[0..file.len() - 4];
-		let error_msg = "Error writing file: ".to_string() + &output_file;
-		fs::write(output_file, compressed_data).expect(&error_msg);
+		if file == "-" {
+			check_filter(&[file], matches)?;
+			return decompress_filter(dictionary.as_ref());
+		}
+		let start = Instant::now();
+		let result = match matches.value_of("bytes") {
+			Some(size) => {
+				let limit = parse_size(size).unwrap() as u64;
+				preview_file(file, limit, dictionary.as_ref())
+			}
+			None => decompress_file(file, matches, dictionary.as_ref()),
+		};
+		report(matches, "decompress", file, &result, start);
+		if let Err(failure) = result {
+			process::exit(failure.status);
+		}
+	}
+	if let Some(archive_file) = matches.value_of("list") {
+		list_archive(archive_file, matches)?;
+	}
+	if let Some(mut values) = matches.values_of("extract") {
+		let archive_file = values.next().unwrap();
+		let mut archive = fs::File::open(archive_file)
+			.and_then(Archive::new)
+			.map_err(|e| Failure::io("Error reading archive", archive_file, e))?;
+		let mut failures = Vec::new();
+		for path in values {
+			let start = Instant::now();
+			let result = extract_entry(&mut archive, archive_file, path);
+			report(matches, "extract", path, &result, start);
+			failures.extend(result.err());
+		}
+		if let Some(failure) = failures.into_iter().next() {
+			process::exit(failure.status);
+		}
+	}
+	if let Some(file) = matches.value_of("test") {
+		let start = Instant::now();
+		let result = test_file(file, dictionary.as_ref());
+		report(matches, "test", file, &result, start);
+		match result {
+			Ok(_) if matches.is_present("quiet") || matches.is_present("json") => {}
+			Ok(_) => println!("{}: OK", file),
+			Err(failure) => process::exit(failure.status),
+		}
+	}
+	if let Some(sub_matches) = matches.subcommand_matches("train") {
+		let selection = Selection::new(matches);
+		let mut samples = Vec::new();
+		for path in sub_matches.values_of("samples").unwrap() {
+			let files = if Path::new(path).is_dir() {
+				tree_files(path, &selection)?
+			} else {
+				vec![path.to_string()]
+			};
+			for file in files {
+				samples.push(
+					fs::read(&file).map_err(|e| Failure::io("Error reading file", &file, e))?,
+				);
+			}
+		}
+		let dictionary = huffman::Dictionary::train_samples(&samples)
+			.ok_or_else(|| Failure::usage("Samples cannot be empty".to_string()))?;
+		let output_file = sub_matches.value_of("output").unwrap();
+		atomic::write(output_file, &dictionary.to_bytes())
+			.map_err(|e| Failure::io("Error writing file", output_file, e))?;
+		if !matches.is_present("quiet") {
+			println!(
+				"{}: dictionary {:#010x} from {} files",
+				output_file,
+				dictionary.id(),
+				samples.len()
+			);
+		}
+	}
+	if let Some(sub_matches) = matches.subcommand_matches("diff") {
+		let original = sub_matches.value_of("original").unwrap();
+		let file = sub_matches.value_of("file").unwrap();
+		let (input, name) = match volume::base_path(file) {
+			Some(base) => {
+				let data = volume::join(Path::new(base))
+					.map_err(|e| Failure::io("Error reading volumes", file, e))?;
+				(Box::new(io::Cursor::new(data)) as Box<dyn Read>, base)
+			}
+			None => {
+				let input =
+					fs::File::open(file).map_err(|e| Failure::io("Error reading file", file, e))?;
+				(Box::new(io::BufReader::new(input)) as Box<dyn Read>, file)
+			}
+		};
+		let original_input =
+			fs::File::open(original).map_err(|e| Failure::io("Error reading file", original, e))?;
+		let mut comparison = Comparison {
+			original: io::BufReader::new(original_input),
+			offset: 0,
+			mismatch: None,
+		};
+		let result = huffman::decompress_stream(input, &mut comparison, None).map(|_| ());
+		let offset = match (result, comparison.mismatch) {
+			(_, Some(offset)) => Some(offset),
+			(Ok(_), None) => comparison
+				.extra()
+				.map_err(|e| Failure::io("Error reading file", original, e))?,
+			(Err(e), None) => {
+				return Err(Failure::io("Error decompressing file", name, e));
+			}
+		};
+		match offset {
+			Some(offset) => {
+				if !matches.is_present("quiet") {
+					println!("{} and {} differ at byte {}", original, file, offset);
+				}
+				process::exit(EXIT_CORRUPT);
+			}
+			None if matches.is_present("quiet") => {}
+			None => println!("{}: matches {}", file, original),
+		}
+	}
+	if let Some(matches) = matches.subcommand_matches("info") {
+		let file = matches.value_of("file").unwrap();
+		let (data, _) = read_compressed(file, None)?;
+		let members = huffman::inspect(&data)
+			.map_err(|e| Failure::corrupt(format!("Error parsing file: {}: {}", file, e)))?;
+		println!("{}", file);
+		println!("  format version: {}", members[0].version);
+		println!("  blocks: {}", members.len());
+		if let Some(mtime) = members[0].metadata.mtime {
+			println!("  modified: {} seconds since epoch", mtime);
+		}
+		if let Some(mode) = members[0].metadata.mode {
+			println!("  permissions: {:o}", mode);
+		}
+		if let Some(name) = &members[0].metadata.name {
+			println!("  original name: {}", name);
+		}
+		if let Some(id) = members.iter().find_map(|member| member.dictionary_id) {
+			println!("  dictionary: {:#010x}", id);
+		}
+		for (i, member) in members.iter().enumerate() {
+			let original_size = match member.original_size {
+				Some(size) => size.to_string(),
+				None => "unknown".to_string(),
+			};
+			let checksum = match member.checksum {
+				Some(checksum) => format!("{:08x}", checksum),
+				None => "none".to_string(),
+			};
+			println!(
+				"  block {}: {}, tree size {}, {} symbols, padding {}, {} bytes, original size {}, checksum {}",
+				i + 1,
+				member.kind,
+				member.tree_size,
+				member.symbols,
+				member.padding,
+				member.compressed_size,
+				original_size,
+				checksum
+			);
+		}
+	}
+	if let Some(matches) = matches.subcommand_matches("codes") {
+		let file = matches.value_of("file").unwrap();
+		let (data, _) = read_compressed(file, None)?;
+		let tables = if data.starts_with(huffman::MAGIC) {
+			huffman::code_tables(&data, dictionary.as_ref())
+				.map_err(|e| Failure::corrupt(format!("Error parsing file: {}: {}", file, e)))?
+		} else {
+			let text = String::from_utf8(data)
+				.map_err(|_| Failure::corrupt(format!("Not UTF-8 text: {}", file)))?;
+			let table = huffman::CodeTable::for_text(&text)
+				.ok_or_else(|| Failure::usage(format!("No symbols in empty file: {}", file)))?;
+			vec![Some(table)]
+		};
+		for (i, table) in tables.iter().enumerate() {
+			if tables.len() > 1 {
+				println!("block {}:", i + 1);
+			}
+			match table {
+				Some(table) if matches.is_present("histogram") => print_lengths(table),
+				Some(table) => print_codes(table),
+				None => println!("  no symbol codes, not coded with a char tree"),
+			}
+		}
+	}
+	if let Some(matches) = matches.subcommand_matches("analyze") {
+		let file = matches.value_of("file").unwrap();
+		let analysis = fs::File::open(file)
+			.and_then(Analysis::from_reader)
+			.map_err(|e| Failure::io("Error reading file", file, e))?;
+		println!("{}", file);
+		println!(
+			"  size: {} bytes, {} distinct",
+			analysis.bytes,
+			analysis.distinct()
+		);
+		println!("  entropy: {:.4} bits per byte", analysis.entropy());
+		println!(
+			"  estimated size: {} bytes, ratio {:.4}",
+			analysis.estimated_size(),
+			analysis.estimated_ratio()
+		);
+		let mut symbols: Vec<(usize, u64)> = (0..256)
+			.map(|byte| (byte, analysis.symbols[byte]))
+			.filter(|&(_, count)| count != 0)
+			.collect();
+		symbols.sort_by_key(|&(byte, count)| (std::cmp::Reverse(count), byte));
+		let most = symbols.first().map_or(1, |&(_, count)| count);
+		for (byte, count) in symbols {
+			let shown = match byte as u8 {
+				byte @ 0x21..=0x7E => format!("'{}'", byte as char),
+				_ => "   ".to_string(),
+			};
+			println!(
+				"  {} 0x{:02x} {:>12} {:>6.2}% {}",
+				shown,
+				byte,
+				count,
+				100.0 * count as f64 / analysis.bytes as f64,
+				"#".repeat((40 * count).div_ceil(most) as usize)
+			);
+		}
+	}
+	if let Some(matches) = matches.subcommand_matches("stats") {
+		let corpus = matches.value_of("corpus").unwrap();
+		let json = CorpusStats::collect(Path::new(corpus))
+			.map_err(|e| Failure::io("Error reading corpus", corpus, e))?
+			.to_json();
+		match matches.value_of("out") {
+			Some(output_file) => {
+				atomic::write(output_file, json.as_bytes())
+					.map_err(|e| Failure::io("Error writing file", output_file, e))?;
+			}
+			None => print!("{}", json),
+		}
+	}
+	if let Some(matches) = matches.subcommand_matches("diff-archive") {
+		let (old, new) = (
+			matches.value_of("old").unwrap(),
+			matches.value_of("new").unwrap(),
+		);
+		let output_file = matches.value_of("output").unwrap();
+		let presets = load_presets(matches)?;
+		let output = AtomicFile::create(output_file)
+			.map_err(|e| Failure::io("Error writing file", output_file, e))?;
+		let dereference = matches.is_present("dereference");
+		snapshot::diff(
+			Path::new(old),
+			Path::new(new),
+			output,
+			&presets,
+			dereference,
+		)
+		.and_then(|output| output.commit())
+		.map_err(|e| Failure::io("Error creating archive", output_file, e))?;
+	}
+	if let Some(matches) = matches.subcommand_matches("apply") {
+		let old = Path::new(matches.value_of("old").unwrap());
+		let delta = matches.value_of("delta").unwrap();
+		let archive_error = |e| Failure::io("Error reading archive", delta, e);
+		let mut archive = fs::File::open(delta)
+			.and_then(Archive::new)
+			.map_err(archive_error)?;
+		let target = match matches.value_of("output") {
+			Some(new) => {
+				snapshot::copy_dir(old, Path::new(new))
+					.map_err(|e| Failure::io("Error copying directory", new, e))?;
+				Path::new(new)
+			}
+			None => old,
+		};
+		snapshot::apply(&mut archive, target).map_err(archive_error)?;
+		if matches.is_present("self-check") {
+			for entry in archive.entries().to_vec() {
+				let check = archive.check(&entry).map_err(archive_error)?;
+				if let Some(check) = check {
+					println!("{}: {}", entry.path, check);
+				}
+			}
+		}
+	}
+	if let Some(sub_matches) = matches.subcommand_matches("serve") {
+		let mut builder = huffman::Compressor::builder();
+		if let Some(dictionary) = dictionary.as_ref() {
+			builder = builder.dictionary(dictionary);
+		}
+		let compressor = builder.build();
+		let threads = threads(matches);
+		match sub_matches.value_of("http") {
+			Some(address) => {
+				let error = |e| Failure::io("Error listening on", address, e);
+				let listener = TcpListener::bind(address).map_err(error)?;
+				logging::log(Level::Info, format_args!("Listening on http://{}", address));
+				http::serve(listener, &compressor, threads).map_err(error)?;
+			}
+			None => serve(
+				sub_matches.value_of("socket").unwrap(),
+				&compressor,
+				threads,
+			)?,
+		}
+	}
+	if let Some(sub_matches) = matches.subcommand_matches("tunnel") {
+		let mut builder = huffman::Compressor::builder();
+		if let Some(dictionary) = dictionary.as_ref() {
+			builder = builder.dictionary(dictionary);
+		}
+		let listen = host_address(sub_matches.value_of("listen").unwrap(), "0.0.0.0");
+		let connect = host_address(sub_matches.value_of("connect").unwrap(), "localhost");
+		let side = match sub_matches.is_present("decompress") {
+			true => Side::Compressed,
+			false => Side::Plain,
+		};
+		let error = |e| Failure::io("Error listening on", &listen, e);
+		let listener = TcpListener::bind(&listen).map_err(error)?;
+		logging::log(
+			Level::Info,
+			format_args!("Forwarding {} to {}", listen, connect),
+		);
+		tunnel::serve(listener, &connect, side, &builder.build()).map_err(error)?;
+	}
+	if let Some(sub_matches) = matches.subcommand_matches("mount") {
+		mount_archive(
+			sub_matches.value_of("archive").unwrap(),
+			sub_matches.value_of("mountpoint").unwrap(),
+		)?;
+	}
+	if let Some(sub_matches) = matches.subcommand_matches("migrate") {
+		let options = compress_options(matches, dictionary.as_ref());
+		let mut failures = Vec::new();
+		for file in sub_matches.values_of("files").unwrap() {
+			match migrate_file(file, &options, sub_matches.is_present("all")) {
+				Ok(_) if matches.is_present("quiet") => {}
+				Ok(Some((old_size, new_size))) => {
+					println!("{}: migrated, {} to {} bytes", file, old_size, new_size)
+				}
+				Ok(None) => println!("{}: current format already", file),
+				Err(failure) => {
+					eprintln!("{}", failure.message);
+					failures.push(failure);
+				}
+			}
+		}
+		if let Some(failure) = failures.into_iter().next() {
+			process::exit(failure.status);
+		}
+	}
+	if matches.subcommand_matches("selftest").is_some() {
+		let mut failed = 0;
+		for vector in selftest::VECTORS {
+			match vector.run() {
+				Ok(()) if matches.is_present("quiet") => {}
+				Ok(()) => println!("{} ({}): OK", vector.name, vector.codec),
+				Err(message) => {
+					failed += 1;
+					eprintln!("{} ({}): FAILED, {}", vector.name, vector.codec, message);
+				}
+			}
+		}
+		if failed != 0 {
+			eprintln!("{} of {} vectors failed", failed, selftest::VECTORS.len());
+			process::exit(EXIT_CORRUPT);
+		}
 	}
+	Ok(())
 }
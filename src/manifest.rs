@@ -0,0 +1,82 @@
+//! Lists of files to compress, for `--files-from`
+//!
+//! One input path per line, optionally followed by a tab and the output path, and by
+//! more tab separated settings, tabs written as `\t` here
+//! ```text
+//! # Output next to the input, as FILE.cmp
+//! logs/app.log
+//! data/table.csv\tbackup/table.cmp
+//! photos/a.jpg\t\talgo=stored
+//! notes.txt\tnotes.cmp\tmodel=word\tblock-size=64K
+//! ```
+//! An empty output field keeps the default name. Paths may hold spaces but not tabs.
+//! Lines starting with `#` are comments.
+
+/// Settings a line may give, each `key=value`
+pub const KEYS: &[&str] = &["algo", "model", "filter", "block-size"];
+
+/// A line of the list
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+	/// Line number, from 1
+	pub line: usize,
+	pub input: String,
+	pub output: Option<String>,
+	/// Settings as given, keys from `KEYS`, at most once each
+	pub settings: Vec<(String, String)>,
+}
+
+impl Entry {
+	/// Value of the setting `key`
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.settings
+			.iter()
+			.find(|(name, _)| name == key)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+/// Parse a list, reporting the first invalid line
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+	let mut entries = Vec::new();
+	for (number, line) in text.lines().enumerate() {
+		if line.trim().is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let error = |msg: &str| format!("Line {}: {}", number + 1, msg);
+		let mut fields = line.trim_end_matches('\r').split('\t');
+		let input = fields.next().unwrap_or_default();
+		if input.is_empty() {
+			return Err(error("missing input path"));
+		}
+		let output = fields
+			.next()
+			.filter(|output| !output.is_empty())
+			.map(|output| output.to_string());
+		let mut settings: Vec<(String, String)> = Vec::new();
+		for field in fields.filter(|field| !field.is_empty()) {
+			let (key, value) = match field.split_once('=') {
+				Some((key, value)) => (key.trim(), value.trim()),
+				None => return Err(error(&format!("expected `key=value`, not `{}`", field))),
+			};
+			if !KEYS.contains(&key) {
+				return Err(error(&format!(
+					"unknown setting `{}`, expected one of {}",
+					key,
+					KEYS.join(", ")
+				)));
+			}
+			if settings.iter().any(|(name, _)| name == key) {
+				return Err(error(&format!("`{}` set twice", key)));
+			}
+			settings.push((key.to_string(), value.to_string()));
+		}
+		entries.push(Entry {
+			line: number + 1,
+			input: input.to_string(),
+			output,
+			settings,
+		});
+	}
+	Ok(entries)
+}
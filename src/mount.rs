@@ -0,0 +1,551 @@
+//! Read-only file system over an archive, mounted with FUSE on Linux
+//!
+//! Speaks the FUSE kernel protocol on `/dev/fuse` itself. Every entry of the archive
+//! shows as a file or symlink, in the directories its path implies; entries deleted in
+//! a later snapshot don't show. Reading a file decodes only the blocks holding the
+//! bytes read, see `Archive::read_range`.
+//!
+//! Mounting needs root, or else the `fusermount3` or `fusermount` helper of the fuse
+//! package. The file system stays mounted until it is unmounted with `umount` or
+//! `fusermount -u`.
+use crate::archive::{Archive, Entry, EntryKind};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::Path;
+use std::process::Command;
+
+/// Inode of the root directory
+const ROOT: u64 = 1;
+/// Seconds the kernel may cache names and attributes, nothing changes while mounted
+const TTL: u64 = 3600;
+/// Most bytes the kernel asks for in a single read
+const MAX_READ: u32 = 128 << 10;
+/// Text decoded at once, so reads in order don't decode the same block again
+const WINDOW: u64 = 1 << 20;
+/// Length of the header of every request
+const IN_HEADER: usize = 40;
+
+// Requests, see linux/fuse.h
+const LOOKUP: u32 = 1;
+const FORGET: u32 = 2;
+const GETATTR: u32 = 3;
+const READLINK: u32 = 5;
+const OPEN: u32 = 14;
+const READ: u32 = 15;
+const STATFS: u32 = 17;
+const RELEASE: u32 = 18;
+const FLUSH: u32 = 25;
+const INIT: u32 = 26;
+const OPENDIR: u32 = 27;
+const READDIR: u32 = 28;
+const RELEASEDIR: u32 = 29;
+const ACCESS: u32 = 34;
+const INTERRUPT: u32 = 36;
+const DESTROY: u32 = 38;
+const BATCH_FORGET: u32 = 42;
+
+/// Protocol version answered to INIT, 7.31 and later kernels accept it
+const MAJOR: u32 = 7;
+const MINOR: u32 = 31;
+/// Open flag letting the kernel keep the pages of a file read before
+const FOPEN_KEEP_CACHE: u32 = 2;
+
+enum Node {
+	Directory(BTreeMap<String, u64>),
+	File(Entry),
+	Symlink(Entry),
+}
+
+struct Inode {
+	node: Node,
+	parent: u64,
+}
+
+/// The tree of an archive, answering FUSE requests
+pub struct Filesystem<R: Read + Seek> {
+	archive: Archive<R>,
+	/// Inode `n` at index `n - 1`
+	inodes: Vec<Inode>,
+	/// Modification time of directories, that of the newest entry
+	mtime: u64,
+	uid: u32,
+	gid: u32,
+	/// Inode, offset and text of the file data decoded last
+	cache: Option<(u64, u64, Vec<u8>)>,
+}
+
+/// Append `value` to `output` in the byte order of the kernel
+fn put(output: &mut Vec<u8>, value: impl Into<u64>, len: usize) {
+	output.extend(&value.into().to_ne_bytes()[..len]);
+}
+
+fn field(body: &[u8], offset: usize, len: usize) -> u64 {
+	let mut bytes = [0; 8];
+	if let Some(value) = body.get(offset..offset + len) {
+		bytes[..len].copy_from_slice(value);
+	}
+	u64::from_ne_bytes(bytes)
+}
+
+impl<R: Read + Seek> Filesystem<R> {
+	pub fn new(archive: Archive<R>) -> Filesystem<R> {
+		// Later entries replace earlier ones of the same path, as in `Archive::find`
+		let mut latest = BTreeMap::new();
+		for entry in archive.entries() {
+			latest.insert(entry.path.clone(), entry.clone());
+		}
+		let mut inodes = vec![Inode {
+			node: Node::Directory(BTreeMap::new()),
+			parent: ROOT,
+		}];
+		let mut mtime = 0;
+		for (path, entry) in latest {
+			if entry.kind == EntryKind::Deleted {
+				continue;
+			}
+			let names: Vec<&str> = path
+				.split('/')
+				.filter(|&name| !name.is_empty() && name != ".")
+				.collect();
+			if names.is_empty() || names.contains(&"..") {
+				continue;
+			}
+			mtime = mtime.max(entry.mtime.unwrap_or(0));
+			let (last, directories) = names.split_last().unwrap();
+			let mut parent = ROOT;
+			for &name in directories {
+				parent = match &inodes[parent as usize - 1].node {
+					Node::Directory(children) => match children.get(name) {
+						Some(&child) => child,
+						None => {
+							inodes.push(Inode {
+								node: Node::Directory(BTreeMap::new()),
+								parent,
+							});
+							let child = inodes.len() as u64;
+							if let Node::Directory(children) = &mut inodes[parent as usize - 1].node
+							{
+								children.insert(name.to_string(), child);
+							}
+							child
+						}
+					},
+					// A file in the way of the path
+					_ => 0,
+				};
+				if parent == 0 {
+					break;
+				}
+			}
+			let ino = inodes.len() as u64 + 1;
+			match inodes.get_mut(parent.wrapping_sub(1) as usize) {
+				Some(Inode {
+					node: Node::Directory(children),
+					..
+				}) if !children.contains_key(*last) => {
+					children.insert(last.to_string(), ino);
+				}
+				_ => continue,
+			}
+			let node = match entry.kind {
+				EntryKind::Symlink => Node::Symlink(entry),
+				_ => Node::File(entry),
+			};
+			inodes.push(Inode { node, parent });
+		}
+		// SAFETY: getuid and getgid can't fail
+		let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+		Filesystem {
+			archive,
+			inodes,
+			mtime,
+			uid,
+			gid,
+			cache: None,
+		}
+	}
+
+	fn inode(&self, ino: u64) -> Result<&Inode, i32> {
+		ino.checked_sub(1)
+			.and_then(|index| self.inodes.get(index as usize))
+			.ok_or(libc::ENOENT)
+	}
+
+	/// `fuse_attr` of `ino`
+	fn attr(&self, ino: u64) -> Result<Vec<u8>, i32> {
+		let (mode, size, nlink, mtime) = match &self.inode(ino)?.node {
+			Node::Directory(_) => (libc::S_IFDIR | 0o555, 0, 2, self.mtime),
+			Node::File(entry) => (
+				libc::S_IFREG | 0o444,
+				entry.size,
+				1,
+				entry.mtime.unwrap_or(0),
+			),
+			Node::Symlink(entry) => (
+				libc::S_IFLNK | 0o777,
+				entry.size,
+				1,
+				entry.mtime.unwrap_or(0),
+			),
+		};
+		let mut attr = Vec::with_capacity(88);
+		put(&mut attr, ino, 8);
+		put(&mut attr, size, 8);
+		put(&mut attr, size.div_ceil(512), 8);
+		for _ in 0..3 {
+			put(&mut attr, mtime, 8);
+		}
+		for _ in 0..3 {
+			put(&mut attr, 0u32, 4);
+		}
+		put(&mut attr, mode, 4);
+		put(&mut attr, nlink as u32, 4);
+		put(&mut attr, self.uid, 4);
+		put(&mut attr, self.gid, 4);
+		put(&mut attr, 0u32, 4);
+		put(&mut attr, 4096u32, 4);
+		put(&mut attr, 0u32, 4);
+		Ok(attr)
+	}
+
+	/// `fuse_entry_out` of `ino`
+	fn entry_out(&self, ino: u64) -> Result<Vec<u8>, i32> {
+		let mut reply = Vec::with_capacity(128);
+		put(&mut reply, ino, 8);
+		put(&mut reply, 0u64, 8);
+		put(&mut reply, TTL, 8);
+		put(&mut reply, TTL, 8);
+		put(&mut reply, 0u64, 8);
+		reply.extend(self.attr(ino)?);
+		Ok(reply)
+	}
+
+	/// `fuse_open_out`, for files only if `directory` is false and the other way round
+	fn open(&self, ino: u64, directory: bool, body: &[u8]) -> Result<Vec<u8>, i32> {
+		match (&self.inode(ino)?.node, directory) {
+			(Node::Directory(_), true) | (Node::File(_), false) => {}
+			(Node::Directory(_), false) => return Err(libc::EISDIR),
+			_ => return Err(libc::ENOTDIR),
+		}
+		if field(body, 0, 4) as i32 & libc::O_ACCMODE != libc::O_RDONLY {
+			return Err(libc::EROFS);
+		}
+		let mut reply = Vec::with_capacity(16);
+		put(&mut reply, 0u64, 8);
+		put(&mut reply, FOPEN_KEEP_CACHE, 4);
+		put(&mut reply, 0u32, 4);
+		Ok(reply)
+	}
+
+	/// Up to `size` bytes of the file `ino` from `offset` on
+	fn read(&mut self, ino: u64, offset: u64, size: u64) -> Result<Vec<u8>, i32> {
+		let entry = match &self.inode(ino)?.node {
+			Node::File(entry) => entry.clone(),
+			_ => return Err(libc::EISDIR),
+		};
+		let end = offset.saturating_add(size).min(entry.size);
+		if offset >= end {
+			return Ok(Vec::new());
+		}
+		let cached = match &self.cache {
+			Some((cached, start, text)) => {
+				*cached == ino && *start <= offset && end <= start + text.len() as u64
+			}
+			None => false,
+		};
+		if !cached {
+			let start = offset - offset % WINDOW;
+			let text = self
+				.archive
+				.read_range(&entry, start..end.max(start + WINDOW))
+				.map_err(|_| libc::EIO)?;
+			// The index may claim more text than the member decodes to
+			if start + (text.len() as u64) < end {
+				return Err(libc::EIO);
+			}
+			self.cache = Some((ino, start, text));
+		}
+		let (_, start, text) = self.cache.as_ref().unwrap();
+		let from = (offset - start) as usize;
+		Ok(text[from..(end - start) as usize].to_vec())
+	}
+
+	/// Entries of the directory `ino` from `offset` on, as many as fit in `size` bytes
+	fn read_dir(&self, ino: u64, offset: u64, size: usize) -> Result<Vec<u8>, i32> {
+		let inode = self.inode(ino)?;
+		let children = match &inode.node {
+			Node::Directory(children) => children,
+			_ => return Err(libc::ENOTDIR),
+		};
+		let mut entries = vec![(".", ino), ("..", inode.parent)];
+		entries.extend(children.iter().map(|(name, &child)| (name.as_str(), child)));
+		let mut reply = Vec::new();
+		for (index, (name, child)) in entries.into_iter().enumerate().skip(offset as usize) {
+			let kind = match self.inode(child)?.node {
+				Node::Directory(_) => libc::DT_DIR,
+				Node::File(_) => libc::DT_REG,
+				Node::Symlink(_) => libc::DT_LNK,
+			};
+			let len = (24 + name.len()).next_multiple_of(8);
+			if reply.len() + len > size {
+				break;
+			}
+			put(&mut reply, child, 8);
+			put(&mut reply, index as u64 + 1, 8);
+			put(&mut reply, name.len() as u32, 4);
+			put(&mut reply, kind as u32, 4);
+			reply.extend(name.as_bytes());
+			reply.resize(reply.len() + len - 24 - name.len(), 0);
+		}
+		Ok(reply)
+	}
+
+	/// `fuse_kstatfs` of the whole file system
+	fn statfs(&self) -> Vec<u8> {
+		let bytes: u64 = self
+			.inodes
+			.iter()
+			.map(|inode| match &inode.node {
+				Node::File(entry) | Node::Symlink(entry) => entry.size,
+				Node::Directory(_) => 0,
+			})
+			.sum();
+		let mut reply = Vec::with_capacity(80);
+		put(&mut reply, bytes.div_ceil(4096), 8);
+		put(&mut reply, 0u64, 8);
+		put(&mut reply, 0u64, 8);
+		put(&mut reply, self.inodes.len() as u64, 8);
+		put(&mut reply, 0u64, 8);
+		put(&mut reply, 4096u32, 4);
+		put(&mut reply, 255u32, 4);
+		put(&mut reply, 4096u32, 4);
+		reply.resize(80, 0);
+		reply
+	}
+
+	/// Reply to the request `opcode` on `ino`, `None` for requests that get none
+	fn handle(&mut self, opcode: u32, ino: u64, body: &[u8]) -> Option<Result<Vec<u8>, i32>> {
+		let reply = match opcode {
+			INIT => {
+				let mut reply = Vec::with_capacity(64);
+				put(&mut reply, MAJOR, 4);
+				put(&mut reply, MINOR, 4);
+				put(&mut reply, field(body, 8, 4), 4);
+				put(&mut reply, 0u32, 4);
+				put(&mut reply, 16u16, 2);
+				put(&mut reply, 12u16, 2);
+				put(&mut reply, MAX_READ, 4);
+				put(&mut reply, 1u32, 4);
+				reply.resize(64, 0);
+				Ok(reply)
+			}
+			LOOKUP => {
+				let name = body.split(|&byte| byte == 0).next().unwrap_or(&[]);
+				match &self.inode(ino) {
+					Ok(Inode {
+						node: Node::Directory(children),
+						..
+					}) => std::str::from_utf8(name)
+						.ok()
+						.and_then(|name| children.get(name))
+						.ok_or(libc::ENOENT)
+						.and_then(|&child| self.entry_out(child)),
+					Ok(_) => Err(libc::ENOTDIR),
+					Err(e) => Err(*e),
+				}
+			}
+			GETATTR => self.attr(ino).map(|attr| {
+				let mut reply = Vec::with_capacity(104);
+				put(&mut reply, TTL, 8);
+				put(&mut reply, 0u64, 8);
+				reply.extend(attr);
+				reply
+			}),
+			READLINK => match &self.inode(ino).map(|inode| &inode.node) {
+				Ok(Node::Symlink(entry)) => {
+					let entry = entry.clone();
+					self.archive
+						.read_link(&entry)
+						.map(String::into_bytes)
+						.map_err(|_| libc::EIO)
+				}
+				Ok(_) => Err(libc::EINVAL),
+				Err(e) => Err(*e),
+			},
+			OPEN => self.open(ino, false, body),
+			OPENDIR => self.open(ino, true, body),
+			READ => self.read(ino, field(body, 8, 8), field(body, 16, 4)),
+			READDIR => self.read_dir(ino, field(body, 8, 8), field(body, 16, 4) as usize),
+			STATFS => Ok(self.statfs()),
+			ACCESS if field(body, 0, 4) as i32 & libc::W_OK != 0 => Err(libc::EROFS),
+			ACCESS | RELEASE | RELEASEDIR | FLUSH | DESTROY => Ok(Vec::new()),
+			FORGET | BATCH_FORGET | INTERRUPT => return None,
+			_ => Err(libc::ENOSYS),
+		};
+		Some(reply)
+	}
+
+	/// Answer the requests read from `fuse` until the file system is unmounted
+	pub fn serve(&mut self, fuse: &File) -> io::Result<()> {
+		let mut buffer = vec![0; MAX_READ as usize + 4096];
+		loop {
+			let len = match (&*fuse).read(&mut buffer) {
+				Ok(len) => len,
+				Err(e) => match e.raw_os_error() {
+					Some(libc::ENODEV) => return Ok(()),
+					Some(libc::EINTR) | Some(libc::EAGAIN) | Some(libc::ENOENT) => continue,
+					_ => return Err(e),
+				},
+			};
+			if len < IN_HEADER {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					"Short FUSE request",
+				));
+			}
+			let opcode = field(&buffer, 4, 4) as u32;
+			let unique = field(&buffer, 8, 8);
+			let ino = field(&buffer, 16, 8);
+			let reply = match self.handle(opcode, ino, &buffer[IN_HEADER..len]) {
+				Some(reply) => reply,
+				None => continue,
+			};
+			let (error, data) = match reply {
+				Ok(data) => (0, data),
+				Err(errno) => (-errno, Vec::new()),
+			};
+			let mut message = Vec::with_capacity(16 + data.len());
+			put(&mut message, (16 + data.len()) as u32, 4);
+			put(&mut message, error as u32, 4);
+			put(&mut message, unique, 8);
+			message.extend(data);
+			// Fails with ENOENT when the request was interrupted meanwhile
+			if let Err(e) = (&*fuse).write(&message) {
+				if e.raw_os_error() != Some(libc::ENOENT) {
+					return Err(e);
+				}
+			}
+			if opcode == DESTROY {
+				return Ok(());
+			}
+		}
+	}
+}
+
+fn cstring(bytes: &[u8]) -> io::Result<CString> {
+	CString::new(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Mount a FUSE file system on `mountpoint` with the system call, which needs root
+fn mount_directly(mountpoint: &Path, source: &str) -> io::Result<File> {
+	let fuse = OpenOptions::new()
+		.read(true)
+		.write(true)
+		.open("/dev/fuse")?;
+	let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+	let options = format!(
+		"fd={},rootmode=40000,user_id={},group_id={},max_read={}",
+		fuse.as_raw_fd(),
+		uid,
+		gid,
+		MAX_READ
+	);
+	let target = cstring(mountpoint.as_os_str().as_bytes())?;
+	let (source, options) = (cstring(source.as_bytes())?, cstring(options.as_bytes())?);
+	// SAFETY: every pointer is to a live, NUL terminated string
+	let result = unsafe {
+		libc::mount(
+			source.as_ptr(),
+			target.as_ptr(),
+			b"fuse.rust_compression\0".as_ptr().cast(),
+			libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV,
+			options.as_ptr().cast(),
+		)
+	};
+	if result != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(fuse)
+}
+
+/// Mount a FUSE file system on `mountpoint` with the setuid helper of the fuse package,
+/// which mounts it and passes the open `/dev/fuse` back over a socket
+fn mount_with_helper(mountpoint: &Path, source: &str) -> io::Result<File> {
+	let mut sockets = [0 as RawFd; 2];
+	// SAFETY: `sockets` has room for both descriptors
+	if unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, sockets.as_mut_ptr()) } != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	// SAFETY: both descriptors were just created and belong to nothing else
+	let (ours, theirs) = unsafe { (File::from_raw_fd(sockets[0]), File::from_raw_fd(sockets[1])) };
+	let options = format!("ro,nosuid,nodev,fsname={},subtype=rust_compression", source);
+	let mut status = Err(io::Error::from(io::ErrorKind::NotFound));
+	for helper in ["fusermount3", "fusermount"] {
+		status = Command::new(helper)
+			.arg("-o")
+			.arg(&options)
+			.arg("--")
+			.arg(mountpoint)
+			.env("_FUSE_COMMFD", theirs.as_raw_fd().to_string())
+			.status();
+		if !matches!(&status, Err(e) if e.kind() == io::ErrorKind::NotFound) {
+			break;
+		}
+	}
+	if !status?.success() {
+		return Err(io::Error::other(format!("{} could not be mounted", source)));
+	}
+	drop(theirs);
+
+	let mut byte = [0u8; 1];
+	let mut iov = libc::iovec {
+		iov_base: byte.as_mut_ptr().cast(),
+		iov_len: 1,
+	};
+	// Room for a control message holding one descriptor, aligned for its header
+	let mut control = [0u64; 8];
+	// SAFETY: msghdr is plain data, all zeros is a valid empty message
+	let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+	message.msg_iov = &mut iov;
+	message.msg_iovlen = 1;
+	message.msg_control = control.as_mut_ptr().cast();
+	message.msg_controllen = std::mem::size_of_val(&control) as _;
+	// SAFETY: `message` points to live buffers of the lengths it gives
+	let fd = unsafe {
+		if libc::recvmsg(ours.as_raw_fd(), &mut message, 0) < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		let header = libc::CMSG_FIRSTHDR(&message);
+		if header.is_null()
+			|| (*header).cmsg_level != libc::SOL_SOCKET
+			|| (*header).cmsg_type != libc::SCM_RIGHTS
+		{
+			return Err(io::Error::other("Mount helper passed no file descriptor"));
+		}
+		(libc::CMSG_DATA(header) as *const RawFd).read_unaligned()
+	};
+	// SAFETY: the descriptor was passed to this process and belongs to nothing else
+	Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Mount `archive` read-only on `mountpoint`, named `source` in the mount table, and
+/// serve it until it is unmounted
+pub fn mount<R: Read + Seek>(
+	archive: Archive<R>,
+	mountpoint: &Path,
+	source: &str,
+) -> io::Result<()> {
+	let mut filesystem = Filesystem::new(archive);
+	// Mount options are separated by commas
+	let source = source.replace(',', "_");
+	let source = source.as_str();
+	let fuse = match mount_directly(mountpoint, source) {
+		Err(e) if e.raw_os_error() == Some(libc::EPERM) => mount_with_helper(mountpoint, source)?,
+		fuse => fuse?,
+	};
+	filesystem.serve(&fuse)
+}
@@ -0,0 +1,258 @@
+//! Huffman codes over an alphabet of `arity` digits instead of bits, e.g. ternary codes
+//! for channels that carry three signal levels
+//!
+//! Every step merges the `arity` least frequent nodes. Unless the number of symbols
+//! is 1 more than a multiple of `arity - 1`, the tree can't be full, so symbols that
+//! never occur are added first to make up for it: they are merged first, end up at
+//! the deepest level and get no code, which keeps the codes of the others optimal.
+//!
+//! Codes are canonical as in `canonical`, with digits in place of bits: shorter codes
+//! first, equal lengths by symbol index, so the code lengths describe them fully.
+//!
+//! `DigitWriter` and `DigitReader` pack digits into bytes, for storing codes where the
+//! channel itself doesn't carry digits.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Read, Write};
+
+fn check_arity(arity: u8) {
+	assert!(arity >= 2, "Codes need at least 2 digits");
+}
+
+/// Code lengths, in digits, of an optimal prefix code over `arity` digits for the given
+/// symbol counts. A symbol occurring on its own still gets a 1 digit code
+pub fn code_lengths(frequencies: &[u64], arity: u8) -> Vec<u8> {
+	check_arity(arity);
+	let arity = arity as usize;
+	let mut heap: BinaryHeap<Reverse<(u64, usize)>> = frequencies
+		.iter()
+		.enumerate()
+		.filter(|(_, &count)| count != 0)
+		.map(|(symbol, &count)| Reverse((count, symbol)))
+		.collect();
+	let mut lengths = vec![0; frequencies.len()];
+	if heap.len() == 1 {
+		let Reverse((_, symbol)) = heap.pop().unwrap();
+		lengths[symbol] = 1;
+		return lengths;
+	}
+	// Nodes are numbered symbols first, then dummies, then every parent after its children
+	let mut parent = vec![0; frequencies.len()];
+	let dummies = (arity - 1 - (heap.len().max(1) - 1) % (arity - 1)) % (arity - 1);
+	for _ in 0..dummies {
+		heap.push(Reverse((0, parent.len())));
+		parent.push(0);
+	}
+	while heap.len() > 1 {
+		let node = parent.len();
+		parent.push(0);
+		let mut sum = 0;
+		for _ in 0..arity {
+			let Reverse((count, child)) = heap.pop().unwrap();
+			sum += count;
+			parent[child] = node;
+		}
+		heap.push(Reverse((sum, node)));
+	}
+	let mut depth = vec![0u8; parent.len()];
+	for node in (0..parent.len().saturating_sub(1)).rev() {
+		depth[node] = depth[parent[node]] + 1;
+	}
+	for (symbol, length) in lengths.iter_mut().enumerate() {
+		if frequencies[symbol] != 0 {
+			*length = depth[symbol];
+		}
+	}
+	lengths
+}
+
+/// Number of codes of every length, 0 to the longest
+fn counts(lengths: &[u8]) -> Vec<u64> {
+	let max = lengths.iter().copied().max().unwrap_or(0) as usize;
+	let mut counts = vec![0u64; max + 1];
+	for &length in lengths {
+		counts[length as usize] += 1;
+	}
+	counts[0] = 0;
+	counts
+}
+
+/// Code of every symbol as its digits, most significant first, assigned in canonical
+/// order. Symbols of length 0 get none
+///
+/// Lengths must come from `code_lengths` or be checked by `Decoder::new`, and
+/// `arity` to the power of the longest must fit 128 bits
+pub fn codes(lengths: &[u8], arity: u8) -> Vec<Vec<u8>> {
+	check_arity(arity);
+	let counts = counts(lengths);
+	let mut next = vec![0u128; counts.len()];
+	let mut code = 0;
+	for length in 1..counts.len() {
+		code = (code + counts[length - 1] as u128) * arity as u128;
+		next[length] = code;
+	}
+	lengths
+		.iter()
+		.map(|&length| {
+			let mut value = next[length as usize];
+			next[length as usize] += 1;
+			let mut digits = vec![0; length as usize];
+			for digit in digits.iter_mut().rev() {
+				*digit = (value % arity as u128) as u8;
+				value /= arity as u128;
+			}
+			digits
+		})
+		.collect()
+}
+
+/// Decodes symbols of a canonical code over `arity` digits
+pub struct Decoder {
+	arity: u8,
+	/// Number of codes of every length
+	counts: Vec<u64>,
+	/// Symbols in canonical order
+	symbols: Vec<usize>,
+}
+
+impl Decoder {
+	/// Returns `None` if the lengths don't describe a prefix code over `arity` digits,
+	/// or codes too long to fit 128 bits
+	pub fn new(lengths: &[u8], arity: u8) -> Option<Decoder> {
+		check_arity(arity);
+		let counts = counts(lengths);
+		(arity as u128).checked_pow(counts.len() as u32 - 1)?;
+		// Kraft inequality, the number of codes of every length left over
+		let mut free: u128 = 1;
+		for &count in &counts[1..] {
+			free = free
+				.checked_mul(arity as u128)?
+				.checked_sub(count as u128)?;
+			// Enough room for every code to come
+			free = free.min(lengths.len() as u128);
+		}
+		let mut symbols: Vec<usize> = (0..lengths.len()).filter(|&s| lengths[s] != 0).collect();
+		symbols.sort_by_key(|&symbol| lengths[symbol]);
+		Some(Decoder {
+			arity,
+			counts,
+			symbols,
+		})
+	}
+	/// Read the next symbol, `None` if the digits run out or match no code
+	pub fn decode<R: Read>(&self, digits: &mut DigitReader<R>) -> io::Result<Option<usize>> {
+		let arity = self.arity as u128;
+		let (mut code, mut first, mut index) = (0u128, 0u128, 0u128);
+		for &count in &self.counts[1..] {
+			match digits.read_digit()? {
+				Some(digit) => code += digit as u128,
+				None => return Ok(None),
+			}
+			if code - first < count as u128 {
+				return Ok(Some(self.symbols[(index + code - first) as usize]));
+			}
+			index += count as u128;
+			first = (first + count as u128) * arity;
+			code *= arity;
+		}
+		Ok(None)
+	}
+}
+
+/// Digits of `arity` packed into a little endian u64 as a number in base `arity`
+fn digits_per_word(arity: u8) -> u32 {
+	(0..)
+		.take_while(|&digits| (arity as u128).pow(digits) <= 1 << 64)
+		.last()
+		.unwrap()
+}
+
+/// Writes digits of `arity`, packing as many into every 8 bytes as fit
+///
+/// The digits of a word are a number in base `arity`, the first digit least
+/// significant. The last word is completed with zero digits
+pub struct DigitWriter<W: Write> {
+	output: W,
+	arity: u8,
+	word: u64,
+	/// Value of the next digit in `word`
+	scale: u64,
+	digits: u32,
+}
+
+impl<W: Write> DigitWriter<W> {
+	pub fn new(output: W, arity: u8) -> DigitWriter<W> {
+		check_arity(arity);
+		DigitWriter {
+			output,
+			arity,
+			word: 0,
+			scale: 1,
+			digits: 0,
+		}
+	}
+	/// Write `digit`, which must be below the arity
+	pub fn write_digit(&mut self, digit: u8) -> io::Result<()> {
+		assert!(digit < self.arity, "Digit beyond the arity");
+		self.word += digit as u64 * self.scale;
+		self.digits += 1;
+		if self.digits == digits_per_word(self.arity) {
+			self.output.write_all(&self.word.to_le_bytes())?;
+			self.word = 0;
+			self.scale = 1;
+			self.digits = 0;
+		} else {
+			self.scale *= self.arity as u64;
+		}
+		Ok(())
+	}
+	/// Write every digit of `code`
+	pub fn write_code(&mut self, code: &[u8]) -> io::Result<()> {
+		code.iter().try_for_each(|&digit| self.write_digit(digit))
+	}
+	/// Write the last word, if any digits are left, returning the output
+	pub fn finish(mut self) -> io::Result<W> {
+		if self.digits > 0 {
+			self.output.write_all(&self.word.to_le_bytes())?;
+		}
+		Ok(self.output)
+	}
+}
+
+/// Reads digits written by `DigitWriter`
+pub struct DigitReader<R: Read> {
+	input: R,
+	arity: u8,
+	word: u64,
+	/// Digits left in `word`
+	digits: u32,
+}
+
+impl<R: Read> DigitReader<R> {
+	pub fn new(input: R, arity: u8) -> DigitReader<R> {
+		check_arity(arity);
+		DigitReader {
+			input,
+			arity,
+			word: 0,
+			digits: 0,
+		}
+	}
+	/// The next digit, `None` at the end of the input
+	pub fn read_digit(&mut self) -> io::Result<Option<u8>> {
+		if self.digits == 0 {
+			let mut word = [0; 8];
+			match self.input.read_exact(&mut word) {
+				Ok(()) => {}
+				Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+				Err(e) => return Err(e),
+			}
+			self.word = u64::from_le_bytes(word);
+			self.digits = digits_per_word(self.arity);
+		}
+		let digit = (self.word % self.arity as u64) as u8;
+		self.word /= self.arity as u64;
+		self.digits -= 1;
+		Ok(Some(digit))
+	}
+}
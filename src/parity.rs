@@ -0,0 +1,348 @@
+//! Reed–Solomon parity, to rebuild damaged parts of a compressed file
+//!
+//! The data is split into shards of equal size, the last one completed with zeros,
+//! and parity shards are computed from them with a Cauchy matrix over GF(2^8). Any
+//! data shards that are damaged can be rebuilt from as many intact parity shards.
+//! A CRC-32 of every shard tells which ones are damaged.
+//!
+//! # Data Format
+//! The parity is appended to the data it protects:
+//! - Magic number `HUP\x01`
+//! - Length of the protected data (u64)
+//! - Shard size (u32)
+//! - Number of data shards (u8)
+//! - Number of parity shards (u8)
+//! - CRC-32 of every data shard, then of every parity shard (u32)
+//! - CRC-32 of the fields above (u32)
+//! - Parity shards
+//! - Length of the parity from its magic number on (u64)
+//! - Magic number `HUP\x01`
+//!
+//! The closing magic number finds the parity from the end of the file. All integers
+//! are little endian.
+use crate::checksum::crc32;
+use crate::cursor::Cursor;
+use std::convert::TryInto;
+
+pub const MAGIC: &[u8] = b"HUP\x01";
+/// Smallest shard worth splitting the data for
+const MIN_SHARD: usize = 512;
+/// Data and parity shards together, every one needs its own element of GF(2^8)
+const MAX_SHARDS: usize = 255;
+/// Bytes after the parity shards
+const FOOTER: usize = 12;
+
+/// Powers of the generator 2 for the polynomial 0x11D, twice over so products of two
+/// logarithms need no reduction
+const EXP: [u8; 512] = {
+	let mut table = [0; 512];
+	let mut value: u16 = 1;
+	let mut i = 0;
+	while i < 512 {
+		table[i] = value as u8;
+		value <<= 1;
+		if value & 0x100 != 0 {
+			value ^= 0x11D;
+		}
+		i += 1;
+	}
+	table
+};
+
+/// Logarithms to the base 2, undefined for 0
+const LOG: [u8; 256] = {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 255 {
+		table[EXP[i] as usize] = i as u8;
+		i += 1;
+	}
+	table
+};
+
+fn mul(a: u8, b: u8) -> u8 {
+	if a == 0 || b == 0 {
+		0
+	} else {
+		EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+	}
+}
+
+fn inv(a: u8) -> u8 {
+	EXP[255 - LOG[a as usize] as usize]
+}
+
+/// Coefficient of data shard `data` in parity shard `parity`, with `data_shards` in all
+fn coefficient(data_shards: usize, parity: usize, data: usize) -> u8 {
+	inv((data_shards + parity) as u8 ^ data as u8)
+}
+
+/// Add `shard` times `factor` to `output`
+fn add_scaled(output: &mut [u8], shard: &[u8], factor: u8) {
+	let products: Vec<u8> = (0..=255).map(|byte| mul(factor, byte)).collect();
+	for (out, &byte) in output.iter_mut().zip(shard) {
+		*out ^= products[byte as usize];
+	}
+}
+
+/// Invert a square matrix, which must be invertible
+fn invert(mut matrix: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+	let n = matrix.len();
+	let mut inverse: Vec<Vec<u8>> = (0..n)
+		.map(|row| (0..n).map(|column| (row == column) as u8).collect())
+		.collect();
+	for column in 0..n {
+		let pivot = (column..n)
+			.find(|&row| matrix[row][column] != 0)
+			.expect("Cauchy matrices are invertible");
+		matrix.swap(column, pivot);
+		inverse.swap(column, pivot);
+		let factor = inv(matrix[column][column]);
+		for value in matrix[column].iter_mut().chain(inverse[column].iter_mut()) {
+			*value = mul(*value, factor);
+		}
+		for row in 0..n {
+			let factor = matrix[row][column];
+			if row == column || factor == 0 {
+				continue;
+			}
+			for i in 0..n {
+				matrix[row][i] ^= mul(factor, matrix[column][i]);
+				inverse[row][i] ^= mul(factor, inverse[column][i]);
+			}
+		}
+	}
+	inverse
+}
+
+/// How the protected data is split
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout {
+	pub data_shards: usize,
+	pub parity_shards: usize,
+	pub shard_size: usize,
+}
+
+impl Layout {
+	/// Layout for `len` bytes of data with parity of at least `percent` percent of it
+	fn new(len: usize, percent: u32) -> Layout {
+		let parity_for = |data_shards: usize| (data_shards * percent as usize).div_ceil(100);
+		let mut data_shards = len.div_ceil(MIN_SHARD).clamp(1, MAX_SHARDS - 1);
+		while data_shards + parity_for(data_shards) > MAX_SHARDS {
+			data_shards -= 1;
+		}
+		Layout {
+			data_shards,
+			parity_shards: parity_for(data_shards).max(1),
+			shard_size: len.div_ceil(data_shards).max(1),
+		}
+	}
+	fn shards(&self) -> usize {
+		self.data_shards + self.parity_shards
+	}
+	/// Length of the parity from its magic number to its end
+	fn len(&self) -> usize {
+		MAGIC.len() + 14 + 4 * self.shards() + 4 + self.parity_shards * self.shard_size + FOOTER
+	}
+}
+
+/// Data shard `index` of `data`, without the zeros completing the last one
+fn data_shard(data: &[u8], layout: Layout, index: usize) -> &[u8] {
+	let start = (index * layout.shard_size).min(data.len());
+	&data[start..(start + layout.shard_size).min(data.len())]
+}
+
+/// CRC-32 of `shard`, completed with zeros to `shard_size`
+fn shard_crc(shard: &[u8], shard_size: usize) -> u32 {
+	let mut padded = shard.to_vec();
+	padded.resize(shard_size, 0);
+	crc32(&padded)
+}
+
+/// Parity to append to `data` so that damaged parts of `percent` percent of it can be
+/// rebuilt, `percent` must be from 1 to 100
+pub fn protect(data: &[u8], percent: u32) -> Vec<u8> {
+	assert!((1..=100).contains(&percent), "Parity from 1 to 100 percent");
+	let layout = Layout::new(data.len(), percent);
+	let parity: Vec<Vec<u8>> = (0..layout.parity_shards)
+		.map(|parity| {
+			let mut shard = vec![0; layout.shard_size];
+			for index in 0..layout.data_shards {
+				let factor = coefficient(layout.data_shards, parity, index);
+				add_scaled(&mut shard, data_shard(data, layout, index), factor);
+			}
+			shard
+		})
+		.collect();
+
+	let mut output = MAGIC.to_vec();
+	output.extend(&(data.len() as u64).to_le_bytes());
+	output.extend(&(layout.shard_size as u32).to_le_bytes());
+	output.push(layout.data_shards as u8);
+	output.push(layout.parity_shards as u8);
+	for index in 0..layout.data_shards {
+		let crc = shard_crc(data_shard(data, layout, index), layout.shard_size);
+		output.extend(&crc.to_le_bytes());
+	}
+	for shard in &parity {
+		output.extend(&crc32(shard).to_le_bytes());
+	}
+	output.extend(&crc32(&output).to_le_bytes());
+	for shard in &parity {
+		output.extend(shard);
+	}
+	output.extend(&(layout.len() as u64).to_le_bytes());
+	output.extend(MAGIC);
+	output
+}
+
+/// The parity appended to data, found by `find`
+struct Parity<'a> {
+	/// Offset of its magic number
+	start: usize,
+	layout: Layout,
+	crcs: Vec<u32>,
+	/// Parity shards, as far as the data holds them
+	shards: &'a [u8],
+}
+
+/// Parse parity starting at `start` in `data`, if it protects the data before it
+fn parse(data: &[u8], start: usize) -> Option<Parity<'_>> {
+	let mut cursor = Cursor::new(&data[start..]);
+	if cursor.take(MAGIC.len(), "magic").ok()? != MAGIC
+		|| cursor.u64("data length").ok()? != start as u64
+	{
+		return None;
+	}
+	let shard_size = cursor.u32("shard size").ok()? as usize;
+	let data_shards = cursor.u8("data shards").ok()? as usize;
+	let parity_shards = cursor.u8("parity shards").ok()? as usize;
+	let crcs = cursor
+		.take(4 * (data_shards + parity_shards), "shard checksums")
+		.ok()?
+		.chunks(4)
+		.map(|crc| u32::from_le_bytes(crc.try_into().unwrap()))
+		.collect();
+	let header = &data[start..start + cursor.position()];
+	if cursor.u32("parity checksum").ok()? != crc32(header)
+		|| data_shards == 0
+		|| parity_shards == 0
+		|| data_shards + parity_shards > MAX_SHARDS
+		|| data_shards * shard_size < start
+	{
+		return None;
+	}
+	Some(Parity {
+		start,
+		layout: Layout {
+			data_shards,
+			parity_shards,
+			shard_size,
+		},
+		crcs,
+		shards: cursor.rest(),
+	})
+}
+
+/// Parity at the end of `data`, by its closing magic number or else by searching for
+/// an intact start if the end is damaged
+fn find(data: &[u8]) -> Option<Parity<'_>> {
+	let by_footer = || {
+		let footer = data.get(data.len().checked_sub(FOOTER)?..)?;
+		if &footer[8..] != MAGIC {
+			return None;
+		}
+		let len = u64::from_le_bytes(footer[..8].try_into().unwrap());
+		parse(data, data.len().checked_sub(len.try_into().ok()?)?)
+	};
+	by_footer().or_else(|| {
+		data.windows(MAGIC.len())
+			.enumerate()
+			.rev()
+			.filter(|&(_, window)| window == MAGIC)
+			.find_map(|(start, _)| parse(data, start))
+	})
+}
+
+/// Outcome of `repair`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Repair {
+	pub layout: Layout,
+	/// Data and parity shards found damaged
+	pub damaged: usize,
+	/// Whether all damaged data shards were rebuilt
+	pub repaired: bool,
+}
+
+/// Remove the parity from the end of `data` and rebuild the damaged shards of the data
+/// before it, as far as the parity allows. `None` if `data` has no parity, and it is
+/// left as it is
+pub fn repair(data: &mut Vec<u8>) -> Option<Repair> {
+	let Parity {
+		start,
+		layout,
+		crcs,
+		shards,
+	} = find(data)?;
+	let parity_shards: Vec<Option<&[u8]>> = (0..layout.parity_shards)
+		.map(|parity| {
+			shards
+				.get(parity * layout.shard_size..(parity + 1) * layout.shard_size)
+				.filter(|&shard| crc32(shard) == crcs[layout.data_shards + parity])
+		})
+		.collect();
+	let parity_shards: Vec<(usize, Vec<u8>)> = parity_shards
+		.into_iter()
+		.enumerate()
+		.filter_map(|(parity, shard)| Some((parity, shard?.to_vec())))
+		.collect();
+	data.truncate(start);
+
+	let missing: Vec<usize> = (0..layout.data_shards)
+		.filter(|&index| {
+			shard_crc(data_shard(data, layout, index), layout.shard_size) != crcs[index]
+		})
+		.collect();
+	let damaged = missing.len() + layout.parity_shards - parity_shards.len();
+	let repaired = missing.len() <= parity_shards.len();
+	if !missing.is_empty() && repaired {
+		// Every parity shard used, less the intact data shards in it, is a sum of the
+		// missing ones, which the inverse of their coefficients turns back
+		let used = &parity_shards[..missing.len()];
+		let sums: Vec<Vec<u8>> = used
+			.iter()
+			.map(|(parity, shard)| {
+				let mut sum = shard.clone();
+				for index in (0..layout.data_shards).filter(|index| !missing.contains(index)) {
+					let factor = coefficient(layout.data_shards, *parity, index);
+					add_scaled(&mut sum, data_shard(data, layout, index), factor);
+				}
+				sum
+			})
+			.collect();
+		let inverse = invert(
+			used.iter()
+				.map(|(parity, _)| {
+					missing
+						.iter()
+						.map(|&index| coefficient(layout.data_shards, *parity, index))
+						.collect()
+				})
+				.collect(),
+		);
+		for (row, &index) in inverse.iter().zip(&missing) {
+			let mut shard = vec![0; layout.shard_size];
+			for (&factor, sum) in row.iter().zip(&sums) {
+				add_scaled(&mut shard, sum, factor);
+			}
+			let start = index * layout.shard_size;
+			let end = (start + layout.shard_size).min(data.len());
+			data[start..end].copy_from_slice(&shard[..end - start]);
+		}
+	}
+	Some(Repair {
+		layout,
+		damaged,
+		repaired,
+	})
+}
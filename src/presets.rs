@@ -0,0 +1,160 @@
+//! Per-extension default settings for archiving and recursive compression
+//!
+//! A preset file maps file extensions to settings, one per line, several of them separated
+//! by commas
+//! ```text
+//! # Already compressed, don't bother
+//! jpg = stored
+//! .png = stored
+//! txt = huffman
+//! log = model word, block-size 1M
+//! csv = filter delta:4
+//! ```
+//! `model`, `filter` and `block-size` take the values of the options of the same names
+//! and imply huffman. Extensions are matched case-insensitively, the leading dot is
+//! optional. Lines starting with `#` are comments.
+use crate::archive::Method;
+use crate::compress::huffman::{CompressOptions, Filter, Model};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Settings applied to a single file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Settings {
+	pub method: Method,
+	/// Huffman settings, `None` where the preset leaves them to the command line
+	pub model: Option<Model>,
+	pub filter: Option<Filter>,
+	pub block_size: Option<usize>,
+}
+
+impl Default for Settings {
+	fn default() -> Settings {
+		Settings {
+			method: Method::Huffman,
+			model: None,
+			filter: None,
+			block_size: None,
+		}
+	}
+}
+
+impl Settings {
+	/// `options` with what these settings set
+	pub fn apply<'a>(&self, options: &CompressOptions<'a>) -> CompressOptions<'a> {
+		CompressOptions {
+			model: self.model.unwrap_or(options.model),
+			filter: self.filter.unwrap_or(options.filter),
+			block_size: self.block_size.or(options.block_size),
+			..options.clone()
+		}
+	}
+}
+
+fn parse_model(model: &str) -> Option<Model> {
+	match model {
+		"char" => Some(Model::Char),
+		"word" => Some(Model::Word),
+		"context" => Some(Model::Context),
+		"digram" => Some(Model::Digram),
+		"rice" => Some(Model::Rice),
+		_ => None,
+	}
+}
+
+fn parse_filter(filter: &str) -> Option<Filter> {
+	match filter.split_once(':') {
+		None if filter == "rle" => Some(Filter::Rle),
+		None if filter == "delta" => Some(Filter::Delta(1)),
+		Some(("delta", width)) => width
+			.parse()
+			.ok()
+			.filter(|&width| width > 0)
+			.map(Filter::Delta),
+		_ => None,
+	}
+}
+
+/// Size like `64K` or `16M`
+fn parse_size(size: &str) -> Option<usize> {
+	let (digits, unit) = match size.char_indices().last() {
+		Some((i, 'K')) | Some((i, 'k')) => (&size[..i], 1 << 10),
+		Some((i, 'M')) | Some((i, 'm')) => (&size[..i], 1 << 20),
+		Some((i, 'G')) | Some((i, 'g')) => (&size[..i], 1 << 30),
+		_ => (size, 1),
+	};
+	digits
+		.parse::<usize>()
+		.ok()
+		.and_then(|digits| digits.checked_mul(unit))
+		.filter(|&size| size != 0)
+}
+
+/// Map from lower case extension (without dot) to settings
+#[derive(Clone, Default, Debug)]
+pub struct Presets {
+	extensions: HashMap<String, Settings>,
+}
+
+impl Presets {
+	/// Parse a preset file, reporting the first invalid line
+	pub fn parse(text: &str) -> Result<Presets, String> {
+		let mut extensions = HashMap::new();
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let error = |msg: &str| format!("Line {}: {}", number + 1, msg);
+			let (extension, value) = match line.find('=') {
+				Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+				None => return Err(error("expected `extension = setting`")),
+			};
+			let extension = extension.trim_start_matches('.').to_lowercase();
+			if extension.is_empty() {
+				return Err(error("missing extension"));
+			}
+			let mut settings = Settings::default();
+			let mut stored = false;
+			for setting in value.split(',').map(str::trim) {
+				let (name, argument) = match setting.split_once(char::is_whitespace) {
+					Some((name, argument)) => (name, Some(argument.trim())),
+					None => (setting, None),
+				};
+				let invalid = || error(&format!("invalid setting `{}`", setting));
+				match (name, argument) {
+					("huffman", None) => {}
+					("stored", None) => stored = true,
+					("model", Some(model)) => {
+						settings.model = Some(parse_model(model).ok_or_else(invalid)?)
+					}
+					("filter", Some(filter)) => {
+						settings.filter = Some(parse_filter(filter).ok_or_else(invalid)?)
+					}
+					("block-size", Some(size)) => {
+						settings.block_size = Some(parse_size(size).ok_or_else(invalid)?)
+					}
+					_ => return Err(error(&format!("unknown setting `{}`", setting))),
+				}
+			}
+			if stored {
+				if settings != Settings::default() {
+					return Err(error("stored takes no huffman settings"));
+				}
+				settings.method = Method::Stored;
+			}
+			extensions.insert(extension, settings);
+		}
+		Ok(Presets { extensions })
+	}
+
+	/// Settings for `path`, falling back to the defaults
+	pub fn for_path(&self, path: &str) -> Settings {
+		Path::new(path)
+			.extension()
+			.and_then(|extension| extension.to_str())
+			.and_then(|extension| self.extensions.get(&extension.to_lowercase()))
+			.copied()
+			.unwrap_or_default()
+	}
+}
@@ -0,0 +1,98 @@
+//! Rice coding, for bytes following a geometric distribution
+//!
+//! Every byte is read as a signed difference and mapped to a value with the small
+//! magnitudes first: 0, -1, 1, -2, ... become 0, 1, 2, 3, ... A value is coded as its
+//! quotient by 2^k in unary, then its remainder in k bits. Prediction residuals, like
+//! the output of the delta filter on slowly varying samples, cluster around 0 and
+//! code in fewer bits than with a huffman code, with no table to store. The parameter
+//! k is picked for every chunk of `CHUNK` bytes as the one coding it shortest.
+//!
+//! # Data Format
+//! - Length of the data in bytes (varint, see `codes`)
+//! - For every chunk: k (3 bits), then every value as its quotient in unary and its
+//!   remainder (k bits)
+//! - Zero padding to a whole byte
+use crate::bits::{BitReader, BitWriter};
+use crate::codes;
+use crate::compress::huffman::DecompressError;
+use crate::cursor::Cursor;
+
+/// Bytes coded with the same parameter
+const CHUNK: usize = 1024;
+/// Largest parameter, coding every value in at most 9 bits
+const MAX_PARAMETER: u8 = 7;
+
+/// Value of `byte`, read as a signed difference
+fn zigzag(byte: u8) -> u8 {
+	let signed = byte as i8;
+	((signed << 1) ^ (signed >> 7)) as u8
+}
+
+/// Reverse `zigzag`
+fn unzigzag(value: u8) -> u8 {
+	(value >> 1) ^ 0u8.wrapping_sub(value & 1)
+}
+
+/// Parameter coding `values` in the fewest bits
+fn parameter(values: &[u8]) -> u8 {
+	(0..=MAX_PARAMETER)
+		.min_by_key(|&k| {
+			values
+				.iter()
+				.map(|&value| (value >> k) as u64 + 1 + k as u64)
+				.sum::<u64>()
+		})
+		.unwrap()
+}
+
+/// Encode `data` with Rice codes
+pub fn encode(data: &[u8]) -> Vec<u8> {
+	let mut output = Vec::new();
+	codes::write_varint(&mut output, data.len() as u64);
+	// Writing to a Vec can't fail
+	let mut bits = BitWriter::new(output);
+	for chunk in data.chunks(CHUNK) {
+		let values: Vec<u8> = chunk.iter().map(|&byte| zigzag(byte)).collect();
+		let k = parameter(&values);
+		bits.write_bits(k as u64, 3).unwrap();
+		for value in values {
+			codes::write_unary(&mut bits, (value >> k) as u64).unwrap();
+			bits.write_bits(value as u64 & ((1 << k) - 1), k).unwrap();
+		}
+	}
+	bits.finish().unwrap().0
+}
+
+/// Decode data produced by `encode`
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+	let mut cursor = Cursor::new(data);
+	let length = cursor.varint("data length")?;
+	let mut bits = BitReader::new(cursor.rest());
+	let read_bits = |bits: &mut BitReader<&[u8]>, count| {
+		bits.read_bits(count)
+			.ok()
+			.flatten()
+			.ok_or(DecompressError::IncompleteCode)
+	};
+	// Every value takes at least a bit, so a length beyond that is corrupt
+	let mut output = Vec::with_capacity(length.min(8 * data.len() as u64) as usize);
+	while (output.len() as u64) < length {
+		let k = read_bits(&mut bits, 3)? as u8;
+		let end = output.len() as u64 + (length - output.len() as u64).min(CHUNK as u64);
+		while (output.len() as u64) < end {
+			let quotient = codes::read_unary(&mut bits)
+				.ok()
+				.flatten()
+				.ok_or(DecompressError::IncompleteCode)?;
+			let value = quotient << k | read_bits(&mut bits, k)?;
+			if value > u8::MAX as u64 {
+				return Err(DecompressError::ValueTooLarge(value));
+			}
+			output.push(unzigzag(value as u8));
+		}
+	}
+	if !bits.at_padding().unwrap_or(false) {
+		return Err(DecompressError::UnusedBits);
+	}
+	Ok(output)
+}
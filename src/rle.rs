@@ -0,0 +1,61 @@
+//! Run-length encoding, a filter applied before entropy coding
+//!
+//! Four equal bytes in a row are followed by a count byte telling how many
+//! more copies of that byte follow, 0 to 255. Everything else is copied as is,
+//! so data without runs grows by at most a byte per four equal bytes.
+use crate::compress::huffman::DecompressError;
+
+/// Equal bytes that announce a count byte
+const RUN_START: usize = 4;
+
+/// Run-length encode `data`
+pub fn encode(data: &[u8]) -> Vec<u8> {
+	let mut output = Vec::new();
+	let mut i = 0;
+	while i < data.len() {
+		let byte = data[i];
+		let run = data[i..]
+			.iter()
+			.take(RUN_START + 255)
+			.take_while(|&&b| b == byte)
+			.count();
+		if run < RUN_START {
+			output.push(byte);
+			i += 1;
+			continue;
+		}
+		output.extend(std::iter::repeat_n(byte, RUN_START));
+		output.push((run - RUN_START) as u8);
+		i += run;
+	}
+	output
+}
+
+/// Reverse `encode`
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+	let mut output = Vec::new();
+	let mut run = 0;
+	let mut i = 0;
+	while i < data.len() {
+		let byte = data[i];
+		run = if output.last() == Some(&byte) {
+			run + 1
+		} else {
+			1
+		};
+		output.push(byte);
+		i += 1;
+		if run == RUN_START {
+			let count = *data.get(i).ok_or(DecompressError::Truncated {
+				field: "run length",
+				offset: i,
+				expected: 1,
+				found: 0,
+			})?;
+			output.extend(std::iter::repeat_n(byte, count as usize));
+			run = 0;
+			i += 1;
+		}
+	}
+	Ok(output)
+}
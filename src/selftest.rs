@@ -0,0 +1,131 @@
+//! Built-in test vectors to check a build works on its platform
+//!
+//! Every vector is encoded by a codec and the output compared to the length and
+//! CRC-32 recorded for it, so a build that codes differently is caught even when
+//! its output still decompresses. Then it's decoded and compared to the input.
+use crate::checksum;
+use crate::codec;
+
+/// Input run through a codec, with the output it is expected to give
+pub struct Vector {
+	pub name: &'static str,
+	/// Name of the codec, see `codec::by_name`
+	pub codec: &'static str,
+	input: fn() -> Vec<u8>,
+	compressed_len: usize,
+	compressed_crc: u32,
+}
+
+/// All vectors, in the order `selftest` runs them
+pub const VECTORS: &[Vector] = &[
+	Vector {
+		name: "empty",
+		codec: "huffman",
+		input: Vec::new,
+		compressed_len: 26,
+		compressed_crc: 0x7d02_aff2,
+	},
+	Vector {
+		name: "single symbol",
+		codec: "huffman",
+		input: || vec![b'a'; 1000],
+		compressed_len: 158,
+		compressed_crc: 0xdad6_0464,
+	},
+	Vector {
+		name: "all 256 bytes as chars",
+		codec: "huffman",
+		input: || (0..=255u8).map(char::from).collect::<String>().into_bytes(),
+		compressed_len: 410,
+		compressed_crc: 0xfe87_165a,
+	},
+	Vector {
+		name: "binary with NUL",
+		codec: "huffman",
+		input: || {
+			random(4096)
+				.map(|n| [0x00, 0x00, 0x00, 0x80, 0xFF, b'a'][n as usize % 6])
+				.collect()
+		},
+		compressed_len: 976,
+		compressed_crc: 0x5702_03dd,
+	},
+	Vector {
+		name: "all 256 bytes",
+		codec: "rle",
+		input: || (0..=255u8).collect(),
+		compressed_len: 264,
+		compressed_crc: 0x4f0c_c2a9,
+	},
+	Vector {
+		name: "random",
+		codec: "huffman",
+		input: || random(4096).map(|n| n as u8).collect(),
+		compressed_len: 4122,
+		compressed_crc: 0x5666_bc43,
+	},
+	Vector {
+		name: "highly skewed",
+		codec: "huffman",
+		input: || {
+			random(4096)
+				.map(|n| match n % 64 {
+					0 => b'a' + (n >> 8) as u8 % 26,
+					_ => b'e',
+				})
+				.collect()
+		},
+		compressed_len: 607,
+		compressed_crc: 0xf732_b0fd,
+	},
+];
+
+/// `count` numbers from a fixed xorshift sequence, the same on every platform
+fn random(count: usize) -> impl Iterator<Item = u32> {
+	let mut state: u32 = 0x2545_F491;
+	(0..count).map(move |_| {
+		state ^= state << 13;
+		state ^= state >> 17;
+		state ^= state << 5;
+		state
+	})
+}
+
+impl Vector {
+	/// Encode and decode the input, describing the first difference found
+	pub fn run(&self) -> Result<(), String> {
+		let codec = codec::by_name(self.codec).expect("Vectors use known codecs");
+		let input = (self.input)();
+		let mut compressed = Vec::new();
+		codec
+			.encode(&input, &mut compressed)
+			.map_err(|e| format!("Error compressing: {}", e))?;
+		let crc = checksum::crc32(&compressed);
+		if compressed.len() != self.compressed_len || crc != self.compressed_crc {
+			return Err(format!(
+				"Compressed to {} bytes with CRC-32 {:08x}, expected {} bytes with CRC-32 {:08x}",
+				compressed.len(),
+				crc,
+				self.compressed_len,
+				self.compressed_crc
+			));
+		}
+		let mut decompressed = Vec::new();
+		codec
+			.decode(&mut &compressed[..], &mut decompressed)
+			.map_err(|e| format!("Error decompressing: {}", e))?;
+		if decompressed != input {
+			return Err(
+				match decompressed.iter().zip(&input).position(|(a, b)| a != b) {
+					Some(offset) => format!("Decompressed text differs at byte {}", offset),
+					None => format!(
+						"Decompressed {} bytes, expected {}",
+						decompressed.len(),
+						input.len()
+					),
+				},
+			);
+		}
+		Ok(())
+	}
+}
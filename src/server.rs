@@ -0,0 +1,138 @@
+//! Compression service over a stream, for callers with many small payloads like
+//! logging agents, saving them starting a process and loading a dictionary every time
+//!
+//! # Protocol
+//! A connection carries any number of requests, each answered before the next is read:
+//! - Operation (u8): `c` to compress, `d` to decompress
+//! - Length of the payload (u32), then the payload
+//!
+//! Every response is:
+//! - Status (u8): 0 on success, 1 on failure
+//! - Length of the body (u32), then the body: the output, or the error message
+//!
+//! All integers are little endian. Payloads over `MAX_PAYLOAD` bytes fail unread, and
+//! decompression fails once its output is over `MAX_OUTPUT` bytes.
+use crate::compress::huffman::{self, Compressor};
+use crate::logging::{info, warning};
+use std::io::{self, Read, Write};
+
+pub const COMPRESS: u8 = b'c';
+pub const DECOMPRESS: u8 = b'd';
+pub const MAX_PAYLOAD: u32 = 64 << 20;
+/// Most bytes a request may decompress to, as filters like rle expand hundredfold
+pub const MAX_OUTPUT: u32 = 256 << 20;
+const OK: u8 = 0;
+const FAILED: u8 = 1;
+
+/// Carry out a single request, see the protocol
+pub fn process(operation: u8, payload: &[u8], compressor: &Compressor) -> Result<Vec<u8>, String> {
+	match operation {
+		COMPRESS => compressor
+			.compress_stream(payload, Vec::new())
+			.map_err(|e| e.to_string()),
+		DECOMPRESS => huffman::decompress_stream_limited(
+			payload,
+			Vec::new(),
+			compressor.options().dictionary,
+			MAX_OUTPUT as u64,
+		)
+		.map_err(|e| e.to_string()),
+		_ => Err(format!("Unknown operation {:?}", operation as char)),
+	}
+}
+
+/// Answer requests on `stream` until the other end closes it
+pub fn handle<S: Read + Write>(mut stream: S, compressor: &Compressor) -> io::Result<()> {
+	loop {
+		let mut header = [0; 5];
+		match stream.read(&mut header[..1])? {
+			0 => return Ok(()),
+			_ => stream.read_exact(&mut header[1..])?,
+		}
+		let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+		let response = if len > MAX_PAYLOAD {
+			io::copy(&mut (&mut stream).take(len as u64), &mut io::sink())?;
+			Err(format!(
+				"Payload of {} bytes, at most {} allowed",
+				len, MAX_PAYLOAD
+			))
+		} else {
+			let mut payload = vec![0; len as usize];
+			stream.read_exact(&mut payload)?;
+			process(header[0], &payload, compressor)
+		};
+		let (status, body) = match response {
+			Ok(output) => (OK, output),
+			Err(message) => (FAILED, message.into_bytes()),
+		};
+		// Decompressing stops at the limit, compressing isn't much over the payload
+		let (status, body) = if body.len() > MAX_OUTPUT as usize {
+			let message = format!(
+				"Output of {} bytes, at most {} allowed",
+				body.len(),
+				MAX_OUTPUT
+			);
+			(FAILED, message.into_bytes())
+		} else {
+			(status, body)
+		};
+		let mut frame = Vec::with_capacity(5 + body.len());
+		frame.push(status);
+		// At most `MAX_OUTPUT`, so the length fits
+		frame.extend(&(body.len() as u32).to_le_bytes());
+		frame.extend(&body);
+		stream.write_all(&frame)?;
+	}
+}
+
+/// Send a request to a server on `stream`, returning its output or error message
+pub fn call<S: Read + Write>(
+	stream: &mut S,
+	operation: u8,
+	payload: &[u8],
+) -> io::Result<Result<Vec<u8>, String>> {
+	let mut frame = Vec::with_capacity(5 + payload.len());
+	frame.push(operation);
+	frame.extend(&(payload.len() as u32).to_le_bytes());
+	frame.extend(payload);
+	stream.write_all(&frame)?;
+	let mut header = [0; 5];
+	stream.read_exact(&mut header)?;
+	let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+	let mut body = vec![0; len as usize];
+	stream.read_exact(&mut body)?;
+	Ok(match header[0] {
+		OK => Ok(body),
+		_ => Err(String::from_utf8_lossy(&body).into_owned()),
+	})
+}
+
+/// Serve connections to `listener` on `threads` threads, at least 1, each serving a
+/// connection at a time, until accepting fails on all of them
+#[cfg(unix)]
+pub fn serve(
+	listener: std::os::unix::net::UnixListener,
+	compressor: &Compressor,
+	threads: usize,
+) -> io::Result<()> {
+	let listener = &listener;
+	std::thread::scope(|scope| {
+		let workers: Vec<_> = (0..threads.max(1))
+			.map(|_| {
+				scope.spawn(move || loop {
+					let (stream, _) = listener.accept()?;
+					info!("Connection opened");
+					match handle(&stream, compressor) {
+						Ok(()) => info!("Connection closed"),
+						Err(e) => warning!("Connection failed: {}", e),
+					}
+				})
+			})
+			.collect();
+		let mut result = Ok(());
+		for worker in workers {
+			result = result.and(worker.join().unwrap());
+		}
+		result
+	})
+}
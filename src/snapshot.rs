@@ -0,0 +1,226 @@
+//! Differential archives between two snapshots of a directory
+//!
+//! `diff` stores every file that is new or changed in the newer snapshot,
+//! and records files that were removed. `apply` replays such an archive
+//! on top of the older snapshot to reconstruct the newer one.
+use crate::archive::{Archive, ArchiveWriter, Entry, EntryKind};
+use crate::compress::huffman::CompressOptions;
+use crate::presets::Presets;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Recursively list regular files and symlinks below `root`,
+/// keyed by their path relative to `root` using `/` as separator
+///
+/// With `dereference` symlinks are followed instead, and listed as what they point to
+pub fn list_files(root: &Path, dereference: bool) -> io::Result<BTreeMap<String, PathBuf>> {
+	fn walk(
+		dir: &Path,
+		prefix: &str,
+		dereference: bool,
+		files: &mut BTreeMap<String, PathBuf>,
+	) -> io::Result<()> {
+		for entry in fs::read_dir(dir)? {
+			let entry = entry?;
+			let name = entry.file_name().into_string().map_err(|_| {
+				io::Error::new(io::ErrorKind::InvalidData, "File name is not UTF-8")
+			})?;
+			let relative = prefix.to_string() + &name;
+			let mut file_type = entry.file_type()?;
+			if file_type.is_symlink() && dereference {
+				// Broken links are listed as they are, for reading them to fail
+				if let Ok(metadata) = fs::metadata(entry.path()) {
+					file_type = metadata.file_type();
+				}
+			}
+			if file_type.is_dir() {
+				walk(&entry.path(), &(relative + "/"), dereference, files)?;
+			} else if file_type.is_file() || file_type.is_symlink() {
+				files.insert(relative, entry.path());
+			}
+		}
+		Ok(())
+	}
+	let mut files = BTreeMap::new();
+	walk(root, "", dereference, &mut files)?;
+	Ok(files)
+}
+
+/// Target of `path` if it is a symlink
+pub fn link_target(path: &Path) -> io::Result<Option<String>> {
+	if !fs::symlink_metadata(path)?.file_type().is_symlink() {
+		return Ok(None);
+	}
+	fs::read_link(path)?
+		.into_os_string()
+		.into_string()
+		.map(Some)
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Link target is not UTF-8"))
+}
+
+/// Create a symlink at `path` pointing to `target`, replacing any file there
+fn make_symlink(target: &str, path: &Path) -> io::Result<()> {
+	match fs::remove_file(path) {
+		Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+		_ => {}
+	}
+	#[cfg(unix)]
+	return std::os::unix::fs::symlink(target, path);
+	#[cfg(not(unix))]
+	return Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		"Symlinks are only supported on Unix",
+	));
+}
+
+/// Write the file or symlink `entry` to `path`, creating missing directories.
+/// Returns the contents of the file, or the target of the symlink
+pub fn extract<R: Read + Seek>(
+	archive: &mut Archive<R>,
+	entry: &Entry,
+	path: &Path,
+) -> io::Result<Vec<u8>> {
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	if entry.kind == EntryKind::Symlink {
+		let target = archive.read_link(entry)?;
+		make_symlink(&target, path)?;
+		return Ok(target.into_bytes());
+	}
+	let contents = archive.read(entry)?;
+	// Write through a new file rather than a symlink left at `path`
+	match fs::symlink_metadata(path) {
+		Ok(metadata) if metadata.file_type().is_symlink() => fs::remove_file(path)?,
+		_ => {}
+	}
+	fs::write(path, &contents)?;
+	Ok(contents)
+}
+
+/// Write an archive to `output` turning `old_dir` into `new_dir`
+///
+/// Each file is stored according to the settings `presets` has for its extension.
+/// Symlinks are stored as links, unless `dereference` stores what they point to.
+pub fn diff<W: Write>(
+	old_dir: &Path,
+	new_dir: &Path,
+	output: W,
+	presets: &Presets,
+	dereference: bool,
+) -> io::Result<W> {
+	let old_files = list_files(old_dir, dereference)?;
+	let new_files = list_files(new_dir, dereference)?;
+	let link = |path: &Path| {
+		if dereference {
+			Ok(None)
+		} else {
+			link_target(path)
+		}
+	};
+	let mut archive = ArchiveWriter::new(output)?;
+	for (relative, path) in &new_files {
+		let mtime = fs::symlink_metadata(path)?
+			.modified()?
+			.duration_since(UNIX_EPOCH)
+			.ok()
+			.map(|mtime| mtime.as_secs());
+		let old_path = old_files.get(relative);
+		if let Some(target) = link(path)? {
+			if let Some(old_path) = old_path {
+				if link(old_path)?.as_ref() == Some(&target) {
+					continue;
+				}
+			}
+			archive.add_symlink(relative, &target, mtime)?;
+			continue;
+		}
+		let contents = fs::read(path)?;
+		if let Some(old_path) = old_path {
+			if link(old_path)?.is_none() && fs::read(old_path)? == contents {
+				continue;
+			}
+		}
+		let settings = presets.for_path(relative);
+		let options = settings.apply(&CompressOptions::default());
+		archive.add_file_with_options(relative, &contents, settings.method, &options, mtime)?;
+	}
+	for relative in old_files.keys() {
+		if !new_files.contains_key(relative) {
+			archive.add_deletion(relative);
+		}
+	}
+	archive.finish()
+}
+
+/// Convert an archive path to a path below `root`,
+/// refusing anything that would escape it
+///
+/// Directories between `root` and the path must not be symlinks, or an archive
+/// could plant a link to anywhere and then write files through it.
+pub fn resolve(root: &Path, relative: &str) -> io::Result<PathBuf> {
+	let relative = Path::new(relative);
+	let escapes = relative
+		.components()
+		.any(|c| !matches!(c, Component::Normal(_)));
+	if escapes {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"Archive path escapes target directory",
+		));
+	}
+	let mut path = root.to_path_buf();
+	if let Some(parent) = relative.parent() {
+		for component in parent.components() {
+			path.push(component);
+			match fs::symlink_metadata(&path) {
+				Ok(metadata) if metadata.file_type().is_symlink() => {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						"Archive path goes through a symlink",
+					))
+				}
+				Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+				_ => {}
+			}
+		}
+	}
+	Ok(root.join(relative))
+}
+
+/// Apply a differential archive to the snapshot in `dir`, in place
+pub fn apply<R: Read + Seek>(archive: &mut Archive<R>, dir: &Path) -> io::Result<()> {
+	for entry in archive.entries().to_vec() {
+		let path = resolve(dir, &entry.path)?;
+		match entry.kind {
+			EntryKind::File | EntryKind::Symlink => {
+				extract(archive, &entry, &path)?;
+			}
+			EntryKind::Deleted => match fs::remove_file(&path) {
+				Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+				_ => {}
+			},
+		}
+	}
+	Ok(())
+}
+
+/// Copy every file and symlink below `from` into `to`
+pub fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+	for (relative, path) in list_files(from, false)? {
+		let target = to.join(&relative);
+		if let Some(parent) = target.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		match link_target(&path)? {
+			Some(link) => make_symlink(&link, &target)?,
+			None => {
+				fs::copy(path, target)?;
+			}
+		}
+	}
+	Ok(())
+}
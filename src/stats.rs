@@ -0,0 +1,122 @@
+//! Corpus statistics for tuning presets and dictionaries
+//!
+//! Aggregates byte distributions over a directory of sample files,
+//! overall and per file type (extension), together with their entropy
+//! and the ratio huffman coding each file on its own would achieve.
+use crate::analysis::{self, Analysis};
+use crate::json;
+use crate::snapshot;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Statistics of a group of files
+#[derive(Clone)]
+pub struct Stats {
+	pub files: u64,
+	pub bytes: u64,
+	/// Number of occurences of every byte value
+	pub symbols: [u64; 256],
+	/// Estimated size when every file is compressed separately
+	pub estimated_size: u64,
+}
+
+impl Default for Stats {
+	fn default() -> Stats {
+		Stats {
+			files: 0,
+			bytes: 0,
+			symbols: [0; 256],
+			estimated_size: 0,
+		}
+	}
+}
+
+impl Stats {
+	fn add_file(&mut self, contents: &[u8]) {
+		let file = Analysis::of(contents);
+		self.estimated_size += file.estimated_size();
+		self.files += 1;
+		self.bytes += file.bytes;
+		for (total, count) in self.symbols.iter_mut().zip(file.symbols.iter()) {
+			*total += count;
+		}
+	}
+
+	/// Shannon entropy in bits per byte
+	pub fn entropy(&self) -> f64 {
+		analysis::entropy(&self.symbols)
+	}
+
+	/// Estimated compressed size divided by original size
+	pub fn estimated_ratio(&self) -> f64 {
+		if self.bytes == 0 {
+			return 1.0;
+		}
+		self.estimated_size as f64 / self.bytes as f64
+	}
+
+	fn write_json(&self, json: &mut String) {
+		let symbols: Vec<String> = self.symbols.iter().map(|c| c.to_string()).collect();
+		write!(
+			json,
+			"{{\"files\": {}, \"bytes\": {}, \"entropy\": {:.4}, \"estimated_ratio\": {:.4}, \"symbols\": [{}]}}",
+			self.files,
+			self.bytes,
+			self.entropy(),
+			self.estimated_ratio(),
+			symbols.join(", ")
+		)
+		.unwrap();
+	}
+}
+
+/// Statistics of a whole corpus
+#[derive(Clone, Default)]
+pub struct CorpusStats {
+	pub total: Stats,
+	/// Keyed by lower case extension, files without one use an empty key
+	pub by_type: BTreeMap<String, Stats>,
+}
+
+impl CorpusStats {
+	/// Read every file below `dir`
+	pub fn collect(dir: &Path) -> io::Result<CorpusStats> {
+		let mut stats = CorpusStats::default();
+		for (relative, path) in snapshot::list_files(dir, false)? {
+			if snapshot::link_target(&path)?.is_some() {
+				continue;
+			}
+			let contents = fs::read(&path)?;
+			let extension = Path::new(&relative)
+				.extension()
+				.and_then(|extension| extension.to_str())
+				.unwrap_or("")
+				.to_lowercase();
+			stats.total.add_file(&contents);
+			stats
+				.by_type
+				.entry(extension)
+				.or_default()
+				.add_file(&contents);
+		}
+		Ok(stats)
+	}
+
+	pub fn to_json(&self) -> String {
+		let mut json = String::from("{\"total\": ");
+		self.total.write_json(&mut json);
+		json.push_str(", \"types\": {");
+		for (i, (extension, stats)) in self.by_type.iter().enumerate() {
+			if i != 0 {
+				json.push_str(", ");
+			}
+			write!(json, "\"{}\": ", json::escape(extension)).unwrap();
+			stats.write_json(&mut json);
+		}
+		json.push_str("}}\n");
+		json
+	}
+}
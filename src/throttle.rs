@@ -0,0 +1,81 @@
+//! Reads and writes capped to a number of bytes per second, so compressing large files
+//! in the background leaves the disk or network file system to others
+//!
+//! `Throttle` sleeps whenever more has passed through it than the rate allows since
+//! it was created. Time spent waiting on its reader or writer counts, so a slow disk
+//! isn't slowed down further, but at most a second of it is made up for later.
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A reader or writer passing at most `rate` bytes per second, all of them without a rate
+pub struct Throttle<T> {
+	inner: T,
+	rate: Option<u64>,
+	start: Instant,
+	/// Bytes passed since `start`
+	bytes: u64,
+}
+
+impl<T> Throttle<T> {
+	pub fn new(inner: T, rate: Option<u64>) -> Throttle<T> {
+		Throttle {
+			inner,
+			rate: rate.filter(|&rate| rate > 0),
+			start: Instant::now(),
+			bytes: 0,
+		}
+	}
+
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+
+	/// Most bytes to pass in a single call, a tenth of a second's worth so the waits
+	/// stay short
+	fn limit(&self, len: usize) -> usize {
+		match self.rate {
+			Some(rate) => len.min((rate / 10).max(1) as usize),
+			None => len,
+		}
+	}
+
+	/// Count `len` bytes that passed and wait until the rate allows them
+	fn pass(&mut self, len: usize) {
+		let rate = match self.rate {
+			Some(rate) => rate,
+			None => return,
+		};
+		self.bytes += len as u64;
+		let due = Duration::from_secs_f64(self.bytes as f64 / rate as f64);
+		let elapsed = self.start.elapsed();
+		if due > elapsed {
+			thread::sleep(due - elapsed);
+		} else if elapsed - due > Duration::from_secs(1) {
+			// Behind by more than a second, don't burst to catch up
+			self.start = Instant::now() - Duration::from_secs(1);
+			self.bytes = rate;
+		}
+	}
+}
+
+impl<R: Read> Read for Throttle<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let limit = self.limit(buf.len());
+		let len = self.inner.read(&mut buf[..limit])?;
+		self.pass(len);
+		Ok(len)
+	}
+}
+
+impl<W: Write> Write for Throttle<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let limit = self.limit(buf.len());
+		let len = self.inner.write(&buf[..limit])?;
+		self.pass(len);
+		Ok(len)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
@@ -0,0 +1,128 @@
+//! TCP tunnel compressing the traffic between its two ends, for slow links
+//!
+//! The end near the clients compresses what they send and decompresses the answers,
+//! the end near the server does the opposite:
+//!
+//! ```text
+//! client -> tunnel (Side::Plain) ==compressed==> tunnel (Side::Compressed) -> server
+//! ```
+//!
+//! Every read is compressed and flushed as a member of its own, see `Encoder::flush`,
+//! so interactive protocols see no delay. A connection closed in one direction is
+//! closed the same way on the far end.
+use crate::compress::huffman::{self, Compressor};
+use crate::logging::{info, warning};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+
+/// Most input read at a time
+const CHUNK: usize = 64 << 10;
+
+/// What the connections accepted by a tunnel end carry
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+	/// Plain traffic from clients, compressed towards the other end
+	Plain,
+	/// Compressed traffic from the other end, decompressed towards the server
+	Compressed,
+}
+
+/// Compress everything read from `input` to `output`, a member per read
+pub fn compress(
+	mut input: impl Read,
+	mut output: impl Write,
+	compressor: &Compressor,
+) -> io::Result<()> {
+	let mut encoder = compressor.encoder();
+	let mut buffer = vec![0; CHUNK];
+	loop {
+		let len = match input.read(&mut buffer) {
+			Ok(0) => break,
+			Ok(len) => len,
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		};
+		let mut compressed = encoder.push(&buffer[..len])?;
+		compressed.extend(encoder.flush()?);
+		output.write_all(&compressed)?;
+	}
+	output.write_all(&encoder.finish()?)?;
+	output.flush()
+}
+
+/// Decompress everything read from `input` to `output`, every member as soon as it
+/// has arrived
+pub fn decompress(
+	mut input: impl Read,
+	mut output: impl Write,
+	compressor: &Compressor,
+) -> io::Result<()> {
+	let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+	let mut decoder = huffman::Decoder::new(compressor.options().dictionary);
+	let mut buffer = vec![0; CHUNK];
+	loop {
+		let len = match input.read(&mut buffer) {
+			Ok(0) => break,
+			Ok(len) => len,
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		};
+		output.write_all(&decoder.push(&buffer[..len]).map_err(invalid)?)?;
+		output.flush()?;
+	}
+	output.write_all(&decoder.finish().map_err(invalid)?)?;
+	output.flush()
+}
+
+/// Carry the traffic of `client` to a new connection to `address` and back, until both
+/// directions are closed
+pub fn forward(
+	client: TcpStream,
+	address: &str,
+	side: Side,
+	compressor: &Compressor,
+) -> io::Result<()> {
+	let server = TcpStream::connect(address)?;
+	client.set_nodelay(true)?;
+	server.set_nodelay(true)?;
+	// Which connection carries compressed traffic
+	let (plain, packed) = match side {
+		Side::Plain => (&client, &server),
+		Side::Compressed => (&server, &client),
+	};
+	let (packing, unpacking) = std::thread::scope(|scope| {
+		let packing = scope.spawn(|| {
+			let result = compress(plain, packed, compressor);
+			packed.shutdown(Shutdown::Write).ok();
+			result
+		});
+		let unpacking = decompress(packed, plain, compressor);
+		plain.shutdown(Shutdown::Write).ok();
+		if unpacking.is_err() {
+			// Stop the other direction too
+			plain.shutdown(Shutdown::Read).ok();
+		}
+		(packing.join().unwrap(), unpacking)
+	});
+	packing.and(unpacking)
+}
+
+/// Forward every connection to `listener` to `address`, each on a thread of its own,
+/// until accepting fails
+pub fn serve(
+	listener: TcpListener,
+	address: &str,
+	side: Side,
+	compressor: &Compressor,
+) -> io::Result<()> {
+	std::thread::scope(|scope| loop {
+		let (client, peer) = listener.accept()?;
+		scope.spawn(move || {
+			info!("Connection from {}", peer);
+			match forward(client, address, side, compressor) {
+				Ok(()) => info!("Connection from {} closed", peer),
+				Err(e) => warning!("Connection from {} failed: {}", peer, e),
+			}
+		});
+	})
+}
@@ -0,0 +1,155 @@
+//! Compressed output split across files of a fixed size, e.g. for upload limits
+//!
+//! # Data Format
+//! The stream is cut into volumes named `NAME.001`, `NAME.002`, ...
+//! Each volume starts with a header:
+//! - Magic bytes: `HUV`
+//! - Volume number (u32), counting from 1
+//! - Flags (u8): 1 on the last volume
+//!
+//! followed by the next bytes of the stream. Every volume but the last holds
+//! exactly the volume size, header included. All integers are little endian.
+use crate::atomic::Pending;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const MAGIC: &[u8; 3] = b"HUV";
+pub const HEADER_LEN: u64 = MAGIC.len() as u64 + 4 + 1;
+const LAST: u8 = 1;
+
+/// Path of volume `number` of the stream `base`
+pub fn volume_path(base: &Path, number: u32) -> PathBuf {
+	let mut path = base.as_os_str().to_owned();
+	path.push(format!(".{:03}", number));
+	PathBuf::from(path)
+}
+
+/// Name of the stream whose first volume is `path`, `None` if it doesn't end in `.001`
+pub fn base_path(path: &str) -> Option<&str> {
+	path.strip_suffix(".001").filter(|base| !base.is_empty())
+}
+
+/// Writes a stream to volumes of `volume_size` bytes, call `finish` at the end
+pub struct VolumeWriter {
+	base: PathBuf,
+	volume_size: u64,
+	current: Option<File>,
+	written: u64,
+	paths: Vec<PathBuf>,
+	/// Removes the volumes if interrupted before `finish`
+	pending: Vec<Pending>,
+}
+
+impl VolumeWriter {
+	/// Volumes are only created once there is data for them.
+	/// Panics unless `volume_size` leaves room after the header
+	pub fn new(base: &Path, volume_size: u64) -> VolumeWriter {
+		assert!(volume_size > HEADER_LEN, "volume too small for its header");
+		VolumeWriter {
+			base: base.to_path_buf(),
+			volume_size,
+			current: None,
+			written: 0,
+			paths: Vec::new(),
+			pending: Vec::new(),
+		}
+	}
+
+	fn next_volume(&mut self) -> io::Result<()> {
+		let number = self.paths.len() as u32 + 1;
+		let path = volume_path(&self.base, number);
+		self.pending.push(Pending::remove(&path));
+		let mut file = File::create(&path)?;
+		self.paths.push(path);
+		file.write_all(MAGIC)?;
+		file.write_all(&number.to_le_bytes())?;
+		file.write_all(&[0])?;
+		self.current = Some(file);
+		self.written = HEADER_LEN;
+		Ok(())
+	}
+
+	/// Mark the last volume, returns the paths of all volumes
+	pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+		if self.current.is_none() {
+			self.next_volume()?;
+		}
+		let file = self.current.as_mut().unwrap();
+		file.seek(SeekFrom::Start(HEADER_LEN - 1))?;
+		file.write_all(&[LAST])?;
+		file.sync_all()?;
+		self.pending.clear();
+		Ok(self.paths)
+	}
+}
+
+impl Write for VolumeWriter {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		if self.current.is_none() || self.written == self.volume_size {
+			self.next_volume()?;
+		}
+		let room = (self.volume_size - self.written).min(buf.len() as u64) as usize;
+		let written = self.current.as_mut().unwrap().write(&buf[..room])?;
+		self.written += written as u64;
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match &mut self.current {
+			Some(file) => file.flush(),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Read the stream `base` back from its volumes, checking none is missing or out of place
+pub fn join(base: &Path) -> io::Result<Vec<u8>> {
+	let mut stream = Vec::new();
+	for number in 1.. {
+		let path = volume_path(base, number);
+		let data = fs::read(&path).map_err(|e| match e.kind() {
+			io::ErrorKind::NotFound if number > 1 => io::Error::new(
+				e.kind(),
+				format!("Missing volume {}: {}", number, path.display()),
+			),
+			_ => e,
+		})?;
+		let invalid = |message: &str| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("{}: {}", path.display(), message),
+			)
+		};
+		if data.len() < HEADER_LEN as usize || !data.starts_with(MAGIC) {
+			return Err(invalid("Not a volume"));
+		}
+		let found = u32::from_le_bytes([data[3], data[4], data[5], data[6]]);
+		if found != number {
+			return Err(invalid(&format!(
+				"Expected volume {}, found volume {}",
+				number, found
+			)));
+		}
+		stream.extend_from_slice(&data[HEADER_LEN as usize..]);
+		if data[HEADER_LEN as usize - 1] & LAST != 0 {
+			break;
+		}
+	}
+	Ok(stream)
+}
+
+/// Remove all volumes of the stream `base`, stopping at the first one missing
+pub fn remove(base: &Path) -> io::Result<()> {
+	for number in 1.. {
+		match fs::remove_file(volume_path(base, number)) {
+			Ok(()) => {}
+			Err(e) if e.kind() == io::ErrorKind::NotFound && number > 1 => break,
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,65 @@
+//! 16-bit symbol model, for UTF-16 text and binary formats built from 2 byte units
+//!
+//! The data is split into units of 2 bytes, little endian, and every unit is a
+//! symbol of a single table. Data of odd length gets a zero byte to complete its
+//! last unit, which decoding drops again.
+//!
+//! # Data Format
+//! - Length of the data in bytes (u64)
+//! - Number of coded units (u32)
+//! - For every coded unit in ascending order: the unit (u16) and its code length (u8)
+//! - Bit stream, zero padded to a whole byte
+//!
+//! The table is that of `generic::Huffman` over u16 symbols, codes are canonical. All
+//! integers are little endian.
+use crate::bits::{BitReader, BitWriter};
+use crate::compress::huffman::DecompressError;
+use crate::cursor::Cursor;
+use crate::generic::Huffman;
+
+/// Longest code, more than any alphabet of 2^16 symbols needs
+const MAX_CODE_LENGTH: u8 = 32;
+
+/// Units of `data`, the last one completed with a zero byte
+fn units(data: &[u8]) -> impl Iterator<Item = u16> + '_ {
+	data.chunks(2)
+		.map(|pair| u16::from_le_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+}
+
+/// Encode `data` with a code over its 16-bit units
+pub fn encode(data: &[u8]) -> Vec<u8> {
+	let code = Huffman::limited(units(data).map(|unit| (unit, 1)), MAX_CODE_LENGTH)
+		.expect("Every unit fits in 32 bits");
+	let mut output = (data.len() as u64).to_le_bytes().to_vec();
+	code.write_table(&mut output);
+	// Writing to a Vec can't fail
+	let mut bits = BitWriter::new(output);
+	let units: Vec<u16> = units(data).collect();
+	code.encode(&units, &mut bits).unwrap();
+	bits.finish().unwrap().0
+}
+
+/// Decode data produced by `encode`
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+	let mut cursor = Cursor::new(data);
+	let length = cursor.u64("data length")?;
+	let code = Huffman::<u16>::read_table(&mut cursor)?;
+	let decoder = code.decoder();
+
+	let mut bits = BitReader::new(cursor.rest());
+	// Every unit takes at least a bit, so a length beyond that is corrupt
+	let mut output = Vec::with_capacity(length.min(16 * data.len() as u64) as usize);
+	while (output.len() as u64) < length {
+		let unit = decoder
+			.decode(&mut bits)
+			.ok()
+			.flatten()
+			.ok_or(DecompressError::IncompleteCode)?;
+		output.extend(&unit.to_le_bytes());
+	}
+	output.truncate(length as usize);
+	if !bits.at_padding().unwrap_or(false) {
+		return Err(DecompressError::UnusedBits);
+	}
+	Ok(output)
+}
@@ -0,0 +1,182 @@
+//! Word based model for natural language text
+//!
+//! Text is split into tokens: runs of alphanumeric chars, runs of whitespace
+//! and single other chars. Tokens occurring more than once make up the alphabet
+//! that is huffman coded. Rare tokens are escaped and spelled out byte by byte
+//! with a second code, so the table only holds tokens that pay for themselves.
+//!
+//! # Data Format
+//! - Number of tokens in the text (u64)
+//! - Number of tokens in the table (u32), each as its length (u8) followed by its UTF-8 bytes
+//! - Code length of every table token, then of the escape symbol, a byte each
+//! - If the escape symbol has a code: code length of every byte value,
+//!   then of the end of spelling symbol, a byte each
+//! - Bit stream, zero padded to a whole byte
+//!
+//! Codes are canonical, see `canonical`. All integers are little endian.
+use crate::bits::{BitReader, BitWriter};
+use crate::canonical::{self, Decoder};
+use crate::compress::huffman::{CompressError, DecompressError};
+use crate::cursor::Cursor;
+use std::collections::HashMap;
+
+/// Longest token in bytes, longer runs are split
+const MAX_TOKEN_LEN: usize = 255;
+
+/// Symbol ending an escaped token in the spelling code
+const END_OF_SPELLING: usize = 256;
+
+/// Split `text` into tokens, concatenating them gives `text` again
+fn tokenize(text: &str) -> Vec<&str> {
+	fn class(c: char) -> u8 {
+		if c.is_alphanumeric() {
+			0
+		} else if c.is_whitespace() {
+			1
+		} else {
+			2
+		}
+	}
+	let mut tokens = Vec::new();
+	let mut start = 0;
+	let mut previous = None;
+	for (i, c) in text.char_indices() {
+		let continues = previous == Some(class(c))
+			&& class(c) != 2
+			&& i + c.len_utf8() - start <= MAX_TOKEN_LEN;
+		if !continues && i != start {
+			tokens.push(&text[start..i]);
+			start = i;
+		}
+		previous = Some(class(c));
+	}
+	if start != text.len() {
+		tokens.push(&text[start..]);
+	}
+	tokens
+}
+
+/// Encode `text` with the word model, with no code longer than `max_length` bits
+pub fn encode(text: &str, max_length: Option<u8>) -> Result<Vec<u8>, CompressError> {
+	let tokens = tokenize(text);
+	let mut counts: HashMap<&str, u64> = HashMap::new();
+	for &token in &tokens {
+		*counts.entry(token).or_insert(0) += 1;
+	}
+	let mut table: Vec<(&str, u64)> = counts.into_iter().filter(|&(_, count)| count > 1).collect();
+	table.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+	let index: HashMap<&str, usize> = table
+		.iter()
+		.enumerate()
+		.map(|(i, &(token, _))| (token, i))
+		.collect();
+	let escape = table.len();
+
+	let mut token_frequencies: Vec<u64> = table.iter().map(|&(_, count)| count).collect();
+	token_frequencies.push(0);
+	let mut byte_frequencies = vec![0u64; END_OF_SPELLING + 1];
+	for &token in &tokens {
+		if !index.contains_key(token) {
+			token_frequencies[escape] += 1;
+			for &byte in token.as_bytes() {
+				byte_frequencies[byte as usize] += 1;
+			}
+			byte_frequencies[END_OF_SPELLING] += 1;
+		}
+	}
+	let token_lengths = canonical::code_lengths_within(&token_frequencies, max_length)?;
+	let byte_lengths = canonical::code_lengths_within(&byte_frequencies, max_length)?;
+
+	let mut output = (tokens.len() as u64).to_le_bytes().to_vec();
+	output.extend(&(table.len() as u32).to_le_bytes());
+	for &(token, _) in &table {
+		output.push(token.len() as u8);
+		output.extend(token.as_bytes());
+	}
+	output.extend(&token_lengths);
+	if token_lengths[escape] != 0 {
+		output.extend(&byte_lengths);
+	}
+
+	let (token_codes, byte_codes) = (
+		canonical::codes(&token_lengths),
+		canonical::codes(&byte_lengths),
+	);
+	// Writing to a Vec can't fail
+	let mut bits = BitWriter::new(Vec::new());
+	for &token in &tokens {
+		match index.get(token) {
+			Some(&i) => bits.write_bits(token_codes[i].0, token_codes[i].1).unwrap(),
+			None => {
+				bits.write_bits(token_codes[escape].0, token_codes[escape].1)
+					.unwrap();
+				for &byte in token.as_bytes() {
+					let (code, length) = byte_codes[byte as usize];
+					bits.write_bits(code, length).unwrap();
+				}
+				let (code, length) = byte_codes[END_OF_SPELLING];
+				bits.write_bits(code, length).unwrap();
+			}
+		}
+	}
+	output.extend(bits.finish().unwrap().0);
+	Ok(output)
+}
+
+/// Decode data produced by `encode`
+pub fn decode(data: &[u8]) -> Result<String, DecompressError> {
+	let mut cursor = Cursor::new(data);
+	let token_count = cursor.u64("token count")?;
+	let table_len = cursor.u32("token table")? as usize;
+	let mut table = Vec::new();
+	for _ in 0..table_len {
+		let len = cursor.u8("token table")? as usize;
+		let token = cursor.take(len, "token table")?;
+		table.push(std::str::from_utf8(token).map_err(|_| DecompressError::InvalidUtf8)?);
+	}
+	let token_lengths = cursor.take(table_len + 1, "code lengths")?;
+	let tokens = Decoder::new(token_lengths).ok_or(DecompressError::InvalidTree)?;
+	let bytes = if token_lengths[table_len] != 0 {
+		let byte_lengths = cursor.take(END_OF_SPELLING + 1, "code lengths")?;
+		Some(Decoder::new(byte_lengths).ok_or(DecompressError::InvalidTree)?)
+	} else {
+		None
+	};
+
+	let mut bits = BitReader::new(cursor.rest());
+	let mut output = Vec::new();
+	for _ in 0..token_count {
+		let symbol = tokens
+			.decode(&mut bits)
+			.ok()
+			.flatten()
+			.ok_or(DecompressError::IncompleteCode)?;
+		if let Some(token) = table.get(symbol) {
+			output.extend(token.as_bytes());
+			continue;
+		}
+		let bytes = bytes.as_ref().ok_or(DecompressError::InvalidTree)?;
+		loop {
+			match bytes
+				.decode(&mut bits)
+				.ok()
+				.flatten()
+				.ok_or(DecompressError::IncompleteCode)?
+			{
+				END_OF_SPELLING => break,
+				byte => output.push(byte as u8),
+			}
+		}
+	}
+	if !bits.at_padding().unwrap_or(false) {
+		return Err(DecompressError::UnusedBits);
+	}
+	String::from_utf8(output).map_err(|_| DecompressError::InvalidUtf8)
+}
+
+/// Number of tokens in the table of data produced by `encode`
+pub fn table_len(data: &[u8]) -> Result<usize, DecompressError> {
+	let mut cursor = Cursor::new(data);
+	cursor.u64("token count")?;
+	Ok(cursor.u32("token table")? as usize)
+}
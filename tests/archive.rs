@@ -0,0 +1,55 @@
+use rust_compression::archive::{Archive, ArchiveWriter, Method};
+use std::io::Cursor;
+
+#[test]
+fn ranges_of_every_method() {
+	// Several blocks of a megabyte each
+	let text: String = (0..400_000).map(|i| format!("{} ", i % 9973)).collect();
+	let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+	writer
+		.add_file("huffman", text.as_bytes(), Method::Huffman, None)
+		.unwrap();
+	writer
+		.add_file("stored", text.as_bytes(), Method::Stored, None)
+		.unwrap();
+	writer.set_dedup(true);
+	writer
+		.add_file("chunked", text.as_bytes(), Method::Huffman, None)
+		.unwrap();
+	let mut archive = Archive::new(writer.finish().unwrap()).unwrap();
+	let len = text.len() as u64;
+	let ranges = [
+		0..10,
+		1_048_570..1_048_600,
+		len - 5..len + 100,
+		2_000_000..2_500_000,
+		len..len + 1,
+	];
+	for path in ["huffman", "stored", "chunked"] {
+		let entry = archive.find(path).unwrap().clone();
+		for range in ranges.clone() {
+			let expected =
+				&text.as_bytes()[range.start.min(len) as usize..range.end.min(len) as usize];
+			assert_eq!(archive.read_range(&entry, range).unwrap(), expected);
+		}
+	}
+}
+
+#[test]
+fn lying_chunked_size_is_an_error() {
+	let text = "chunked text ".repeat(1000);
+	let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+	writer.set_dedup(true);
+	writer
+		.add_file("f.txt", text.as_bytes(), Method::Huffman, None)
+		.unwrap();
+	let mut data = writer.finish().unwrap().into_inner();
+	let index_offset = Archive::new(Cursor::new(&data)).unwrap().index_offset() as usize;
+	// Count, path length, path, kind, method, offset and length come before the size
+	let size = index_offset + 4 + 2 + "f.txt".len() + 2 + 16;
+	data[size..size + 8].copy_from_slice(&0x7fff_ffff_ffff_ff00u64.to_le_bytes());
+	let mut archive = Archive::new(Cursor::new(&data)).unwrap();
+	let entry = archive.find("f.txt").unwrap().clone();
+	assert_eq!(entry.size, 0x7fff_ffff_ffff_ff00);
+	assert!(archive.read(&entry).is_err());
+}
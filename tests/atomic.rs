@@ -0,0 +1,47 @@
+use rust_compression::atomic::{self, AtomicFile, Pending};
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+
+// `undo_pending` leaves the registry locked, so this file holds a single test
+#[test]
+fn interrupted_files_are_undone() {
+	let dir = std::env::temp_dir().join(format!("atomic-test-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+
+	let replaced = dir.join("replaced");
+	fs::write(&replaced, "old contents").unwrap();
+	let mut unfinished = AtomicFile::create(&replaced).unwrap();
+	unfinished.write_all(b"new").unwrap();
+
+	let finished = dir.join("finished");
+	atomic::write(&finished, b"complete").unwrap();
+
+	let appended = dir.join("appended");
+	fs::write(&appended, "HEAD body INDEX").unwrap();
+	let restore = Pending::restore(&appended, &[0..4, 10..u64::MAX]).unwrap();
+	let mut file = fs::OpenOptions::new().write(true).open(&appended).unwrap();
+	file.write_all(b"head").unwrap();
+	file.seek(SeekFrom::Start(10)).unwrap();
+	file.write_all(b"more data, new index").unwrap();
+
+	let volume = dir.join("volume");
+	let remove = Pending::remove(&volume);
+	fs::write(&volume, "part").unwrap();
+	let kept = dir.join("kept");
+	drop(Pending::remove(&kept));
+	fs::write(&kept, "done").unwrap();
+
+	atomic::undo_pending();
+	// Dropping them would wait for the registry
+	std::mem::forget((unfinished, restore, remove));
+	let mut names: Vec<String> = fs::read_dir(&dir)
+		.unwrap()
+		.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+		.collect();
+	names.sort();
+	assert_eq!(names, ["appended", "finished", "kept", "replaced"]);
+	assert_eq!(fs::read(&replaced).unwrap(), b"old contents");
+	assert_eq!(fs::read(&finished).unwrap(), b"complete");
+	assert_eq!(fs::read(&appended).unwrap(), b"HEAD body INDEX");
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,252 @@
+mod common;
+
+use common::random;
+use rust_compression::compress::huffman::{self, Compressor, DecompressError, Filter, Model};
+
+fn round_trip(compressor: &Compressor, data: &[u8]) {
+	let compressed = compressor.compress_stream(data, Vec::new()).unwrap();
+	let dictionary = compressor.options().dictionary;
+	assert_eq!(
+		huffman::decompress_bytes(&compressed, dictionary).unwrap(),
+		data
+	);
+}
+
+#[test]
+fn every_byte_on_its_own() {
+	let compressor = Compressor::default();
+	for byte in 0..=255u8 {
+		round_trip(&compressor, &[byte]);
+		round_trip(&compressor, &[byte; 1000]);
+	}
+}
+
+#[test]
+fn every_pair_of_bytes() {
+	let compressor = Compressor::default();
+	for first in 0..=255u8 {
+		let data: Vec<u8> = (0..=255u8).flat_map(|second| [first, second]).collect();
+		round_trip(&compressor, &data);
+	}
+}
+
+#[test]
+fn all_byte_values() {
+	let compressor = Compressor::default();
+	let data: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+	round_trip(&compressor, &data);
+	let skewed: Vec<u8> = random(1, 10_000, 256)
+		.into_iter()
+		.map(|byte| byte.saturating_sub(200))
+		.collect();
+	round_trip(&compressor, &skewed);
+}
+
+#[test]
+fn random_data_of_every_length() {
+	let compressor = Compressor::default();
+	for len in 0..=600 {
+		round_trip(&compressor, &random(len as u32 + 1, len, 256));
+		round_trip(&compressor, &random(len as u32 + 1, len, 3));
+	}
+}
+
+#[test]
+fn random_data_with_every_setting() {
+	let data = random(0x2545_F491, 50_000, 256)
+		.into_iter()
+		.map(|byte| byte / 16 * (byte % 2))
+		.collect::<Vec<u8>>();
+	let dictionary = huffman::Dictionary::train("sample text");
+	let compressors = [
+		Compressor::builder().model(Model::Word).build(),
+		Compressor::builder().model(Model::Context).build(),
+		Compressor::builder().model(Model::Digram).build(),
+		Compressor::builder().model(Model::Auto).build(),
+		Compressor::builder().model(Model::Rice).build(),
+		Compressor::builder()
+			.model(Model::Rice)
+			.filter(Filter::Delta(2))
+			.build(),
+		Compressor::builder().filter(Filter::Rle).build(),
+		Compressor::builder().filter(Filter::Delta(2)).build(),
+		Compressor::builder().max_code_length(10).build(),
+		Compressor::builder().dictionary(&dictionary).build(),
+		Compressor::builder().block_size(1000).build(),
+		Compressor::builder().adaptive_blocks(true).build(),
+		Compressor::builder().shannon_fano(true).build(),
+	];
+	for compressor in &compressors {
+		round_trip(compressor, &data);
+		round_trip(compressor, &random(7, 5000, 256));
+	}
+}
+
+#[test]
+fn binary_and_text_blocks_mixed() {
+	let mut data = b"plain text with a NUL \0 in it\n".repeat(100);
+	data.extend(random(3, 3000, 256));
+	data.extend("ünïcödé text ".repeat(100).as_bytes());
+	round_trip(&Compressor::builder().block_size(1024).build(), &data);
+	round_trip(&Compressor::builder().adaptive_blocks(true).build(), &data);
+}
+
+#[test]
+fn chunked_encoding_and_decoding() {
+	let data = random(11, 20_000, 256);
+	let options = huffman::CompressOptions {
+		block_size: Some(4096),
+		..huffman::CompressOptions::default()
+	};
+	let mut encoder = huffman::Encoder::new(options);
+	let mut compressed = Vec::new();
+	for chunk in data.chunks(777) {
+		compressed.extend(encoder.push(chunk).unwrap());
+	}
+	compressed.extend(encoder.finish().unwrap());
+	let mut decoder = huffman::Decoder::new(None);
+	let mut decompressed = Vec::new();
+	for chunk in compressed.chunks(333) {
+		decompressed.extend(decoder.push(chunk).unwrap());
+	}
+	decompressed.extend(decoder.finish().unwrap());
+	assert_eq!(decompressed, data);
+}
+
+#[test]
+fn flushed_messages_decode_at_once() {
+	let mut encoder = huffman::Encoder::new(huffman::CompressOptions::default());
+	let mut decoder = huffman::Decoder::new(None);
+	for message in ["request", "", "response ünïcödé", "\0\x01binary"] {
+		let mut compressed = encoder.push(message.as_bytes()).unwrap();
+		compressed.extend(encoder.flush().unwrap());
+		assert_eq!(compressed.is_empty(), message.is_empty());
+		assert_eq!(decoder.push(&compressed).unwrap(), message.as_bytes());
+	}
+	assert!(encoder.finish().unwrap().is_empty());
+	assert!(decoder.finish().unwrap().is_empty());
+}
+
+#[test]
+fn limited_decompression_stops_past_the_limit() {
+	let data = vec![b'a'; 10_000];
+	let compressed = Compressor::default()
+		.compress_stream(&data[..], Vec::new())
+		.unwrap();
+	let output =
+		huffman::decompress_stream_limited(&compressed[..], Vec::new(), None, 10_000).unwrap();
+	assert_eq!(output, data);
+	let error =
+		huffman::decompress_stream_limited(&compressed[..], Vec::new(), None, 9_999).unwrap_err();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert!(matches!(
+		error.get_ref().unwrap().downcast_ref(),
+		Some(DecompressError::OutputTooLarge(9_999))
+	));
+}
+
+#[test]
+fn text_with_nul() {
+	let text = "\0a\0\0b\0".repeat(50);
+	assert_eq!(
+		huffman::decompress(&huffman::compress(&text)).unwrap(),
+		text
+	);
+}
+
+#[test]
+fn binary_data_is_no_text() {
+	let compressed = Compressor::default()
+		.compress_stream(&[0xFF, 0xFE, 0x00][..], Vec::new())
+		.unwrap();
+	assert_eq!(
+		huffman::decompress(&compressed),
+		Err(DecompressError::InvalidUtf8)
+	);
+}
+
+#[test]
+fn dictionary_id_is_checked() {
+	let dictionary = huffman::Dictionary::train("sample text");
+	let other = huffman::Dictionary::train("other sample");
+	let compressed = Compressor::builder()
+		.dictionary(&dictionary)
+		.build()
+		.compress_stream(&b"sample"[..], Vec::new())
+		.unwrap();
+	let required = dictionary.id();
+	assert_eq!(
+		huffman::decompress_bytes(&compressed, Some(&other)),
+		Err(DecompressError::WrongDictionary {
+			required,
+			given: Some(other.id())
+		})
+	);
+	assert_eq!(
+		huffman::decompress_bytes(&compressed, None),
+		Err(DecompressError::WrongDictionary {
+			required,
+			given: None
+		})
+	);
+	assert_eq!(
+		huffman::decompress_bytes(&compressed, Some(&dictionary)).unwrap(),
+		b"sample"
+	);
+}
+
+#[test]
+fn table_from_byte_frequencies() {
+	let data = random(17, 20_000, 16);
+	let mut frequencies = std::collections::HashMap::new();
+	for &byte in &data {
+		*frequencies.entry(byte).or_insert(0u64) += 1;
+	}
+	frequencies.insert(200, 0);
+	let table = huffman::CodeTable::from_frequencies(&frequencies).unwrap();
+	assert_eq!(table.entries.len(), 16);
+	assert!(table.entries.iter().all(|entry| entry.code.len() == 4));
+	assert_eq!(table.validate(), Ok(()));
+	let dictionary = huffman::Dictionary::from_table(&table).unwrap();
+	round_trip(
+		&Compressor::builder().dictionary(&dictionary).build(),
+		&data,
+	);
+	assert!(huffman::CodeTable::from_frequencies(&std::iter::once((1, 0)).collect()).is_none());
+}
+
+/// Text whose optimal tree is deeper than 8 bits, the counts of its letters growing like
+/// Fibonacci numbers
+fn skewed_text() -> String {
+	let (mut a, mut b) = (1, 1);
+	let mut text = String::new();
+	for letter in "abcdefghijklmnop".chars() {
+		text.push_str(&letter.to_string().repeat(a));
+		let next = a + b;
+		a = b;
+		b = next;
+	}
+	text
+}
+
+#[test]
+fn self_check_accepts_length_limited_trees() {
+	let compressor = Compressor::builder().max_code_length(8).build();
+	let compressed = compressor
+		.compress_stream(skewed_text().as_bytes(), Vec::new())
+		.unwrap();
+	let check = huffman::check_model(&compressed).unwrap().unwrap();
+	assert!(check.is_consistent(), "{:?}", check);
+}
+
+#[test]
+fn self_check_accepts_shannon_fano_trees() {
+	// Counts for which Shannon–Fano codes are longer than huffman codes
+	let text = "a".repeat(15) + &"b".repeat(7) + &"c".repeat(6) + &"d".repeat(6) + &"e".repeat(5);
+	let compressor = Compressor::builder().shannon_fano(true).build();
+	let compressed = compressor
+		.compress_stream(text.as_bytes(), Vec::new())
+		.unwrap();
+	let check = huffman::check_model(&compressed).unwrap().unwrap();
+	assert!(check.is_consistent(), "{:?}", check);
+}
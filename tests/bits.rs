@@ -0,0 +1,60 @@
+use rust_compression::bits::{BitReader, BitWriter};
+
+#[test]
+fn round_trip() {
+	let mut writer = BitWriter::new(Vec::new());
+	writer.write_bit(true).unwrap();
+	writer.write_bits(0b0110, 4).unwrap();
+	writer.write_bits(0x1_2345_6789, 33).unwrap();
+	assert_eq!(writer.padding(), 2);
+	let (bytes, padding) = writer.finish().unwrap();
+	assert_eq!((bytes.len(), padding), (5, 2));
+
+	let mut reader = BitReader::new(&bytes[..]);
+	assert_eq!(reader.read_bit().unwrap(), Some(true));
+	assert_eq!(reader.read_bits(4).unwrap(), Some(0b0110));
+	assert_eq!(reader.read_bits(33).unwrap(), Some(0x1_2345_6789));
+	assert!(reader.at_padding().unwrap());
+}
+
+#[test]
+fn most_significant_bit_first() {
+	let mut writer = BitWriter::new(Vec::new());
+	writer.write_bits(0b101, 3).unwrap();
+	let (bytes, padding) = writer.finish().unwrap();
+	assert_eq!(bytes, [0b1010_0000]);
+	assert_eq!(padding, 5);
+}
+
+#[test]
+fn whole_bytes_need_no_padding() {
+	let mut writer = BitWriter::new(Vec::new());
+	writer.write_bits(0xABCD, 16).unwrap();
+	assert_eq!(writer.padding(), 0);
+	assert_eq!(writer.finish().unwrap(), (vec![0xAB, 0xCD], 0));
+}
+
+#[test]
+fn end_of_input() {
+	let mut reader = BitReader::new(&[0xFF][..]);
+	assert_eq!(reader.read_bits(8).unwrap(), Some(0xFF));
+	assert_eq!(reader.read_bit().unwrap(), None);
+
+	let mut reader = BitReader::new(&[0xFF][..]);
+	assert_eq!(reader.read_bits(9).unwrap(), None);
+}
+
+#[test]
+fn padding_detection() {
+	let mut reader = BitReader::new(&[0b1000_0000][..]);
+	reader.read_bit().unwrap();
+	assert!(reader.at_padding().unwrap());
+
+	let mut reader = BitReader::new(&[0b1000_0001][..]);
+	reader.read_bit().unwrap();
+	assert!(!reader.at_padding().unwrap());
+
+	let mut reader = BitReader::new(&[0xFF, 0x00][..]);
+	reader.read_bits(8).unwrap();
+	assert!(!reader.at_padding().unwrap());
+}
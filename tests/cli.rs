@@ -0,0 +1,122 @@
+use rust_compression::archive::{ArchiveWriter, Method};
+use rust_compression::compress::huffman::{self, Metadata};
+use rust_compression::gzip;
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_rust_compression"))
+		.args(args)
+		.output()
+		.unwrap()
+}
+
+/// Run with `input` on stdin
+fn run_with_input(args: &[&str], input: &[u8]) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_rust_compression"))
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(input).unwrap();
+	child.wait_with_output().unwrap()
+}
+
+/// Run with `dir` as the current directory
+fn run_in(dir: &Path, args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_rust_compression"))
+		.args(args)
+		.current_dir(dir)
+		.output()
+		.unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("cli-test-{}-{}", name, std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+#[test]
+fn archive_is_created_and_listed() {
+	let dir = temp_dir("archive");
+	let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+	fs::write(&a, "first file ".repeat(100)).unwrap();
+	fs::write(&b, "second file").unwrap();
+	let archive = dir.join("notes.hfa");
+	let archive = archive.to_str().unwrap();
+	let output = run(&[
+		"-q",
+		"-c",
+		a.to_str().unwrap(),
+		b.to_str().unwrap(),
+		"--archive",
+		archive,
+	]);
+	assert!(output.status.success(), "{:?}", output);
+	let output = run(&["-l", archive]);
+	assert!(output.status.success(), "{:?}", output);
+	let listing = String::from_utf8(output.stdout).unwrap();
+	assert!(
+		listing.contains("a.txt") && listing.contains("b.txt"),
+		"{}",
+		listing
+	);
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn gzip_on_stdin_is_recompressed() {
+	let text = "line of text\n".repeat(5000);
+	let data = gzip::compress(text.as_bytes(), &Metadata::default());
+	let output = run_with_input(&["-c", "-", "--from", "gzip"], &data);
+	assert!(output.status.success(), "{:?}", output);
+	assert_eq!(
+		huffman::decompress_bytes(&output.stdout, None).unwrap(),
+		text.as_bytes()
+	);
+	let output = run_with_input(&["-c", "-", "--from", "gzip"], b"not gzip");
+	assert_eq!(output.status.code(), Some(1));
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_refuses_to_write_through_planted_symlinks() {
+	let dir = temp_dir("symlink");
+	let (target, outside) = (dir.join("target"), dir.join("outside"));
+	fs::create_dir_all(&target).unwrap();
+	fs::create_dir_all(&outside).unwrap();
+	let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+	writer
+		.add_symlink("a", outside.to_str().unwrap(), None)
+		.unwrap();
+	writer
+		.add_file("a/pwned", b"written outside", Method::Stored, None)
+		.unwrap();
+	let archive = dir.join("delta.hfa");
+	fs::write(&archive, writer.finish().unwrap().into_inner()).unwrap();
+	let output = run_in(&target, &["-x", archive.to_str().unwrap(), "a", "a/pwned"]);
+	assert!(!output.status.success(), "{:?}", output);
+	assert!(!outside.join("pwned").exists());
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dumping_the_table_of_an_empty_file_is_a_usage_error() {
+	let dir = temp_dir("dump-table");
+	let (input, table) = (dir.join("empty.txt"), dir.join("table.json"));
+	fs::write(&input, "").unwrap();
+	let output = run(&[
+		"-c",
+		input.to_str().unwrap(),
+		"--dump-table",
+		table.to_str().unwrap(),
+	]);
+	assert_eq!(output.status.code(), Some(2), "{:?}", output);
+	assert!(!table.exists());
+	fs::remove_dir_all(&dir).unwrap();
+}
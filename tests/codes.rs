@@ -0,0 +1,57 @@
+use rust_compression::bits::{BitReader, BitWriter};
+use rust_compression::codes;
+use rust_compression::cursor::Cursor;
+
+#[test]
+fn known_codes() {
+	let mut writer = BitWriter::new(Vec::new());
+	codes::write_unary(&mut writer, 3).unwrap();
+	codes::write_gamma(&mut writer, 5).unwrap();
+	codes::write_delta(&mut writer, 2).unwrap();
+	let (bytes, padding) = writer.finish().unwrap();
+	// 1110, 00101, 010 0
+	assert_eq!(bytes, [0b1110_0010, 0b1010_0000]);
+	assert_eq!(padding, 3);
+
+	let mut varint = Vec::new();
+	codes::write_varint(&mut varint, 300);
+	assert_eq!(varint, [0xAC, 0x02]);
+}
+
+#[test]
+fn round_trip() {
+	let numbers: Vec<u64> = (0..64)
+		.flat_map(|shift| [1 << shift, (1 << shift) | 1, u64::MAX >> shift])
+		.collect();
+	let mut writer = BitWriter::new(Vec::new());
+	let mut varints = Vec::new();
+	for &n in &numbers {
+		codes::write_gamma(&mut writer, n).unwrap();
+		codes::write_delta(&mut writer, n).unwrap();
+		codes::write_unary(&mut writer, n % 100).unwrap();
+		codes::write_varint(&mut varints, n);
+	}
+	let (bytes, _) = writer.finish().unwrap();
+
+	let mut reader = BitReader::new(&bytes[..]);
+	let mut cursor = Cursor::new(&varints);
+	for &n in &numbers {
+		assert_eq!(codes::read_gamma(&mut reader).unwrap(), Some(n));
+		assert_eq!(codes::read_delta(&mut reader).unwrap(), Some(n));
+		assert_eq!(codes::read_unary(&mut reader).unwrap(), Some(n % 100));
+		assert_eq!(cursor.varint("number").unwrap(), n);
+	}
+	assert!(reader.at_padding().unwrap());
+	assert!(cursor.rest().is_empty());
+}
+
+#[test]
+fn invalid_codes() {
+	assert_eq!(
+		codes::read_gamma(&mut BitReader::new(&[0x00][..])).unwrap(),
+		None
+	);
+	assert!(codes::read_gamma(&mut BitReader::new(&[0x00; 9][..])).is_err());
+	assert!(Cursor::new(&[0xFF; 10]).varint("number").is_err());
+	assert!(Cursor::new(&[0x80]).varint("number").is_err());
+}
@@ -0,0 +1,14 @@
+//! Fixtures shared by the tests
+
+/// `count` bytes from a xorshift sequence starting at `seed`, every byte kept below `range`
+pub fn random(seed: u32, count: usize, range: u32) -> Vec<u8> {
+	let mut state = seed;
+	(0..count)
+		.map(|_| {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			(state % range) as u8
+		})
+		.collect()
+}
@@ -0,0 +1,106 @@
+use rust_compression::ffi::*;
+use std::ptr;
+use std::slice;
+
+/// Copy out and release a buffer the library returned
+unsafe fn take(data: *mut u8, len: usize) -> Vec<u8> {
+	let bytes = slice::from_raw_parts(data, len).to_vec();
+	huffman_free(data, len);
+	bytes
+}
+
+fn round_trip(text: &[u8]) -> Vec<u8> {
+	unsafe {
+		let mut len = 0;
+		let compressed = huffman_compress(text.as_ptr(), text.len(), &mut len);
+		assert!(!compressed.is_null());
+		let compressed = take(compressed, len);
+		let decompressed = huffman_decompress(compressed.as_ptr(), compressed.len(), &mut len);
+		assert!(!decompressed.is_null());
+		take(decompressed, len)
+	}
+}
+
+#[test]
+fn one_shot_round_trip() {
+	assert_eq!(round_trip(b"hello hello"), b"hello hello");
+	assert_eq!(round_trip(&[0, 255, 1, 254]), [0, 255, 1, 254]);
+	assert_eq!(round_trip(b""), b"");
+}
+
+#[test]
+fn empty_input_may_be_null() {
+	unsafe {
+		let mut len = 1;
+		let compressed = huffman_compress(ptr::null(), 0, &mut len);
+		assert!(!compressed.is_null());
+		take(compressed, len);
+	}
+}
+
+#[test]
+fn corrupt_input_fails() {
+	unsafe {
+		let mut len = 0;
+		assert!(huffman_decompress(b"garbage".as_ptr(), 7, &mut len).is_null());
+		assert!(huffman_decompress(ptr::null(), 0, &mut len).is_null());
+		assert!(huffman_compress(ptr::null(), 5, &mut len).is_null());
+	}
+}
+
+#[test]
+fn streams_round_trip() {
+	let text = "streamed text ".repeat(10_000);
+	unsafe {
+		let (mut out, mut len) = (ptr::null_mut(), 0);
+		let encoder = huffman_encoder_new();
+		let mut compressed = Vec::new();
+		for chunk in text.as_bytes().chunks(1000) {
+			assert_eq!(
+				huffman_encoder_feed(encoder, chunk.as_ptr(), chunk.len(), &mut out, &mut len),
+				0
+			);
+			if !out.is_null() {
+				compressed.extend(take(out, len));
+			}
+		}
+		assert_eq!(huffman_encoder_finish(encoder, &mut out, &mut len), 0);
+		if !out.is_null() {
+			compressed.extend(take(out, len));
+		}
+		// Finished encoders only fail
+		assert_eq!(huffman_encoder_flush(encoder, &mut out, &mut len), -1);
+		huffman_encoder_destroy(encoder);
+
+		let decoder = huffman_decoder_new();
+		let mut decompressed = Vec::new();
+		for chunk in compressed.chunks(777) {
+			assert_eq!(
+				huffman_decoder_feed(decoder, chunk.as_ptr(), chunk.len(), &mut out, &mut len),
+				0
+			);
+			if !out.is_null() {
+				decompressed.extend(take(out, len));
+			}
+		}
+		assert_eq!(huffman_decoder_finish(decoder, &mut out, &mut len), 0);
+		if !out.is_null() {
+			decompressed.extend(take(out, len));
+		}
+		huffman_decoder_destroy(decoder);
+		assert_eq!(decompressed, text.as_bytes());
+	}
+}
+
+#[test]
+fn corrupt_stream_fails() {
+	unsafe {
+		let (mut out, mut len) = (ptr::null_mut(), 0);
+		let decoder = huffman_decoder_new();
+		let garbage = b"not compressed at all";
+		let fed =
+			huffman_decoder_feed(decoder, garbage.as_ptr(), garbage.len(), &mut out, &mut len);
+		assert!(fed == -1 || huffman_decoder_finish(decoder, &mut out, &mut len) == -1);
+		huffman_decoder_destroy(decoder);
+	}
+}
@@ -0,0 +1,80 @@
+use rust_compression::bits::{BitReader, BitWriter};
+use rust_compression::compress::huffman::DecompressError;
+use rust_compression::cursor::Cursor;
+use rust_compression::generic::{Huffman, Symbol};
+
+/// Writes the table, then the codes of `symbols`, and reads both back
+fn round_trip<T: Symbol + std::fmt::Debug>(symbols: &[T]) {
+	let code = Huffman::from_symbols(symbols.iter().cloned());
+	let mut output = Vec::new();
+	code.write_table(&mut output);
+	let mut bits = BitWriter::new(output);
+	code.encode(symbols, &mut bits).unwrap();
+	let data = bits.finish().unwrap().0;
+
+	let mut cursor = Cursor::new(&data);
+	let read = Huffman::<T>::read_table(&mut cursor).unwrap();
+	assert!(read.lengths().eq(code.lengths()));
+	let decoder = read.decoder();
+	let mut bits = BitReader::new(cursor.rest());
+	for symbol in symbols {
+		assert_eq!(decoder.decode(&mut bits).unwrap().as_ref(), Some(symbol));
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+enum Token {
+	Word(u32),
+	Space,
+	End,
+}
+
+impl Symbol for Token {
+	fn write(&self, output: &mut Vec<u8>) {
+		match self {
+			Token::Word(id) => {
+				output.push(0);
+				id.write(output);
+			}
+			Token::Space => output.push(1),
+			Token::End => output.push(2),
+		}
+	}
+	fn read(cursor: &mut Cursor) -> Result<Token, DecompressError> {
+		match cursor.u8("token")? {
+			0 => Ok(Token::Word(cursor.u32("word")?)),
+			1 => Ok(Token::Space),
+			2 => Ok(Token::End),
+			_ => Err(DecompressError::InvalidTree),
+		}
+	}
+}
+
+#[test]
+fn symbol_types() {
+	round_trip(&b"abracadabra"[..]);
+	round_trip(&"ünïcödé text, with 👍".chars().collect::<Vec<char>>());
+	round_trip(&[7u16, 7, 7]);
+	round_trip(&(0..5000u32).map(|i| i * i % 997).collect::<Vec<u32>>());
+	round_trip(&[u64::MAX, 0, u64::MAX]);
+	let mut tokens: Vec<Token> = (0..300)
+		.flat_map(|i| [Token::Word(i % 17), Token::Space])
+		.collect();
+	tokens.push(Token::End);
+	round_trip(&tokens);
+}
+
+#[test]
+fn codes_are_optimal_and_limited() {
+	let frequencies = [('a', 1), ('b', 1), ('c', 2), ('d', 4), ('e', 8), ('f', 0)];
+	let code = Huffman::from_frequencies(frequencies);
+	let lengths: Vec<(char, u8)> = code
+		.lengths()
+		.map(|(&symbol, length)| (symbol, length))
+		.collect();
+	assert_eq!(lengths, [('a', 4), ('b', 4), ('c', 3), ('d', 2), ('e', 1)]);
+	assert_eq!(code.code(&'f'), None);
+	let limited = Huffman::limited(frequencies, 3).unwrap();
+	assert!(limited.lengths().all(|(_, length)| length <= 3));
+	assert!(Huffman::limited(frequencies, 2).is_none());
+}
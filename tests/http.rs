@@ -0,0 +1,55 @@
+use rust_compression::compress::huffman::Compressor;
+use rust_compression::http;
+use rust_compression::server::MAX_PAYLOAD;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+/// Send `request` to a server answering a single request, and return the response
+fn request(request: &[u8]) -> String {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let address = listener.local_addr().unwrap();
+	let server = thread::spawn(move || {
+		let (stream, _) = listener.accept().unwrap();
+		http::handle(&stream, &Compressor::builder().build())
+	});
+	let mut stream = TcpStream::connect(address).unwrap();
+	stream.write_all(request).unwrap();
+	let mut response = Vec::new();
+	stream.read_to_end(&mut response).unwrap();
+	server.join().unwrap().unwrap();
+	String::from_utf8_lossy(&response).into_owned()
+}
+
+#[test]
+fn chunked_body_over_the_limit_is_too_large() {
+	for path in ["/compress", "/decompress"] {
+		let response = request(
+			format!(
+				"POST {} HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n",
+				path,
+				MAX_PAYLOAD as u64 + 1
+			)
+			.as_bytes(),
+		);
+		assert!(response.starts_with("HTTP/1.1 413 "), "{}", response);
+	}
+}
+
+#[test]
+fn overflowing_chunk_size_is_too_large() {
+	let response = request(
+		b"POST /compress HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+		10\r\n0123456789abcdef\r\nFFFFFFFFFFFFFFFF\r\n",
+	);
+	assert!(response.starts_with("HTTP/1.1 413 "), "{}", response);
+}
+
+#[test]
+fn chunked_body_is_compressed() {
+	let response = request(
+		b"POST /compress HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+		5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+	);
+	assert!(response.starts_with("HTTP/1.1 200 "), "{}", response);
+}
@@ -0,0 +1,82 @@
+use rust_compression::checksum;
+use rust_compression::compress::huffman::Metadata;
+use rust_compression::gzip;
+use rust_compression::inflate::{Format, Header, Reader};
+use std::io::{self, Read};
+
+fn inflate(data: &[u8], format: Format) -> io::Result<Vec<u8>> {
+	let mut output = Vec::new();
+	Reader::new(data, format)?.read_to_end(&mut output)?;
+	Ok(output)
+}
+
+#[test]
+fn gzip_members_read_as_one_stream() {
+	let metadata = Metadata {
+		name: Some("notes.txt".to_string()),
+		mtime: Some(1_600_000_000),
+		..Metadata::default()
+	};
+	let first = "ünïcödé text ".repeat(10_000);
+	let mut data = gzip::compress(first.as_bytes(), &metadata);
+	data.extend(gzip::compress(b"", &Metadata::default()));
+	data.extend(gzip::compress(b"second member", &Metadata::default()));
+	assert_eq!(Format::detect(&data), Some(Format::Gzip));
+	let reader = Reader::new(&data[..], Format::Gzip).unwrap();
+	assert_eq!(
+		reader.header(),
+		&Header {
+			name: Some("notes.txt".to_string()),
+			mtime: Some(1_600_000_000),
+		}
+	);
+	assert_eq!(
+		inflate(&data, Format::Gzip).unwrap(),
+		(first + "second member").as_bytes()
+	);
+}
+
+#[test]
+fn stored_blocks_and_back_references() {
+	// Stored block, as written by zlib at level 0
+	let stored = [
+		1, 12, 0, 243, 255, 115, 116, 111, 114, 101, 100, 32, 98, 108, 111, 99, 107,
+	];
+	let mut data = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+	data.extend(&stored);
+	data.extend(&checksum::crc32(b"stored block").to_le_bytes());
+	data.extend(&12u32.to_le_bytes());
+	assert_eq!(inflate(&data, Format::Gzip).unwrap(), b"stored block");
+
+	// zlib at level 9, mostly matches
+	let zlib = [
+		120, 218, 43, 201, 87, 72, 74, 85, 200, 47, 82, 200, 203, 47, 81, 40, 1, 113, 116, 20, 74,
+		50, 18, 75, 20, 50, 139, 129, 116, 170, 66, 97, 105, 106, 113, 73, 102, 126, 158, 30, 68,
+		114, 192, 84, 2, 0, 249, 222, 57, 153,
+	];
+	assert_eq!(Format::detect(&zlib), Some(Format::Zlib));
+	assert_eq!(
+		inflate(&zlib, Format::Zlib).unwrap(),
+		"to be or not to be, that is the question. "
+			.repeat(4)
+			.as_bytes()
+	);
+}
+
+#[test]
+fn damage_is_invalid_data() {
+	let data = gzip::compress(&[7; 5000], &Metadata::default());
+	let mut damaged = data.clone();
+	let crc = damaged.len() - 8;
+	damaged[crc] ^= 1;
+	for input in [
+		&damaged[..],
+		&data[..data.len() - 3],
+		&data[..20],
+		b"\x1f\x8b\x07",
+	] {
+		let error = inflate(input, Format::Gzip).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", error);
+	}
+	assert!(inflate(&data, Format::Zlib).is_err());
+}
@@ -0,0 +1,67 @@
+//! Data written before members existed, a bare `len | postorder | padding | data`
+//! payload, must keep decompressing
+use rust_compression::compress::huffman;
+
+/// `abracadabra`
+const ABRACADABRA: &[u8] = &[
+	0x09, 0x61, 0x63, 0x64, 0x00, 0x62, 0x72, 0x00, 0x00, 0x00, 0x01, 0x6e, 0x8a, 0xdc,
+];
+
+/// `aaaaaaa`, a tree of a single leaf
+const SINGLE_SYMBOL: &[u8] = &[0x01, 0x61, 0x01, 0x00];
+
+/// `PANGRAM`
+const PANGRAMS: &[u8] = &[
+	0x3d, 0x20, 0x6a, 0x79, 0x00, 0x64, 0x6d, 0x00, 0x00, 0x68, 0x69, 0x67, 0x00, 0x00, 0x00, 0x00,
+	0x65, 0x6e, 0x0a, 0x62, 0x00, 0x00, 0x00, 0x76, 0x74, 0x00, 0x61, 0x00, 0x21, 0x2e, 0x00, 0x78,
+	0x00, 0x72, 0x00, 0x00, 0x00, 0x71, 0x7a, 0x00, 0x54, 0x66, 0x00, 0x00, 0x6f, 0x00, 0x77, 0x73,
+	0x00, 0x6b, 0x6c, 0x00, 0x00, 0x70, 0x63, 0x00, 0x75, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xc9,
+	0xa0, 0xc3, 0xee, 0xf7, 0xa2, 0x7b, 0xef, 0x12, 0x33, 0xdb, 0x44, 0x7d, 0x7e, 0x72, 0x6d, 0x11,
+	0x72, 0x96, 0x83, 0xba, 0xe2, 0x91, 0x5a, 0xfb, 0x33, 0x64, 0xd0, 0x61, 0xf7, 0x7b, 0xd1, 0x3d,
+	0xf7, 0x89, 0x19, 0xed, 0xa2, 0x3e, 0xbf, 0x39, 0x36, 0x88, 0xb9, 0x4b, 0x41, 0xdd, 0x71, 0x48,
+	0xad, 0x79, 0x57, 0xd5, 0xd2, 0xb1, 0x30,
+];
+
+const PANGRAM: &str = "The quick brown fox jumps over the lazy dog.\n\
+	The quick brown fox jumps over the lazy dog again!\n";
+
+fn cases() -> [(&'static [u8], &'static str); 3] {
+	[
+		(ABRACADABRA, "abracadabra"),
+		(SINGLE_SYMBOL, "aaaaaaa"),
+		(PANGRAMS, PANGRAM),
+	]
+}
+
+#[test]
+fn decompress() {
+	for (data, text) in cases() {
+		assert_eq!(huffman::decompress(data).unwrap(), text);
+		assert_eq!(
+			huffman::decompress_bytes(data, None).unwrap(),
+			text.as_bytes()
+		);
+	}
+}
+
+#[test]
+fn decompress_in_chunks() {
+	for (data, text) in cases() {
+		let mut decoder = huffman::Decoder::new(None);
+		let mut decompressed = Vec::new();
+		for chunk in data.chunks(3) {
+			decompressed.extend(decoder.push(chunk).unwrap());
+		}
+		decompressed.extend(decoder.finish().unwrap());
+		assert_eq!(decompressed, text.as_bytes());
+	}
+}
+
+#[test]
+fn inspect() {
+	for (data, _) in cases() {
+		let members = huffman::inspect(data).unwrap();
+		assert_eq!(members.len(), 1);
+		assert_eq!(members[0].version, 0);
+	}
+}
@@ -0,0 +1,138 @@
+//! Malformed input fails with an error, rather than panicking or decoding to garbage
+use rust_compression::compress::huffman::{self, Compressor, DecompressError, Model};
+
+/// `abracadabra` in the bare format of the first versions,
+/// `len | postorder | padding | data`
+const ABRACADABRA: &[u8] = &[
+	0x09, 0x61, 0x63, 0x64, 0x00, 0x62, 0x72, 0x00, 0x00, 0x00, 0x01, 0x6e, 0x8a, 0xdc,
+];
+/// Offset of the padding byte in `ABRACADABRA`
+const PADDING: usize = 10;
+
+fn with(offset: usize, byte: u8) -> Vec<u8> {
+	let mut data = ABRACADABRA.to_vec();
+	data[offset] = byte;
+	data
+}
+
+#[test]
+fn empty_input() {
+	assert_eq!(huffman::decompress(&[]), Err(DecompressError::Empty));
+	assert_eq!(
+		huffman::decompress_bytes(&[], None),
+		Err(DecompressError::Empty)
+	);
+}
+
+#[test]
+fn truncated_tree_and_padding() {
+	let truncated = |data: &[u8], name: &str| {
+		matches!(
+			huffman::decompress(data),
+			Err(DecompressError::Truncated { field, .. }) if field == name
+		)
+	};
+	assert!(truncated(&ABRACADABRA[..2], "huffman tree"));
+	assert!(truncated(&ABRACADABRA[..PADDING - 1], "huffman tree"));
+	assert!(truncated(&ABRACADABRA[..PADDING], "padding"));
+}
+
+#[test]
+fn unbalanced_post_order() {
+	// An inner node before any leaves
+	assert_eq!(
+		huffman::decompress(&with(1, 0)),
+		Err(DecompressError::InvalidTree)
+	);
+	// A leaf instead of the root, leaving three trees
+	assert_eq!(
+		huffman::decompress(&with(9, b'x')),
+		Err(DecompressError::InvalidTree)
+	);
+}
+
+#[test]
+fn impossible_padding() {
+	assert_eq!(
+		huffman::decompress(&with(PADDING, 8)),
+		Err(DecompressError::InvalidPadding(8))
+	);
+	assert_eq!(
+		huffman::decompress(&with(PADDING, 255)),
+		Err(DecompressError::InvalidPadding(255))
+	);
+	// Padding with no data to pad
+	assert_eq!(
+		huffman::decompress(&[0x01, 0x61, 0x03]),
+		Err(DecompressError::InvalidPadding(3))
+	);
+}
+
+#[test]
+fn code_cut_off_by_padding() {
+	assert_eq!(
+		huffman::decompress(&with(PADDING, 7)),
+		Err(DecompressError::IncompleteCode)
+	);
+}
+
+/// Text compressed by `compressors`
+fn compressed(compressors: &[Compressor]) -> Vec<Vec<u8>> {
+	let text = "malformed input, truncated and corrupted ".repeat(10);
+	compressors
+		.iter()
+		.map(|compressor| {
+			compressor
+				.compress_stream(text.as_bytes(), Vec::new())
+				.unwrap()
+		})
+		.collect()
+}
+
+/// Compressors writing a single member for the text of `compressed`
+fn single_member() -> [Compressor<'static>; 4] {
+	[
+		Compressor::builder().build(),
+		Compressor::builder().model(Model::Word).build(),
+		Compressor::builder().model(Model::Context).build(),
+		Compressor::builder().model(Model::Rice).build(),
+	]
+}
+
+#[test]
+fn every_truncation_fails() {
+	// Streams of several members may end after any of them
+	for data in compressed(&single_member()) {
+		for len in 0..data.len() {
+			assert!(
+				huffman::decompress_bytes(&data[..len], None).is_err(),
+				"{} of {} bytes",
+				len,
+				data.len()
+			);
+			if len == 0 {
+				// An empty stream is no text, rather than a truncated one
+				continue;
+			}
+			let mut decoder = huffman::Decoder::new(None);
+			let result = decoder.push(&data[..len]).and_then(|_| decoder.finish());
+			assert!(result.is_err(), "{} of {} bytes", len, data.len());
+		}
+	}
+}
+
+#[test]
+fn corrupted_bytes_dont_panic() {
+	let mut compressors = single_member().to_vec();
+	compressors.push(Compressor::builder().block_size(100).build());
+	for data in compressed(&compressors) {
+		for offset in 0..data.len() {
+			for flip in [0x01, 0x80, 0xff] {
+				let mut corrupted = data.clone();
+				corrupted[offset] ^= flip;
+				let _ = huffman::decompress_bytes(&corrupted, None);
+				let _ = huffman::check_model(&corrupted);
+			}
+		}
+	}
+}
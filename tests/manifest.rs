@@ -0,0 +1,51 @@
+use rust_compression::manifest::{self, Entry};
+
+#[test]
+fn lines_give_output_and_settings() {
+	let text = "# comment\n\nlogs/app log\ndata.csv\tbackup/data.cmp\r\nphoto.jpg\t\talgo=stored\tblock-size = 64K\n";
+	let entries = manifest::parse(text).unwrap();
+	assert_eq!(
+		entries,
+		[
+			Entry {
+				line: 3,
+				input: "logs/app log".to_string(),
+				output: None,
+				settings: vec![],
+			},
+			Entry {
+				line: 4,
+				input: "data.csv".to_string(),
+				output: Some("backup/data.cmp".to_string()),
+				settings: vec![],
+			},
+			Entry {
+				line: 5,
+				input: "photo.jpg".to_string(),
+				output: None,
+				settings: vec![
+					("algo".to_string(), "stored".to_string()),
+					("block-size".to_string(), "64K".to_string()),
+				],
+			},
+		]
+	);
+	assert_eq!(entries[2].get("algo"), Some("stored"));
+	assert_eq!(entries[2].get("model"), None);
+}
+
+#[test]
+fn invalid_lines_are_reported() {
+	for (text, error) in [
+		("a\n\tout.cmp\n", "Line 2: missing input path"),
+		(
+			"a\t\tstored\n",
+			"Line 1: expected `key=value`, not `stored`",
+		),
+		("a\t\tlevel=9\n", "Line 1: unknown setting `level`"),
+		("a\t\talgo=rle\talgo=lzw\n", "Line 1: `algo` set twice"),
+	] {
+		let message = manifest::parse(text).unwrap_err();
+		assert!(message.starts_with(error), "{}", message);
+	}
+}
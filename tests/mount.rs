@@ -0,0 +1,68 @@
+#![cfg(all(feature = "fuse", target_os = "linux"))]
+use rust_compression::archive::{Archive, ArchiveWriter, Method};
+use rust_compression::mount::Filesystem;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::thread;
+
+const READ: u32 = 15;
+const DESTROY: u32 = 38;
+
+/// A FUSE request of `opcode` on `ino`, as the kernel sends it
+fn request(opcode: u32, unique: u64, ino: u64, body: &[u8]) -> Vec<u8> {
+	let mut request = Vec::new();
+	request.extend(&(40 + body.len() as u32).to_ne_bytes());
+	request.extend(&opcode.to_ne_bytes());
+	request.extend(&unique.to_ne_bytes());
+	request.extend(&ino.to_ne_bytes());
+	request.resize(40, 0);
+	request.extend(body);
+	request
+}
+
+/// Serve `archive` over a socket standing in for /dev/fuse and send it `requests`,
+/// returning the error of each reply
+fn errors(archive: Vec<u8>, requests: &[Vec<u8>]) -> Vec<i32> {
+	let mut fds = [0; 2];
+	// SAFETY: `fds` has room for both descriptors
+	assert_eq!(
+		unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0, fds.as_mut_ptr()) },
+		0
+	);
+	// SAFETY: both descriptors were just created and are owned by nothing else
+	let (mut kernel, fuse) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+	let server = thread::spawn(move || {
+		let mut filesystem = Filesystem::new(Archive::new(Cursor::new(archive)).unwrap());
+		filesystem.serve(&fuse)
+	});
+	let mut errors = Vec::new();
+	for request in requests {
+		kernel.write_all(request).unwrap();
+		let mut reply = vec![0; 1 << 18];
+		let len = kernel.read(&mut reply).unwrap();
+		assert!(len >= 16);
+		errors.push(i32::from_ne_bytes(reply[4..8].try_into().unwrap()));
+	}
+	server.join().unwrap().unwrap();
+	errors
+}
+
+#[test]
+fn reading_past_what_an_entry_decodes_to_fails() {
+	let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+	writer.add_file("f", b"", Method::Stored, None).unwrap();
+	let mut data = writer.finish().unwrap().into_inner();
+	let index_offset = Archive::new(Cursor::new(&data)).unwrap().index_offset() as usize;
+	// Count, path length, path, kind, method, offset and length come before the size
+	let size = index_offset + 4 + 2 + "f".len() + 2 + 16;
+	data[size..size + 8].copy_from_slice(&100u64.to_le_bytes());
+	let mut read = vec![0; 40];
+	read[16..20].copy_from_slice(&4096u32.to_ne_bytes());
+	let errors = errors(
+		data,
+		&[request(READ, 1, 2, &read), request(DESTROY, 2, 1, &[])],
+	);
+	assert_eq!(errors, [-libc::EIO, 0]);
+}
@@ -0,0 +1,57 @@
+use rust_compression::canonical;
+use rust_compression::nary::{self, Decoder, DigitReader, DigitWriter};
+
+/// Sum of the code lengths weighted by frequency
+fn cost(frequencies: &[u64], lengths: &[u8]) -> u64 {
+	frequencies
+		.iter()
+		.zip(lengths)
+		.map(|(&frequency, &length)| frequency * length as u64)
+		.sum()
+}
+
+#[test]
+fn optimal_lengths() {
+	let frequencies = [5, 4, 3, 2, 1];
+	assert_eq!(nary::code_lengths(&frequencies, 3), [1, 1, 2, 2, 2]);
+	// 4 symbols don't fill a ternary tree, a dummy takes the place of a fifth
+	assert_eq!(nary::code_lengths(&[1, 1, 1, 1], 3), [2, 2, 1, 1]);
+	assert_eq!(nary::code_lengths(&[0, 9, 0], 5), [0, 1, 0]);
+	let skewed: Vec<u64> = (0..40).map(|i| (i * i * 7 + 3) % 101).collect();
+	assert_eq!(
+		nary::code_lengths(&skewed, 2),
+		canonical::code_lengths(&skewed)
+	);
+	let costs: Vec<u64> = (2..=6)
+		.map(|arity| cost(&skewed, &nary::code_lengths(&skewed, arity)))
+		.collect();
+	assert!(costs.windows(2).all(|pair| pair[0] > pair[1]));
+}
+
+#[test]
+fn codes_round_trip() {
+	let frequencies: Vec<u64> = (0..300).map(|i| (i * 31 % 17) as u64).collect();
+	let symbols: Vec<usize> = (0..5000)
+		.map(|i| i * 7 % 300)
+		.filter(|&symbol| frequencies[symbol] != 0)
+		.collect();
+	for arity in [2, 3, 4, 7, 10, 16, 255] {
+		let lengths = nary::code_lengths(&frequencies, arity);
+		let codes = nary::codes(&lengths, arity);
+		assert_eq!(codes[1].len(), lengths[1] as usize);
+		assert!(codes.iter().flatten().all(|&digit| digit < arity));
+		let mut writer = DigitWriter::new(Vec::new(), arity);
+		for &symbol in &symbols {
+			writer.write_code(&codes[symbol]).unwrap();
+		}
+		let packed = writer.finish().unwrap();
+
+		let decoder = Decoder::new(&lengths, arity).unwrap();
+		let mut reader = DigitReader::new(&packed[..], arity);
+		for &symbol in &symbols {
+			assert_eq!(decoder.decode(&mut reader).unwrap(), Some(symbol));
+		}
+	}
+	assert!(Decoder::new(&[1, 1, 1, 1], 3).is_none());
+	assert!(Decoder::new(&[1, 1, 2, 2, 2], 3).is_some());
+}
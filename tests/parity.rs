@@ -0,0 +1,52 @@
+mod common;
+
+use common::random;
+use rust_compression::parity;
+
+fn protected(data: &[u8], percent: u32) -> Vec<u8> {
+	let mut output = data.to_vec();
+	output.extend(parity::protect(data, percent));
+	output
+}
+
+#[test]
+fn intact_data_is_kept() {
+	for len in [0, 1, 511, 512, 513, 100_000] {
+		let data = random(len as u32 + 1, len, 256);
+		let mut file = protected(&data, 10);
+		let repair = parity::repair(&mut file).unwrap();
+		assert_eq!(repair.damaged, 0);
+		assert_eq!(file, data);
+	}
+	let mut data = random(5, 1000, 256);
+	assert_eq!(parity::repair(&mut data), None);
+}
+
+#[test]
+fn damaged_shards_are_rebuilt() {
+	let data = random(9, 100_000, 256);
+	let mut file = protected(&data, 20);
+	// A run of zeros over several shards, and some bytes here and there
+	for byte in &mut file[30_000..35_000] {
+		*byte = 0;
+	}
+	for offset in [10, 60_000, 99_999] {
+		file[offset] ^= 0x55;
+	}
+	let repair = parity::repair(&mut file).unwrap();
+	assert!(repair.repaired);
+	assert!(repair.damaged > 3);
+	assert_eq!(file, data);
+}
+
+#[test]
+fn too_much_damage_is_reported() {
+	let data = random(13, 10_000, 256);
+	let mut file = protected(&data, 10);
+	for byte in &mut file[..5000] {
+		*byte = 0;
+	}
+	let repair = parity::repair(&mut file).unwrap();
+	assert!(!repair.repaired);
+	assert_eq!(file.len(), data.len());
+}
@@ -0,0 +1,51 @@
+use rust_compression::archive::Method;
+use rust_compression::compress::huffman::{CompressOptions, Filter, Model};
+use rust_compression::presets::{Presets, Settings};
+
+#[test]
+fn settings_by_extension() {
+	let presets = Presets::parse(
+		"# comment\njpg = stored\n.LOG = model word, block-size 64K\ncsv = huffman, filter delta:4\n",
+	)
+	.unwrap();
+	assert_eq!(presets.for_path("a/photo.JPG").method, Method::Stored);
+	let log = presets.for_path("app.log");
+	assert_eq!(
+		log,
+		Settings {
+			method: Method::Huffman,
+			model: Some(Model::Word),
+			filter: None,
+			block_size: Some(64 << 10),
+		}
+	);
+	let options = presets.for_path("table.csv").apply(&CompressOptions {
+		model: Model::Context,
+		..CompressOptions::default()
+	});
+	assert_eq!(options.model, Model::Context);
+	assert_eq!(options.filter, Filter::Delta(4));
+	assert_eq!(presets.for_path("notes.txt"), Settings::default());
+}
+
+#[test]
+fn invalid_settings_are_reported() {
+	for (text, error) in [
+		("jpg\n", "Line 1: expected `extension = setting`"),
+		(
+			"log = model words\n",
+			"Line 1: invalid setting `model words`",
+		),
+		(
+			"log = filter delta:0\n",
+			"Line 1: invalid setting `filter delta:0`",
+		),
+		(
+			"jpg = stored, model word\n",
+			"Line 1: stored takes no huffman settings",
+		),
+		("log = level 7\n", "Line 1: unknown setting `level 7`"),
+	] {
+		assert_eq!(Presets::parse(text).unwrap_err(), error);
+	}
+}
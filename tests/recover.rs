@@ -0,0 +1,51 @@
+use rust_compression::compress::huffman::{self, Compressor, DecompressError};
+
+fn compressed(text: &str) -> Vec<u8> {
+	Compressor::builder()
+		.block_size(1000)
+		.build()
+		.compress_stream(text.as_bytes(), Vec::new())
+		.unwrap()
+}
+
+fn text() -> String {
+	(0..400).map(|i| format!("line {}\n", i)).collect()
+}
+
+#[test]
+fn intact_data() {
+	let text = text();
+	let (recovered, lost) = huffman::recover(&compressed(&text), None, None).unwrap();
+	assert_eq!(recovered, text.as_bytes());
+	assert!(lost.is_empty());
+}
+
+#[test]
+fn damaged_payload_is_filled() {
+	let text = text();
+	let mut data = compressed(&text);
+	data[100] ^= 0x10;
+	let (recovered, lost) = huffman::recover(&data, None, None).unwrap();
+	assert_eq!(lost.len(), 1);
+	assert_eq!((lost[0].offset, lost[0].length), (0, Some(1000)));
+	assert_eq!(recovered.len(), text.len());
+	assert!(recovered[..1000].iter().all(|&byte| byte == 0));
+	assert_eq!(recovered[1000..], text.as_bytes()[1000..]);
+
+	let (recovered, _) = huffman::recover(&data, None, Some(b"?")).unwrap();
+	assert_eq!(recovered, [b"?", &text.as_bytes()[1000..]].concat());
+}
+
+#[test]
+fn damaged_header_is_skipped() {
+	let text = text();
+	let mut data = compressed(&text);
+	let second = data[3..].windows(3).position(|w| w == b"HUF").unwrap() + 3;
+	data[second + 4] = 0xFF;
+	let (recovered, lost) = huffman::recover(&data, None, None).unwrap();
+	assert_eq!(lost.len(), 1);
+	assert_eq!(lost[0].compressed.start, second);
+	assert_eq!(lost[0].error, DecompressError::UnknownFlags(0xFF));
+	assert_eq!(recovered.len(), text.len() - 1000);
+	assert_eq!(recovered[1000..], text.as_bytes()[2000..]);
+}
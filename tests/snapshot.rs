@@ -0,0 +1,24 @@
+use rust_compression::archive::{Archive, ArchiveWriter, Method};
+use rust_compression::snapshot;
+use std::fs;
+use std::io::Cursor;
+
+#[cfg(unix)]
+#[test]
+fn apply_refuses_to_write_through_planted_symlinks() {
+	let dir = std::env::temp_dir().join(format!("snapshot-test-{}", std::process::id()));
+	let (target, outside) = (dir.join("target"), dir.join("outside"));
+	fs::create_dir_all(&target).unwrap();
+	fs::create_dir_all(&outside).unwrap();
+	let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+	writer
+		.add_symlink("a", outside.to_str().unwrap(), None)
+		.unwrap();
+	writer
+		.add_file("a/pwned", b"written outside", Method::Stored, None)
+		.unwrap();
+	let mut archive = Archive::new(writer.finish().unwrap()).unwrap();
+	assert!(snapshot::apply(&mut archive, &target).is_err());
+	assert!(!outside.join("pwned").exists());
+	fs::remove_dir_all(&dir).unwrap();
+}
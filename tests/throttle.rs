@@ -0,0 +1,23 @@
+use rust_compression::throttle::Throttle;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+#[test]
+fn rate_caps_reads_and_writes() {
+	let data = vec![7; 3000];
+	let start = Instant::now();
+	let mut output = Throttle::new(Vec::new(), Some(10_000));
+	output.write_all(&data).unwrap();
+	let mut read = Vec::new();
+	Throttle::new(&data[..], Some(10_000))
+		.read_to_end(&mut read)
+		.unwrap();
+	assert!(start.elapsed() >= Duration::from_millis(600));
+	assert_eq!(output.into_inner(), data);
+	assert_eq!(read, data);
+
+	let start = Instant::now();
+	let mut output = Throttle::new(Vec::new(), None);
+	output.write_all(&vec![0; 1 << 20]).unwrap();
+	assert!(start.elapsed() < Duration::from_millis(500));
+}
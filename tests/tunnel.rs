@@ -0,0 +1,70 @@
+use rust_compression::compress::huffman::Compressor;
+use rust_compression::tunnel::{self, Side};
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::thread;
+
+/// Listener on a free port of localhost, with its address
+fn listen() -> (TcpListener, String) {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let address = listener.local_addr().unwrap().to_string();
+	(listener, address)
+}
+
+/// Start a tunnel end forwarding to `address`, returning the address it listens on
+fn start(address: String, side: Side) -> String {
+	let (listener, own) = listen();
+	thread::spawn(move || tunnel::serve(listener, &address, side, &Compressor::default()));
+	own
+}
+
+#[test]
+fn traffic_passes_both_ends() {
+	// Answers every line it is sent in upper case, and counts the bytes after EOF
+	let (server, address) = listen();
+	thread::spawn(move || {
+		for stream in server.incoming() {
+			let mut stream = stream.unwrap();
+			let mut total = 0;
+			let mut buffer = [0; 4096];
+			loop {
+				let len = stream.read(&mut buffer).unwrap();
+				if len == 0 {
+					break;
+				}
+				total += len;
+				stream
+					.write_all(&buffer[..len].to_ascii_uppercase())
+					.unwrap();
+			}
+			stream
+				.write_all(format!("\n{} bytes", total).as_bytes())
+				.unwrap();
+		}
+	});
+	let far = start(address, Side::Compressed);
+	let near = start(far, Side::Plain);
+
+	let mut client = TcpStream::connect(&near).unwrap();
+	// Every message is answered before the connection closes, so nothing waits on a block
+	let messages = ["hello\n", "tunnel ünïcödé\n"];
+	for message in messages {
+		client.write_all(message.as_bytes()).unwrap();
+		let mut answer = vec![0; message.len()];
+		client.read_exact(&mut answer).unwrap();
+		assert_eq!(answer, message.to_ascii_uppercase().as_bytes());
+	}
+	let large = "all work and no play ".repeat(2_000);
+	client.write_all(large.as_bytes()).unwrap();
+	client.shutdown(Shutdown::Write).unwrap();
+	let mut rest = String::new();
+	client.read_to_string(&mut rest).unwrap();
+	assert_eq!(
+		rest,
+		format!(
+			"{}\n{} bytes",
+			large.to_uppercase(),
+			messages.concat().len() + large.len()
+		)
+	);
+}